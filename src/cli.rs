@@ -1,14 +1,24 @@
 // One module per subcommand
+mod bench;
 mod collections;
+mod cookies;
 mod generate;
+mod graphql;
 mod import;
 mod request;
+mod run;
+mod serve;
 mod show;
+mod test;
+mod websocket;
 
 use crate::{
     cli::{
-        collections::CollectionsCommand, generate::GenerateCommand,
-        import::ImportCommand, request::RequestCommand, show::ShowCommand,
+        bench::BenchCommand, collections::CollectionsCommand,
+        cookies::CookiesCommand, generate::GenerateCommand,
+        graphql::GraphQlCommand, import::ImportCommand,
+        request::RequestCommand, run::RunCommand, serve::ServeCommand,
+        show::ShowCommand, test::TestCommand, websocket::WebSocketCommand,
     },
     GlobalArgs,
 };
@@ -19,10 +29,19 @@ use std::process::ExitCode;
 #[derive(Clone, Debug, clap::Subcommand)]
 pub enum CliCommand {
     Request(RequestCommand),
+    Test(TestCommand),
+    Bench(BenchCommand),
+    Run(RunCommand),
+    Serve(ServeCommand),
     Generate(GenerateCommand),
     Import(ImportCommand),
     Collections(CollectionsCommand),
+    Cookies(CookiesCommand),
     Show(ShowCommand),
+    #[clap(name = "graphql")]
+    GraphQl(GraphQlCommand),
+    #[clap(name = "websocket")]
+    WebSocket(WebSocketCommand),
 }
 
 /// An executable subcommand. This trait isn't strictly necessary because we do
@@ -40,9 +59,16 @@ impl CliCommand {
         match self {
             Self::Generate(command) => command.execute(global).await,
             Self::Request(command) => command.execute(global).await,
+            Self::Test(command) => command.execute(global).await,
+            Self::Bench(command) => command.execute(global).await,
+            Self::Run(command) => command.execute(global).await,
+            Self::Serve(command) => command.execute(global).await,
             Self::Import(command) => command.execute(global).await,
             Self::Collections(command) => command.execute(global).await,
+            Self::Cookies(command) => command.execute(global).await,
             Self::Show(command) => command.execute(global).await,
+            Self::GraphQl(command) => command.execute(global).await,
+            Self::WebSocket(command) => command.execute(global).await,
         }
     }
 }
@@ -0,0 +1,467 @@
+use crate::{
+    cli::{
+        request::{BuildRequestCommand, CANCELLED_EXIT_CODE},
+        Subcommand,
+    },
+    collection::{cereal::serde_duration, Recipe},
+    http::{HttpEngine, RecipeOptions, Request, RequestBuilder},
+    template::TemplateContext,
+    GlobalArgs,
+};
+use async_trait::async_trait;
+use clap::Parser;
+use futures::future;
+use indexmap::IndexMap;
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    process::ExitCode,
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+use tokio::signal;
+
+/// Number of requests to send if neither `-n` nor `--duration` is given
+const DEFAULT_REQUESTS: u64 = 100;
+
+/// Send a request many times concurrently, and report latency and throughput
+///
+/// By default, the recipe is rendered once and the same request is sent each
+/// time. Requests are not stored in history unless `--history` is given.
+/// Ctrl-C stops sending and reports on the requests that completed. A second
+/// Ctrl-C exits immediately, without a report. The recipe's `download` field
+/// is ignored, so responses are never written to a file.
+#[derive(Clone, Debug, Parser)]
+pub struct BenchCommand {
+    #[clap(flatten)]
+    build_request: BuildRequestCommand,
+
+    /// Total number of requests to send. Defaults to 100, or unlimited if
+    /// `--duration` is given
+    #[clap(long = "requests", short = 'n')]
+    requests: Option<u64>,
+
+    /// Maximum number of requests in flight at once
+    #[clap(
+        long,
+        short,
+        default_value_t = 10,
+        value_parser = clap::value_parser!(u32).range(1..),
+    )]
+    concurrency: u32,
+
+    /// Stop sending new requests after this much time (e.g. `30s`). If `-n`
+    /// is also given, stop at whichever limit is hit first
+    #[clap(long, value_parser = serde_duration::parse)]
+    duration: Option<Duration>,
+
+    /// Render the recipe again for each request, e.g. to generate unique IDs
+    #[clap(long)]
+    render_each: bool,
+
+    /// Store each request in history
+    #[clap(long)]
+    history: bool,
+
+    /// Print the report as JSON
+    #[clap(long)]
+    json: bool,
+}
+
+#[async_trait]
+impl Subcommand for BenchCommand {
+    async fn execute(self, global: GlobalArgs) -> anyhow::Result<ExitCode> {
+        let (http_engine, mut recipe, template_context) =
+            self.build_request.load_recipe(global).await?;
+        let recipe_id = recipe.id.clone();
+        // Concurrent requests would all write to the same file, and writing
+        // to disk would skew the latency anyway
+        recipe.download = None;
+        // Render up front, so a broken recipe fails immediately instead of
+        // once per request
+        let source = if self.render_each {
+            RequestSource::Recipe(recipe)
+        } else {
+            RequestSource::Request(
+                RequestBuilder::new(recipe, RecipeOptions::default())
+                    .build(&template_context)
+                    .await?,
+            )
+        };
+
+        let requests = match (self.requests, self.duration) {
+            (Some(requests), _) => requests,
+            (None, Some(_)) => u64::MAX,
+            (None, None) => DEFAULT_REQUESTS,
+        };
+        let start = Instant::now();
+        let runner = Runner {
+            // Triggered requests still go through the template context's
+            // engine, so they follow the normal history rules
+            http_engine: http_engine.with_history(self.history),
+            template_context,
+            source,
+            requests,
+            deadline: self.duration.map(|duration| start + duration),
+            sent: AtomicU64::new(0),
+            stopped: AtomicBool::new(false),
+        };
+
+        eprintln!(
+            "Benchmarking `{recipe_id}` with concurrency {}...",
+            self.concurrency
+        );
+        let workers = future::join_all(
+            (0..self.concurrency).map(|_| runner.run_worker()),
+        );
+        tokio::pin!(workers);
+        let outcomes = tokio::select! {
+            outcomes = &mut workers => outcomes,
+            Ok(()) = signal::ctrl_c() => {
+                // Let in-flight requests finish, so they can be reported. If
+                // the user doesn't want to wait, they can cancel again
+                eprintln!("Stopping... (Ctrl-C again to exit immediately)");
+                runner.stopped.store(true, Ordering::Relaxed);
+                tokio::select! {
+                    outcomes = &mut workers => outcomes,
+                    Ok(()) = signal::ctrl_c() => {
+                        return Ok(ExitCode::from(CANCELLED_EXIT_CODE));
+                    }
+                }
+            }
+        };
+
+        let report =
+            BenchReport::new(outcomes.into_iter().flatten(), start.elapsed());
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        } else {
+            report.print();
+        }
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+/// Where each benchmarked request comes from
+enum RequestSource {
+    /// Send a copy of this request each time
+    Request(Request),
+    /// Render this recipe for each request
+    Recipe(Recipe),
+}
+
+/// State shared between all the workers of a benchmark
+struct Runner {
+    http_engine: HttpEngine,
+    template_context: TemplateContext,
+    source: RequestSource,
+    /// Total number of requests to send
+    requests: u64,
+    /// Don't send any requests after this time
+    deadline: Option<Instant>,
+    /// Number of requests that have been started
+    sent: AtomicU64,
+    /// Set when the user cancels the benchmark
+    stopped: AtomicBool,
+}
+
+impl Runner {
+    /// Send requests one at a time until a limit is reached. Each worker is
+    /// one unit of concurrency.
+    async fn run_worker(&self) -> Vec<Outcome> {
+        let mut outcomes = Vec::new();
+        while !self.stopped.load(Ordering::Relaxed)
+            && self
+                .deadline
+                .map_or(true, |deadline| Instant::now() < deadline)
+            && self.sent.fetch_add(1, Ordering::Relaxed) < self.requests
+        {
+            outcomes.push(self.send().await);
+        }
+        outcomes
+    }
+
+    /// Build and send a single request. Latency only counts the time to send
+    /// it, not to render it.
+    async fn send(&self) -> Outcome {
+        let request = match &self.source {
+            RequestSource::Request(request) => request.duplicate(),
+            RequestSource::Recipe(recipe) => {
+                match RequestBuilder::new(
+                    recipe.clone(),
+                    RecipeOptions::default(),
+                )
+                .build(&self.template_context)
+                .await
+                {
+                    Ok(request) => request,
+                    Err(error) => {
+                        return Outcome::Error(format!("{:#}", error.error))
+                    }
+                }
+            }
+        };
+        let start = Instant::now();
+        match self.http_engine.clone().send(request.into()).await {
+            Ok(record) => Outcome::Response {
+                status: record.response.status.as_u16(),
+                latency: start.elapsed(),
+            },
+            Err(error) => Outcome::Error(format!("{:#}", error.error)),
+        }
+    }
+}
+
+/// Result of a single benchmarked request
+enum Outcome {
+    Response {
+        status: u16,
+        latency: Duration,
+    },
+    /// The request couldn't be built or sent
+    Error(String),
+}
+
+/// Summary of a benchmark. Latency only includes requests that got a response.
+#[derive(Debug, Serialize)]
+struct BenchReport {
+    /// Number of requests that completed, including errors
+    requests: u64,
+    errors: u64,
+    duration_secs: f64,
+    /// Completed requests per second
+    throughput: f64,
+    /// `null` if no requests got a response
+    latency: Option<LatencyStats>,
+    /// Number of responses for each status code
+    status_codes: BTreeMap<u16, u64>,
+    /// Number of occurrences of each error message, most common first
+    error_messages: IndexMap<String, u64>,
+}
+
+/// Latency statistics, in milliseconds
+#[derive(Debug, Serialize)]
+#[cfg_attr(test, derive(PartialEq))]
+struct LatencyStats {
+    min_ms: f64,
+    mean_ms: f64,
+    p50_ms: f64,
+    p90_ms: f64,
+    p95_ms: f64,
+    p99_ms: f64,
+    max_ms: f64,
+}
+
+impl BenchReport {
+    fn new(outcomes: impl Iterator<Item = Outcome>, elapsed: Duration) -> Self {
+        let mut latencies = Vec::new();
+        let mut status_codes: BTreeMap<u16, u64> = BTreeMap::new();
+        let mut error_messages: IndexMap<String, u64> = IndexMap::new();
+        for outcome in outcomes {
+            match outcome {
+                Outcome::Response { status, latency } => {
+                    latencies.push(latency);
+                    *status_codes.entry(status).or_default() += 1;
+                }
+                Outcome::Error(message) => {
+                    *error_messages.entry(message).or_default() += 1;
+                }
+            }
+        }
+        error_messages.sort_by(|_, a, _, b| b.cmp(a));
+
+        let errors: u64 = error_messages.values().sum();
+        let requests = latencies.len() as u64 + errors;
+        let duration_secs = elapsed.as_secs_f64();
+        Self {
+            requests,
+            errors,
+            duration_secs,
+            throughput: if duration_secs > 0.0 {
+                requests as f64 / duration_secs
+            } else {
+                0.0
+            },
+            latency: LatencyStats::new(latencies),
+            status_codes,
+            error_messages,
+        }
+    }
+
+    fn print(&self) {
+        println!(
+            "{} requests in {:.2}s ({:.1} req/s), {} errors",
+            self.requests, self.duration_secs, self.throughput, self.errors
+        );
+
+        if let Some(latency) = &self.latency {
+            println!("\nLatency");
+            for (label, value) in [
+                ("min", latency.min_ms),
+                ("mean", latency.mean_ms),
+                ("p50", latency.p50_ms),
+                ("p90", latency.p90_ms),
+                ("p95", latency.p95_ms),
+                ("p99", latency.p99_ms),
+                ("max", latency.max_ms),
+            ] {
+                println!("  {label:<5}{value:>10.2}ms");
+            }
+        }
+
+        if !self.status_codes.is_empty() {
+            println!("\nStatus codes");
+            for (status, count) in &self.status_codes {
+                println!("  {status}  {count}");
+            }
+        }
+
+        if !self.error_messages.is_empty() {
+            println!("\nErrors");
+            for (message, count) in &self.error_messages {
+                println!("  {count}  {message}");
+            }
+        }
+    }
+}
+
+impl LatencyStats {
+    fn new(mut latencies: Vec<Duration>) -> Option<Self> {
+        if latencies.is_empty() {
+            return None;
+        }
+        latencies.sort();
+        let ms = |duration: &Duration| duration.as_secs_f64() * 1000.0;
+        // Nearest-rank method
+        let percentile = |p: f64| {
+            let rank = (p / 100.0 * latencies.len() as f64).ceil() as usize;
+            ms(&latencies[rank.clamp(1, latencies.len()) - 1])
+        };
+        Some(Self {
+            min_ms: ms(&latencies[0]),
+            mean_ms: latencies.iter().map(ms).sum::<f64>()
+                / latencies.len() as f64,
+            p50_ms: percentile(50.0),
+            p90_ms: percentile(90.0),
+            p95_ms: percentile(95.0),
+            p99_ms: percentile(99.0),
+            max_ms: ms(latencies.last().expect("Latencies is not empty")),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn millis(latencies: impl IntoIterator<Item = u64>) -> Vec<Duration> {
+        latencies.into_iter().map(Duration::from_millis).collect()
+    }
+
+    /// Percentiles use the nearest-rank method, regardless of input order
+    #[rstest]
+    #[case::empty(vec![], None)]
+    #[case::single(
+        millis([5]),
+        Some(LatencyStats {
+            min_ms: 5.0,
+            mean_ms: 5.0,
+            p50_ms: 5.0,
+            p90_ms: 5.0,
+            p95_ms: 5.0,
+            p99_ms: 5.0,
+            max_ms: 5.0,
+        }),
+    )]
+    #[case::ten(
+        millis((1..=10).rev()),
+        Some(LatencyStats {
+            min_ms: 1.0,
+            mean_ms: 5.5,
+            p50_ms: 5.0,
+            p90_ms: 9.0,
+            p95_ms: 10.0,
+            p99_ms: 10.0,
+            max_ms: 10.0,
+        }),
+    )]
+    #[case::hundred(
+        millis(1..=100),
+        Some(LatencyStats {
+            min_ms: 1.0,
+            mean_ms: 50.5,
+            p50_ms: 50.0,
+            p90_ms: 90.0,
+            p95_ms: 95.0,
+            p99_ms: 99.0,
+            max_ms: 100.0,
+        }),
+    )]
+    fn test_latency_stats(
+        #[case] latencies: Vec<Duration>,
+        #[case] expected: Option<LatencyStats>,
+    ) {
+        assert_eq!(LatencyStats::new(latencies), expected);
+    }
+
+    /// Errors are counted as requests, but don't contribute to latency
+    #[rstest]
+    #[case::mixed(
+        vec![
+            Outcome::Response { status: 200, latency: Duration::from_millis(10) },
+            Outcome::Error("refused".into()),
+            Outcome::Response { status: 404, latency: Duration::from_millis(30) },
+            Outcome::Error("timeout".into()),
+            Outcome::Response { status: 200, latency: Duration::from_millis(20) },
+            Outcome::Error("timeout".into()),
+        ],
+        Duration::from_secs(2),
+        3.0,
+        Some(20.0),
+        &[(200, 2), (404, 1)],
+        &[("timeout", 2), ("refused", 1)],
+    )]
+    #[case::errors_only(
+        vec![Outcome::Error("refused".into())],
+        Duration::from_millis(500),
+        2.0,
+        None,
+        &[],
+        &[("refused", 1)],
+    )]
+    #[case::empty(vec![], Duration::ZERO, 0.0, None, &[], &[])]
+    fn test_bench_report(
+        #[case] outcomes: Vec<Outcome>,
+        #[case] elapsed: Duration,
+        #[case] expected_throughput: f64,
+        #[case] expected_mean_ms: Option<f64>,
+        #[case] expected_status_codes: &[(u16, u64)],
+        #[case] expected_errors: &[(&str, u64)],
+    ) {
+        let requests = outcomes.len() as u64;
+        let report = BenchReport::new(outcomes.into_iter(), elapsed);
+        assert_eq!(report.requests, requests);
+        assert_eq!(
+            report.errors,
+            expected_errors.iter().map(|(_, count)| count).sum::<u64>()
+        );
+        assert_eq!(report.throughput, expected_throughput);
+        assert_eq!(
+            report.latency.map(|latency| latency.mean_ms),
+            expected_mean_ms
+        );
+        assert_eq!(
+            report.status_codes.into_iter().collect::<Vec<_>>(),
+            expected_status_codes
+        );
+        assert_eq!(
+            report
+                .error_messages
+                .iter()
+                .map(|(message, count)| (message.as_str(), *count))
+                .collect::<Vec<_>>(),
+            expected_errors
+        );
+    }
+}
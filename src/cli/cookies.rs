@@ -0,0 +1,125 @@
+use crate::{
+    cli::Subcommand,
+    collection::{CollectionFile, ProfileId},
+    db::Database,
+    http::Cookie,
+    GlobalArgs,
+};
+use anyhow::anyhow;
+use async_trait::async_trait;
+use clap::Parser;
+use std::process::ExitCode;
+
+/// View and modify the cookie jar for a collection
+///
+/// Cookies are only stored if the `cookies` config field is enabled. Each
+/// profile has its own jar.
+#[derive(Clone, Debug, Parser)]
+pub struct CookiesCommand {
+    /// Profile whose jar should be used. If omitted, use the jar for requests
+    /// made without a profile
+    #[clap(long = "profile", short, global = true)]
+    profile: Option<ProfileId>,
+
+    #[command(subcommand)]
+    subcommand: CookiesSubcommand,
+}
+
+#[derive(Clone, Debug, clap::Subcommand)]
+enum CookiesSubcommand {
+    /// List all cookies in the jar
+    #[command(visible_alias = "ls")]
+    List,
+    /// Set the value of a cookie, creating it if it doesn't exist
+    Set {
+        name: String,
+        value: String,
+        /// Host that the cookie applies to
+        #[clap(long)]
+        domain: String,
+        /// Path that the cookie applies to
+        #[clap(long, default_value = "/")]
+        path: String,
+    },
+    /// Delete all cookies with a name
+    #[command(visible_alias = "rm")]
+    Delete {
+        name: String,
+        /// Only delete cookies for this domain
+        #[clap(long)]
+        domain: Option<String>,
+    },
+    /// Delete all cookies in the jar
+    Clear,
+}
+
+#[async_trait]
+impl Subcommand for CookiesCommand {
+    async fn execute(self, global: GlobalArgs) -> anyhow::Result<ExitCode> {
+        let collection_path = CollectionFile::try_path(global.file)?;
+        let database = Database::load()?.into_collection(&collection_path)?;
+        let profile_id = self.profile.as_ref();
+        let cookies = database.get_cookies(profile_id)?;
+
+        match self.subcommand {
+            CookiesSubcommand::List => {
+                for cookie in cookies {
+                    println!("{cookie}");
+                }
+            }
+            CookiesSubcommand::Set {
+                name,
+                value,
+                domain,
+                path,
+            } => {
+                let domain = domain.to_ascii_lowercase();
+                // Update the existing cookie if there is one, so we retain
+                // its other attributes
+                let cookie = match cookies.into_iter().find(|cookie| {
+                    cookie.name == name
+                        && cookie.domain == domain
+                        && cookie.path == path
+                }) {
+                    Some(cookie) => Cookie { value, ..cookie },
+                    None => Cookie {
+                        name,
+                        value,
+                        domain,
+                        host_only: true,
+                        path,
+                        secure: false,
+                        http_only: false,
+                        expires: None,
+                    },
+                };
+                database.set_cookie(profile_id, &cookie)?;
+                println!("{cookie}");
+            }
+            CookiesSubcommand::Delete { name, domain } => {
+                let domain = domain.map(|domain| domain.to_ascii_lowercase());
+                let matching = cookies
+                    .into_iter()
+                    .filter(|cookie| {
+                        cookie.name == name
+                            && domain
+                                .as_ref()
+                                .map_or(true, |domain| &cookie.domain == domain)
+                    })
+                    .collect::<Vec<_>>();
+                if matching.is_empty() {
+                    return Err(anyhow!("No cookie named `{name}`"));
+                }
+                for cookie in matching {
+                    database.delete_cookie(profile_id, &cookie)?;
+                    println!("Deleted {cookie}");
+                }
+            }
+            CookiesSubcommand::Clear => {
+                let count = database.clear_cookies(profile_id)?;
+                println!("Deleted {count} cookie(s)");
+            }
+        }
+        Ok(ExitCode::SUCCESS)
+    }
+}
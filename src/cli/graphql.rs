@@ -0,0 +1,102 @@
+use crate::{
+    cli::{request::BuildRequestCommand, Subcommand},
+    collection::CollectionFile,
+    db::Database,
+    GlobalArgs,
+};
+use anyhow::{anyhow, Context};
+use async_trait::async_trait;
+use clap::Parser;
+use std::process::ExitCode;
+
+/// Introspect and check GraphQL recipes
+#[derive(Clone, Debug, Parser)]
+#[clap(aliases=&["gql"])]
+pub struct GraphQlCommand {
+    #[command(subcommand)]
+    subcommand: GraphQlSubcommand,
+}
+
+#[derive(Clone, Debug, clap::Subcommand)]
+enum GraphQlSubcommand {
+    /// Fetch the schema from a recipe's server and cache it. The request is
+    /// built from the recipe, with the body replaced by an introspection
+    /// query. Prints all available operations.
+    Introspect {
+        #[clap(flatten)]
+        build_request: BuildRequestCommand,
+    },
+    /// Check that all fields selected by a recipe's query exist in its cached
+    /// schema. Run `introspect` first to fetch the schema. Exits with an
+    /// error code if any fields are invalid.
+    Check {
+        #[clap(flatten)]
+        build_request: BuildRequestCommand,
+    },
+}
+
+#[async_trait]
+impl Subcommand for GraphQlCommand {
+    async fn execute(self, global: GlobalArgs) -> anyhow::Result<ExitCode> {
+        match self.subcommand {
+            GraphQlSubcommand::Introspect { build_request } => {
+                let (http_engine, request) =
+                    build_request.build_introspection_request(global).await?;
+                let schema = http_engine.introspect(request.into()).await?;
+                for (kind, field) in schema.operations() {
+                    println!("{kind} {field}");
+                }
+                Ok(ExitCode::SUCCESS)
+            }
+            GraphQlSubcommand::Check { build_request } => {
+                let collection_path =
+                    CollectionFile::try_path(global.file.clone())?;
+                let database =
+                    Database::load()?.into_collection(&collection_path)?;
+                // Build the request so we check exactly what would be sent.
+                // Don't trigger any chained requests though
+                let (_, request) =
+                    build_request.build_request(global, false).await?;
+                let query = request
+                    .body
+                    .as_ref()
+                    .and_then(|body| {
+                        serde_json::from_slice::<serde_json::Value>(body).ok()
+                    })
+                    .and_then(|body| {
+                        Some(body.get("query")?.as_str()?.to_owned())
+                    })
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "Recipe `{}` does not have a GraphQL body",
+                            request.recipe_id
+                        )
+                    })?;
+
+                let schema = database
+                    .get_graphql_schema(
+                        request.profile_id.as_ref(),
+                        &request.recipe_id,
+                    )?
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "No schema cached for `{}`; run \
+                            `slumber graphql introspect` first",
+                            request.recipe_id
+                        )
+                    })?;
+                let problems = schema.validate(&query).with_context(|| {
+                    format!("Error checking `{}`", request.recipe_id)
+                })?;
+                if problems.is_empty() {
+                    Ok(ExitCode::SUCCESS)
+                } else {
+                    for problem in problems {
+                        println!("{problem}");
+                    }
+                    Ok(ExitCode::FAILURE)
+                }
+            }
+        }
+    }
+}
@@ -1,15 +1,23 @@
 use crate::{
     cli::Subcommand,
-    collection::{CollectionFile, ProfileId, RecipeId},
+    collection::{
+        CollectionFile, ProfileId, Recipe, RecipeId, RecipeTree,
+        WebSocketRecipe,
+    },
     config::Config,
     db::Database,
-    http::{HttpEngine, RecipeOptions, Request, RequestBuilder},
+    http::{
+        introspection_recipe, HttpEngine, RecipeOptions, Request,
+        RequestBuilder, RequestError, RequestRecord, RequestTimings,
+        StreamHandler,
+    },
     template::{Prompt, Prompter, TemplateContext, TemplateError},
     util::{MaybeStr, ResultExt},
     GlobalArgs,
 };
 use anyhow::{anyhow, Context};
 use async_trait::async_trait;
+use bytesize::ByteSize;
 use clap::Parser;
 use dialoguer::{console::Style, Input, Password};
 use indexmap::IndexMap;
@@ -18,16 +26,25 @@ use reqwest::header::HeaderMap;
 use std::{
     error::Error,
     fmt::{self, Display, Formatter},
-    io::{self, Write},
+    io::{self, IsTerminal, Write},
+    path::PathBuf,
     process::ExitCode,
     str::FromStr,
+    sync::{mpsc, OnceLock},
+    thread,
+    time::Instant,
 };
+use tokio::{signal, sync::oneshot};
 use tracing::warn;
 
 /// Exit code to return when `exit_status` flag is set and the HTTP response has
 /// an error status code
 const HTTP_ERROR_EXIT_CODE: u8 = 2;
 
+/// Exit code to return when the request is cancelled with Ctrl-C. This follows
+/// the shell convention of 128 + SIGINT
+pub const CANCELLED_EXIT_CODE: u8 = 130;
+
 /// Execute a single request, and print its response
 #[derive(Clone, Debug, Parser)]
 #[clap(aliases=&["req", "rq"])]
@@ -47,6 +64,15 @@ pub struct RequestCommand {
     #[clap(long)]
     no_body: bool,
 
+    /// Print a breakdown of how long each phase of the request took
+    #[clap(long, short)]
+    verbose: bool,
+
+    /// Stream the response body to this file instead of printing it. This
+    /// overrides the recipe's `download` field
+    #[clap(long, short = 'O')]
+    output: Option<PathBuf>,
+
     /// Set process exit code based on HTTP response status. If the status is
     /// <400, exit code is 0. If it's >=400, exit code is 2.
     #[clap(long)]
@@ -81,7 +107,28 @@ pub struct BuildRequestCommand {
 #[async_trait]
 impl Subcommand for RequestCommand {
     async fn execute(self, global: GlobalArgs) -> anyhow::Result<ExitCode> {
-        let (http_engine, request) = self
+        // Ctrl-C cancels the request, including any prompts or triggered
+        // requests that are still being built. Dropping the future aborts all
+        // of it.
+        let start_time = Instant::now();
+        tokio::select! {
+            biased;
+            Ok(()) = signal::ctrl_c() => {
+                eprintln!(
+                    "Request cancelled after {}ms",
+                    start_time.elapsed().as_millis()
+                );
+                Ok(ExitCode::from(CANCELLED_EXIT_CODE))
+            }
+            result = self.run(global) => result,
+        }
+    }
+}
+
+impl RequestCommand {
+    /// Build and send the request, then print the response
+    async fn run(self, global: GlobalArgs) -> anyhow::Result<ExitCode> {
+        let (http_engine, mut request) = self
             .build_request
             // Don't execute sub-requests in a dry run
             .build_request(global, !self.dry_run)
@@ -97,6 +144,9 @@ impl Subcommand for RequestCommand {
                     error
                 }
             })?;
+        if let Some(output) = self.output {
+            request.download = Some(output);
+        }
 
         // HTTP engine will be defined iff dry_run was not enabled
         if let Some(http_engine) = http_engine {
@@ -106,8 +156,12 @@ impl Subcommand for RequestCommand {
                 eprintln!("{}", HeaderDisplay(&request.headers));
             }
 
-            // Run the request
-            let record = http_engine.send(request.into()).await?;
+            // Run the request. Downloads report their progress as they go
+            let record = if request.download.is_some() {
+                send_download(http_engine, request).await?
+            } else {
+                http_engine.send(request.into()).await?
+            };
             let status = record.response.status;
 
             // Print stuff!
@@ -117,15 +171,28 @@ impl Subcommand for RequestCommand {
             if self.headers {
                 eprintln!("{}", HeaderDisplay(&record.response.headers));
             }
-            if !self.no_body {
-                // If body is not UTF-8, write the raw bytes instead (e.g if
-                // downloading an image)
-                let body = &record.response.body;
-                if let Some(text) = body.text() {
+            if let (true, Some(timings)) = (self.verbose, &record.timings) {
+                eprintln!("{}", TimingDisplay(timings));
+            }
+            for annotation in &record.annotations {
+                eprintln!("{annotation}");
+            }
+            if let Some(download) = &record.response.download {
+                eprintln!(
+                    "Saved {} to {}",
+                    ByteSize(download.size).to_string_as(false),
+                    download.path.display()
+                );
+            } else if !self.no_body {
+                // If body is not text, write the raw bytes instead (e.g if
+                // downloading an image). Text is converted to UTF-8 according
+                // to its charset
+                let response = &record.response;
+                if let Some(text) = response.text() {
                     print!("{}", text);
                 } else {
                     io::stdout()
-                        .write(body.bytes())
+                        .write(response.body.bytes())
                         .context("Error writing to stdout")?;
                 }
             }
@@ -154,6 +221,111 @@ impl BuildRequestCommand {
         global: GlobalArgs,
         trigger_dependencies: bool,
     ) -> anyhow::Result<(Option<HttpEngine>, Request)> {
+        self.build_request_with(global, trigger_dependencies, |recipes, id| {
+            recipes.get_recipe(id).cloned().ok_or_else(|| {
+                anyhow!(
+                    "No recipe with ID `{id}`; options are: {}",
+                    recipes.recipe_ids().join(", ")
+                )
+            })
+        })
+        .await
+    }
+
+    /// Render the handshake request for the WebSocket recipe specified by the
+    /// user. Dependencies can always be triggered, because the connection is
+    /// going to be opened anyway.
+    pub async fn build_websocket_request(
+        self,
+        global: GlobalArgs,
+    ) -> anyhow::Result<(HttpEngine, Request)> {
+        let (http_engine, request) = self
+            .build_request_with(global, true, |recipes, id| {
+                recipes.get_websocket(id).map(WebSocketRecipe::to_recipe).ok_or_else(
+                    || {
+                        anyhow!(
+                            "No WebSocket recipe with ID `{id}`; options are: {}",
+                            recipes.websocket_ids().join(", ")
+                        )
+                    },
+                )
+            })
+            .await?;
+        // Engine is always present when dependencies can be triggered
+        Ok((http_engine.expect("HTTP engine not initialized"), request))
+    }
+
+    /// Render a GraphQL introspection request for the recipe specified by
+    /// the user. See [introspection_recipe]
+    pub async fn build_introspection_request(
+        self,
+        global: GlobalArgs,
+    ) -> anyhow::Result<(HttpEngine, Request)> {
+        let (http_engine, request) = self
+            .build_request_with(global, true, |recipes, id| {
+                recipes.get_recipe(id).map(introspection_recipe).ok_or_else(
+                    || {
+                        anyhow!(
+                            "No recipe with ID `{id}`; options are: {}",
+                            recipes.recipe_ids().join(", ")
+                        )
+                    },
+                )
+            })
+            .await?;
+        // Engine is always present when dependencies can be triggered
+        Ok((http_engine.expect("HTTP engine not initialized"), request))
+    }
+
+    /// Load the recipe specified by the user and the context needed to render
+    /// it, without rendering it. Use this to build the same recipe multiple
+    /// times. Dependencies can always be triggered.
+    pub async fn load_recipe(
+        self,
+        global: GlobalArgs,
+    ) -> anyhow::Result<(HttpEngine, Recipe, TemplateContext)> {
+        let (http_engine, recipe, template_context) = self
+            .load_recipe_with(global, true, |recipes, id| {
+                recipes.get_recipe(id).cloned().ok_or_else(|| {
+                    anyhow!(
+                        "No recipe with ID `{id}`; options are: {}",
+                        recipes.recipe_ids().join(", ")
+                    )
+                })
+            })
+            .await?;
+        // Engine is always present when dependencies can be triggered
+        Ok((
+            http_engine.expect("HTTP engine not initialized"),
+            recipe,
+            template_context,
+        ))
+    }
+
+    /// Render a request, using the given function to get the recipe from the
+    /// collection
+    async fn build_request_with(
+        self,
+        global: GlobalArgs,
+        trigger_dependencies: bool,
+        get_recipe: impl FnOnce(&RecipeTree, &RecipeId) -> anyhow::Result<Recipe>,
+    ) -> anyhow::Result<(Option<HttpEngine>, Request)> {
+        let (http_engine, recipe, template_context) = self
+            .load_recipe_with(global, trigger_dependencies, get_recipe)
+            .await?;
+        let request = RequestBuilder::new(recipe, RecipeOptions::default())
+            .build(&template_context)
+            .await?;
+        Ok((http_engine, request))
+    }
+
+    /// Load the collection, and find the recipe using the given function
+    async fn load_recipe_with(
+        self,
+        global: GlobalArgs,
+        trigger_dependencies: bool,
+        get_recipe: impl FnOnce(&RecipeTree, &RecipeId) -> anyhow::Result<Recipe>,
+    ) -> anyhow::Result<(Option<HttpEngine>, Recipe, TemplateContext)> {
         let collection_path = CollectionFile::try_path(global.file)?;
         let database = Database::load()?.into_collection(&collection_path)?;
         let collection_file = CollectionFile::load(collection_path).await?;
@@ -178,19 +350,8 @@ impl BuildRequestCommand {
         }
 
         // Find recipe by ID
-        let recipe = collection
-            .recipes
-            .get_recipe(&self.recipe_id)
-            .ok_or_else(|| {
-                anyhow!(
-                    "No recipe with ID `{}`; options are: {}",
-                    self.recipe_id,
-                    collection.recipes.recipe_ids().join(", ")
-                )
-            })?
-            .clone();
+        let recipe = get_recipe(&collection.recipes, &self.recipe_id)?;
 
-        // Build the request
         let overrides: IndexMap<_, _> = self.overrides.into_iter().collect();
         let template_context = TemplateContext {
             selected_profile: self.profile,
@@ -201,55 +362,100 @@ impl BuildRequestCommand {
             prompter: Box::new(CliPrompter),
             recursion_count: Default::default(),
         };
-        let request = RequestBuilder::new(recipe, RecipeOptions::default())
-            .build(&template_context)
-            .await?;
-        Ok((http_engine, request))
+        Ok((http_engine, recipe, template_context))
+    }
+}
+
+/// Send a request that downloads its body to a file, printing its progress
+/// to stderr (if it's a terminal)
+async fn send_download(
+    http_engine: HttpEngine,
+    request: Request,
+) -> Result<RequestRecord, RequestError> {
+    let show_progress = io::stderr().is_terminal();
+    // The stream is never closed early, so just hang onto the sender
+    let (_close_stream, cancel) = oneshot::channel();
+    let stream = StreamHandler {
+        on_retry: Box::new(|_| {}),
+        on_start: Box::new(|_, _| {}),
+        on_chunk: Box::new(|_| {}),
+        on_download: Box::new(move |progress| {
+            if show_progress {
+                eprint!("\r\x1b[2KDownloading... {progress}");
+            }
+        }),
+        cancel,
+    };
+    let result = http_engine.send_streaming(request.into(), stream).await;
+    if show_progress {
+        eprintln!();
     }
+    result
 }
 
 /// Prompt the user for input on the CLI
 #[derive(Debug)]
-struct CliPrompter;
+pub struct CliPrompter;
 
 impl Prompter for CliPrompter {
     fn prompt(&self, prompt: Prompt) {
-        // This will implicitly queue the prompts by blocking the main thread.
-        // Since the CLI has nothing else to do while waiting on a response,
-        // that's fine.
-        let result = if prompt.sensitive {
-            // Dialoguer doesn't support default values here so there's nothing
-            // we can do
-            if prompt.default.is_some() {
-                warn!(
-                    "Default value not supported for sensitive prompts in CLI"
-                );
-            }
+        // Reading from stdin blocks, so prompts are shown on a dedicated
+        // thread. Blocking the async runtime would keep Ctrl-C from being
+        // handled until the prompt is answered. The thread shows prompts one
+        // at a time, which queues them. It's never joined; exiting the process
+        // kills it, even mid-prompt.
+        static PROMPTS: OnceLock<mpsc::Sender<Prompt>> = OnceLock::new();
+        let prompts = PROMPTS.get_or_init(|| {
+            let (sender, receiver) = mpsc::channel::<Prompt>();
+            thread::spawn(move || {
+                for prompt in receiver {
+                    show_prompt(prompt);
+                }
+            });
+            sender
+        });
+        // The receiver lives as long as the process, so this can't fail
+        let _ = prompts.send(prompt);
+    }
+}
 
-            Password::new()
-                .with_prompt(prompt.message)
-                .allow_empty_password(true)
-                .interact()
-        } else {
-            let mut input =
-                Input::new().with_prompt(prompt.message).allow_empty(true);
-            if let Some(default) = prompt.default {
-                input = input.default(default);
-            }
-            input.interact()
-        };
+/// Show a prompt in the terminal and wait for the user's answer. This blocks!
+fn show_prompt(prompt: Prompt) {
+    // The render that asked may have been cancelled while this was queued
+    if prompt.channel.is_closed() {
+        return;
+    }
+
+    let result = if prompt.sensitive {
+        // Dialoguer doesn't support default values here so there's nothing
+        // we can do
+        if prompt.default.is_some() {
+            warn!("Default value not supported for sensitive prompts in CLI");
+        }
 
-        // If we failed to read the value, print an error and report nothing
-        if let Ok(value) =
-            result.context("Error reading value from prompt").traced()
-        {
-            prompt.channel.respond(value);
+        Password::new()
+            .with_prompt(prompt.message)
+            .allow_empty_password(true)
+            .interact()
+    } else {
+        let mut input =
+            Input::new().with_prompt(prompt.message).allow_empty(true);
+        if let Some(default) = prompt.default {
+            input = input.default(default);
         }
+        input.interact()
+    };
+
+    // If we failed to read the value, print an error and report nothing
+    if let Ok(value) =
+        result.context("Error reading value from prompt").traced()
+    {
+        prompt.channel.respond(value);
     }
 }
 
 /// Parse a single key=value pair for an argument
-fn parse_key_val<T, U>(
+pub fn parse_key_val<T, U>(
     s: &str,
 ) -> Result<(T, U), Box<dyn Error + Send + Sync + 'static>>
 where
@@ -265,7 +471,7 @@ where
 }
 
 /// Wrapper making it easy to print a header map
-struct HeaderDisplay<'a>(&'a HeaderMap);
+pub struct HeaderDisplay<'a>(pub &'a HeaderMap);
 
 impl<'a> Display for HeaderDisplay<'a> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
@@ -281,3 +487,23 @@ impl<'a> Display for HeaderDisplay<'a> {
         Ok(())
     }
 }
+
+/// Wrapper to print request timings as a waterfall
+struct TimingDisplay<'a>(&'a RequestTimings);
+
+impl<'a> Display for TimingDisplay<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        const BAR_WIDTH: usize = 40;
+        let total = self.0.total();
+        for phase in self.0.phases() {
+            writeln!(
+                f,
+                "{:<10}{:>8}ms  {}",
+                phase.name,
+                phase.duration.as_millis(),
+                phase.bar(total, BAR_WIDTH)
+            )?;
+        }
+        writeln!(f, "{:<10}{:>8}ms", "Total", total.as_millis())
+    }
+}
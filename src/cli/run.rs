@@ -0,0 +1,169 @@
+use crate::{
+    cli::{
+        request::{parse_key_val, CliPrompter},
+        Subcommand,
+    },
+    collection::{CollectionFile, ProfileId, WorkflowId},
+    config::Config,
+    db::Database,
+    http::{run_workflow, HttpEngine, StepOutcome, StepUpdate},
+    template::TemplateContext,
+    GlobalArgs,
+};
+use anyhow::anyhow;
+use async_trait::async_trait;
+use clap::Parser;
+use dialoguer::console::Style;
+use indexmap::IndexMap;
+use itertools::Itertools;
+use std::process::ExitCode;
+
+/// Run a workflow, sending each of its steps in order
+///
+/// Values captured from each step's response are available to later steps.
+/// The workflow stops at the first step that fails.
+#[derive(Clone, Debug, Parser)]
+pub struct RunCommand {
+    /// ID of the workflow to run
+    workflow_id: WorkflowId,
+
+    /// ID of the profile to pull template values from
+    #[clap(long = "profile", short)]
+    profile: Option<ProfileId>,
+
+    /// List of key=value template field overrides
+    #[clap(
+        long = "override",
+        short = 'o',
+        value_parser = parse_key_val::<String, String>,
+    )]
+    overrides: Vec<(String, String)>,
+}
+
+#[async_trait]
+impl Subcommand for RunCommand {
+    async fn execute(self, global: GlobalArgs) -> anyhow::Result<ExitCode> {
+        let collection_path = CollectionFile::try_path(global.file)?;
+        let database = Database::load()?.into_collection(&collection_path)?;
+        let collection =
+            CollectionFile::load(collection_path).await?.collection;
+        let config = Config::load()?;
+        let http_engine = HttpEngine::new(&config, database.clone());
+
+        if let Some(profile_id) = &self.profile {
+            collection.profiles.get(profile_id).ok_or_else(|| {
+                anyhow!(
+                    "No profile with ID `{profile_id}`; options are: {}",
+                    collection.profiles.keys().join(", ")
+                )
+            })?;
+        }
+        let workflow = collection
+            .workflows
+            .get(&self.workflow_id)
+            .cloned()
+            .ok_or_else(|| {
+                anyhow!(
+                    "No workflow with ID `{}`; options are: {}",
+                    self.workflow_id,
+                    collection.workflows.keys().join(", ")
+                )
+            })?;
+
+        let mut template_context = TemplateContext {
+            selected_profile: self.profile,
+            collection,
+            http_engine: Some(http_engine.clone()),
+            database,
+            overrides: self.overrides.into_iter().collect::<IndexMap<_, _>>(),
+            prompter: Box::new(CliPrompter),
+            recursion_count: Default::default(),
+        };
+
+        let mut counts = StepCounts::default();
+        let passed = run_workflow(
+            &workflow,
+            &http_engine,
+            &mut template_context,
+            |index, update| {
+                let label =
+                    format!("{}. {}", index + 1, workflow.steps[index].recipe);
+                match update {
+                    StepUpdate::Started => {}
+                    StepUpdate::Skipped => {
+                        counts.skipped += 1;
+                        println!("- {label} (skipped)");
+                    }
+                    StepUpdate::Finished(outcome) => {
+                        if outcome.passed() {
+                            counts.passed += 1;
+                        } else {
+                            counts.failed += 1;
+                        }
+                        print_outcome(&label, &outcome);
+                    }
+                }
+            },
+        )
+        .await;
+
+        let not_run = workflow.steps.len()
+            - counts.passed
+            - counts.skipped
+            - counts.failed;
+        println!(
+            "\n{} steps: {} passed, {} skipped, {} failed, {not_run} not run",
+            workflow.steps.len(),
+            counts.passed,
+            counts.skipped,
+            counts.failed,
+        );
+
+        Ok(if passed {
+            ExitCode::SUCCESS
+        } else {
+            ExitCode::FAILURE
+        })
+    }
+}
+
+#[derive(Default)]
+struct StepCounts {
+    passed: usize,
+    skipped: usize,
+    failed: usize,
+}
+
+/// Print the outcome of a single step, including its captures and assertions
+fn print_outcome(label: &str, outcome: &StepOutcome) {
+    let (pass_style, fail_style) =
+        (Style::new().green(), Style::new().red().bold());
+    let mark = if outcome.passed() {
+        pass_style.apply_to("✓")
+    } else {
+        fail_style.apply_to("✗")
+    };
+    match &outcome.record {
+        Some(record) => {
+            let duration = record.duration().num_milliseconds();
+            println!(
+                "{mark} {label} ({}, {duration}ms)",
+                record.response.status
+            );
+        }
+        None => println!("{mark} {label}"),
+    }
+    if !outcome.captures.is_empty() {
+        println!("    captured {}", outcome.captures.keys().join(", "));
+    }
+    for assertion in &outcome.assertions {
+        if assertion.passed() {
+            println!("    {} {assertion}", pass_style.apply_to("✓"));
+        } else {
+            println!("    {} {assertion}", fail_style.apply_to("✗"));
+        }
+    }
+    if let Some(error) = &outcome.error {
+        println!("    {}", fail_style.apply_to(format!("{error:#}")));
+    }
+}
@@ -0,0 +1,132 @@
+use crate::{
+    cli::{request::HeaderDisplay, Subcommand},
+    collection::{cereal::serde_duration, CollectionFile, ProfileId},
+    db::Database,
+    http::{MockHit, MockOptions, MockServer},
+    util::MaybeStr,
+    GlobalArgs,
+};
+use anyhow::Context;
+use async_trait::async_trait;
+use clap::Parser;
+use dialoguer::console::Style;
+use reqwest::StatusCode;
+use std::{
+    fmt::Write,
+    net::{IpAddr, SocketAddr, TcpListener},
+    process::ExitCode,
+    time::Duration,
+};
+use tokio::signal;
+
+/// Start a mock HTTP server that answers requests from the collection
+///
+/// Each request is matched to a recipe by its method and URL path, with the
+/// host stripped. It's answered with the recipe's `example_response`, or else
+/// the most recent response to the recipe in history. Every request received
+/// is printed, along with how it was answered.
+#[derive(Clone, Debug, Parser)]
+pub struct ServeCommand {
+    /// ID of the profile used to resolve each recipe's host, and to find
+    /// responses in history
+    #[clap(long = "profile", short)]
+    profile: Option<ProfileId>,
+
+    /// Address to listen on
+    #[clap(long, default_value = "127.0.0.1")]
+    host: IpAddr,
+
+    /// Port to listen on
+    #[clap(long, default_value_t = 3000)]
+    port: u16,
+
+    /// Respond to every matched request with this status, instead of the
+    /// response's own
+    #[clap(long)]
+    status: Option<StatusCode>,
+
+    /// Wait this long before every response (e.g. `500ms`). This replaces
+    /// any `latency` in example responses
+    #[clap(long, value_parser = serde_duration::parse)]
+    latency: Option<Duration>,
+}
+
+#[async_trait]
+impl Subcommand for ServeCommand {
+    async fn execute(self, global: GlobalArgs) -> anyhow::Result<ExitCode> {
+        let collection_path = CollectionFile::try_path(global.file)?;
+        let database = Database::load()?.into_collection(&collection_path)?;
+        let collection =
+            CollectionFile::load(collection_path).await?.collection;
+        let server = MockServer::new(
+            &collection,
+            database,
+            MockOptions {
+                profile_id: self.profile,
+                status: self.status,
+                latency: self.latency,
+            },
+        )?;
+
+        let address = SocketAddr::new(self.host, self.port);
+        let listener = TcpListener::bind(address)
+            .with_context(|| format!("Error listening on {address}"))?;
+        println!(
+            "Serving {} recipes on http://{address}",
+            server.routes().len()
+        );
+        let path_width = server
+            .routes()
+            .iter()
+            .map(|route| route.path().len())
+            .max()
+            .unwrap_or_default();
+        for route in server.routes() {
+            println!(
+                "  {:<7} {:<path_width$}  {}",
+                route.method.as_str(),
+                route.path(),
+                route.recipe_id
+            );
+        }
+        println!();
+
+        server
+            .serve(listener, print_hit, async {
+                let _ = signal::ctrl_c().await;
+            })
+            .await?;
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+/// Print a request, and how it was answered. Requests can be handled
+/// concurrently, so everything is printed at once to keep them from
+/// interleaving.
+fn print_hit(hit: MockHit) {
+    let status_style = if hit.status.is_success() {
+        Style::new().green()
+    } else if hit.status.is_client_error() || hit.status.is_server_error() {
+        Style::new().red()
+    } else {
+        Style::new().yellow()
+    };
+    let recipe_id = hit
+        .recipe_id
+        .as_ref()
+        .map(ToString::to_string)
+        .unwrap_or_else(|| "-".into());
+
+    let mut output = format!(
+        "{} {} -> {recipe_id} {} ({})\n{}",
+        hit.method,
+        hit.uri,
+        status_style.apply_to(hit.status),
+        hit.source,
+        HeaderDisplay(&hit.headers),
+    );
+    if !hit.body.is_empty() {
+        let _ = writeln!(output, "{}", MaybeStr(&hit.body));
+    }
+    println!("{output}");
+}
@@ -0,0 +1,417 @@
+use crate::{
+    cli::{
+        request::{parse_key_val, CliPrompter},
+        Subcommand,
+    },
+    collection::{Collection, CollectionFile, ProfileId, Recipe, RecipeId},
+    config::Config,
+    db::Database,
+    http::{AssertionResult, HttpEngine, RecipeOptions, RequestBuilder},
+    template::TemplateContext,
+    GlobalArgs,
+};
+use anyhow::{anyhow, bail, Context};
+use async_trait::async_trait;
+use clap::Parser;
+use dialoguer::console::Style;
+use indexmap::IndexMap;
+use itertools::Itertools;
+use reqwest::StatusCode;
+use std::{
+    collections::HashSet,
+    fmt::Write,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    time::{Duration, Instant},
+};
+
+/// Send requests and check their responses against each recipe's assertions
+///
+/// Recipes are sent one at a time. If a recipe uses a chain that loads
+/// another tested recipe's response, that recipe is sent first.
+#[derive(Clone, Debug, Parser)]
+pub struct TestCommand {
+    /// Recipes and/or folders to test. Folders include every recipe under
+    /// them that has assertions. If omitted, test every recipe with
+    /// assertions
+    recipe_ids: Vec<RecipeId>,
+
+    /// ID of the profile to pull template values from
+    #[clap(long = "profile", short)]
+    profile: Option<ProfileId>,
+
+    /// List of key=value template field overrides
+    #[clap(
+        long = "override",
+        short = 'o',
+        value_parser = parse_key_val::<String, String>,
+    )]
+    overrides: Vec<(String, String)>,
+
+    /// Write results to this file, in JUnit XML format
+    #[clap(long)]
+    junit: Option<PathBuf>,
+}
+
+/// Outcome of testing a single recipe
+struct TestResult {
+    recipe_id: RecipeId,
+    duration: Duration,
+    /// Error if the request couldn't be built or sent
+    outcome: anyhow::Result<TestOutcome>,
+}
+
+/// Outcome of a test that got a response
+struct TestOutcome {
+    status: StatusCode,
+    assertions: Vec<AssertionResult>,
+}
+
+#[async_trait]
+impl Subcommand for TestCommand {
+    async fn execute(self, global: GlobalArgs) -> anyhow::Result<ExitCode> {
+        let collection_path = CollectionFile::try_path(global.file)?;
+        let database = Database::load()?.into_collection(&collection_path)?;
+        let collection =
+            CollectionFile::load(collection_path).await?.collection;
+        let config = Config::load()?;
+        let http_engine = HttpEngine::new(&config, database.clone());
+
+        if let Some(profile_id) = &self.profile {
+            collection.profiles.get(profile_id).ok_or_else(|| {
+                anyhow!(
+                    "No profile with ID `{profile_id}`; options are: {}",
+                    collection.profiles.keys().join(", ")
+                )
+            })?;
+        }
+        let recipes = select_recipes(&collection, &self.recipe_ids)?;
+        if recipes.is_empty() {
+            bail!("No recipes with assertions to test");
+        }
+
+        let template_context = TemplateContext {
+            selected_profile: self.profile,
+            collection,
+            http_engine: Some(http_engine.clone()),
+            database,
+            overrides: self.overrides.into_iter().collect::<IndexMap<_, _>>(),
+            prompter: Box::new(CliPrompter),
+            recursion_count: Default::default(),
+        };
+
+        let mut results = Vec::with_capacity(recipes.len());
+        for recipe in recipes {
+            let result =
+                run_test(&http_engine, &template_context, recipe).await;
+            print_result(&result);
+            results.push(result);
+        }
+
+        let passed = results.iter().filter(|result| result.passed()).count();
+        let failed = results.len() - passed;
+        println!(
+            "\n{} tests: {passed} passed, {failed} failed",
+            results.len()
+        );
+
+        if let Some(path) = &self.junit {
+            write_junit(path, &results)?;
+        }
+
+        Ok(if failed == 0 {
+            ExitCode::SUCCESS
+        } else {
+            ExitCode::FAILURE
+        })
+    }
+}
+
+impl TestResult {
+    fn passed(&self) -> bool {
+        match &self.outcome {
+            Ok(outcome) => outcome.assertions.iter().all(|a| a.passed()),
+            Err(_) => false,
+        }
+    }
+}
+
+/// Get the recipes to test, in the order they should be sent. A recipe named
+/// explicitly is always tested, even if it has no assertions. A recipe
+/// included via a folder, or because no IDs were given, is only tested if it
+/// has assertions.
+fn select_recipes(
+    collection: &Collection,
+    ids: &[RecipeId],
+) -> anyhow::Result<Vec<Recipe>> {
+    for id in ids {
+        match collection.recipes.get(id) {
+            Some(node) if node.websocket().is_none() => {}
+            _ => bail!(
+                "No recipe or folder with ID `{id}`; options are: {}",
+                collection
+                    .recipes
+                    .iter()
+                    .filter(|(_, node)| node.websocket().is_none())
+                    .map(|(_, node)| node.id())
+                    .join(", ")
+            ),
+        }
+    }
+
+    let selected = collection
+        .recipes
+        .iter()
+        .filter_map(|(lookup_key, node)| {
+            let recipe = node.recipe()?;
+            let included = ids.contains(&recipe.id)
+                || (!recipe.assertions.is_empty()
+                    && (ids.is_empty()
+                        || lookup_key
+                            .as_slice()
+                            .iter()
+                            .any(|id| ids.contains(id))));
+            included.then_some(recipe)
+        })
+        .collect_vec();
+
+    // Send each recipe after any selected recipes that it depends on. Visiting
+    // each recipe only once also breaks dependency cycles
+    fn visit<'a>(
+        collection: &'a Collection,
+        selected: &[&'a Recipe],
+        recipe: &'a Recipe,
+        visited: &mut HashSet<&'a RecipeId>,
+        sorted: &mut Vec<Recipe>,
+    ) {
+        if !visited.insert(&recipe.id) {
+            return;
+        }
+        for dependency in collection.recipe_dependencies(recipe) {
+            if let Some(dependency) =
+                selected.iter().find(|recipe| &recipe.id == dependency)
+            {
+                visit(collection, selected, dependency, visited, sorted);
+            }
+        }
+        sorted.push(recipe.clone());
+    }
+
+    let mut visited = HashSet::new();
+    let mut sorted = Vec::with_capacity(selected.len());
+    for recipe in &selected {
+        visit(collection, &selected, recipe, &mut visited, &mut sorted);
+    }
+    Ok(sorted)
+}
+
+/// Build and send a recipe's request, then check its assertions
+async fn run_test(
+    http_engine: &HttpEngine,
+    template_context: &TemplateContext,
+    recipe: Recipe,
+) -> TestResult {
+    let start = Instant::now();
+    let recipe_id = recipe.id.clone();
+    let assertions = recipe.assertions.clone();
+    let outcome: anyhow::Result<TestOutcome> = async {
+        let request = RequestBuilder::new(recipe, RecipeOptions::default())
+            .build(template_context)
+            .await?;
+        let record = http_engine.clone().send(request.into()).await?;
+        // If the body can't be parsed, body assertions will fail. Don't
+        // bother parsing if there are none
+        let body = if assertions.body.is_empty() {
+            None
+        } else {
+            record.response.parse_body().ok()
+        };
+        Ok(TestOutcome {
+            status: record.response.status,
+            assertions: assertions.check(&record, body.as_deref()),
+        })
+    }
+    .await;
+    TestResult {
+        recipe_id,
+        duration: start.elapsed(),
+        outcome,
+    }
+}
+
+/// Print the result of a single test, and the result of each of its
+/// assertions
+fn print_result(result: &TestResult) {
+    let (pass_style, fail_style) =
+        (Style::new().green(), Style::new().red().bold());
+    let (mark, style) = if result.passed() {
+        ("✓", &pass_style)
+    } else {
+        ("✗", &fail_style)
+    };
+    let duration = result.duration.as_millis();
+    match &result.outcome {
+        Ok(outcome) => {
+            println!(
+                "{} {} ({}, {duration}ms)",
+                style.apply_to(mark),
+                result.recipe_id,
+                outcome.status
+            );
+            for assertion in &outcome.assertions {
+                if assertion.passed() {
+                    println!("    {} {assertion}", pass_style.apply_to("✓"));
+                } else {
+                    println!("    {} {assertion}", fail_style.apply_to("✗"));
+                }
+            }
+        }
+        Err(error) => {
+            println!(
+                "{} {} ({duration}ms)",
+                style.apply_to(mark),
+                result.recipe_id
+            );
+            println!("    {}", fail_style.apply_to(format!("{error:#}")));
+        }
+    }
+}
+
+/// Write test results to a file in JUnit XML format. Each recipe is a test
+/// case, and each failed assertion is a `<failure>`. Requests that couldn't be
+/// built or sent are reported as an `<error>`.
+fn write_junit(path: &Path, results: &[TestResult]) -> anyhow::Result<()> {
+    let errors = results
+        .iter()
+        .filter(|result| result.outcome.is_err())
+        .count();
+    let failures = results
+        .iter()
+        .filter(|result| result.outcome.is_ok() && !result.passed())
+        .count();
+    let time: f64 = results
+        .iter()
+        .map(|result| result.duration.as_secs_f64())
+        .sum();
+    let counts = format!(
+        r#"tests="{}" failures="{failures}" errors="{errors}" time="{time:.3}""#,
+        results.len()
+    );
+
+    // Writing to a string is infallible
+    let mut xml = String::new();
+    let _ = writeln!(xml, r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    let _ = writeln!(xml, "<testsuites {counts}>");
+    let _ = writeln!(xml, r#"  <testsuite name="slumber" {counts}>"#);
+    for result in results {
+        let _ = writeln!(
+            xml,
+            r#"    <testcase name="{}" classname="slumber" time="{:.3}">"#,
+            escape_xml(&result.recipe_id),
+            result.duration.as_secs_f64()
+        );
+        match &result.outcome {
+            Ok(outcome) => {
+                for assertion in
+                    outcome.assertions.iter().filter(|a| !a.passed())
+                {
+                    let _ = writeln!(
+                        xml,
+                        r#"      <failure message="{}"/>"#,
+                        escape_xml(&assertion.to_string())
+                    );
+                }
+            }
+            Err(error) => {
+                let _ = writeln!(
+                    xml,
+                    r#"      <error message="{}"/>"#,
+                    escape_xml(&format!("{error:#}"))
+                );
+            }
+        }
+        let _ = writeln!(xml, "    </testcase>");
+    }
+    let _ = writeln!(xml, "  </testsuite>");
+    let _ = writeln!(xml, "</testsuites>");
+
+    std::fs::write(path, xml).with_context(|| {
+        format!("Error writing JUnit report to `{}`", path.display())
+    })
+}
+
+/// Escape text for use in an XML attribute
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\n' => escaped.push_str("&#10;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        collection::{ChainSource, RecipeNode, RecipeTree},
+        http::Assertions,
+        test_util::*,
+    };
+    use factori::create;
+    use rstest::rstest;
+
+    /// A recipe is sent after the recipes it depends on, even if it comes
+    /// first in the collection. Dependencies that aren't selected are skipped
+    #[rstest]
+    #[case::all(&[], &["login", "get_user"])]
+    #[case::explicit(&["get_user", "login"], &["login", "get_user"])]
+    #[case::dependency_not_selected(&["get_user"], &["get_user"])]
+    fn test_select_recipes(#[case] ids: &[&str], #[case] expected: &[&str]) {
+        let assertions = Assertions {
+            headers: vec!["content-type".into()],
+            ..Default::default()
+        };
+        let get_user = create!(
+            Recipe,
+            id: "get_user".into(),
+            url: "{{host}}/users/{{chains.user_id}}".into(),
+            assertions: assertions.clone(),
+        );
+        let login = create!(Recipe, id: "login".into(), assertions: assertions);
+        let chain = create!(
+            Chain,
+            id: "user_id".into(),
+            source: ChainSource::Request {
+                recipe: "login".into(),
+                trigger: Default::default(),
+            },
+        );
+        let collection = create!(
+            Collection,
+            recipes: RecipeTree::new(IndexMap::from([
+                (get_user.id.clone(), RecipeNode::Recipe(get_user)),
+                (login.id.clone(), RecipeNode::Recipe(login)),
+            ]))
+            .unwrap(),
+            chains: IndexMap::from([(chain.id.clone(), chain)]),
+        );
+
+        let ids = ids.iter().copied().map(RecipeId::from).collect_vec();
+        let selected = select_recipes(&collection, &ids).unwrap();
+        assert_eq!(
+            selected
+                .iter()
+                .map(|recipe| recipe.id.as_str())
+                .collect_vec(),
+            expected
+        );
+    }
+}
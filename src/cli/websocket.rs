@@ -0,0 +1,80 @@
+use crate::{
+    cli::{request::BuildRequestCommand, Subcommand},
+    http::{WebSocketEvent, WebSocketMessage},
+    GlobalArgs,
+};
+use anyhow::Context;
+use async_trait::async_trait;
+use clap::Parser;
+use std::{
+    io::{self, BufRead, Write},
+    process::ExitCode,
+    thread,
+};
+use tokio::sync::mpsc;
+
+/// Open a WebSocket connection from a WebSocket recipe. Each line from stdin
+/// is sent as a text message, and each received message is printed on its own
+/// line. The connection is closed when stdin ends.
+#[derive(Clone, Debug, Parser)]
+#[clap(aliases=&["ws"])]
+pub struct WebSocketCommand {
+    #[clap(flatten)]
+    build_request: BuildRequestCommand,
+}
+
+#[async_trait]
+impl Subcommand for WebSocketCommand {
+    async fn execute(self, global: GlobalArgs) -> anyhow::Result<ExitCode> {
+        let (http_engine, request) =
+            self.build_request.build_websocket_request(global).await?;
+
+        // Forward stdin lines until it closes. Dropping the sender tells the
+        // session to close the connection. This uses a plain thread rather
+        // than a task, because a blocked read would stop the runtime from
+        // shutting down if the server closes the connection first
+        let (sender, outgoing) = mpsc::unbounded_channel();
+        thread::spawn(move || {
+            for line in io::stdin().lock().lines() {
+                let Ok(line) = line else { break };
+                if sender.send(line).is_err() {
+                    // Connection is already closed
+                    break;
+                }
+            }
+        });
+
+        // Everything other than received messages goes to stderr, so the
+        // output can be piped elsewhere
+        let mut stdout = io::stdout();
+        let mut result = Ok(());
+        http_engine
+            .connect_websocket(request.into(), outgoing, |event| match event {
+                // Hang onto the first error, if any
+                WebSocketEvent::Received(message) if result.is_ok() => {
+                    result = print_message(&mut stdout, &message);
+                }
+                WebSocketEvent::Closed {
+                    reason: Some(reason),
+                } => eprintln!("Connection closed: {reason}"),
+                _ => {}
+            })
+            .await?;
+        result.context("Error writing to stdout")?;
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+/// Print a received message on its own line. Binary messages are written raw
+fn print_message(
+    stdout: &mut impl Write,
+    message: &WebSocketMessage,
+) -> io::Result<()> {
+    match message {
+        WebSocketMessage::Text(text) => writeln!(stdout, "{text}"),
+        WebSocketMessage::Binary(bytes) => {
+            stdout.write_all(bytes)?;
+            writeln!(stdout)
+        }
+    }
+}
@@ -1,7 +1,7 @@
 //! A request collection defines recipes, profiles, etc. that make requests
 //! possible
 
-mod cereal;
+pub(crate) mod cereal;
 mod insomnia;
 mod models;
 mod recipe_tree;
@@ -2,13 +2,14 @@
 
 use crate::{
     collection::{
-        recipe_tree::RecipeNode, Chain, ChainId, Profile, ProfileId, RecipeId,
+        recipe_tree::RecipeNode, Chain, ChainId, Profile, ProfileId,
+        RecipeBody, RecipeId, Workflow, WorkflowId,
     },
     template::Template,
 };
 use serde::{
-    de::{Error, Visitor},
-    Deserialize, Deserializer,
+    de::{EnumAccess, Error, VariantAccess, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
 };
 use std::hash::Hash;
 
@@ -35,10 +36,19 @@ impl HasId for RecipeNode {
         match self {
             RecipeNode::Folder(folder) => folder.id = id,
             RecipeNode::Recipe(recipe) => recipe.id = id,
+            RecipeNode::WebSocket(websocket) => websocket.id = id,
         }
     }
 }
 
+impl HasId for Workflow {
+    type Id = WorkflowId;
+
+    fn set_id(&mut self, id: Self::Id) {
+        self.id = id;
+    }
+}
+
 impl HasId for Chain {
     type Id = ChainId;
 
@@ -76,52 +86,130 @@ impl<'de> Deserialize<'de> for Template {
     where
         D: Deserializer<'de>,
     {
-        struct TemplateVisitor;
+        deserializer.deserialize_any(TemplateVisitor)
+    }
+}
+
+struct TemplateVisitor;
+
+macro_rules! visit_primitive {
+    ($func:ident, $type:ty) => {
+        fn $func<E>(self, v: $type) -> Result<Self::Value, E>
+        where
+            E: Error,
+        {
+            Template::try_from(v.to_string()).map_err(E::custom)
+        }
+    };
+}
+
+impl<'de> Visitor<'de> for TemplateVisitor {
+    type Value = Template;
+
+    fn expecting(
+        &self,
+        formatter: &mut std::fmt::Formatter,
+    ) -> std::fmt::Result {
+        formatter.write_str("string, number, or boolean")
+    }
+
+    visit_primitive!(visit_bool, bool);
+    visit_primitive!(visit_u64, u64);
+    visit_primitive!(visit_i64, i64);
+    visit_primitive!(visit_f64, f64);
+    visit_primitive!(visit_str, &str);
+}
+
+/// Raw bodies are serialized as a plain template. Structured bodies use a tag
+/// to indicate their type, e.g. `!graphql`
+impl Serialize for RecipeBody {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            Self::Raw(template) => template.serialize(serializer),
+            Self::GraphQl(body) => serializer.serialize_newtype_variant(
+                "RecipeBody",
+                1,
+                Self::GRAPHQL_TAG,
+                body,
+            ),
+        }
+    }
+}
+
+/// Accept anything that's a valid template as a raw body, or a tagged
+/// structured body
+impl<'de> Deserialize<'de> for RecipeBody {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct RecipeBodyVisitor;
 
-        macro_rules! visit_primitive {
+        macro_rules! visit_raw {
             ($func:ident, $type:ty) => {
                 fn $func<E>(self, v: $type) -> Result<Self::Value, E>
                 where
                     E: Error,
                 {
-                    Template::try_from(v.to_string()).map_err(E::custom)
+                    TemplateVisitor.$func(v).map(RecipeBody::Raw)
                 }
             };
         }
 
-        impl<'de> Visitor<'de> for TemplateVisitor {
-            type Value = Template;
+        impl<'de> Visitor<'de> for RecipeBodyVisitor {
+            type Value = RecipeBody;
 
             fn expecting(
                 &self,
                 formatter: &mut std::fmt::Formatter,
             ) -> std::fmt::Result {
-                formatter.write_str("string, number, or boolean")
+                formatter.write_str("string, number, boolean, or !graphql")
             }
 
-            visit_primitive!(visit_bool, bool);
-            visit_primitive!(visit_u64, u64);
-            visit_primitive!(visit_i64, i64);
-            visit_primitive!(visit_f64, f64);
-            visit_primitive!(visit_str, &str);
+            visit_raw!(visit_bool, bool);
+            visit_raw!(visit_u64, u64);
+            visit_raw!(visit_i64, i64);
+            visit_raw!(visit_f64, f64);
+            visit_raw!(visit_str, &str);
+
+            fn visit_enum<A>(self, data: A) -> Result<Self::Value, A::Error>
+            where
+                A: EnumAccess<'de>,
+            {
+                let (tag, variant) = data.variant::<String>()?;
+                match tag.as_str() {
+                    RecipeBody::GRAPHQL_TAG => {
+                        variant.newtype_variant().map(RecipeBody::GraphQl)
+                    }
+                    _ => Err(A::Error::unknown_variant(
+                        &tag,
+                        &[RecipeBody::GRAPHQL_TAG],
+                    )),
+                }
+            }
         }
 
-        deserializer.deserialize_any(TemplateVisitor)
+        deserializer.deserialize_any(RecipeBodyVisitor)
     }
 }
 
-/// Serialize/deserialize a duration with unit shorthand. This does *not* handle
-/// subsecond precision. Supported units are:
+/// Serialize/deserialize a duration with unit shorthand. Supported units are:
+/// - ms
 /// - s
 /// - m
 /// - h
 /// - d
-/// Examples: `30s`, `5m`, `12h`, `3d`
+/// Examples: `500ms`, `30s`, `5m`, `12h`, `3d`. Durations are always
+/// serialized as seconds, so subsecond precision is lost.
 pub mod serde_duration {
     use regex::Regex;
     use serde::{de::Error, Deserialize, Deserializer, Serializer};
     use std::{sync::OnceLock, time::Duration};
 
+    const UNIT_MILLISECOND: &str = "ms";
     const UNIT_SECOND: &str = "s";
     const UNIT_MINUTE: &str = "m";
     const UNIT_HOUR: &str = "h";
@@ -146,36 +234,80 @@ pub mod serde_duration {
     where
         D: Deserializer<'de>,
     {
+        let s = String::deserialize(deserializer)?;
+        parse(&s).map_err(D::Error::custom)
+    }
+
+    /// Parse a duration from a string such as `30s` or `500ms`
+    pub fn parse(s: &str) -> Result<Duration, String> {
         // unstable: use LazyLock https://github.com/rust-lang/rust/pull/121377
         static REGEX: OnceLock<Regex> = OnceLock::new();
-        let s = String::deserialize(deserializer)?;
         let regex = REGEX.get_or_init(|| Regex::new("^(\\d+)(\\w+)$").unwrap());
-        if let Some(captures) = regex.captures(&s) {
+        if let Some(captures) = regex.captures(s) {
             let quantity: u64 = captures
                 .get(1)
                 .expect("No first group")
                 .as_str()
                 .parse()
                 // Error should be impossible because the regex only allows ints
-                .map_err(|_| D::Error::custom("Invalid int"))?;
+                .map_err(|_| "Invalid int")?;
             let unit = captures.get(2).expect("No second group").as_str();
             let seconds = match unit {
+                UNIT_MILLISECOND => return Ok(Duration::from_millis(quantity)),
                 UNIT_SECOND => quantity,
                 UNIT_MINUTE => quantity * 60,
                 UNIT_HOUR => quantity * 60 * 60,
                 UNIT_DAY => quantity * 60 * 60 * 24,
                 _ => {
-                    return Err(D::Error::custom(format!(
+                    return Err(format!(
                         "Unknown duration unit: {unit:?}; must be one of {:?}",
-                        [UNIT_SECOND, UNIT_MINUTE, UNIT_HOUR, UNIT_DAY]
-                    )))
+                        [
+                            UNIT_MILLISECOND,
+                            UNIT_SECOND,
+                            UNIT_MINUTE,
+                            UNIT_HOUR,
+                            UNIT_DAY
+                        ]
+                    ))
                 }
             };
             Ok(Duration::from_secs(seconds))
         } else {
-            Err(D::Error::custom(
-                "Invalid duration, must be \"<quantity><unit>\" (e.g. \"12d\")",
-            ))
+            Err(
+                "Invalid duration, must be \"<quantity><unit>\" (e.g. \"12d\")"
+                    .into(),
+            )
+        }
+    }
+
+    /// (De)serialize an optional duration, in the same format
+    pub mod option {
+        use serde::{Deserialize, Deserializer, Serializer};
+        use std::time::Duration;
+
+        pub fn serialize<S>(
+            duration: &Option<Duration>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            match duration {
+                Some(duration) => super::serialize(duration, serializer),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D>(
+            deserializer: D,
+        ) -> Result<Option<Duration>, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            #[derive(Deserialize)]
+            struct Wrap(#[serde(with = "super")] Duration);
+
+            Ok(Option::<Wrap>::deserialize(deserializer)?.map(|Wrap(d)| d))
         }
     }
 
@@ -208,6 +340,7 @@ pub mod serde_duration {
         }
 
         #[rstest]
+        #[case::milliseconds("500ms", Duration::from_millis(500))]
         #[case::seconds_zero("0s", Duration::from_secs(0))]
         #[case::seconds_short("1s", Duration::from_secs(1))]
         #[case::seconds_longer("100s", Duration::from_secs(100))]
@@ -236,7 +369,7 @@ pub mod serde_duration {
         )]
         #[case::invalid_unit(
             "3hr",
-            r#"Unknown duration unit: "hr"; must be one of ["s", "m", "h", "d"]"#
+            r#"Unknown duration unit: "hr"; must be one of ["ms", "s", "m", "h", "d"]"#
         )]
         fn test_deserialize_error(
             #[case] s: &'static str,
@@ -249,7 +382,13 @@ pub mod serde_duration {
 
 #[cfg(test)]
 mod tests {
-    use crate::template::Template;
+    use crate::{
+        collection::{
+            Collection, GraphQlBody, RecipeBody, Workflow, WorkflowStep,
+        },
+        template::Template,
+    };
+    use indexmap::{indexmap, IndexMap};
     use rstest::rstest;
     use serde_test::{assert_de_tokens, Token};
 
@@ -270,4 +409,98 @@ mod tests {
     fn test_deserialize_template(#[case] token: Token, #[case] expected: &str) {
         assert_de_tokens(&Template::from(expected), &[token]);
     }
+
+    #[rstest]
+    #[case::raw("body: hello {{name}}", RecipeBody::Raw("hello {{name}}".into()))]
+    #[case::raw_number("body: 3", RecipeBody::Raw("3".into()))]
+    #[case::graphql(
+        "body: !graphql\n  query: \"{ user(id: 1) { name } }\"\n  variables: \"{}\"",
+        RecipeBody::GraphQl(GraphQlBody {
+            query: "{ user(id: 1) { name } }".into(),
+            variables: Some("{}".into()),
+            operation_name: None,
+        })
+    )]
+    fn test_recipe_body(#[case] yaml: &str, #[case] expected: RecipeBody) {
+        #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+        struct Wrap {
+            body: RecipeBody,
+        }
+
+        let deserialized: Wrap = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(deserialized.body, expected);
+        // Make sure it round trips
+        let serialized = serde_yaml::to_string(&deserialized).unwrap();
+        assert_eq!(
+            serde_yaml::from_str::<Wrap>(&serialized).unwrap(),
+            deserialized
+        );
+    }
+
+    #[test]
+    fn test_recipe_body_unknown_tag() {
+        let error = serde_yaml::from_str::<RecipeBody>("!soap {}").unwrap_err();
+        assert!(
+            error.to_string().contains("unknown variant `soap`"),
+            "Unexpected error {error}"
+        );
+    }
+
+    /// Workflow IDs come from their keys, and steps use `if` for conditions
+    #[test]
+    fn test_workflows() {
+        let yaml = r#"
+workflows:
+  onboarding:
+    steps:
+      - recipe: login
+        capture:
+          token: $.token
+      - recipe: invite
+        overrides:
+          email: "{{user}}@example.com"
+        if: "{{send_invite}}"
+"#;
+        let collection: Collection = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(
+            collection.workflows,
+            indexmap! {
+                "onboarding".to_owned().into() => Workflow {
+                    id: "onboarding".to_owned().into(),
+                    name: None,
+                    steps: vec![
+                        WorkflowStep {
+                            recipe: "login".into(),
+                            overrides: IndexMap::new(),
+                            capture: indexmap! {
+                                "token".into() => "$.token".parse().unwrap(),
+                            },
+                            assertions: Default::default(),
+                            condition: None,
+                        },
+                        WorkflowStep {
+                            recipe: "invite".into(),
+                            overrides: indexmap! {
+                                "email".into() => "{{user}}@example.com".into(),
+                            },
+                            capture: IndexMap::new(),
+                            assertions: Default::default(),
+                            condition: Some("{{send_invite}}".into()),
+                        },
+                    ],
+                },
+            }
+        );
+    }
+
+    /// Empty fields can be omitted, and aren't written back out
+    #[test]
+    fn test_workflow_defaults() {
+        let workflow: Workflow =
+            serde_yaml::from_str("name: Nothing yet").unwrap();
+        assert_eq!(workflow.steps, vec![]);
+
+        let step: WorkflowStep = serde_yaml::from_str("recipe: login").unwrap();
+        assert_eq!(serde_yaml::to_string(&step).unwrap(), "recipe: login\n");
+    }
 }
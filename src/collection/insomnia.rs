@@ -67,6 +67,9 @@ impl Collection {
             // Parse templates into chains:
             // https://github.com/LucasPickering/slumber/issues/164
             chains: IndexMap::new(),
+            retry: None,
+            hooks: Default::default(),
+            workflows: IndexMap::new(),
             _ignore: serde::de::IgnoredAny,
         })
     }
@@ -249,6 +252,7 @@ impl From<Environment> for Profile {
                 .into_iter()
                 .map(|(k, v)| (k, Template::dangerous(v)))
                 .collect(),
+            proxy: None,
         }
     }
 }
@@ -260,6 +264,8 @@ impl From<RequestGroup> for RecipeNode {
             name: Some(folder.name),
             // This will be populated later
             children: IndexMap::new(),
+            retry: None,
+            hooks: Default::default(),
         })
     }
 }
@@ -300,7 +306,7 @@ impl From<Request> for RecipeNode {
             name: Some(request.name),
             method: request.method,
             url: request.url,
-            body: request.body.map(|body| body.text),
+            body: request.body.map(|body| body.text.into()),
             query: request
                 .parameters
                 .into_iter()
@@ -308,6 +314,12 @@ impl From<Request> for RecipeNode {
                 .collect(),
             headers,
             authentication,
+            decompress: None,
+            retry: None,
+            download: None,
+            hooks: Default::default(),
+            assertions: Default::default(),
+            example_response: None,
         })
     }
 }
@@ -374,6 +386,7 @@ fn build_profiles(
                     id,
                     name: Some(environment.name),
                     data,
+                    proxy: None,
                 },
             )
         })
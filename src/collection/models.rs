@@ -3,9 +3,9 @@
 use crate::{
     collection::{
         cereal,
-        recipe_tree::{RecipeNode, RecipeTree},
+        recipe_tree::{RecipeLookupKey, RecipeNode, RecipeTree},
     },
-    http::{ContentType, Query},
+    http::{Assertions, ContentType, ProxyConfig, Query, RetryPolicy},
     template::Template,
 };
 use anyhow::anyhow;
@@ -31,6 +31,20 @@ pub struct Collection {
     /// intuitive
     #[serde(default, rename = "requests")]
     pub recipes: RecipeTree,
+    /// Default retry policy for all recipes. Can be overridden per folder or
+    /// recipe
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry: Option<RetryPolicy>,
+    /// Default hooks for all recipes. Can be overridden per folder or recipe
+    #[serde(default, skip_serializing_if = "Hooks::is_empty")]
+    pub hooks: Hooks,
+    /// Ordered sequences of recipes, run with `slumber run` or from the TUI
+    #[serde(
+        default,
+        deserialize_with = "cereal::deserialize_id_map",
+        skip_serializing_if = "IndexMap::is_empty"
+    )]
+    pub workflows: IndexMap<WorkflowId, Workflow>,
     /// A hack-ish to allow users to add arbitrary data to their collection
     /// file without triggering a unknown field error. Ideally we could
     /// ignore anything that starts with `.` (recursively) but that
@@ -48,6 +62,10 @@ pub struct Profile {
     pub id: ProfileId,
     pub name: Option<String>,
     pub data: IndexMap<String, Template>,
+    /// Send requests through this proxy, instead of the one in the global
+    /// config
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<ProxyConfig>,
 }
 
 #[derive(
@@ -80,6 +98,14 @@ pub struct Folder {
         rename = "requests"
     )]
     pub children: IndexMap<RecipeId, RecipeNode>,
+    /// Retry policy for all recipes in this folder, unless a nested folder or
+    /// the recipe has its own
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry: Option<RetryPolicy>,
+    /// Hooks for all recipes in this folder, unless a nested folder or the
+    /// recipe has its own
+    #[serde(default, skip_serializing_if = "Hooks::is_empty")]
+    pub hooks: Hooks,
 }
 
 /// A definition of how to make a request. This is *not* called `Request` in
@@ -98,7 +124,117 @@ pub struct Recipe {
     /// wrong which is helpful.
     pub method: Method,
     pub url: Template,
-    pub body: Option<Template>,
+    pub body: Option<RecipeBody>,
+    pub authentication: Option<Authentication>,
+    #[serde(default)]
+    pub query: IndexMap<String, Template>,
+    #[serde(default)]
+    pub headers: IndexMap<String, Template>,
+    /// Automatically decompress the response body? If `None`, defer to the
+    /// global config
+    pub decompress: Option<bool>,
+    /// Retry the request if it fails. If `None`, use the policy from the
+    /// nearest folder, or the collection
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry: Option<RetryPolicy>,
+    /// Stream the response body to this file, instead of loading it into
+    /// memory. Relative paths are resolved from the current directory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub download: Option<Template>,
+    /// External commands to run before the request is sent and after the
+    /// response is received. Each hook that isn't defined here is taken from
+    /// the nearest folder, or the collection
+    #[serde(default, skip_serializing_if = "Hooks::is_empty")]
+    pub hooks: Hooks,
+    /// Checks on the response, run by `slumber test` and shown in the TUI
+    #[serde(default, skip_serializing_if = "Assertions::is_empty")]
+    pub assertions: Assertions,
+    /// Canned response for `slumber serve` to send when this recipe is hit
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub example_response: Option<ExampleResponse>,
+}
+
+/// A static response to a recipe, for the mock server to send in place of a
+/// real one
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(default, deny_unknown_fields)]
+pub struct ExampleResponse {
+    /// Validated when the mock server starts, rather than during
+    /// deserialization, so an invalid code doesn't break the whole collection
+    pub status: u16,
+    pub headers: IndexMap<String, String>,
+    pub body: String,
+    /// Wait this long before responding
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        with = "cereal::serde_duration::option"
+    )]
+    pub latency: Option<Duration>,
+}
+
+/// External commands that can modify a request before it's sent, or process
+/// the response after it's received. See the `http::hook` module for the
+/// protocol.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(deny_unknown_fields)]
+pub struct Hooks {
+    /// Run just before the request is sent. Receives the request as JSON, and
+    /// can modify it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pre_request: Option<HookCommand>,
+    /// Run after a response is received. Receives the request and response as
+    /// JSON, and can modify the response body, attach annotations, or fail
+    /// the request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_response: Option<HookCommand>,
+}
+
+/// A command to run as a hook: the program, followed by its arguments
+pub type HookCommand = Vec<String>;
+
+/// Body of a recipe. Most bodies are a single template that's sent as-is, but
+/// some formats have their own structure that we build the body from. Custom
+/// (de)serialization is in the `cereal` module, so a plain string can be
+/// given for a raw body.
+#[derive(Clone, Debug)]
+#[cfg_attr(test, derive(PartialEq))]
+pub enum RecipeBody {
+    /// Template text, sent as-is after rendering
+    Raw(Template),
+    /// A GraphQL operation, serialized as the standard JSON envelope
+    GraphQl(GraphQlBody),
+}
+
+/// A GraphQL request body. Each field is rendered, then combined into a JSON
+/// object of the form `{"query": "...", "variables": {...},
+/// "operationName": "..."}`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(deny_unknown_fields)]
+pub struct GraphQlBody {
+    pub query: Template,
+    /// Must render to a JSON object
+    pub variables: Option<Template>,
+    /// Which operation in the query to execute. Only needed if the query
+    /// defines multiple operations
+    pub operation_name: Option<Template>,
+}
+
+/// A definition of how to open a WebSocket connection. The connection is
+/// opened with an HTTP handshake, so the URL, query, headers, and
+/// authentication all behave the same as in a [Recipe]. Once connected,
+/// messages are sent and received interactively.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(deny_unknown_fields)]
+pub struct WebSocketRecipe {
+    #[serde(skip)] // This will be auto-populated from the map key
+    pub id: RecipeId,
+    pub name: Option<String>,
+    /// `http(s)://` URLs are converted to `ws(s)://`
+    pub url: Template,
     pub authentication: Option<Authentication>,
     #[serde(default)]
     pub query: IndexMap<String, Template>,
@@ -121,6 +257,57 @@ pub struct Recipe {
 )]
 pub struct RecipeId(String);
 
+/// A sequence of recipes to send in order, where each step can use values
+/// captured from the responses of earlier steps
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(deny_unknown_fields)]
+pub struct Workflow {
+    #[serde(skip)] // This will be auto-populated from the map key
+    pub id: WorkflowId,
+    pub name: Option<String>,
+    #[serde(default)]
+    pub steps: Vec<WorkflowStep>,
+}
+
+#[derive(
+    Clone,
+    Debug,
+    Deref,
+    Default,
+    Display,
+    Eq,
+    From,
+    Hash,
+    PartialEq,
+    Serialize,
+    Deserialize,
+)]
+pub struct WorkflowId(String);
+
+/// A single step in a workflow, which sends one recipe
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(deny_unknown_fields)]
+pub struct WorkflowStep {
+    pub recipe: RecipeId,
+    /// Template field overrides for this step only. These are rendered before
+    /// the step, so they can use captured values
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub overrides: IndexMap<String, Template>,
+    /// Values to extract from the response body, keyed by the field name
+    /// they're available under in later steps
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub capture: IndexMap<String, Query>,
+    /// Checks to run against the response. If any fail, the workflow stops
+    #[serde(default, skip_serializing_if = "Assertions::is_empty")]
+    pub assertions: Assertions,
+    /// Skip the step unless this renders to a truthy value. Empty, `false`,
+    /// and `0` are falsy
+    #[serde(default, rename = "if", skip_serializing_if = "Option::is_none")]
+    pub condition: Option<Template>,
+}
+
 /// HTTP method. This is duplicated from reqwest's Method so we can enforce
 /// the method is valid during deserialization. This is also generally more
 /// ergonomic at the cost of some flexibility.
@@ -274,6 +461,109 @@ pub enum ChainRequestTrigger {
     Always,
 }
 
+impl Collection {
+    /// Get the retry policy that applies to a recipe. The definition closest
+    /// to the recipe wins: the recipe itself, then each of its parent folders
+    /// from the inside out, then the collection default.
+    pub fn retry_policy(&self, recipe_id: &RecipeId) -> Option<&RetryPolicy> {
+        self.inherited(
+            recipe_id,
+            |folder| folder.retry.as_ref(),
+            |recipe| recipe.retry.as_ref(),
+        )
+        .or(self.retry.as_ref())
+    }
+
+    /// Get the hooks that apply to a recipe. Each hook is resolved
+    /// separately, the same as [Self::retry_policy], so a recipe can define
+    /// its own post-response hook but still inherit the pre-request hook.
+    pub fn hooks(&self, recipe_id: &RecipeId) -> Hooks {
+        let pre_request = self
+            .inherited(
+                recipe_id,
+                |folder| folder.hooks.pre_request.as_ref(),
+                |recipe| recipe.hooks.pre_request.as_ref(),
+            )
+            .or(self.hooks.pre_request.as_ref());
+        let post_response = self
+            .inherited(
+                recipe_id,
+                |folder| folder.hooks.post_response.as_ref(),
+                |recipe| recipe.hooks.post_response.as_ref(),
+            )
+            .or(self.hooks.post_response.as_ref());
+        Hooks {
+            pre_request: pre_request.cloned(),
+            post_response: post_response.cloned(),
+        }
+    }
+
+    /// Find the node closest to a recipe (including the recipe itself) that
+    /// defines a setting. Doesn't check the collection-level default
+    fn inherited<'a, T>(
+        &'a self,
+        recipe_id: &RecipeId,
+        folder_setting: impl Fn(&'a Folder) -> Option<&'a T>,
+        recipe_setting: impl Fn(&'a Recipe) -> Option<&'a T>,
+    ) -> Option<&'a T> {
+        let path = self
+            .recipes
+            .get_lookup_key(recipe_id)
+            .map(RecipeLookupKey::as_slice)
+            .unwrap_or_default();
+        path.iter()
+            .rev()
+            .filter_map(|id| self.recipes.get(id))
+            .find_map(|node| match node {
+                RecipeNode::Folder(folder) => folder_setting(folder),
+                RecipeNode::Recipe(recipe) => recipe_setting(recipe),
+                RecipeNode::WebSocket(_) => None,
+            })
+    }
+
+    /// Get the IDs of the recipes whose responses a recipe uses, via chains
+    /// with a request source. Only chains used directly in the recipe are
+    /// checked, not chains nested within other chains.
+    pub fn recipe_dependencies(&self, recipe: &Recipe) -> Vec<&RecipeId> {
+        recipe
+            .templates()
+            .into_iter()
+            .flat_map(Template::chain_ids)
+            .filter_map(|chain_id| self.chains.get(&ChainId(chain_id)))
+            .filter_map(|chain| match &chain.source {
+                ChainSource::Request { recipe, .. } => Some(recipe),
+                _ => None,
+            })
+            .unique()
+            .collect()
+    }
+}
+
+impl Hooks {
+    /// Are there no hooks defined?
+    pub fn is_empty(&self) -> bool {
+        self.pre_request.is_none() && self.post_response.is_none()
+    }
+}
+
+impl Default for ExampleResponse {
+    fn default() -> Self {
+        Self {
+            status: 200,
+            headers: IndexMap::new(),
+            body: String::new(),
+            latency: None,
+        }
+    }
+}
+
+impl Workflow {
+    /// Get a presentable name for this workflow
+    pub fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.id)
+    }
+}
+
 impl Profile {
     /// Get a presentable name for this profile
     pub fn name(&self) -> &str {
@@ -293,6 +583,71 @@ impl Recipe {
     pub fn name(&self) -> &str {
         self.name.as_deref().unwrap_or(&self.id)
     }
+
+    /// Get every template in this recipe
+    fn templates(&self) -> Vec<&Template> {
+        let mut templates = vec![&self.url];
+        templates.extend(self.query.values());
+        templates.extend(self.headers.values());
+        match &self.body {
+            Some(RecipeBody::Raw(body)) => templates.push(body),
+            Some(RecipeBody::GraphQl(body)) => {
+                templates.push(&body.query);
+                templates.extend(&body.variables);
+                templates.extend(&body.operation_name);
+            }
+            None => {}
+        }
+        match &self.authentication {
+            Some(Authentication::Basic { username, password }) => {
+                templates.push(username);
+                templates.extend(password);
+            }
+            Some(Authentication::Bearer(token)) => templates.push(token),
+            None => {}
+        }
+        templates.extend(&self.download);
+        templates
+    }
+}
+
+impl RecipeBody {
+    /// The YAML tag for GraphQL bodies
+    pub const GRAPHQL_TAG: &'static str = "graphql";
+}
+
+impl From<Template> for RecipeBody {
+    fn from(template: Template) -> Self {
+        Self::Raw(template)
+    }
+}
+
+impl WebSocketRecipe {
+    /// Get a presentable name for this recipe
+    pub fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.id)
+    }
+
+    /// Get the recipe for the HTTP handshake that opens the connection. This
+    /// allows the request to be built the same as any other
+    pub fn to_recipe(&self) -> Recipe {
+        Recipe {
+            id: self.id.clone(),
+            name: self.name.clone(),
+            method: Method::Get,
+            url: self.url.clone(),
+            body: None,
+            authentication: self.authentication.clone(),
+            query: self.query.clone(),
+            headers: self.headers.clone(),
+            decompress: None,
+            retry: None,
+            download: None,
+            hooks: Hooks::default(),
+            assertions: Assertions::default(),
+            example_response: None,
+        }
+    }
 }
 
 /// For deserialization
@@ -316,3 +671,48 @@ impl From<Method> for String {
         method.to_string()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+    use factori::create;
+    use indexmap::indexmap;
+
+    /// Dependencies are found via chains with a request source, in any of
+    /// the recipe's templates
+    #[test]
+    fn test_recipe_dependencies() {
+        let chain = |id: &str, recipe: &str| {
+            let chain = create!(
+                Chain,
+                id: id.into(),
+                source: ChainSource::Request {
+                    recipe: recipe.into(),
+                    trigger: Default::default(),
+                },
+            );
+            (chain.id.clone(), chain)
+        };
+        let recipe = create!(
+            Recipe,
+            url: "{{host}}/users/{{chains.user_id}}".into(),
+            headers: indexmap! {
+                "Authorization".into() => "{{chains.token}}".into(),
+                "X-Copy".into() => "{{chains.user_id_again}}".into(),
+            },
+        );
+        let collection = create!(
+            Collection,
+            chains: IndexMap::from([
+                chain("user_id", "create_user"),
+                chain("user_id_again", "create_user"),
+                chain("token", "login"),
+            ]),
+        );
+        assert_eq!(
+            collection.recipe_dependencies(&recipe),
+            vec![&RecipeId::from("create_user"), &RecipeId::from("login")]
+        );
+    }
+}
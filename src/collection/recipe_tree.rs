@@ -1,6 +1,8 @@
 //! Recipe/folder tree structure
 
-use crate::collection::{cereal::deserialize_id_map, Folder, Recipe, RecipeId};
+use crate::collection::{
+    cereal::deserialize_id_map, Folder, Recipe, RecipeId, WebSocketRecipe,
+};
 use derive_more::{Debug, From};
 use indexmap::{map::Values, IndexMap};
 use serde::{de::Error, Deserialize, Deserializer, Serialize};
@@ -30,7 +32,7 @@ pub struct RecipeTree {
 #[cfg_attr(test, derive(PartialEq))]
 pub struct RecipeLookupKey(Vec<RecipeId>);
 
-/// A node in the recipe tree, either a folder or recipe
+/// A node in the recipe tree: a folder, an HTTP recipe, or a WebSocket recipe
 #[derive(Clone, Debug, From, Serialize, Deserialize)]
 #[cfg_attr(test, derive(PartialEq))]
 #[serde(rename_all = "snake_case", deny_unknown_fields)]
@@ -41,6 +43,8 @@ pub enum RecipeNode {
     /// folders
     #[serde(rename = "request")]
     Recipe(Recipe),
+    #[serde(rename = "websocket")]
+    WebSocket(WebSocketRecipe),
 }
 
 impl RecipeTree {
@@ -83,10 +87,10 @@ impl RecipeTree {
             }
             match node {
                 RecipeNode::Folder(folder) => nodes = &folder.children,
-                RecipeNode::Recipe(recipe) => panic!(
+                RecipeNode::Recipe(_) | RecipeNode::WebSocket(_) => panic!(
                     "Lookup key {lookup_key:?} attempts to traverse through \
                     recipe node `{}`",
-                    recipe.id
+                    node.id()
                 ),
             }
         }
@@ -99,6 +103,12 @@ impl RecipeTree {
         self.get(id).and_then(RecipeNode::recipe)
     }
 
+    /// Get a **WebSocket recipe** by ID. If the ID isn't in the tree, or
+    /// points to anything else, return `None`
+    pub fn get_websocket(&self, id: &RecipeId) -> Option<&WebSocketRecipe> {
+        self.get(id).and_then(RecipeNode::websocket)
+    }
+
     /// Get all **recipe** IDs in the tree. Useful for printing a list to the
     /// user
     pub fn recipe_ids(&self) -> impl Iterator<Item = &RecipeId> {
@@ -107,6 +117,13 @@ impl RecipeTree {
             .filter(|id| self.get_recipe(id).is_some())
     }
 
+    /// Get all **WebSocket recipe** IDs in the tree
+    pub fn websocket_ids(&self) -> impl Iterator<Item = &RecipeId> {
+        self.nodes_by_id
+            .keys()
+            .filter(|id| self.get_websocket(id).is_some())
+    }
+
     /// Get a flat iterator over all nodes in the tree, using depth first
     /// search. Each yielded item will include the lookup key to retrieve
     /// that item.
@@ -131,10 +148,13 @@ impl RecipeTree {
                             self.stack.push(folder.children.values());
                             return Some(((&self.path).into(), node));
                         }
-                        Some(node @ RecipeNode::Recipe(recipe)) => {
+                        Some(
+                            node @ (RecipeNode::Recipe(_)
+                            | RecipeNode::WebSocket(_)),
+                        ) => {
                             let mut lookup_key: RecipeLookupKey =
                                 (&self.path).into();
-                            lookup_key.0.push(recipe.id.clone());
+                            lookup_key.0.push(node.id().clone());
                             return Some((lookup_key, node));
                         }
                         None => {
@@ -187,6 +207,7 @@ impl RecipeNode {
         match self {
             RecipeNode::Folder(folder) => &folder.id,
             RecipeNode::Recipe(recipe) => &recipe.id,
+            RecipeNode::WebSocket(websocket) => &websocket.id,
         }
     }
 
@@ -195,6 +216,7 @@ impl RecipeNode {
         match self {
             RecipeNode::Folder(folder) => folder.name(),
             RecipeNode::Recipe(recipe) => recipe.name(),
+            RecipeNode::WebSocket(websocket) => websocket.name(),
         }
     }
 
@@ -202,14 +224,22 @@ impl RecipeNode {
     pub fn recipe(&self) -> Option<&Recipe> {
         match self {
             RecipeNode::Recipe(recipe) => Some(recipe),
-            RecipeNode::Folder(_) => None,
+            RecipeNode::Folder(_) | RecipeNode::WebSocket(_) => None,
+        }
+    }
+
+    /// If this node is a WebSocket recipe, return it. Otherwise return `None`
+    pub fn websocket(&self) -> Option<&WebSocketRecipe> {
+        match self {
+            RecipeNode::WebSocket(websocket) => Some(websocket),
+            RecipeNode::Folder(_) | RecipeNode::Recipe(_) => None,
         }
     }
 
     /// If this node is a folder, return it. Otherwise return `None`
     pub fn folder(&self) -> Option<&Folder> {
         match self {
-            RecipeNode::Recipe(_) => None,
+            RecipeNode::Recipe(_) | RecipeNode::WebSocket(_) => None,
             RecipeNode::Folder(folder) => Some(folder),
         }
     }
@@ -230,7 +260,12 @@ impl From<&Vec<&RecipeId>> for RecipeLookupKey {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{test_util::*, util::assert_err};
+    use crate::{
+        collection::{HookCommand, Hooks, Method},
+        http::RetryPolicy,
+        test_util::*,
+        util::assert_err,
+    };
     use factori::create;
     use indexmap::indexmap;
     use itertools::Itertools;
@@ -397,6 +432,23 @@ mod tests {
         );
     }
 
+    /// WebSocket recipes are leaf nodes, but they aren't HTTP recipes
+    #[test]
+    fn test_websocket() {
+        let yaml = mapping([(
+            "ws",
+            tagged_mapping("!websocket", [("url", "ws://localhost".into())]),
+        )]);
+        let tree = serde_yaml::from_value::<RecipeTree>(yaml).unwrap();
+
+        let websocket = tree.get_websocket(&id("ws")).unwrap();
+        assert_eq!(websocket.id, id("ws"));
+        assert_eq!(websocket.to_recipe().method, Method::Get);
+        assert_eq!(tree.get_recipe(&id("ws")), None);
+        assert_eq!(tree.recipe_ids().next(), None);
+        assert_eq!(tree.websocket_ids().collect_vec(), vec![&id("ws")]);
+    }
+
     /// Test successful serialization/deserialization
     #[rstest]
     fn test_deserialization(tree: IndexMap<RecipeId, RecipeNode>) {
@@ -451,4 +503,91 @@ mod tests {
             "Deserialization failed"
         );
     }
+
+    /// Retry policy is taken from the closest node to the recipe that
+    /// defines one, falling back to the collection
+    #[rstest]
+    #[case::recipe("r3", Some(3))]
+    #[case::nearest_folder("r2", Some(2))]
+    #[case::collection("r1", Some(1))]
+    #[case::unknown_recipe("unknown", Some(1))]
+    fn test_retry_policy(
+        mut tree: IndexMap<RecipeId, RecipeNode>,
+        #[case] recipe_id: &str,
+        #[case] expected_attempts: Option<u32>,
+    ) {
+        fn retry(max_attempts: u32) -> Option<RetryPolicy> {
+            Some(RetryPolicy {
+                max_attempts,
+                ..Default::default()
+            })
+        }
+
+        // Assign a policy to each level: collection, f1, f2, and r3
+        let RecipeNode::Folder(f1) = &mut tree[&id("f1")] else {
+            unreachable!()
+        };
+        f1.retry = retry(10);
+        let RecipeNode::Folder(f2) = &mut f1.children[&id("f2")] else {
+            unreachable!()
+        };
+        f2.retry = retry(2);
+        let RecipeNode::Recipe(r3) = &mut f1.children[&id("r3")] else {
+            unreachable!()
+        };
+        r3.retry = retry(3);
+        let collection = create!(
+            Collection,
+            recipes: RecipeTree::new(tree).unwrap(),
+            retry: retry(1),
+        );
+
+        assert_eq!(
+            collection
+                .retry_policy(&id(recipe_id))
+                .map(|retry| retry.max_attempts),
+            expected_attempts
+        );
+    }
+
+    /// Each hook is resolved separately, from the closest node that defines
+    /// it
+    #[rstest]
+    #[case::recipe("r3", Some("r3"), Some("f1"))]
+    #[case::folder("r2", Some("collection"), Some("f1"))]
+    #[case::collection("r1", Some("collection"), Some("collection"))]
+    fn test_hooks(
+        mut tree: IndexMap<RecipeId, RecipeNode>,
+        #[case] recipe_id: &str,
+        #[case] expected_pre_request: Option<&str>,
+        #[case] expected_post_response: Option<&str>,
+    ) {
+        fn command(program: &str) -> Option<HookCommand> {
+            Some(vec![program.to_owned()])
+        }
+
+        let RecipeNode::Folder(f1) = &mut tree[&id("f1")] else {
+            unreachable!()
+        };
+        f1.hooks.post_response = command("f1");
+        let RecipeNode::Recipe(r3) = &mut f1.children[&id("r3")] else {
+            unreachable!()
+        };
+        r3.hooks.pre_request = command("r3");
+        let collection = create!(
+            Collection,
+            recipes: RecipeTree::new(tree).unwrap(),
+            hooks: Hooks {
+                pre_request: command("collection"),
+                post_response: command("collection"),
+            },
+        );
+
+        let hooks = collection.hooks(&id(recipe_id));
+        assert_eq!(hooks.pre_request, expected_pre_request.and_then(command));
+        assert_eq!(
+            hooks.post_response,
+            expected_post_response.and_then(command)
+        );
+    }
 }
@@ -1,4 +1,5 @@
 use crate::{
+    http::{ProxyConfig, TlsConfig},
     tui::input::{Action, InputBinding},
     util::{
         parse_yaml,
@@ -25,6 +26,21 @@ pub struct Config {
     /// Should templates be rendered inline in the UI, or should we show the
     /// raw text?
     pub preview_templates: bool,
+    /// Should compressed response bodies be automatically decompressed? Can be
+    /// overridden per-recipe
+    pub decompress: bool,
+    /// Response bodies larger than this (in bytes) won't be parsed
+    /// automatically in the TUI. Parsing enables prettification and querying,
+    /// but is slow for very large bodies. Parsing can still be triggered
+    /// manually.
+    pub large_body_size: u64,
+    /// Enable the cookie jar? If enabled, cookies set by responses are stored
+    /// (per collection and profile) and sent on subsequent matching requests
+    pub cookies: bool,
+    /// Send requests through this proxy. Can be overridden per-profile
+    pub proxy: Option<ProxyConfig>,
+    /// Client certificate and CA settings, keyed by hostname
+    pub tls: IndexMap<String, TlsConfig>,
 
     /// Overrides for default key bindings
     pub input_bindings: IndexMap<Action, InputBinding>,
@@ -69,6 +85,11 @@ impl Default for Config {
         Self {
             ignore_certificate_hosts: Vec::new(),
             preview_templates: true,
+            decompress: true,
+            large_body_size: 5 * 1000 * 1000,
+            cookies: false,
+            proxy: None,
+            tls: IndexMap::default(),
             input_bindings: IndexMap::default(),
         }
     }
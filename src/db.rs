@@ -3,7 +3,7 @@
 
 use crate::{
     collection::{ProfileId, RecipeId},
-    http::{RequestId, RequestRecord},
+    http::{Cookie, GraphQlSchema, RequestId, RequestRecord},
     util::{
         paths::{DataDirectory, FileGuard},
         ResultExt,
@@ -129,6 +129,61 @@ impl Database {
             // serialization of all binary blobs, so there's no easy way to
             // migrate it all. It's easiest just to wipe it all out.
             M::up("DELETE FROM requests; DELETE FROM ui_state;").down(""),
+            M::up(
+                // Cookie jar. Domain/path/name identify a cookie, and are
+                // pulled out so we can replace/delete. The full cookie is
+                // serialized as msgpack. profile_id is nullable, so uniqueness
+                // can't be enforced with a key. Instead we always delete
+                // before inserting.
+                "CREATE TABLE cookies (
+                    collection_id   UUID NOT NULL,
+                    profile_id      TEXT,
+                    domain          TEXT NOT NULL,
+                    path            TEXT NOT NULL,
+                    name            TEXT NOT NULL,
+                    cookie          BLOB NOT NULL,
+                    FOREIGN KEY(collection_id) REFERENCES collections(id)
+                )",
+            )
+            .down("DROP TABLE cookies"),
+            M::up(
+                // Schemas fetched via GraphQL introspection, serialized as
+                // msgpack. Same as cookies, profile_id is nullable so we
+                // delete before inserting.
+                "CREATE TABLE graphql_schemas (
+                    collection_id   UUID NOT NULL,
+                    profile_id      TEXT,
+                    recipe_id       TEXT NOT NULL,
+                    schema          BLOB NOT NULL,
+                    FOREIGN KEY(collection_id) REFERENCES collections(id)
+                )",
+            )
+            .down("DROP TABLE graphql_schemas"),
+            M::up(
+                // Number of times a request was sent, when retries are
+                // enabled. Existing records were all single attempts
+                "ALTER TABLE requests
+                ADD COLUMN attempts INTEGER NOT NULL DEFAULT 1",
+            )
+            .down("ALTER TABLE requests DROP COLUMN attempts"),
+            M::up(
+                // Phase timings, serialized as msgpack. Nullable because
+                // older records don't have them
+                "ALTER TABLE requests ADD COLUMN timings BLOB",
+            )
+            .down("ALTER TABLE requests DROP COLUMN timings"),
+            M::up(
+                // Redirect hops, serialized as msgpack. Nullable because
+                // older records don't have them
+                "ALTER TABLE requests ADD COLUMN redirects BLOB",
+            )
+            .down("ALTER TABLE requests DROP COLUMN redirects"),
+            M::up(
+                // Notes from the post-response hook, serialized as msgpack.
+                // Nullable because older records don't have them
+                "ALTER TABLE requests ADD COLUMN annotations BLOB",
+            )
+            .down("ALTER TABLE requests DROP COLUMN annotations"),
         ]);
         migrations.to_latest(connection)?;
         Ok(())
@@ -208,6 +263,55 @@ impl Database {
             )
             .context("Error migrating table `ui_state`")
             .traced()?;
+        connection
+            .execute(
+                // Same as UI state: target cookies get overwritten by source
+                // cookies with the same identity
+                "DELETE FROM cookies AS target
+                WHERE target.collection_id = :target AND EXISTS (
+                    SELECT 1 FROM cookies AS source
+                    WHERE source.collection_id = :source
+                        AND source.profile_id IS target.profile_id
+                        AND source.domain = target.domain
+                        AND source.path = target.path
+                        AND source.name = target.name
+                )",
+                named_params! {":source": source, ":target": target},
+            )
+            .context("Error migrating table `cookies`")
+            .traced()?;
+        connection
+            .execute(
+                "UPDATE cookies SET collection_id = :target
+                WHERE collection_id = :source",
+                named_params! {":source": source, ":target": target},
+            )
+            .context("Error migrating table `cookies`")
+            .traced()?;
+
+        connection
+            .execute(
+                // Same as cookies: source schemas replace target schemas for
+                // the same profile+recipe
+                "DELETE FROM graphql_schemas AS target
+                WHERE target.collection_id = :target AND EXISTS (
+                    SELECT 1 FROM graphql_schemas AS source
+                    WHERE source.collection_id = :source
+                        AND source.profile_id IS target.profile_id
+                        AND source.recipe_id = target.recipe_id
+                )",
+                named_params! {":source": source, ":target": target},
+            )
+            .context("Error migrating table `graphql_schemas`")
+            .traced()?;
+        connection
+            .execute(
+                "UPDATE graphql_schemas SET collection_id = :target
+                WHERE collection_id = :source",
+                named_params! {":source": source, ":target": target},
+            )
+            .context("Error migrating table `graphql_schemas`")
+            .traced()?;
 
         connection
             .execute(
@@ -347,10 +451,15 @@ impl CollectionDatabase {
                     end_time,
                     request,
                     response,
-                    status_code
+                    status_code,
+                    attempts,
+                    timings,
+                    redirects,
+                    annotations
                 )
                 VALUES (:id, :collection_id, :profile_id, :recipe_id,
-                    :start_time, :end_time, :request, :response, :status_code)",
+                    :start_time, :end_time, :request, :response, :status_code,
+                    :attempts, :timings, :redirects, :annotations)",
                 named_params! {
                     ":id": record.id,
                     ":collection_id": self.collection_id,
@@ -361,6 +470,10 @@ impl CollectionDatabase {
                     ":request": &Bytes(&*record.request),
                     ":response": &Bytes(&record.response),
                     ":status_code": record.response.status.as_u16(),
+                    ":attempts": record.attempts,
+                    ":timings": record.timings.as_ref().map(Bytes),
+                    ":redirects": Bytes(&record.redirects),
+                    ":annotations": Bytes(&record.annotations),
                 },
             )
             .context(format!("Error saving request {} to database", record.id))
@@ -420,6 +533,202 @@ impl CollectionDatabase {
             .traced()?;
         Ok(())
     }
+
+    /// Get all cookies in the jar for a profile. If the given profile is
+    /// `None`, get cookies that have no associated profile.
+    pub fn get_cookies(
+        &self,
+        profile_id: Option<&ProfileId>,
+    ) -> anyhow::Result<Vec<Cookie>> {
+        self.database
+            .connection()
+            .prepare(
+                "SELECT cookie FROM cookies
+                WHERE collection_id = :collection_id
+                    AND profile_id IS :profile_id
+                ORDER BY domain, path, name",
+            )?
+            .query_map(
+                named_params! {
+                    ":collection_id": self.collection_id,
+                    ":profile_id": profile_id,
+                },
+                |row| Ok(row.get::<_, Bytes<Cookie>>("cookie")?.0),
+            )
+            .context("Error fetching cookies")?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .context("Error extracting cookie data")
+            .traced()
+    }
+
+    /// Add a cookie to the jar for a profile, replacing any existing cookie
+    /// with the same domain, path, and name
+    pub fn set_cookie(
+        &self,
+        profile_id: Option<&ProfileId>,
+        cookie: &Cookie,
+    ) -> anyhow::Result<()> {
+        debug!(?profile_id, ?cookie, "Setting cookie");
+        let connection = self.database.connection();
+        let transaction = connection.unchecked_transaction()?;
+        Self::delete_cookie_helper(&transaction, self, profile_id, cookie)?;
+        transaction
+            .execute(
+                "INSERT INTO cookies
+                (collection_id, profile_id, domain, path, name, cookie)
+                VALUES (:collection_id, :profile_id, :domain, :path, :name,
+                    :cookie)",
+                named_params! {
+                    ":collection_id": self.collection_id,
+                    ":profile_id": profile_id,
+                    ":domain": &cookie.domain,
+                    ":path": &cookie.path,
+                    ":name": &cookie.name,
+                    ":cookie": Bytes(cookie),
+                },
+            )
+            .context("Error saving cookie to database")
+            .traced()?;
+        transaction.commit()?;
+        Ok(())
+    }
+
+    /// Remove a single cookie from the jar for a profile
+    pub fn delete_cookie(
+        &self,
+        profile_id: Option<&ProfileId>,
+        cookie: &Cookie,
+    ) -> anyhow::Result<()> {
+        debug!(?profile_id, ?cookie, "Deleting cookie");
+        Self::delete_cookie_helper(
+            &self.database.connection(),
+            self,
+            profile_id,
+            cookie,
+        )
+    }
+
+    /// Remove all cookies from the jar for a profile. Return the number of
+    /// cookies deleted
+    pub fn clear_cookies(
+        &self,
+        profile_id: Option<&ProfileId>,
+    ) -> anyhow::Result<usize> {
+        debug!(?profile_id, "Clearing cookies");
+        self.database
+            .connection()
+            .execute(
+                "DELETE FROM cookies
+                WHERE collection_id = :collection_id
+                    AND profile_id IS :profile_id",
+                named_params! {
+                    ":collection_id": self.collection_id,
+                    ":profile_id": profile_id,
+                },
+            )
+            .context("Error clearing cookies")
+            .traced()
+    }
+
+    /// Get the cached GraphQL schema for a profile+recipe, or `None` if the
+    /// schema has never been introspected
+    pub fn get_graphql_schema(
+        &self,
+        profile_id: Option<&ProfileId>,
+        recipe_id: &RecipeId,
+    ) -> anyhow::Result<Option<GraphQlSchema>> {
+        self.database
+            .connection()
+            .query_row(
+                "SELECT schema FROM graphql_schemas
+                WHERE collection_id = :collection_id
+                    AND profile_id IS :profile_id
+                    AND recipe_id = :recipe_id",
+                named_params! {
+                    ":collection_id": self.collection_id,
+                    ":profile_id": profile_id,
+                    ":recipe_id": recipe_id,
+                },
+                |row| Ok(row.get::<_, Bytes<GraphQlSchema>>("schema")?.0),
+            )
+            .optional()
+            .with_context(|| {
+                format!("Error fetching GraphQL schema for recipe {recipe_id}")
+            })
+            .traced()
+    }
+
+    /// Cache a GraphQL schema for a profile+recipe, replacing the previous
+    /// one
+    pub fn set_graphql_schema(
+        &self,
+        profile_id: Option<&ProfileId>,
+        recipe_id: &RecipeId,
+        schema: &GraphQlSchema,
+    ) -> anyhow::Result<()> {
+        debug!(?profile_id, %recipe_id, "Saving GraphQL schema");
+        let connection = self.database.connection();
+        let transaction = connection.unchecked_transaction()?;
+        transaction
+            .execute(
+                "DELETE FROM graphql_schemas
+                WHERE collection_id = :collection_id
+                    AND profile_id IS :profile_id
+                    AND recipe_id = :recipe_id",
+                named_params! {
+                    ":collection_id": self.collection_id,
+                    ":profile_id": profile_id,
+                    ":recipe_id": recipe_id,
+                },
+            )
+            .context("Error deleting GraphQL schema")
+            .traced()?;
+        transaction
+            .execute(
+                "INSERT INTO graphql_schemas
+                (collection_id, profile_id, recipe_id, schema)
+                VALUES (:collection_id, :profile_id, :recipe_id, :schema)",
+                named_params! {
+                    ":collection_id": self.collection_id,
+                    ":profile_id": profile_id,
+                    ":recipe_id": recipe_id,
+                    ":schema": Bytes(schema),
+                },
+            )
+            .context("Error saving GraphQL schema to database")
+            .traced()?;
+        transaction.commit()?;
+        Ok(())
+    }
+
+    /// Delete a cookie by its identity. Split out so it can be used within a
+    /// transaction
+    fn delete_cookie_helper(
+        connection: &Connection,
+        database: &Self,
+        profile_id: Option<&ProfileId>,
+        cookie: &Cookie,
+    ) -> anyhow::Result<()> {
+        connection
+            .execute(
+                "DELETE FROM cookies
+                WHERE collection_id = :collection_id
+                    AND profile_id IS :profile_id
+                    AND domain = :domain
+                    AND path = :path
+                    AND name = :name",
+                named_params! {
+                    ":collection_id": database.collection_id,
+                    ":profile_id": profile_id,
+                    ":domain": &cookie.domain,
+                    ":path": &cookie.path,
+                    ":name": &cookie.name,
+                },
+            )
+            .context("Error deleting cookie")
+            .traced()?;
+        Ok(())
+    }
 }
 
 /// Test-only helpers
@@ -563,6 +872,18 @@ impl<'a, 'b> TryFrom<&'a Row<'b>> for RequestRecord {
             // Deserialize from bytes
             request: Arc::new(row.get::<_, Bytes<_>>("request")?.0),
             response: row.get::<_, Bytes<_>>("response")?.0,
+            attempts: row.get("attempts")?,
+            timings: row
+                .get::<_, Option<Bytes<_>>>("timings")?
+                .map(|bytes| bytes.0),
+            redirects: row
+                .get::<_, Option<Bytes<_>>>("redirects")?
+                .map(|bytes| bytes.0)
+                .unwrap_or_default(),
+            annotations: row
+                .get::<_, Option<Bytes<_>>>("annotations")?
+                .map(|bytes| bytes.0)
+                .unwrap_or_default(),
         })
     }
 }
@@ -591,6 +912,12 @@ mod tests {
         collection1.set_ui(ui_key, "value1").unwrap();
         collection2.insert_request(&record2).unwrap();
         collection2.set_ui(ui_key, "value2").unwrap();
+        collection1
+            .set_graphql_schema(profile_id, recipe_id, &graphql_schema("A"))
+            .unwrap();
+        collection2
+            .set_graphql_schema(profile_id, recipe_id, &graphql_schema("B"))
+            .unwrap();
 
         // Sanity checks
         assert_eq!(
@@ -634,6 +961,12 @@ mod tests {
             collection1.get_ui::<_, String>(ui_key).unwrap(),
             Some("value2".into())
         );
+        assert_eq!(
+            collection1
+                .get_graphql_schema(profile_id, recipe_id)
+                .unwrap(),
+            Some(graphql_schema("B"))
+        );
 
         // Make sure collection2 was deleted
         assert_eq!(
@@ -749,4 +1082,123 @@ mod tests {
             Some("value2".into())
         );
     }
+
+    /// Test cookie jar storage, and that it's scoped by collection+profile
+    #[test]
+    fn test_cookies() {
+        let database = Database::testing();
+        let collection1 = database
+            .clone()
+            .into_collection(Path::new("slumber.yml"))
+            .unwrap();
+        let collection2 = database
+            .clone()
+            .into_collection(Path::new("README.md"))
+            .unwrap();
+        let profile_id = ProfileId::from("profile1");
+        let profile_id = Some(&profile_id);
+
+        let cookie = |name: &str, value: &str| Cookie {
+            name: name.into(),
+            value: value.into(),
+            domain: "example.com".into(),
+            host_only: true,
+            path: "/".into(),
+            secure: false,
+            http_only: false,
+            expires: None,
+        };
+        collection1.set_cookie(None, &cookie("a", "1")).unwrap();
+        collection1
+            .set_cookie(profile_id, &cookie("a", "2"))
+            .unwrap();
+        collection1
+            .set_cookie(profile_id, &cookie("b", "3"))
+            .unwrap();
+        collection2.set_cookie(None, &cookie("a", "4")).unwrap();
+
+        // Setting again replaces
+        collection1.set_cookie(None, &cookie("a", "5")).unwrap();
+
+        assert_eq!(collection1.get_cookies(None).unwrap(), [cookie("a", "5")]);
+        assert_eq!(
+            collection1.get_cookies(profile_id).unwrap(),
+            [cookie("a", "2"), cookie("b", "3")]
+        );
+        assert_eq!(collection2.get_cookies(None).unwrap(), [cookie("a", "4")]);
+
+        collection1
+            .delete_cookie(profile_id, &cookie("a", ""))
+            .unwrap();
+        assert_eq!(
+            collection1.get_cookies(profile_id).unwrap(),
+            [cookie("b", "3")]
+        );
+
+        assert_eq!(collection1.clear_cookies(None).unwrap(), 1);
+        assert_eq!(collection1.get_cookies(None).unwrap(), []);
+        // Other collection is untouched
+        assert_eq!(collection2.get_cookies(None).unwrap(), [cookie("a", "4")]);
+    }
+
+    #[test]
+    fn test_graphql_schema() {
+        let database = Database::testing();
+        let collection1 = database
+            .clone()
+            .into_collection(Path::new("slumber.yml"))
+            .unwrap();
+        let collection2 = database
+            .clone()
+            .into_collection(Path::new("README.md"))
+            .unwrap();
+        let profile_id = ProfileId::from("profile1");
+        let profile_id = Some(&profile_id);
+        let recipe_id = RecipeId::from("recipe1");
+
+        assert_eq!(
+            collection1.get_graphql_schema(None, &recipe_id).unwrap(),
+            None
+        );
+        collection1
+            .set_graphql_schema(None, &recipe_id, &graphql_schema("A"))
+            .unwrap();
+        collection1
+            .set_graphql_schema(profile_id, &recipe_id, &graphql_schema("B"))
+            .unwrap();
+        // Setting again replaces
+        collection1
+            .set_graphql_schema(None, &recipe_id, &graphql_schema("C"))
+            .unwrap();
+
+        assert_eq!(
+            collection1.get_graphql_schema(None, &recipe_id).unwrap(),
+            Some(graphql_schema("C"))
+        );
+        assert_eq!(
+            collection1
+                .get_graphql_schema(profile_id, &recipe_id)
+                .unwrap(),
+            Some(graphql_schema("B"))
+        );
+        assert_eq!(
+            collection2.get_graphql_schema(None, &recipe_id).unwrap(),
+            None
+        );
+    }
+
+    /// Build a minimal GraphQL schema, distinguished by its query type name
+    fn graphql_schema(query_type: &str) -> GraphQlSchema {
+        GraphQlSchema::from_response(
+            serde_json::json!({"data": {"__schema": {
+                "queryType": {"name": query_type},
+                "mutationType": null,
+                "subscriptionType": null,
+                "types": [],
+            }}})
+            .to_string()
+            .as_bytes(),
+        )
+        .unwrap()
+    }
 }
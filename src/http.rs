@@ -33,33 +33,86 @@
 //! | RequestRecord |
 //! +---------------+
 
+mod assertion;
+mod charset;
+mod compression;
+mod cookies;
+mod graphql;
+mod hook;
+mod mock;
 mod parse;
+mod proxy;
 mod query;
 mod record;
+mod retry;
+mod sse;
+mod timing;
+mod tls;
+mod transport;
+mod unix;
+mod websocket;
+mod workflow;
 
+pub use assertion::{
+    summarize as summarize_assertions, AssertionResult, Assertions,
+    BodyAssertion, StatusAssertion, StatusRange,
+};
+pub use charset::decode_text;
+pub use cookies::Cookie;
+pub use graphql::{
+    introspection_recipe, response_errors, GraphQlSchema, OperationKind,
+    SchemaField,
+};
+pub use mock::{MockHit, MockOptions, MockRoute, MockServer, MockSource};
 pub use parse::*;
+pub use proxy::ProxyConfig;
 pub use query::*;
 pub use record::*;
+pub use retry::{RetryErrorKind, RetryPolicy};
+pub use sse::{EventStreamParser, ServerEvent};
+pub use timing::{RequestTimings, TimingPhase};
+pub use tls::TlsConfig;
+pub use unix::{UnixTarget, UNIX_SCHEME};
+pub use websocket::{websocket_url, WebSocketEvent, WebSocketMessage};
+pub use workflow::{run_workflow, StepOutcome, StepUpdate};
 
 use crate::{
-    collection::{self, Authentication, Method, Recipe},
+    collection::{self, Authentication, Method, Recipe, RecipeBody},
     config::Config,
     db::CollectionDatabase,
-    template::{Template, TemplateContext},
+    http::{
+        timing::ConnectionTimings, transport::Transport,
+        websocket::WebSocketTransport,
+    },
+    template::{Prompt, Prompter, Template, TemplateContext},
     util::ResultExt,
 };
-use anyhow::Context;
+use anyhow::{bail, Context};
 use base64::{prelude::BASE64_STANDARD, write::EncoderWriter};
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
 use chrono::Utc;
 use futures::future;
 use indexmap::IndexMap;
 use reqwest::{
     header::{self, HeaderMap, HeaderName, HeaderValue},
-    Client,
+    Client, StatusCode,
+};
+use rustls::ClientConfig;
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    future::Future,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::{
+    fs::File,
+    io::AsyncWriteExt,
+    sync::{mpsc, oneshot},
+    try_join,
 };
-use std::{collections::HashSet, future::Future, io::Write, sync::Arc};
-use tokio::try_join;
 use tracing::{debug, info, info_span};
 use url::Url;
 
@@ -70,40 +123,98 @@ const USER_AGENT: &str =
 /// de-asyncify HTTP so it can be called in the main TUI thread. All heavy
 /// lifting will be pushed to background tasks.
 ///
-/// This is safe and cheap to clone because reqwest's `Client` type uses `Arc`
-/// internally. [reqwest::Client]
+/// This is safe and cheap to clone because the underlying clients use `Arc`
+/// internally.
 #[derive(Clone, Debug)]
 pub struct HttpEngine {
-    client: Client,
+    /// reqwest can't build a request without a client, so this is only used
+    /// for building. Requests are sent with the clients below
+    request_builder: Client,
+    client: Transport,
     /// This client ignores TLS cert errors. Only use it if the user
     /// specifically wants to ignore errors for the request!
-    danger_client: Client,
+    danger_client: Transport,
     database: CollectionDatabase,
     danger_hostnames: HashSet<String>,
+    /// Global default for response decompression. Can be overridden per
+    /// request
+    decompress: bool,
+    /// Is the cookie jar enabled?
+    cookies: bool,
+    /// Global proxy config. Can be overridden per profile
+    proxy: Option<ProxyConfig>,
+    /// Client certificate and CA settings, keyed by hostname
+    tls: Arc<IndexMap<String, TlsConfig>>,
+    /// Proxy and TLS settings are bound to a client, so we build a client for
+    /// each combination of them on demand
+    custom_clients: Arc<Mutex<HashMap<ClientKey, Transport>>>,
+    /// Should completed requests be stored in history?
+    history: bool,
+}
+
+/// Give up after following this many redirects for a single request
+const MAX_REDIRECTS: usize = 10;
+
+/// Minimum time between progress reports for a download, so a fast download
+/// doesn't flood the consumer
+const DOWNLOAD_PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// All the settings that require a dedicated client. Requests with the same
+/// key can share a client
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+struct ClientKey {
+    proxy: Option<ProxyConfig>,
+    /// The host is excluded from the configured proxy by `no_proxy`, so
+    /// proxies from environment variables must be ignored too
+    bypass_proxy: bool,
+    /// Ignore TLS cert errors?
+    danger: bool,
+    /// Host whose TLS config should be applied
+    tls_host: Option<String>,
 }
 
 impl HttpEngine {
     /// Build a new HTTP engine, which can be used for the entire program life
     pub fn new(config: &Config, database: CollectionDatabase) -> Self {
         Self {
-            client: Client::builder()
-                .user_agent(USER_AGENT)
-                .build()
-                .expect("Error building reqwest client"),
-            danger_client: Client::builder()
-                .user_agent(USER_AGENT)
-                .danger_accept_invalid_certs(true)
+            request_builder: Client::builder()
+                .no_proxy()
                 .build()
                 .expect("Error building reqwest client"),
+            client: Transport::new(
+                None,
+                false,
+                tls::default_client_config(false),
+            )
+            .expect("Error building HTTP client"),
+            danger_client: Transport::new(
+                None,
+                false,
+                tls::default_client_config(true),
+            )
+            .expect("Error building HTTP client"),
             database,
             danger_hostnames: config
                 .ignore_certificate_hosts
                 .iter()
                 .cloned()
                 .collect(),
+            decompress: config.decompress,
+            cookies: config.cookies,
+            proxy: config.proxy.clone(),
+            tls: Arc::new(config.tls.clone()),
+            custom_clients: Default::default(),
+            history: true,
         }
     }
 
+    /// Enable or disable storing completed requests in history. Enabled by
+    /// default
+    pub fn with_history(mut self, history: bool) -> Self {
+        self.history = history;
+        self
+    }
+
     /// Launch an HTTP request. Upon completion, it will automatically be
     /// registered in the database for posterity.
     ///
@@ -120,40 +231,253 @@ impl HttpEngine {
     pub async fn send(
         self,
         request: Arc<Request>,
+    ) -> Result<RequestRecord, RequestError> {
+        self.send_inner(request, None).await
+    }
+
+    /// Launch an HTTP request, streaming the response body if it's an event
+    /// stream. Other responses are handled the same as [Self::send]. The
+    /// returned record contains the full body that was received before the
+    /// stream ended or was cancelled.
+    pub async fn send_streaming(
+        self,
+        request: Arc<Request>,
+        stream: StreamHandler,
+    ) -> Result<RequestRecord, RequestError> {
+        self.send_inner(request, Some(stream)).await
+    }
+
+    /// Open a WebSocket connection using a request built from a WebSocket
+    /// recipe, and run the session until it's closed. Each string received
+    /// from `outgoing` is sent as a text message, and the connection is
+    /// closed once all senders for `outgoing` are dropped. Every event in the
+    /// session is passed to `on_event`.
+    ///
+    /// Cookies from the jar are sent with the handshake. The connection uses
+    /// the same proxy and TLS settings as an HTTP request to the same host.
+    pub async fn connect_websocket(
+        self,
+        request: Arc<Request>,
+        outgoing: mpsc::UnboundedReceiver<String>,
+        on_event: impl FnMut(WebSocketEvent),
+    ) -> anyhow::Result<()> {
+        let span = info_span!("WebSocket session", request_id = %request.id);
+        span.in_scope(|| async move {
+            let mut headers = request.headers.clone();
+            if !headers.contains_key(header::USER_AGENT) {
+                headers.insert(
+                    header::USER_AGENT,
+                    HeaderValue::from_static(USER_AGENT),
+                );
+            }
+            if let Some(cookies) = self.cookie_header(&request, &request.url) {
+                headers.insert(header::COOKIE, cookies);
+            }
+
+            let key = self.client_key(&request);
+            // reqwest reads proxies from the environment for HTTP requests,
+            // but here we have to do it ourselves
+            let proxy = match key.proxy.clone() {
+                Some(proxy) => Some(proxy),
+                None if !key.bypass_proxy => {
+                    ProxyConfig::from_env(&websocket_url(&request.url)?)
+                }
+                None => None,
+            };
+            let transport = WebSocketTransport {
+                proxy,
+                tls: self.tls_config(&key).await?,
+            };
+            websocket::run_session(
+                &request.url,
+                headers,
+                transport,
+                outgoing,
+                on_event,
+            )
+            .await
+            .traced()
+        })
+        .await
+    }
+
+    /// Send a GraphQL introspection request (built from
+    /// [introspection_recipe]), and cache the returned schema in the database
+    /// for the request's profile+recipe. The request is *not* stored in
+    /// history.
+    pub async fn introspect(
+        self,
+        request: Arc<Request>,
+    ) -> anyhow::Result<GraphQlSchema> {
+        let span =
+            info_span!("GraphQL introspection", request_id = %request.id);
+        span.in_scope(|| async move {
+            let response = self
+                .send_request_helper(&request, &mut None)
+                .await?
+                .response;
+            let schema = GraphQlSchema::from_response(response.body.bytes())
+                .with_context(|| {
+                    format!(
+                        "Introspection request returned {}",
+                        response.status
+                    )
+                })?;
+            self.database.set_graphql_schema(
+                request.profile_id.as_ref(),
+                &request.recipe_id,
+                &schema,
+            )?;
+            Ok(schema)
+        })
+        .await
+        .traced()
+    }
+
+    async fn send_inner(
+        self,
+        request: Arc<Request>,
+        mut stream: Option<StreamHandler>,
     ) -> Result<RequestRecord, RequestError> {
         let id = request.id;
 
         let span = info_span!("HTTP request", request_id = %id);
         span.in_scope(|| async move {
-            // This start time will be accurate because the request doesn't
-            // launch until this whole future is awaited
+            let request = Self::run_pre_request_hook(request).await?;
+            // The request sent uses the jar's cookies and the proxy config at
+            // send time. Record them, so history reflects what was actually
+            // sent
+            let sent_request = self.sent_request(&request);
+            let max_attempts = request
+                .retry
+                .as_ref()
+                .map_or(1, |retry| retry.max_attempts.max(1));
+            let mut attempt = 1;
+
+            // Each attempt is timed separately, and the final one determines
+            // the timing of the returned record
+            let (result, start_time, end_time) = loop {
+                // This start time will be accurate because the request doesn't
+                // launch until this whole future is awaited
+
+                // Technically the elapsed time will include the conversion
+                // time, but that should be extremely minimal compared to
+                // network IO
+                let start_time = Utc::now();
+                let is_streaming = stream.is_some();
+                let result =
+                    self.send_request_helper(&request, &mut stream).await;
+                let end_time = Utc::now();
+
+                // Once a stream has started, the consumer already has the
+                // response so we can't start over
+                let streamed = is_streaming && stream.is_none();
+                let delay = match (&result, &request.retry) {
+                    _ if attempt >= max_attempts || streamed => None,
+                    (Ok(exchange), Some(retry))
+                        if retry
+                            .should_retry_status(exchange.response.status) =>
+                    {
+                        Some(
+                            retry.delay(
+                                attempt,
+                                Some(&exchange.response.headers),
+                            ),
+                        )
+                    }
+                    (Err(error), Some(retry))
+                        if retry.should_retry_error(error) =>
+                    {
+                        Some(retry.delay(attempt, None))
+                    }
+                    _ => None,
+                };
+                let Some(delay) = delay else {
+                    break (result, start_time, end_time);
+                };
 
-            // Technically the elapsed time will include the conversion time,
-            // but that should be extremely minimal compared to network IO
-            let start_time = Utc::now();
-            let result = self.send_request_helper(&request).await;
-            let end_time = Utc::now();
+                match &result {
+                    Ok(exchange) => info!(
+                        status = exchange.response.status.as_u16(),
+                        attempt,
+                        ?delay,
+                        "Retrying request"
+                    ),
+                    Err(error) => {
+                        info!(%error, attempt, ?delay, "Retrying request")
+                    }
+                }
+                // Intermediate attempts get their own ID, so the final
+                // attempt can keep the request's ID
+                if let (Ok(exchange), Some(true), true) = (
+                    result,
+                    request.retry.as_ref().map(|retry| retry.store_attempts),
+                    self.history,
+                ) {
+                    let _ = self.database.insert_request(&RequestRecord {
+                        id: RequestId::new(),
+                        request: Arc::clone(&sent_request),
+                        response: exchange.response,
+                        start_time,
+                        end_time,
+                        attempts: attempt,
+                        timings: Some(exchange.timings),
+                        redirects: exchange.redirects,
+                        annotations: Vec::new(),
+                    });
+                }
+
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+                if let Some(stream) = &mut stream {
+                    (stream.on_retry)(attempt);
+                }
+            };
 
             // Attach metadata to the error and yeet it
             match result {
                 // Can't use map_err because we need to conditionally move
                 // the request
-                Ok(response) => {
+                Ok(exchange) => {
+                    let response = exchange.response;
                     info!(status = response.status.as_u16(), "Response");
-                    let record = RequestRecord {
+                    let mut record = RequestRecord {
                         id,
-                        request,
+                        request: Arc::clone(&sent_request),
                         response,
                         start_time,
                         end_time,
+                        attempts: attempt,
+                        timings: Some(exchange.timings),
+                        redirects: exchange.redirects,
+                        annotations: Vec::new(),
                     };
 
+                    // The hook can fail the request, in which case it isn't
+                    // stored in history
+                    if let Some(command) = &request.post_response_hook {
+                        if let Err(error) =
+                            hook::run_post_response(command, &mut record)
+                                .await
+                                .context("Error in post-response hook")
+                        {
+                            return Err(RequestError {
+                                request: sent_request,
+                                start_time,
+                                end_time,
+                                error,
+                            });
+                        }
+                    }
+
                     // Error here should *not* kill the request
-                    let _ = self.database.insert_request(&record);
+                    if self.history {
+                        let _ = self.database.insert_request(&record);
+                    }
                     Ok(record)
                 }
                 Err(error) => Err(RequestError {
-                    request,
+                    request: sent_request,
                     start_time,
                     end_time,
                     error,
@@ -164,31 +488,318 @@ impl HttpEngine {
         .await
     }
 
+    /// Run the request's pre-request hook, if it has one, and get the request
+    /// with the hook's changes applied. The modified request keeps the same
+    /// ID. This runs on every send, so a hook that signs the request produces
+    /// a fresh signature each time.
+    async fn run_pre_request_hook(
+        request: Arc<Request>,
+    ) -> Result<Arc<Request>, RequestError> {
+        let Some(command) = &request.pre_request_hook else {
+            return Ok(request);
+        };
+        let start_time = Utc::now();
+        let mut modified = Request {
+            id: request.id,
+            ..request.duplicate()
+        };
+        match hook::run_pre_request(command, &mut modified)
+            .await
+            .context("Error in pre-request hook")
+        {
+            Ok(()) => Ok(Arc::new(modified)),
+            Err(error) => Err(RequestError {
+                request,
+                start_time,
+                end_time: Utc::now(),
+                error,
+            })
+            .traced(),
+        }
+    }
+
     /// An exact encapsulation of the "request". The execution of this function
-    /// is synonymous with a request's elapsed time.
+    /// is synonymous with a request's elapsed time. Returns the response
+    /// along with how long each phase of the request took, and the redirects
+    /// that were followed to get it.
     async fn send_request_helper(
         &self,
         request: &Request,
-    ) -> reqwest::Result<Response> {
+        stream: &mut Option<StreamHandler>,
+    ) -> anyhow::Result<Exchange> {
         // Convert to reqwest format as part of the execution. This means
         // certain builder errors will show up as "request" errors which is
         // janky, but reqwest already doesn't report some builder erorrs until
         // you execute the request, and this is much easier than frontloading
         // the conversion during the build process.
-        let reqwest_request = self.convert_request(request)?;
+        let mut reqwest_request = self.convert_request(request)?;
 
-        // If the user wants to ignore cert errors on this host, use the client
-        // that's set up for that
-        let host = reqwest_request.url().host_str().unwrap_or_default();
-        let client = if self.danger_hostnames.contains(host) {
-            &self.danger_client
+        let (reqwest_response, start, connection, redirects) =
+            if let Some(target) = UnixTarget::from_url(&request.url) {
+                // Socket requests skip the client, so we have to add its
+                // default headers ourselves. Proxy and TLS don't apply
+                reqwest_request
+                    .headers_mut()
+                    .entry(header::USER_AGENT)
+                    .or_insert(HeaderValue::from_static(USER_AGENT));
+                let start = Instant::now();
+                let response = target.send(reqwest_request).await?;
+                // Socket responses don't know their URL, and redirects aren't
+                // followed because they could point anywhere
+                self.store_cookies(request, &request.url, response.headers());
+                (response, start, ConnectionTimings::default(), Vec::new())
+            } else {
+                let key = self.client_key(request);
+                let client = self.client(key).await?;
+
+                let start = Instant::now();
+                let (response, connection, redirects) = self
+                    .execute_following_redirects(
+                        &client,
+                        request,
+                        reqwest_request,
+                    )
+                    .await?;
+                (response, start, connection, redirects)
+            };
+        let waiting = start.elapsed();
+        let response = if let Some(path) = &request.download {
+            let on_download =
+                stream.as_mut().map(|stream| &mut stream.on_download);
+            self.download_response(request, reqwest_response, path, on_download)
+                .await?
         } else {
-            &self.client
+            // The handler is consumed once a stream starts
+            let stream = if is_event_stream(reqwest_response.headers()) {
+                stream.take()
+            } else {
+                None
+            };
+            match stream {
+                Some(stream) => {
+                    self.stream_response(request, reqwest_response, stream)
+                        .await?
+                }
+                // Load the full response and convert it to our format
+                None => {
+                    self.convert_response(request, reqwest_response).await?
+                }
+            }
+        };
+        let timings =
+            RequestTimings::new(connection, waiting, start.elapsed() - waiting);
+        Ok(Exchange {
+            response,
+            timings,
+            redirects,
+        })
+    }
+
+    /// Send a request, following any redirects. reqwest can follow redirects
+    /// itself, but it doesn't expose the intermediate responses, so we do it
+    /// ourselves to record each hop. Cookies set by every hop are stored.
+    /// Returns the final response, the total time spent opening connections,
+    /// and the redirect hops.
+    async fn execute_following_redirects(
+        &self,
+        client: &Transport,
+        request: &Request,
+        mut reqwest_request: reqwest::Request,
+    ) -> anyhow::Result<(reqwest::Response, ConnectionTimings, Vec<RedirectHop>)>
+    {
+        let mut connection = ConnectionTimings::default();
+        let mut redirects = Vec::new();
+        loop {
+            // Bodies are always in memory, so this only fails for streams
+            let next_request = reqwest_request.try_clone();
+            let (response, hop_connection) =
+                timing::measure_connection(client.execute(reqwest_request))
+                    .await;
+            let response = response?;
+            connection.add(hop_connection);
+            self.store_cookies(request, response.url(), response.headers());
+
+            let Some((next_request, location)) =
+                next_request.zip(redirect_location(&response))
+            else {
+                return Ok((response, connection, redirects));
+            };
+            if redirects.len() >= MAX_REDIRECTS {
+                bail!("Too many redirects (limit is {MAX_REDIRECTS})");
+            }
+            info!(
+                status = response.status().as_u16(),
+                %location,
+                "Following redirect"
+            );
+            let status = response.status();
+            redirects.push(RedirectHop {
+                url: response.url().clone(),
+                status,
+                headers: response.headers().clone(),
+            });
+            reqwest_request =
+                self.redirect_request(request, next_request, status, location);
+        }
+    }
+
+    /// Build the request for the next hop of a redirect, following the same
+    /// rules as browsers (and reqwest)
+    fn redirect_request(
+        &self,
+        request: &Request,
+        mut next: reqwest::Request,
+        status: StatusCode,
+        location: Url,
+    ) -> reqwest::Request {
+        // 303 always switches to GET. 301 and 302 do too for POST, for
+        // historical reasons
+        let switch_to_get = match status {
+            StatusCode::SEE_OTHER => next.method() != reqwest::Method::HEAD,
+            StatusCode::MOVED_PERMANENTLY | StatusCode::FOUND => {
+                next.method() == reqwest::Method::POST
+            }
+            _ => false,
         };
+        if switch_to_get {
+            *next.method_mut() = reqwest::Method::GET;
+            *next.body_mut() = None;
+            for name in [
+                header::CONTENT_TYPE,
+                header::CONTENT_LENGTH,
+                header::CONTENT_ENCODING,
+            ] {
+                next.headers_mut().remove(name);
+            }
+        }
+
+        // Don't leak credentials to another server
+        if next.url().origin() != location.origin() {
+            for name in [
+                header::AUTHORIZATION,
+                header::PROXY_AUTHORIZATION,
+                header::COOKIE,
+            ] {
+                next.headers_mut().remove(name);
+            }
+        }
+        *next.url_mut() = location;
+
+        // Earlier hops may have changed the jar, so get its cookies again
+        if !request.headers.contains_key(header::COOKIE) {
+            next.headers_mut().remove(header::COOKIE);
+        }
+        if let Some(cookies) = self.cookie_header(request, next.url()) {
+            next.headers_mut().insert(header::COOKIE, cookies);
+        }
+        next
+    }
+
+    /// Get the proxy and TLS settings that apply to a request
+    fn client_key(&self, request: &Request) -> ClientKey {
+        // If the user wants to ignore cert errors on this host, use a client
+        // that's set up for that
+        let host = request.url.host_str().unwrap_or_default();
+        let configured_proxy = request.proxy.as_ref().or(self.proxy.as_ref());
+        let proxy = request.effective_proxy(self.proxy.as_ref());
+        ClientKey {
+            proxy: proxy.cloned(),
+            bypass_proxy: configured_proxy.is_some() && proxy.is_none(),
+            danger: self.danger_hostnames.contains(host),
+            tls_host: self.tls.contains_key(host).then(|| host.to_owned()),
+        }
+    }
 
-        let reqwest_response = client.execute(reqwest_request).await?;
-        // Load the full response and convert it to our format
-        self.convert_response(reqwest_response).await
+    /// Get the client to send a request with. Clients are cheap to clone
+    async fn client(&self, key: ClientKey) -> anyhow::Result<Transport> {
+        // Use the stock clients when possible
+        if let ClientKey {
+            proxy: None,
+            bypass_proxy: false,
+            danger,
+            tls_host: None,
+        } = key
+        {
+            return Ok(if danger {
+                self.danger_client.clone()
+            } else {
+                self.client.clone()
+            });
+        }
+
+        if let Some(client) = self
+            .custom_clients
+            .lock()
+            .expect("Client lock poisoned")
+            .get(&key)
+        {
+            return Ok(client.clone());
+        }
+
+        // We can't hold the lock while building, because loading TLS config
+        // is async. Worst case two requests build the same client at once
+        info!(?key, "Building client");
+        let tls = self.tls_config(&key).await?;
+        let client = Transport::new(key.proxy.as_ref(), key.bypass_proxy, tls)?;
+        self.custom_clients
+            .lock()
+            .expect("Client lock poisoned")
+            .insert(key, client.clone());
+        Ok(client)
+    }
+
+    /// Build the TLS config for a client. This loads the host's certificate
+    /// files, if it has any TLS settings.
+    async fn tls_config(
+        &self,
+        key: &ClientKey,
+    ) -> anyhow::Result<ClientConfig> {
+        Ok(if let Some(host) = &key.tls_host {
+            // Only env vars are available to the password template, because
+            // TLS config is global rather than tied to a collection
+            let template_context = TemplateContext {
+                collection: Default::default(),
+                selected_profile: None,
+                http_engine: None,
+                database: self.database.clone(),
+                overrides: Default::default(),
+                prompter: Box::new(NoPrompter),
+                recursion_count: Default::default(),
+            };
+            self.tls[host]
+                .client_config(key.danger, &template_context)
+                .await
+                .with_context(|| {
+                    format!("Error loading TLS config for {host}")
+                })?
+        } else {
+            tls::default_client_config(key.danger)
+        })
+    }
+
+    /// Should the response for this request be decompressed?
+    fn should_decompress(&self, request: &Request) -> bool {
+        request.decompress.unwrap_or(self.decompress)
+    }
+
+    /// Get a decoder to decompress a response body as it arrives, if enabled
+    /// and the body is encoded. Unlike a loaded body, we can't fall back to
+    /// the raw content if decompression fails partway through, so decoding
+    /// errors are fatal. An unsupported encoding is passed through as-is.
+    fn stream_decoder(
+        &self,
+        request: &Request,
+        headers: &HeaderMap,
+    ) -> Option<compression::StreamDecoder> {
+        let content_encoding = headers
+            .get(header::CONTENT_ENCODING)
+            .and_then(|value| value.to_str().ok())
+            .filter(|_| self.should_decompress(request))?;
+        compression::StreamDecoder::new(content_encoding)
+            .context("Error decompressing response body")
+            .traced()
+            .ok()
+            .flatten()
     }
 
     /// Convert from our request type to reqwest's. The input request should
@@ -203,10 +814,17 @@ impl HttpEngine {
         &self,
         request: &Request,
     ) -> reqwest::Result<reqwest::Request> {
+        // reqwest won't build a request for a socket URL, so give it the
+        // equivalent HTTP URL. It's only used for the path and query
+        let url = match UnixTarget::from_url(&request.url) {
+            Some(target) => target.http_url(),
+            None => request.url.clone(),
+        };
+
         // Convert to reqwest's request format
         let mut request_builder = self
-            .client
-            .request(request.method.clone(), request.url.clone())
+            .request_builder
+            .request(request.method.clone(), url)
             .headers(request.headers.clone());
 
         // Add body
@@ -214,14 +832,103 @@ impl HttpEngine {
             request_builder = request_builder.body(body.clone());
         }
 
+        // Tell the server what we can decompress, unless the user already did
+        if self.should_decompress(request)
+            && !request.headers.contains_key(header::ACCEPT_ENCODING)
+        {
+            request_builder = request_builder
+                .header(header::ACCEPT_ENCODING, compression::ACCEPT_ENCODING);
+        }
+
+        if let Some(cookies) = self.cookie_header(request, &request.url) {
+            request_builder = request_builder.header(header::COOKIE, cookies);
+        }
+
         request_builder.build()
     }
 
+    /// Get the request as it will be sent, with the `Cookie` header from the
+    /// jar included and the proxy it goes through. If neither applies, this
+    /// is the same request.
+    fn sent_request(&self, request: &Arc<Request>) -> Arc<Request> {
+        let cookies = self.cookie_header(request, &request.url);
+        // Socket requests never go through a proxy
+        let sent_proxy = UnixTarget::from_url(&request.url)
+            .is_none()
+            .then(|| request.effective_proxy(self.proxy.as_ref()))
+            .flatten()
+            .map(ProxyConfig::redacted_url);
+        if cookies.is_none() && sent_proxy.is_none() {
+            return Arc::clone(request);
+        }
+
+        let mut headers = request.headers.clone();
+        if let Some(cookies) = cookies {
+            headers.insert(header::COOKIE, cookies);
+        }
+        Arc::new(Request {
+            id: request.id,
+            headers,
+            sent_proxy,
+            ..request.duplicate()
+        })
+    }
+
+    /// Get the `Cookie` header to attach from the jar for a URL, if it's
+    /// enabled. If the user set their own header, the jar isn't used.
+    fn cookie_header(
+        &self,
+        request: &Request,
+        url: &Url,
+    ) -> Option<HeaderValue> {
+        if !self.cookies || request.headers.contains_key(header::COOKIE) {
+            return None;
+        }
+        let cookies = self
+            .database
+            .get_cookies(request.profile_id.as_ref())
+            .ok()?;
+        Cookie::header_value(&cookies, url, Utc::now())?
+            .try_into()
+            .ok()
+    }
+
+    /// Store any cookies set by a response in the jar, if it's enabled.
+    /// Errors are logged and discarded, because they shouldn't kill the
+    /// request.
+    fn store_cookies(&self, request: &Request, url: &Url, headers: &HeaderMap) {
+        if !self.cookies {
+            return;
+        }
+        let profile_id = request.profile_id.as_ref();
+        let now = Utc::now();
+        for value in headers.get_all(header::SET_COOKIE) {
+            let Some(cookie) = value
+                .to_str()
+                .ok()
+                .and_then(|value| Cookie::parse(value, url, now))
+            else {
+                continue;
+            };
+            // An expired cookie is how servers tell us to delete one
+            let _ = if cookie.is_expired(now) {
+                self.database.delete_cookie(profile_id, &cookie)
+            } else {
+                self.database.set_cookie(profile_id, &cookie)
+            };
+        }
+    }
+
     /// Convert reqwest's response type into ours. This is async because the
     /// response content is not necessarily loaded when we first get the
     /// response. Only fallible if the response content fails to load.
+    ///
+    /// If enabled, the body will be decompressed according to its
+    /// `Content-Encoding` header. If decompression fails, the raw body is
+    /// kept so the user can still see what they got.
     async fn convert_response(
         &self,
+        request: &Request,
         response: reqwest::Response,
     ) -> reqwest::Result<Response> {
         // Copy response metadata out first, because we need to move the
@@ -230,14 +937,273 @@ impl HttpEngine {
         let headers = response.headers().clone();
 
         // Pre-resolve the content, so we get all the async work done
-        let body = response.bytes().await?.into();
+        let mut body = response.bytes().await?;
+        let mut encoded_size = None;
+
+        let content_encoding = headers
+            .get(header::CONTENT_ENCODING)
+            .and_then(|value| value.to_str().ok())
+            .filter(|_| self.should_decompress(request));
+        if let Some(content_encoding) = content_encoding {
+            if let Ok(Some(decoded)) =
+                compression::decompress(content_encoding, &body)
+                    .context("Error decompressing response body")
+                    .traced()
+            {
+                encoded_size = Some(body.len() as u64);
+                body = decoded.into();
+            }
+        }
 
         Ok(Response {
             status,
             headers,
-            body,
+            body: body.into(),
+            encoded_size,
+            download: None,
         })
     }
+
+    /// Read a response body chunk by chunk, passing each chunk to the stream
+    /// handler as it arrives. Stops when the body ends or the handler cancels.
+    /// If enabled, each chunk is decompressed before it's passed on.
+    async fn stream_response(
+        &self,
+        request: &Request,
+        mut response: reqwest::Response,
+        stream: StreamHandler,
+    ) -> anyhow::Result<Response> {
+        let StreamHandler {
+            on_start,
+            mut on_chunk,
+            mut cancel,
+            ..
+        } = stream;
+        let status = response.status();
+        let headers = response.headers().clone();
+        let mut decoder = self.stream_decoder(request, &headers);
+        on_start(status, headers.clone());
+
+        let mut body = BytesMut::new();
+        let mut encoded_size = 0;
+        let mut cancelled = false;
+        loop {
+            tokio::select! {
+                chunk = response.chunk() => match chunk? {
+                    Some(chunk) => {
+                        encoded_size += chunk.len() as u64;
+                        let chunk = match &mut decoder {
+                            Some(decoder) => decoder
+                                .decode(&chunk)
+                                .context("Error decompressing response body")?
+                                .into(),
+                            None => chunk,
+                        };
+                        if !chunk.is_empty() {
+                            body.extend_from_slice(&chunk);
+                            on_chunk(chunk);
+                        }
+                    }
+                    None => break,
+                },
+                // Sender being dropped also counts as a cancellation
+                _ = &mut cancel => {
+                    info!("Response stream closed");
+                    cancelled = true;
+                    break;
+                }
+            }
+        }
+
+        let encoded_size = if let Some(decoder) = decoder {
+            // A closed stream ends partway through the encoded content, so
+            // there's nothing more to get out of it
+            if !cancelled {
+                let rest: Bytes = decoder
+                    .finish()
+                    .context("Error decompressing response body")?
+                    .into();
+                if !rest.is_empty() {
+                    body.extend_from_slice(&rest);
+                    on_chunk(rest);
+                }
+            }
+            Some(encoded_size)
+        } else {
+            None
+        };
+
+        Ok(Response {
+            status,
+            headers,
+            body: body.freeze().into(),
+            encoded_size,
+            download: None,
+        })
+    }
+
+    /// Write a response body to a file as it arrives, instead of loading it
+    /// into memory. If enabled, the body is decompressed as it's written.
+    /// Progress is reported periodically, and once more when the body ends.
+    /// If the download doesn't finish, because of an error or because the
+    /// request was cancelled, the partial file is deleted.
+    async fn download_response(
+        &self,
+        request: &Request,
+        mut response: reqwest::Response,
+        path: &Path,
+        mut on_download: Option<&mut Box<dyn FnMut(DownloadProgress) + Send>>,
+    ) -> anyhow::Result<Response> {
+        let status = response.status();
+        let headers = response.headers().clone();
+        let path = env::current_dir()
+            .context("Error getting current directory")?
+            .join(path);
+        info!(path = %path.display(), "Downloading response body");
+
+        let mut decoder = self.stream_decoder(request, &headers);
+
+        let write_error =
+            || format!("Error writing to download file `{}`", path.display());
+        let mut file = File::create(&path).await.with_context(|| {
+            format!("Error creating download file `{}`", path.display())
+        })?;
+        let guard = PartialDownload(&path);
+        let mut progress = DownloadProgress {
+            written: 0,
+            total: response.content_length(),
+        };
+        let mut size = 0;
+        let mut last_report = Instant::now();
+        while let Some(chunk) = response.chunk().await? {
+            progress.written += chunk.len() as u64;
+            let chunk = match &mut decoder {
+                Some(decoder) => decoder
+                    .decode(&chunk)
+                    .context("Error decompressing response body")?
+                    .into(),
+                None => chunk,
+            };
+            file.write_all(&chunk).await.with_context(write_error)?;
+            size += chunk.len() as u64;
+            if let Some(on_download) = &mut on_download {
+                if last_report.elapsed() >= DOWNLOAD_PROGRESS_INTERVAL {
+                    on_download(progress);
+                    last_report = Instant::now();
+                }
+            }
+        }
+        let encoded_size = if let Some(decoder) = decoder {
+            let rest = decoder
+                .finish()
+                .context("Error decompressing response body")?;
+            file.write_all(&rest).await.with_context(write_error)?;
+            size += rest.len() as u64;
+            Some(progress.written)
+        } else {
+            None
+        };
+        file.flush().await.with_context(write_error)?;
+        guard.keep();
+        if let Some(on_download) = on_download {
+            on_download(progress);
+        }
+
+        Ok(Response {
+            status,
+            headers,
+            body: Bytes::new().into(),
+            encoded_size,
+            download: Some(Download { path, size }),
+        })
+    }
+}
+
+/// Deletes a download file when dropped, unless the download finished. This
+/// covers errors as well as the request future being dropped mid-download.
+struct PartialDownload<'a>(&'a Path);
+
+impl<'a> PartialDownload<'a> {
+    /// The download finished, so keep the file
+    fn keep(self) {
+        std::mem::forget(self);
+    }
+}
+
+impl<'a> Drop for PartialDownload<'a> {
+    fn drop(&mut self) {
+        info!(path = %self.0.display(), "Deleting partial download");
+        let _ = std::fs::remove_file(self.0)
+            .with_context(|| {
+                format!(
+                    "Error deleting partial download `{}`",
+                    self.0.display()
+                )
+            })
+            .traced();
+    }
+}
+
+/// Callbacks for following a request while it's in flight, and receiving a
+/// response body incrementally. Only event streams (`text/event-stream`) are
+/// streamed, because they may never end. Compressed streams aren't supported,
+/// and are loaded in full instead. Downloads are never streamed to the
+/// handler, but their progress is reported to it.
+#[derive(derive_more::Debug)]
+pub struct StreamHandler {
+    /// Called before each retry, with the number of the attempt that's about
+    /// to start (2 for the first retry)
+    #[debug(skip)]
+    pub on_retry: Box<dyn FnMut(u32) + Send>,
+    /// Called when the response headers are received, if the body is going
+    /// to be streamed
+    #[debug(skip)]
+    pub on_start: Box<dyn FnOnce(StatusCode, HeaderMap) + Send>,
+    /// Called for each chunk of the body, as it arrives
+    #[debug(skip)]
+    pub on_chunk: Box<dyn FnMut(Bytes) + Send>,
+    /// Called periodically as a download is written to its file
+    #[debug(skip)]
+    pub on_download: Box<dyn FnMut(DownloadProgress) + Send>,
+    /// Close the stream when this resolves, or when its sender is dropped
+    pub cancel: oneshot::Receiver<()>,
+}
+
+/// Everything received for one attempt at sending a request
+struct Exchange {
+    response: Response,
+    timings: RequestTimings,
+    /// Redirects that were followed to get the response, in order
+    redirects: Vec<RedirectHop>,
+}
+
+/// Get the URL that a response redirects to, if it's a redirect
+fn redirect_location(response: &reqwest::Response) -> Option<Url> {
+    let is_redirect = matches!(
+        response.status(),
+        StatusCode::MOVED_PERMANENTLY
+            | StatusCode::FOUND
+            | StatusCode::SEE_OTHER
+            | StatusCode::TEMPORARY_REDIRECT
+            | StatusCode::PERMANENT_REDIRECT
+    );
+    if !is_redirect {
+        return None;
+    }
+    let location = response.headers().get(header::LOCATION)?.to_str().ok()?;
+    // Location can be relative to the current URL
+    response.url().join(location).ok()
+}
+
+/// Should a response with these headers be streamed?
+fn is_event_stream(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| ContentType::from_header(value).ok())
+        .is_some_and(|content_type| {
+            matches!(content_type, ContentType::EventStream)
+        })
 }
 
 /// The foundation of a request. This builder captures *how* the request will
@@ -332,18 +1298,25 @@ impl RequestBuilder {
         template_context: &TemplateContext,
     ) -> anyhow::Result<Request> {
         // Render everything in parallel
-        let (url, headers, body) = try_join!(
+        let (url, mut headers, body, download) = try_join!(
             self.render_url(template_context),
             self.render_headers(template_context),
             self.render_body(template_context),
+            self.render_download(template_context),
         )?;
 
-        info!(
-            recipe_id = %self.recipe.id,
-            "Built request from recipe",
-        );
+        // GraphQL bodies are always JSON
+        if matches!(self.recipe.body, Some(RecipeBody::GraphQl(_)))
+            && !headers.contains_key(header::CONTENT_TYPE)
+        {
+            headers.insert(
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("application/json"),
+            );
+        }
 
-        Ok(Request {
+        let hooks = template_context.collection.hooks(&self.recipe.id);
+        let request = Request {
             id: self.id,
             profile_id: template_context.selected_profile.clone(),
             recipe_id: self.recipe.id.clone(),
@@ -351,7 +1324,48 @@ impl RequestBuilder {
             url,
             headers,
             body,
-        })
+            decompress: self.recipe.decompress,
+            retry: self.recipe.retry.clone().or_else(|| {
+                template_context
+                    .collection
+                    .retry_policy(&self.recipe.id)
+                    .cloned()
+            }),
+            proxy: template_context
+                .selected_profile
+                .as_ref()
+                .and_then(|profile_id| {
+                    template_context.collection.profiles.get(profile_id)
+                })
+                .and_then(|profile| profile.proxy.clone()),
+            sent_proxy: None,
+            download,
+            pre_request_hook: hooks.pre_request,
+            post_response_hook: hooks.post_response,
+        };
+
+        info!(
+            recipe_id = %self.recipe.id,
+            "Built request from recipe",
+        );
+
+        Ok(request)
+    }
+
+    /// Render the path that the response body should be downloaded to, if
+    /// any
+    async fn render_download(
+        &self,
+        template_context: &TemplateContext,
+    ) -> anyhow::Result<Option<PathBuf>> {
+        let Some(template) = &self.recipe.download else {
+            return Ok(None);
+        };
+        let path = template
+            .render(template_context)
+            .await
+            .context("Error rendering download path")?;
+        Ok(Some(path.into()))
     }
 
     /// Render URL, including query params
@@ -530,14 +1544,34 @@ impl RequestBuilder {
         &self,
         template_context: &TemplateContext,
     ) -> anyhow::Result<Option<Bytes>> {
-        let body =
-            Template::render_opt(self.recipe.body.as_ref(), template_context)
-                .await
-                .context("Error rendering body")?;
-        Ok(body.map(Bytes::from))
+        let body = match &self.recipe.body {
+            None => None,
+            Some(RecipeBody::Raw(template)) => Some(
+                template
+                    .render(template_context)
+                    .await
+                    .context("Error rendering body")?
+                    .into(),
+            ),
+            Some(RecipeBody::GraphQl(body)) => Some(
+                graphql::render_body(body, template_context)
+                    .await
+                    .context("Error rendering GraphQL body")?,
+            ),
+        };
+        Ok(body)
     }
 }
 
+/// Prompter for templates rendered outside of any request. There's no one to
+/// ask, so prompts never get a response
+#[derive(Debug)]
+struct NoPrompter;
+
+impl Prompter for NoPrompter {
+    fn prompt(&self, _: Prompt) {}
+}
+
 impl From<Method> for reqwest::Method {
     fn from(method: Method) -> Self {
         match method {
@@ -557,13 +1591,19 @@ impl From<Method> for reqwest::Method {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{collection::Authentication, test_util::*};
+    use crate::{
+        collection::{Authentication, Hooks, ProfileId},
+        test_util::*,
+        util::assert_err,
+    };
     use factori::create;
     use indexmap::indexmap;
+    use itertools::Itertools;
     use pretty_assertions::assert_eq;
     use reqwest::Method;
     use rstest::rstest;
-    use std::collections::HashMap;
+    use std::{collections::HashMap, time::Duration};
+    use uuid::Uuid;
 
     #[tokio::test]
     async fn test_build_request() {
@@ -620,6 +1660,13 @@ mod tests {
                     .unwrap(),
                 body: Some(Vec::from(b"{\"group_id\":\"3\"}").into()),
                 headers: header_map(expected_headers),
+                decompress: None,
+                proxy: None,
+                sent_proxy: None,
+                retry: None,
+                download: None,
+                pre_request_hook: None,
+                post_response_hook: None,
             }
         );
     }
@@ -682,6 +1729,13 @@ mod tests {
                 url: "http://localhost".parse().unwrap(),
                 headers: (&expected_headers).try_into().unwrap(),
                 body: None,
+                decompress: None,
+                proxy: None,
+                sent_proxy: None,
+                retry: None,
+                download: None,
+                pre_request_hook: None,
+                post_response_hook: None,
             }
         );
     }
@@ -727,7 +1781,971 @@ mod tests {
                 url: "http://localhost?mode=sudo".parse().unwrap(),
                 headers: (&expected_headers).try_into().unwrap(),
                 body: None,
+                decompress: None,
+                proxy: None,
+                sent_proxy: None,
+                retry: None,
+                download: None,
+                pre_request_hook: None,
+                post_response_hook: None,
+            }
+        );
+    }
+
+    /// Test that compressed bodies are decompressed iff enabled
+    #[rstest]
+    #[case::enabled(None, true)]
+    #[case::disabled(Some(false), false)]
+    #[tokio::test]
+    async fn test_decompress(
+        #[case] decompress: Option<bool>,
+        #[case] expect_decompressed: bool,
+    ) {
+        use flate2::{write::GzEncoder, Compression};
+
+        let body = b"{\"hello\": \"goodbye\"}";
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(body).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let mut server = mockito::Server::new_async().await;
+        let url = server.url();
+        let mock = server
+            .mock("GET", "/get")
+            .match_header(
+                "accept-encoding",
+                if expect_decompressed {
+                    mockito::Matcher::Exact(compression::ACCEPT_ENCODING.into())
+                } else {
+                    mockito::Matcher::Missing
+                },
+            )
+            .with_header("content-encoding", "gzip")
+            .with_body(&compressed)
+            .create_async()
+            .await;
+
+        let http_engine =
+            HttpEngine::new(&Config::default(), CollectionDatabase::testing());
+        let request = create!(
+            Request,
+            url: format!("{url}/get").parse().unwrap(),
+            decompress: decompress,
+        );
+        let record = http_engine.send(request.into()).await.unwrap();
+
+        mock.assert();
+        if expect_decompressed {
+            assert_eq!(record.response.body.bytes().as_ref(), body);
+            assert_eq!(
+                record.response.encoded_size,
+                Some(compressed.len() as u64)
+            );
+        } else {
+            assert_eq!(record.response.body.bytes().as_ref(), compressed);
+            assert_eq!(record.response.encoded_size, None);
+        }
+    }
+
+    /// Test that cookies are stored from responses and sent on later requests,
+    /// scoped by profile
+    #[tokio::test]
+    async fn test_cookies() {
+        let mut server = mockito::Server::new_async().await;
+        let url: Url = format!("{}/login", server.url()).parse().unwrap();
+        let login_mock = server
+            .mock("GET", "/login")
+            .match_header("cookie", mockito::Matcher::Missing)
+            .with_header("set-cookie", "session=abc; Path=/")
+            .with_header("set-cookie", "other=xyz; Path=/")
+            .create_async()
+            .await;
+        let database = CollectionDatabase::testing();
+        let config = Config {
+            cookies: true,
+            ..Config::default()
+        };
+        let http_engine = HttpEngine::new(&config, database.clone());
+        let profile_id = ProfileId::from("profile1");
+
+        let request = create!(
+            Request,
+            profile_id: Some(profile_id.clone()),
+            url: url.clone(),
+        );
+        http_engine.clone().send(request.into()).await.unwrap();
+        login_mock.assert();
+        assert_eq!(
+            database
+                .get_cookies(Some(&profile_id))
+                .unwrap()
+                .into_iter()
+                .map(|cookie| cookie.name)
+                .collect_vec(),
+            ["other", "session"]
+        );
+        // Other profiles don't get the cookies
+        assert_eq!(database.get_cookies(None).unwrap(), []);
+
+        // Cookies get sent back. An expired cookie deletes from the jar
+        let mock = server
+            .mock("GET", "/login")
+            .match_header(
+                "cookie",
+                mockito::Matcher::AnyOf(vec![
+                    "other=xyz; session=abc".into(),
+                    "session=abc; other=xyz".into(),
+                ]),
+            )
+            .with_header("set-cookie", "session=; Max-Age=0")
+            .create_async()
+            .await;
+        let request = create!(
+            Request,
+            profile_id: Some(profile_id.clone()),
+            url: url.clone(),
+        );
+        let record = http_engine.send(request.into()).await.unwrap();
+        mock.assert();
+        // The sent cookies are recorded in history
+        let cookie = record.request.headers.get(header::COOKIE).unwrap();
+        assert!(
+            ["other=xyz; session=abc", "session=abc; other=xyz"]
+                .contains(&cookie.to_str().unwrap()),
+            "Unexpected cookie header {cookie:?}"
+        );
+        assert_eq!(
+            database
+                .get_last_request(Some(&profile_id), &record.request.recipe_id)
+                .unwrap()
+                .unwrap()
+                .request
+                .headers,
+            record.request.headers
+        );
+        assert_eq!(
+            database
+                .get_cookies(Some(&profile_id))
+                .unwrap()
+                .into_iter()
+                .map(|cookie| cookie.name)
+                .collect_vec(),
+            ["other"]
+        );
+    }
+
+    /// Test that failed requests are retried according to their policy, and
+    /// intermediate attempts are stored in history if enabled
+    #[rstest]
+    #[case::retry_until_success(3, &[503, 503, 200], 200, 3)]
+    #[case::attempts_exhausted(2, &[503, 503, 200], 503, 2)]
+    #[case::status_not_retried(3, &[500, 200], 500, 1)]
+    #[tokio::test]
+    async fn test_retry(
+        #[case] max_attempts: u32,
+        #[case] statuses: &[usize],
+        #[case] expected_status: u16,
+        #[case] expected_attempts: u32,
+    ) {
+        let mut server = mockito::Server::new_async().await;
+        let url: Url = format!("{}/flaky", server.url()).parse().unwrap();
+        // Each mock only responds once, so the responses go in order
+        let mut mocks = Vec::new();
+        for status in statuses {
+            mocks.push(
+                server
+                    .mock("GET", "/flaky")
+                    .with_status(*status)
+                    .expect_at_most(1)
+                    .create_async()
+                    .await,
+            );
+        }
+        let database = CollectionDatabase::testing();
+        let http_engine = HttpEngine::new(&Config::default(), database.clone());
+
+        let retried = Arc::new(Mutex::new(Vec::new()));
+        let stream = StreamHandler {
+            on_retry: Box::new({
+                let retried = Arc::clone(&retried);
+                move |attempt| retried.lock().unwrap().push(attempt)
+            }),
+            on_start: Box::new(|_, _| {}),
+            on_chunk: Box::new(|_| {}),
+            on_download: Box::new(|_| {}),
+            cancel: oneshot::channel().1,
+        };
+        let request = create!(
+            Request,
+            url: url,
+            retry: Some(RetryPolicy {
+                max_attempts,
+                backoff: Duration::ZERO,
+                store_attempts: true,
+                ..Default::default()
+            }),
+        );
+        let record = http_engine
+            .send_streaming(request.into(), stream)
+            .await
+            .unwrap();
+
+        assert_eq!(record.response.status.as_u16(), expected_status);
+        assert_eq!(record.attempts, expected_attempts);
+        assert_eq!(
+            *retried.lock().unwrap(),
+            (2..=expected_attempts).collect_vec()
+        );
+        // The final attempt keeps the request ID, and is the latest in history
+        let last = database
+            .get_last_request(None, &record.request.recipe_id)
+            .unwrap()
+            .unwrap();
+        assert_eq!(last.id, record.id);
+        assert_eq!(last.attempts, expected_attempts);
+        for mock in &mocks[..expected_attempts as usize] {
+            mock.assert();
+        }
+    }
+
+    /// Test that a download is written to its file instead of being kept in
+    /// memory, and its progress is reported
+    #[tokio::test]
+    async fn test_download() {
+        let body = "download me".repeat(1000);
+        let mut server = mockito::Server::new_async().await;
+        let url: Url = format!("{}/file", server.url()).parse().unwrap();
+        let mock = server
+            .mock("GET", "/file")
+            .with_body(&body)
+            .create_async()
+            .await;
+        let database = CollectionDatabase::testing();
+        let http_engine = HttpEngine::new(&Config::default(), database.clone());
+
+        let path = download_path();
+        let progress = Arc::new(Mutex::new(Vec::new()));
+        let (_close_stream, cancel) = oneshot::channel();
+        let stream = StreamHandler {
+            on_retry: Box::new(|_| {}),
+            on_start: Box::new(|_, _| {}),
+            on_chunk: Box::new(|_| {}),
+            on_download: Box::new({
+                let progress = Arc::clone(&progress);
+                move |update| progress.lock().unwrap().push(update)
+            }),
+            cancel,
+        };
+        let request = create!(Request, url: url, download: Some(path.clone()));
+        let record = http_engine
+            .send_streaming(request.into(), stream)
+            .await
+            .unwrap();
+
+        mock.assert();
+        let size = body.len() as u64;
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), body);
+        assert_eq!(record.response.body.bytes().as_ref(), b"");
+        assert_eq!(
+            record.response.download,
+            Some(Download {
+                path: path.clone(),
+                size
+            })
+        );
+        assert_eq!(record.response.size().as_u64(), size);
+        // The final update is always reported
+        assert_eq!(
+            progress.lock().unwrap().last(),
+            Some(&DownloadProgress {
+                written: size,
+                total: Some(size)
+            })
+        );
+        // History gets the path, not the bytes
+        let last = database
+            .get_last_request(None, &record.request.recipe_id)
+            .unwrap()
+            .unwrap();
+        assert_eq!(last.response.download, record.response.download);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    /// Test that a compressed download is decompressed as it's written
+    #[tokio::test]
+    async fn test_download_gzip() {
+        let body = "download me".repeat(1000);
+        let mut encoder = flate2::write::GzEncoder::new(
+            Vec::new(),
+            flate2::Compression::default(),
+        );
+        encoder.write_all(body.as_bytes()).unwrap();
+        let encoded = encoder.finish().unwrap();
+        let mut server = mockito::Server::new_async().await;
+        let url: Url = format!("{}/file", server.url()).parse().unwrap();
+        let mock = server
+            .mock("GET", "/file")
+            .with_header("content-encoding", "gzip")
+            .with_body(&encoded)
+            .create_async()
+            .await;
+        let http_engine =
+            HttpEngine::new(&Config::default(), CollectionDatabase::testing());
+
+        let path = download_path();
+        let request = create!(Request, url: url, download: Some(path.clone()));
+        let record = http_engine.send(request.into()).await.unwrap();
+
+        mock.assert();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), body);
+        assert_eq!(
+            record.response.download,
+            Some(Download {
+                path: path.clone(),
+                size: body.len() as u64,
+            })
+        );
+        assert_eq!(record.response.encoded_size, Some(encoded.len() as u64));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    /// Test that a download that fails partway through doesn't leave a
+    /// partial file behind
+    #[tokio::test]
+    async fn test_download_error() {
+        let mut server = mockito::Server::new_async().await;
+        let url: Url = format!("{}/file", server.url()).parse().unwrap();
+        let mock = server
+            .mock("GET", "/file")
+            .with_header("content-encoding", "gzip")
+            .with_body("not gzip")
+            .create_async()
+            .await;
+        let http_engine =
+            HttpEngine::new(&Config::default(), CollectionDatabase::testing());
+
+        let path = download_path();
+        let request = create!(Request, url: url, download: Some(path.clone()));
+        assert_err!(
+            http_engine.send(request.into()).await,
+            "Error decompressing response body"
+        );
+
+        mock.assert();
+        assert!(!path.exists(), "Partial download was not deleted");
+    }
+
+    /// The pre-request hook runs when the request is sent, not when it's
+    /// built, so building a request for a preview doesn't trigger it
+    #[tokio::test]
+    async fn test_pre_request_hook() {
+        let mut server = mockito::Server::new_async().await;
+        let host = server.url();
+        let mock = server
+            .mock("GET", "/users")
+            .match_header("x-signature", "signed")
+            .create_async()
+            .await;
+        let command: Vec<String> = vec![
+            "sh".into(),
+            "-c".into(),
+            r#"echo '{"headers": {"x-signature": "signed"}}'"#.into(),
+        ];
+        let context = create!(
+            TemplateContext,
+            collection: create!(
+                Collection,
+                hooks: Hooks {
+                    pre_request: Some(command.clone()),
+                    ..Default::default()
+                },
+            ),
+        );
+        let recipe =
+            create!(Recipe, url: format!("{host}/users").as_str().into());
+
+        let request = RequestBuilder::new(recipe, RecipeOptions::default())
+            .build(&context)
+            .await
+            .unwrap();
+        assert_eq!(request.pre_request_hook, Some(command));
+        assert_eq!(request.headers.get("x-signature"), None);
+
+        let id = request.id;
+        let http_engine =
+            HttpEngine::new(&Config::default(), CollectionDatabase::testing());
+        let record = http_engine.send(request.into()).await.unwrap();
+        mock.assert();
+        // History gets the request as it was sent
+        assert_eq!(record.id, id);
+        assert_eq!(record.request.id, id);
+        assert_eq!(
+            record.request.headers.get("x-signature").unwrap(),
+            "signed"
+        );
+    }
+
+    /// Get a unique path to download to, so tests don't clobber each other
+    fn download_path() -> PathBuf {
+        env::temp_dir()
+            .join(format!("slumber_test_download_{}.txt", Uuid::new_v4()))
+    }
+
+    /// Test that redirects are followed manually, and each hop is recorded.
+    /// Cookies set along the way should be sent on later hops, and the method
+    /// should change according to the status
+    #[rstest]
+    #[case::found(302, "GET")]
+    #[case::temporary(307, "POST")]
+    #[tokio::test]
+    async fn test_redirects(
+        #[case] first_status: usize,
+        #[case] expected_method: &str,
+    ) {
+        let mut server = mockito::Server::new_async().await;
+        let url: Url = format!("{}/start", server.url()).parse().unwrap();
+        let start_mock = server
+            .mock("POST", "/start")
+            .with_status(first_status)
+            .with_header("location", "/middle")
+            .with_header("set-cookie", "session=abc; Path=/")
+            .create_async()
+            .await;
+        let middle_mock = server
+            .mock(expected_method, "/middle")
+            .match_header("cookie", "session=abc")
+            .with_status(301)
+            .with_header("location", &format!("{}/end", server.url()))
+            .create_async()
+            .await;
+        let end_mock = server
+            .mock("GET", "/end")
+            .match_header("cookie", "session=abc")
+            .with_body("done")
+            .create_async()
+            .await;
+        let database = CollectionDatabase::testing();
+        let config = Config {
+            cookies: true,
+            ..Config::default()
+        };
+        let http_engine = HttpEngine::new(&config, database.clone());
+
+        let request = create!(
+            Request,
+            method: reqwest::Method::POST,
+            url: url.clone(),
+            body: Some("data".into()),
+        );
+        let record = http_engine.send(request.into()).await.unwrap();
+
+        start_mock.assert();
+        middle_mock.assert();
+        end_mock.assert();
+        assert_eq!(record.response.body.bytes().as_ref(), b"done");
+        let hops = record
+            .redirects
+            .iter()
+            .map(|hop| (hop.url.path(), hop.status.as_u16(), hop.location()))
+            .collect_vec();
+        let end_url = format!("{}/end", server.url());
+        assert_eq!(
+            hops,
+            [
+                ("/start", first_status as u16, Some("/middle")),
+                ("/middle", 301, Some(end_url.as_str())),
+            ]
+        );
+        assert!(record.redirects[0].headers.contains_key(header::SET_COOKIE));
+        // Hops are stored in history
+        let last = database
+            .get_last_request(None, &record.request.recipe_id)
+            .unwrap()
+            .unwrap();
+        assert_eq!(last.redirects, record.redirects);
+    }
+
+    /// Test sending a request over a Unix socket
+    #[tokio::test]
+    async fn test_unix_socket() {
+        let socket = env::temp_dir().join("slumber_test.sock");
+        let _ = std::fs::remove_file(&socket);
+        let listener = tokio::net::UnixListener::bind(&socket).unwrap();
+        // Accept a single request, and send back a canned response
+        let server = tokio::spawn(async move {
+            use tokio::io::AsyncReadExt;
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut received = Vec::new();
+            let mut buf = [0; 1024];
+            while !received.ends_with(b"\r\n\r\n") {
+                let n = stream.read(&mut buf).await.unwrap();
+                received.extend_from_slice(&buf[..n]);
+            }
+            stream
+                .write_all(
+                    b"HTTP/1.1 200 OK\r\ncontent-type: application/json\r\n\
+                    content-length: 2\r\n\r\n[]",
+                )
+                .await
+                .unwrap();
+            String::from_utf8(received).unwrap()
+        });
+
+        let database = CollectionDatabase::testing();
+        let http_engine = HttpEngine::new(&Config::default(), database.clone());
+        let url = format!("unix://{}:/containers?all=true", socket.display())
+            .parse()
+            .unwrap();
+        let request = create!(Request, url: url);
+        let record = http_engine.send(request.into()).await.unwrap();
+
+        let received = server.await.unwrap();
+        assert!(
+            received.starts_with("GET /containers?all=true HTTP/1.1\r\n"),
+            "Unexpected request: {received}"
+        );
+        assert!(received.contains("host: localhost\r\n"));
+        assert_eq!(record.response.status, StatusCode::OK);
+        assert_eq!(record.response.body.bytes().as_ref(), b"[]");
+        // Stored in history like any other request
+        let last = database
+            .get_last_request(None, &record.request.recipe_id)
+            .unwrap()
+            .unwrap();
+        assert_eq!(last.id, record.id);
+        let _ = std::fs::remove_file(&socket);
+    }
+
+    /// The TLS handshake is timed separately. This also tests that cert
+    /// errors are ignored for hosts that opt in, because the server's cert
+    /// isn't trusted
+    #[tokio::test]
+    async fn test_timings_tls() {
+        let pem = std::fs::read("./test_data/tls/client.pem").unwrap();
+        let certificates = rustls_pemfile::certs(&mut pem.as_slice())
+            .unwrap()
+            .into_iter()
+            .map(rustls::Certificate)
+            .collect();
+        let key = std::fs::read("./test_data/tls/client.key").unwrap();
+        let key = rustls_pemfile::pkcs8_private_keys(&mut key.as_slice())
+            .unwrap()
+            .remove(0);
+        let server_config = rustls::ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(certificates, rustls::PrivateKey(key))
+            .unwrap();
+        let acceptor = tokio_rustls::TlsAcceptor::from(Arc::new(server_config));
+        let listener =
+            tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = acceptor.accept(stream).await.unwrap();
+            let mut received = Vec::new();
+            while !received.ends_with(b"\r\n\r\n") {
+                let mut buf = [0; 1024];
+                let n = tokio::io::AsyncReadExt::read(&mut stream, &mut buf)
+                    .await
+                    .unwrap();
+                received.extend(&buf[..n]);
             }
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\n\r\nok")
+                .await
+                .unwrap();
+            stream.flush().await.unwrap();
+        });
+
+        let config = Config {
+            ignore_certificate_hosts: vec!["127.0.0.1".into()],
+            ..Config::default()
+        };
+        let http_engine =
+            HttpEngine::new(&config, CollectionDatabase::testing());
+        let url = format!("https://127.0.0.1:{port}/").parse().unwrap();
+        let request = create!(Request, url: url);
+        let record = http_engine.send(request.into()).await.unwrap();
+        server.await.unwrap();
+
+        assert_eq!(record.response.body.bytes().as_ref(), b"ok");
+        let timings = record.timings.as_ref().expect("Missing timings");
+        // IP addresses don't need a lookup
+        assert_eq!(timings.dns, None);
+        assert!(timings.connect.is_some(), "Missing connect timing");
+        assert!(timings.tls.is_some(), "Missing TLS timing");
+    }
+
+    /// Test that phase timings are captured, including the DNS lookup, and
+    /// stored with the record
+    #[tokio::test]
+    async fn test_timings() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server.mock("GET", "/").create_async().await;
+        // Use a hostname instead of an IP, so there's a lookup to time
+        let port = server
+            .host_with_port()
+            .rsplit_once(':')
+            .unwrap()
+            .1
+            .to_owned();
+        let url: Url = format!("http://localhost:{port}/").parse().unwrap();
+        let database = CollectionDatabase::testing();
+        let http_engine = HttpEngine::new(&Config::default(), database.clone());
+
+        let request = create!(Request, url: url);
+        let record = http_engine.send(request.into()).await.unwrap();
+
+        mock.assert();
+        let timings = record.timings.as_ref().expect("Missing timings");
+        assert!(timings.dns.is_some(), "Missing DNS timing");
+        assert!(timings.connect.is_some(), "Missing connect timing");
+        assert_eq!(timings.tls, None);
+        assert!(timings.total() <= record.duration().to_std().unwrap());
+        let last = database
+            .get_last_request(None, &record.request.recipe_id)
+            .unwrap()
+            .unwrap();
+        assert_eq!(last.timings, record.timings);
+    }
+
+    /// Test that requests aren't stored when history is disabled
+    #[rstest]
+    #[case::enabled(true)]
+    #[case::disabled(false)]
+    #[tokio::test]
+    async fn test_history(#[case] history: bool) {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server.mock("GET", "/").create_async().await;
+        let url: Url = server.url().parse().unwrap();
+        let database = CollectionDatabase::testing();
+        let http_engine = HttpEngine::new(&Config::default(), database.clone())
+            .with_history(history);
+
+        let request = create!(Request, url: url);
+        let record = http_engine.send(request.into()).await.unwrap();
+
+        mock.assert();
+        let last = database
+            .get_last_request(None, &record.request.recipe_id)
+            .unwrap();
+        assert_eq!(last.map(|last| last.id), history.then_some(record.id));
+    }
+
+    /// Test that event streams are passed to the stream handler as they
+    /// arrive, and the stream can be closed before it ends
+    #[rstest]
+    #[case::complete(false)]
+    #[case::cancelled(true)]
+    #[tokio::test]
+    async fn test_stream(#[case] cancel: bool) {
+        let mut server = mockito::Server::new_async().await;
+        let url: Url = format!("{}/events", server.url()).parse().unwrap();
+        server
+            .mock("GET", "/events")
+            .with_header("content-type", "text/event-stream")
+            .with_chunked_body(move |writer| {
+                writer.write_all(b"data: a\n\n")?;
+                // If we're testing cancellation, never end the stream. The
+                // write will fail once the client disconnects
+                if cancel {
+                    loop {
+                        writer.write_all(b": keepalive\n")?;
+                        writer.flush()?;
+                        std::thread::sleep(std::time::Duration::from_millis(
+                            10,
+                        ));
+                    }
+                }
+                writer.write_all(b"data: b\n\n")
+            })
+            .create_async()
+            .await;
+        let http_engine =
+            HttpEngine::new(&Config::default(), CollectionDatabase::testing());
+
+        let started = Arc::new(Mutex::new(None));
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let (cancel_tx, cancel_rx) = oneshot::channel();
+        let mut cancel_tx = Some(cancel_tx);
+        let stream = StreamHandler {
+            on_retry: Box::new(|_| {}),
+            on_start: Box::new({
+                let started = Arc::clone(&started);
+                move |status, _| *started.lock().unwrap() = Some(status)
+            }),
+            on_chunk: Box::new({
+                let received = Arc::clone(&received);
+                move |chunk| {
+                    received.lock().unwrap().extend_from_slice(&chunk);
+                    // Close the stream as soon as we get something. Otherwise
+                    // hold onto the sender so the stream stays open
+                    if cancel {
+                        if let Some(cancel_tx) = cancel_tx.take() {
+                            let _ = cancel_tx.send(());
+                        }
+                    }
+                }
+            }),
+            on_download: Box::new(|_| {}),
+            cancel: cancel_rx,
+        };
+
+        let request = create!(Request, url: url);
+        let record = http_engine
+            .send_streaming(request.into(), stream)
+            .await
+            .unwrap();
+
+        assert_eq!(*started.lock().unwrap(), Some(StatusCode::OK));
+        let received = received.lock().unwrap();
+        assert_eq!(record.response.body.bytes(), received.as_slice());
+        let events = EventStreamParser::parse(&received)
+            .into_iter()
+            .map(|event| event.data)
+            .collect_vec();
+        if cancel {
+            assert_eq!(events, ["a"]);
+        } else {
+            assert_eq!(events, ["a", "b"]);
+        }
+    }
+
+    /// Test that a compressed event stream is decompressed as it arrives,
+    /// instead of waiting for the stream to end
+    #[tokio::test]
+    async fn test_stream_gzip() {
+        let mut server = mockito::Server::new_async().await;
+        let url: Url = format!("{}/events", server.url()).parse().unwrap();
+        server
+            .mock("GET", "/events")
+            .with_header("content-type", "text/event-stream")
+            .with_header("content-encoding", "gzip")
+            .with_chunked_body(|writer| {
+                let mut encoder = flate2::write::GzEncoder::new(
+                    writer,
+                    flate2::Compression::default(),
+                );
+                encoder.write_all(b"data: a\n\n")?;
+                // Never end the stream, so the event can only be received if
+                // it's decoded as soon as it arrives. The write will fail once
+                // the client disconnects
+                loop {
+                    encoder.flush()?;
+                    std::thread::sleep(std::time::Duration::from_millis(10));
+                    encoder.write_all(b": keepalive\n")?;
+                }
+            })
+            .create_async()
+            .await;
+        let http_engine =
+            HttpEngine::new(&Config::default(), CollectionDatabase::testing());
+
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let (cancel_tx, cancel_rx) = oneshot::channel();
+        let mut cancel_tx = Some(cancel_tx);
+        let stream = StreamHandler {
+            on_retry: Box::new(|_| {}),
+            on_start: Box::new(|_, _| {}),
+            on_chunk: Box::new({
+                let received = Arc::clone(&received);
+                move |chunk| {
+                    received.lock().unwrap().extend_from_slice(&chunk);
+                    if let Some(cancel_tx) = cancel_tx.take() {
+                        let _ = cancel_tx.send(());
+                    }
+                }
+            }),
+            on_download: Box::new(|_| {}),
+            cancel: cancel_rx,
+        };
+
+        let request = create!(Request, url: url);
+        let record = http_engine
+            .send_streaming(request.into(), stream)
+            .await
+            .unwrap();
+
+        let received = received.lock().unwrap();
+        assert_eq!(record.response.body.bytes(), received.as_slice());
+        let events = EventStreamParser::parse(&received)
+            .into_iter()
+            .map(|event| event.data)
+            .collect_vec();
+        assert_eq!(events, ["a"]);
+        assert!(record.response.encoded_size.is_some());
+    }
+
+    /// Test that requests are sent through the global proxy, or the profile's
+    /// override
+    #[rstest]
+    #[case::global(true, false)]
+    #[case::profile(false, true)]
+    #[case::profile_overrides_global(true, true)]
+    #[tokio::test]
+    async fn test_proxy(#[case] global: bool, #[case] profile: bool) {
+        // The mock server acts as the proxy. With an HTTP proxy, the request
+        // line contains the full target URL
+        let mut server = mockito::Server::new_async().await;
+        let proxy_url: Url = server.url().parse().unwrap();
+        let mock = server
+            .mock("GET", mockito::Matcher::Any)
+            .match_header("host", "slumber.test")
+            .with_body("proxied")
+            .create_async()
+            .await;
+
+        let proxy = |url: Url| ProxyConfig {
+            url,
+            username: None,
+            password: None,
+            no_proxy: vec![],
+        };
+        // If both are given, the global proxy is bogus so the request will
+        // fail if it's used
+        let config = Config {
+            proxy: global.then(|| {
+                proxy(if profile {
+                    "http://localhost:1".parse().unwrap()
+                } else {
+                    proxy_url.clone()
+                })
+            }),
+            ..Config::default()
+        };
+        let http_engine =
+            HttpEngine::new(&config, CollectionDatabase::testing());
+        let request = create!(
+            Request,
+            url: "http://slumber.test/get".parse().unwrap(),
+            proxy: profile.then(|| proxy(proxy_url.clone())),
+        );
+        let record = http_engine.send(request.into()).await.unwrap();
+
+        mock.assert();
+        assert_eq!(record.response.body.bytes().as_ref(), b"proxied");
+        // The proxy used is recorded, so history doesn't depend on the config
+        assert_eq!(record.request.sent_proxy, Some(proxy_url));
+    }
+
+    /// Hosts excluded by `no_proxy` are sent directly
+    #[tokio::test]
+    async fn test_no_proxy() {
+        let mut server = mockito::Server::new_async().await;
+        let url: Url = server.url().parse().unwrap();
+        let mock = server
+            .mock("GET", "/get")
+            .with_body("direct")
+            .create_async()
+            .await;
+
+        // The proxy is bogus, so the request will fail if it's used
+        let config = Config {
+            proxy: Some(ProxyConfig {
+                url: "http://localhost:1".parse().unwrap(),
+                username: None,
+                password: None,
+                no_proxy: vec![url.host_str().unwrap().to_owned()],
+            }),
+            ..Config::default()
+        };
+        let http_engine =
+            HttpEngine::new(&config, CollectionDatabase::testing());
+        let request = create!(Request, url: url.join("/get").unwrap());
+        let record = http_engine.send(request.into()).await.unwrap();
+
+        mock.assert();
+        assert_eq!(record.response.body.bytes().as_ref(), b"direct");
+        assert_eq!(record.request.sent_proxy, None);
+    }
+
+    /// GraphQL bodies are serialized into a JSON envelope, with the content
+    /// type set automatically
+    #[tokio::test]
+    async fn test_build_graphql_request() {
+        let recipe = create!(
+            Recipe,
+            method: "POST".parse().unwrap(),
+            url: "http://localhost/graphql".into(),
+            body: Some(RecipeBody::GraphQl(collection::GraphQlBody {
+                query: "{ user { name } }".into(),
+                variables: None,
+                operation_name: None,
+            })),
+        );
+        let request = RequestBuilder::new(recipe, RecipeOptions::default())
+            .build(&create!(TemplateContext))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            request.headers,
+            header_map([("content-type", "application/json")])
+        );
+        assert_eq!(
+            request.body.as_deref(),
+            Some(br#"{"query":"{ user { name } }"}"#.as_slice())
+        );
+    }
+
+    /// Introspection responses are cached in the DB, but not stored in
+    /// history
+    #[tokio::test]
+    async fn test_introspect() {
+        let mut server = mockito::Server::new_async().await;
+        let url = server.url();
+        let mock = server
+            .mock("POST", "/graphql")
+            .match_body(mockito::Matcher::Regex("IntrospectionQuery".into()))
+            .with_header("content-type", "application/json")
+            .with_body(
+                r#"{"data": {"__schema": {
+                    "queryType": {"name": "Query"},
+                    "mutationType": null,
+                    "subscriptionType": null,
+                    "types": [{
+                        "kind": "OBJECT",
+                        "name": "Query",
+                        "fields": [{
+                            "name": "hello",
+                            "args": [],
+                            "type": {"kind": "SCALAR", "name": "String", "ofType": null}
+                        }]
+                    }]
+                }}}"#,
+            )
+            .create_async()
+            .await;
+
+        let database = CollectionDatabase::testing();
+        let http_engine = HttpEngine::new(&Config::default(), database.clone());
+        let recipe =
+            create!(Recipe, url: format!("{url}/graphql").as_str().into());
+        let recipe_id = recipe.id.clone();
+        let request = RequestBuilder::new(
+            introspection_recipe(&recipe),
+            RecipeOptions::default(),
+        )
+        .build(&create!(TemplateContext))
+        .await
+        .unwrap();
+        let schema = http_engine.introspect(request.into()).await.unwrap();
+
+        mock.assert();
+        let operations = schema
+            .operations()
+            .into_iter()
+            .map(|(kind, field)| format!("{kind} {field}"))
+            .collect_vec();
+        assert_eq!(operations, ["Query hello: String"]);
+        assert_eq!(
+            database.get_graphql_schema(None, &recipe_id).unwrap(),
+            Some(schema)
         );
+        assert!(database
+            .get_last_request(None, &recipe_id)
+            .unwrap()
+            .is_none());
     }
 }
@@ -0,0 +1,581 @@
+//! Assertions let a recipe declare what a good response looks like. They're
+//! checked by `slumber test`, and shown for the latest response in the TUI.
+
+use crate::{
+    collection::cereal,
+    http::{Query, RequestRecord, ResponseContent},
+};
+use derive_more::Display;
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::{str::FromStr, time::Duration};
+
+/// Checks to run against a recipe's response
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(default, deny_unknown_fields)]
+pub struct Assertions {
+    /// Expected status code, or range of codes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<StatusAssertion>,
+    /// Names of headers that must be present in the response
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub headers: Vec<String>,
+    /// Checks on values in the parsed response body
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub body: Vec<BodyAssertion>,
+    /// Upper bound on how long the request can take, including the entire
+    /// response body
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        with = "cereal::serde_duration::option"
+    )]
+    pub max_duration: Option<Duration>,
+}
+
+/// Expected status of a response: a single code (`200`), a class of codes
+/// (`"2xx"`), or an inclusive range (`"200-299"`)
+#[derive(
+    Copy, Clone, Debug, Display, Eq, PartialEq, Serialize, Deserialize,
+)]
+#[serde(untagged)]
+pub enum StatusAssertion {
+    Code(u16),
+    Range(StatusRange),
+}
+
+/// An inclusive range of status codes
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub struct StatusRange {
+    pub start: u16,
+    pub end: u16,
+}
+
+/// A check on the result of a JSONPath query against the response body. The
+/// body is parsed according to its content type, then converted to JSON for
+/// querying. Each check that's given is run separately. If no checks are
+/// given, the path just has to exist.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(deny_unknown_fields)]
+pub struct BodyAssertion {
+    pub path: Query,
+    /// Does the query return any results (`true`), or none (`false`)?
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exists: Option<bool>,
+    /// The query returns exactly this value
+    #[serde(
+        default,
+        deserialize_with = "deserialize_some",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub equals: Option<serde_json::Value>,
+    /// The query returns an array containing this value, or a string
+    /// containing this substring
+    #[serde(
+        default,
+        deserialize_with = "deserialize_some",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub contains: Option<serde_json::Value>,
+    /// The query returns a value that matches this regex. Non-string values
+    /// are matched against their JSON representation
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub matches: Option<String>,
+}
+
+/// Outcome of checking one assertion against a response
+#[derive(Clone, Debug, PartialEq)]
+pub struct AssertionResult {
+    /// What was checked, e.g. "status is 200"
+    pub assertion: String,
+    /// Why the check failed. `None` if it passed
+    pub failure: Option<String>,
+}
+
+impl Assertions {
+    /// Are there no assertions defined?
+    pub fn is_empty(&self) -> bool {
+        self.status.is_none()
+            && self.headers.is_empty()
+            && self.body.is_empty()
+            && self.max_duration.is_none()
+    }
+
+    /// Check a response against every assertion. `body` is the parsed response
+    /// body. If it's `None` (e.g. because it failed to parse), all body
+    /// assertions fail.
+    pub fn check(
+        &self,
+        record: &RequestRecord,
+        body: Option<&dyn ResponseContent>,
+    ) -> Vec<AssertionResult> {
+        let mut results = Vec::new();
+        let response = &record.response;
+
+        if let Some(status) = self.status {
+            results.push(AssertionResult::new(
+                format!("status is {status}"),
+                (!status.matches(response.status.as_u16()))
+                    .then(|| format!("got {}", response.status)),
+            ));
+        }
+
+        for header in &self.headers {
+            results.push(AssertionResult::new(
+                format!("header `{header}` is present"),
+                (!response.headers.contains_key(header.as_str()))
+                    .then(|| "missing".to_owned()),
+            ));
+        }
+
+        let json = body.map(ResponseContent::to_json);
+        for assertion in &self.body {
+            results.extend(assertion.check(json.as_deref()));
+        }
+
+        if let Some(max_duration) = self.max_duration {
+            let duration = record.duration();
+            let max = chrono::Duration::from_std(max_duration)
+                .unwrap_or(chrono::Duration::max_value());
+            results.push(AssertionResult::new(
+                format!("duration is at most {}ms", max.num_milliseconds()),
+                (duration > max)
+                    .then(|| format!("took {}ms", duration.num_milliseconds())),
+            ));
+        }
+
+        results
+    }
+}
+
+impl StatusAssertion {
+    /// Does this status code satisfy the assertion?
+    pub fn matches(&self, status: u16) -> bool {
+        match self {
+            Self::Code(code) => *code == status,
+            Self::Range(range) => (range.start..=range.end).contains(&status),
+        }
+    }
+}
+
+impl std::fmt::Display for StatusRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Show a whole class in the same form that it was probably given in
+        if self.start % 100 == 0 && self.end == self.start + 99 {
+            write!(f, "{}xx", self.start / 100)
+        } else {
+            write!(f, "{}-{}", self.start, self.end)
+        }
+    }
+}
+
+impl FromStr for StatusRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || {
+            format!(
+                "Invalid status `{s}`; must be a code (`200`), a class \
+                (`2xx`), or a range (`200-299`)"
+            )
+        };
+        let parse =
+            |code: &str| code.trim().parse::<u16>().map_err(|_| error());
+
+        if let Some((start, end)) = s.split_once('-') {
+            let (start, end) = (parse(start)?, parse(end)?);
+            if start > end {
+                return Err(error());
+            }
+            Ok(Self { start, end })
+        } else if let Some(class) = s.strip_suffix("xx") {
+            let class = parse(class)?;
+            if !(1..=5).contains(&class) {
+                return Err(error());
+            }
+            Ok(Self {
+                start: class * 100,
+                end: class * 100 + 99,
+            })
+        } else {
+            let code = parse(s)?;
+            Ok(Self {
+                start: code,
+                end: code,
+            })
+        }
+    }
+}
+
+/// For deserialization
+impl TryFrom<String> for StatusRange {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+/// For serialization
+impl From<StatusRange> for String {
+    fn from(range: StatusRange) -> Self {
+        range.to_string()
+    }
+}
+
+impl BodyAssertion {
+    /// Run each check in this assertion against the response body, which has
+    /// been converted to JSON
+    fn check(&self, body: Option<&serde_json::Value>) -> Vec<AssertionResult> {
+        let path = &self.path;
+        let Some(body) = body else {
+            return vec![AssertionResult::new(
+                format!("`{path}` can be queried"),
+                Some("body could not be parsed".into()),
+            )];
+        };
+        let values = self.path.query_values(body);
+
+        let mut results = Vec::new();
+        // With no other checks, the path just has to exist
+        let no_checks = self.equals.is_none()
+            && self.contains.is_none()
+            && self.matches.is_none();
+        if let Some(expected) = self.exists.or(no_checks.then_some(true)) {
+            let (assertion, failure) = if expected {
+                (format!("`{path}` exists"), values.is_empty())
+            } else {
+                (format!("`{path}` does not exist"), !values.is_empty())
+            };
+            results.push(AssertionResult::new(
+                assertion,
+                failure.then(|| format!("got {} result(s)", values.len())),
+            ));
+        }
+
+        // The rest of the checks require a single value
+        let single = match values.as_slice() {
+            [value] => Ok(*value),
+            _ => Err(format!("got {} results, expected 1", values.len())),
+        };
+
+        if let Some(expected) = &self.equals {
+            results.push(AssertionResult::new(
+                format!("`{path}` equals {expected}"),
+                match &single {
+                    Ok(value) if *value == expected => None,
+                    Ok(value) => Some(format!("got {value}")),
+                    Err(error) => Some(error.clone()),
+                },
+            ));
+        }
+
+        if let Some(expected) = &self.contains {
+            results.push(AssertionResult::new(
+                format!("`{path}` contains {expected}"),
+                match (&single, expected) {
+                    (Ok(serde_json::Value::Array(items)), _)
+                        if items.contains(expected) =>
+                    {
+                        None
+                    }
+                    (
+                        Ok(serde_json::Value::String(value)),
+                        serde_json::Value::String(expected),
+                    ) if value.contains(expected.as_str()) => None,
+                    (Ok(value), _) => Some(format!("got {value}")),
+                    (Err(error), _) => Some(error.clone()),
+                },
+            ));
+        }
+
+        if let Some(pattern) = &self.matches {
+            results.push(AssertionResult::new(
+                format!("`{path}` matches `{pattern}`"),
+                match (Regex::new(pattern), single) {
+                    (Err(error), _) => Some(format!("invalid regex: {error}")),
+                    (Ok(regex), Ok(value)) => {
+                        let text = match value {
+                            serde_json::Value::String(text) => text.clone(),
+                            value => value.to_string(),
+                        };
+                        (!regex.is_match(&text)).then(|| format!("got {value}"))
+                    }
+                    (Ok(_), Err(error)) => Some(error),
+                },
+            ));
+        }
+
+        results
+    }
+}
+
+impl AssertionResult {
+    fn new(assertion: String, failure: Option<String>) -> Self {
+        Self { assertion, failure }
+    }
+
+    pub fn passed(&self) -> bool {
+        self.failure.is_none()
+    }
+}
+
+impl std::fmt::Display for AssertionResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.assertion)?;
+        if let Some(failure) = &self.failure {
+            write!(f, ": {failure}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Summarize a set of results, e.g. "2/3 assertions passed"
+pub fn summarize(results: &[AssertionResult]) -> String {
+    let passed = results.iter().filter(|result| result.passed()).count();
+    format!("{passed}/{} assertions passed", results.len())
+}
+
+/// Deserialize a value that's known to be present, so that an explicit `null`
+/// becomes `Some(Value::Null)` instead of `None`
+fn deserialize_some<'de, D>(
+    deserializer: D,
+) -> Result<Option<serde_json::Value>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    serde_json::Value::deserialize(deserializer).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{http::ContentType, test_util::*};
+    use chrono::Utc;
+    use factori::create;
+    use reqwest::StatusCode;
+    use rstest::rstest;
+    use serde_json::json;
+
+    fn body_assertion(path: &str) -> BodyAssertion {
+        BodyAssertion {
+            path: path.parse().unwrap(),
+            exists: None,
+            equals: None,
+            contains: None,
+            matches: None,
+        }
+    }
+
+    /// Get just the failure messages from a check
+    fn failures(results: Vec<AssertionResult>) -> Vec<Option<String>> {
+        results.into_iter().map(|result| result.failure).collect()
+    }
+
+    #[rstest]
+    #[case::code("200", StatusAssertion::Code(200))]
+    #[case::class(
+        "\"4xx\"",
+        StatusAssertion::Range(StatusRange { start: 400, end: 499 })
+    )]
+    #[case::range(
+        "\"200-204\"",
+        StatusAssertion::Range(StatusRange { start: 200, end: 204 })
+    )]
+    fn test_deserialize_status(
+        #[case] yaml: &str,
+        #[case] expected: StatusAssertion,
+    ) {
+        assert_eq!(
+            serde_yaml::from_str::<StatusAssertion>(yaml).unwrap(),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case::not_a_number("\"abc\"")]
+    #[case::backwards("\"299-200\"")]
+    fn test_deserialize_status_error(#[case] yaml: &str) {
+        assert!(serde_yaml::from_str::<StatusAssertion>(yaml).is_err());
+    }
+
+    /// An explicit null is a value to compare against, not a missing field
+    #[test]
+    fn test_deserialize_equals_null() {
+        let assertion: BodyAssertion =
+            serde_yaml::from_str("{path: $.a, equals: null}").unwrap();
+        assert_eq!(assertion.equals, Some(serde_json::Value::Null));
+    }
+
+    #[rstest]
+    #[case::code(StatusAssertion::Code(200), StatusCode::OK, None)]
+    #[case::code_fail(
+        StatusAssertion::Code(200),
+        StatusCode::CREATED,
+        Some("got 201 Created")
+    )]
+    #[case::range(
+        StatusAssertion::Range(StatusRange { start: 200, end: 299 }),
+        StatusCode::NO_CONTENT,
+        None
+    )]
+    #[case::range_fail(
+        StatusAssertion::Range(StatusRange { start: 200, end: 299 }),
+        StatusCode::NOT_FOUND,
+        Some("got 404 Not Found")
+    )]
+    fn test_status(
+        #[case] status: StatusAssertion,
+        #[case] actual: StatusCode,
+        #[case] expected_failure: Option<&str>,
+    ) {
+        let assertions = Assertions {
+            status: Some(status),
+            ..Default::default()
+        };
+        let record = create!(
+            RequestRecord,
+            response: create!(Response, status: actual),
+        );
+        assert_eq!(
+            failures(assertions.check(&record, None)),
+            vec![expected_failure.map(String::from)]
+        );
+    }
+
+    #[test]
+    fn test_headers_and_duration() {
+        let assertions = Assertions {
+            headers: vec!["content-type".into(), "x-request-id".into()],
+            max_duration: Some(Duration::from_millis(500)),
+            ..Default::default()
+        };
+        let end_time = Utc::now();
+        let record = create!(
+            RequestRecord,
+            response: create!(
+                Response,
+                headers: header_map([("content-type", "application/json")]),
+            ),
+            start_time: end_time
+                - chrono::Duration::try_milliseconds(750).unwrap(),
+            end_time: end_time,
+        );
+        assert_eq!(
+            assertions.check(&record, None),
+            vec![
+                AssertionResult::new(
+                    "header `content-type` is present".into(),
+                    None
+                ),
+                AssertionResult::new(
+                    "header `x-request-id` is present".into(),
+                    Some("missing".into())
+                ),
+                AssertionResult::new(
+                    "duration is at most 500ms".into(),
+                    Some("took 750ms".into())
+                ),
+            ]
+        );
+    }
+
+    #[rstest]
+    #[case::exists_default(body_assertion("$.name"), vec![None])]
+    #[case::exists_fail(
+        body_assertion("$.missing"),
+        vec![Some("got 0 result(s)")],
+    )]
+    #[case::not_exists(
+        BodyAssertion { exists: Some(false), ..body_assertion("$.missing") },
+        vec![None],
+    )]
+    #[case::equals(
+        BodyAssertion { equals: Some(json!("Frodo")), ..body_assertion("$.name") },
+        vec![None],
+    )]
+    #[case::equals_fail(
+        BodyAssertion { equals: Some(json!(3)), ..body_assertion("$.age") },
+        vec![Some("got 50")],
+    )]
+    #[case::equals_multiple(
+        BodyAssertion { equals: Some(json!("ring")), ..body_assertion("$.items[*]") },
+        vec![Some("got 2 results, expected 1")],
+    )]
+    #[case::contains_array(
+        BodyAssertion { contains: Some(json!("ring")), ..body_assertion("$.items") },
+        vec![None],
+    )]
+    #[case::contains_string(
+        BodyAssertion { contains: Some(json!("rod")), ..body_assertion("$.name") },
+        vec![None],
+    )]
+    #[case::contains_fail(
+        BodyAssertion { contains: Some(json!("sword")), ..body_assertion("$.items") },
+        vec![Some(r#"got ["ring","mithril"]"#)],
+    )]
+    #[case::matches(
+        BodyAssertion { matches: Some("^F.*o$".into()), ..body_assertion("$.name") },
+        vec![None],
+    )]
+    #[case::matches_number(
+        BodyAssertion { matches: Some("^\\d+$".into()), ..body_assertion("$.age") },
+        vec![None],
+    )]
+    #[case::matches_invalid(
+        BodyAssertion { matches: Some("(".into()), ..body_assertion("$.name") },
+        vec![Some("invalid regex")],
+    )]
+    #[case::multiple_checks(
+        BodyAssertion {
+            exists: Some(true),
+            equals: Some(json!(51)),
+            ..body_assertion("$.age")
+        },
+        vec![None, Some("got 50")],
+    )]
+    fn test_body(
+        #[case] assertion: BodyAssertion,
+        #[case] expected_failures: Vec<Option<&str>>,
+    ) {
+        let assertions = Assertions {
+            body: vec![assertion],
+            ..Default::default()
+        };
+        let body = ContentType::Json
+            .parse_content(
+                br#"{"name": "Frodo", "age": 50, "items": ["ring", "mithril"]}"#,
+            )
+            .unwrap();
+        let failures = failures(assertions.check(
+            &create!(RequestRecord),
+            Some(body.as_ref() as &dyn ResponseContent),
+        ));
+        assert_eq!(failures.len(), expected_failures.len());
+        for (failure, expected) in failures.iter().zip(expected_failures) {
+            match (failure, expected) {
+                (None, None) => {}
+                (Some(failure), Some(expected)) => assert!(
+                    failure.starts_with(expected),
+                    "Expected `{failure}` to start with `{expected}`"
+                ),
+                _ => panic!("Expected {expected:?}, got {failure:?}"),
+            }
+        }
+    }
+
+    /// Body assertions fail if the body couldn't be parsed
+    #[test]
+    fn test_body_unparsed() {
+        let assertions = Assertions {
+            body: vec![body_assertion("$.name")],
+            ..Default::default()
+        };
+        assert_eq!(
+            failures(assertions.check(&create!(RequestRecord), None)),
+            vec![Some("body could not be parsed".into())]
+        );
+    }
+}
@@ -0,0 +1,85 @@
+//! Character encoding detection for text bodies. Bodies are stored as raw
+//! bytes, and only decoded to text (UTF-8) as needed. The encoding is
+//! determined by a byte order mark if present, then the `charset` parameter of
+//! the `Content-Type` header, then falls back to UTF-8.
+
+use encoding_rs::{Encoding, UTF_8};
+use std::borrow::Cow;
+
+/// Get the encoding declared by the `charset` parameter of a `Content-Type`
+/// header value. Returns `None` if there is no `charset` parameter, or its
+/// value isn't a known encoding label.
+pub fn from_content_type(header_value: &[u8]) -> Option<&'static Encoding> {
+    let header_value = std::str::from_utf8(header_value).ok()?;
+    header_value
+        .split(';')
+        .skip(1) // First item is the MIME type
+        .filter_map(|parameter| parameter.split_once('='))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("charset"))
+        .and_then(|(_, value)| {
+            let value = value.trim().trim_matches('"');
+            Encoding::for_label(value.as_bytes())
+        })
+}
+
+/// Determine the encoding of some bytes. A byte order mark takes priority
+/// over the declared charset. Returns the encoding and the length of the BOM,
+/// which should be skipped when decoding.
+pub fn detect(
+    bytes: &[u8],
+    charset: Option<&'static Encoding>,
+) -> (&'static Encoding, usize) {
+    Encoding::for_bom(bytes).unwrap_or((charset.unwrap_or(UTF_8), 0))
+}
+
+/// Decode bytes to text, according to their detected encoding. Returns `None`
+/// if the bytes aren't valid in that encoding, which generally means the
+/// content is binary. If the bytes are already UTF-8 (without a BOM), this
+/// will not allocate.
+pub fn decode_text<'a>(
+    bytes: &'a [u8],
+    charset: Option<&'static Encoding>,
+) -> Option<Cow<'a, str>> {
+    let (encoding, bom_length) = detect(bytes, charset);
+    encoding.decode_without_bom_handling_and_without_replacement(
+        &bytes[bom_length..],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding_rs::{UTF_16LE, WINDOWS_1252};
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::none("text/plain", None)]
+    #[case::utf8("text/plain; charset=utf-8", Some(UTF_8))]
+    #[case::latin1("text/html; charset=ISO-8859-1", Some(WINDOWS_1252))]
+    #[case::quoted("text/html;charset=\"windows-1252\"", Some(WINDOWS_1252))]
+    #[case::other_params(
+        "text/html; boundary=x; Charset=utf-16le",
+        Some(UTF_16LE)
+    )]
+    #[case::unknown("text/plain; charset=fake", None)]
+    fn test_from_content_type(
+        #[case] header_value: &str,
+        #[case] expected: Option<&'static Encoding>,
+    ) {
+        assert_eq!(from_content_type(header_value.as_bytes()), expected);
+    }
+
+    #[rstest]
+    #[case::utf8(b"caf\xc3\xa9", None, Some("café"))]
+    #[case::utf8_bom(b"\xef\xbb\xbfcaf\xc3\xa9", None, Some("café"))]
+    #[case::latin1(b"caf\xe9", Some(WINDOWS_1252), Some("café"))]
+    #[case::utf16_bom(b"\xff\xfeh\0i\0", Some(WINDOWS_1252), Some("hi"))]
+    #[case::binary(b"\xff\xff\x00", None, None)]
+    fn test_decode_text(
+        #[case] bytes: &[u8],
+        #[case] charset: Option<&'static Encoding>,
+        #[case] expected: Option<&str>,
+    ) {
+        assert_eq!(decode_text(bytes, charset).as_deref(), expected);
+    }
+}
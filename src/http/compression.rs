@@ -0,0 +1,397 @@
+//! Decompression of response bodies, according to the `Content-Encoding`
+//! header. Reqwest can do this itself, but it throws away the encoded body in
+//! the process, and we want to know how big it was on the wire.
+
+use anyhow::{anyhow, Context};
+use std::{
+    io::{self, Read, Write},
+    sync::{Arc, Mutex},
+};
+
+/// Value for the `Accept-Encoding` header when decompression is enabled. This
+/// should include every encoding supported by [decompress]
+pub const ACCEPT_ENCODING: &str = "gzip, deflate, br, zstd";
+
+/// Decompress a body according to a `Content-Encoding` header value. If
+/// multiple encodings are listed, they're undone in reverse order, because
+/// that's the reverse of the order in which they were applied. Returns `None`
+/// if the body isn't encoded, i.e. there's nothing to decompress.
+pub fn decompress(
+    content_encoding: &str,
+    body: &[u8],
+) -> anyhow::Result<Option<Vec<u8>>> {
+    let encodings = parse_encodings(content_encoding);
+    if encodings.is_empty() {
+        return Ok(None);
+    }
+
+    let mut decoded = body.to_vec();
+    for encoding in encodings.iter().rev() {
+        decoded = decompress_one(encoding, &decoded)
+            .with_context(|| format!("Error decoding `{encoding}` content"))?;
+    }
+    Ok(Some(decoded))
+}
+
+/// Get the encodings listed in a `Content-Encoding` header value, in the
+/// order they were applied. `identity` is a no-op, so it's left out.
+fn parse_encodings(content_encoding: &str) -> Vec<String> {
+    content_encoding
+        .split(',')
+        .map(|encoding| encoding.trim().to_ascii_lowercase())
+        .filter(|encoding| !encoding.is_empty() && encoding != "identity")
+        .collect()
+}
+
+/// Decompresses a body incrementally, as it arrives. This is for downloads,
+/// where the body is never held in memory all at once, and event streams,
+/// where each chunk is shown as soon as it arrives. Supports the same
+/// encodings as [decompress].
+#[derive(derive_more::Debug)]
+pub struct StreamDecoder {
+    /// Decoder for the last encoding that was applied. Each decoder writes
+    /// into the one for the previous encoding, and the first one writes into
+    /// the output buffer.
+    #[debug(skip)]
+    decoder: Box<dyn Decode>,
+    output: SharedBuffer,
+}
+
+impl StreamDecoder {
+    /// Build a decoder for a `Content-Encoding` header value. Returns `None`
+    /// if the body isn't encoded, i.e. there's nothing to decompress.
+    pub fn new(content_encoding: &str) -> anyhow::Result<Option<Self>> {
+        let encodings = parse_encodings(content_encoding);
+        if encodings.is_empty() {
+            return Ok(None);
+        }
+
+        let output = SharedBuffer::default();
+        let mut decoder: Box<dyn Decode> = Box::new(output.clone());
+        for encoding in &encodings {
+            decoder = decoder_one(encoding, decoder).with_context(|| {
+                format!("Error decoding `{encoding}` content")
+            })?;
+        }
+        Ok(Some(Self { decoder, output }))
+    }
+
+    /// Decode the next chunk of the body. Returns whatever decoded content is
+    /// ready, which may be empty if the decoder needs more input.
+    pub fn decode(&mut self, chunk: &[u8]) -> anyhow::Result<Vec<u8>> {
+        self.decoder.write_all(chunk)?;
+        self.decoder.flush()?;
+        Ok(self.output.take())
+    }
+
+    /// Signal the end of the body, and get any remaining decoded content.
+    /// Fails if the body ended partway through the encoded content.
+    pub fn finish(self) -> anyhow::Result<Vec<u8>> {
+        self.decoder.finish()?;
+        Ok(self.output.take())
+    }
+}
+
+/// One link in a chain of streaming decoders. Each link writes its decoded
+/// content into the next one.
+trait Decode: Write + Send {
+    /// Write out any remaining content, then finish the rest of the chain
+    fn finish(self: Box<Self>) -> io::Result<()>;
+}
+
+/// Build a streaming decoder for a single encoding, which writes decoded
+/// content to the given decoder
+fn decoder_one(
+    encoding: &str,
+    inner: Box<dyn Decode>,
+) -> anyhow::Result<Box<dyn Decode>> {
+    Ok(match encoding {
+        "gzip" | "x-gzip" => {
+            Box::new(flate2::write::MultiGzDecoder::new(inner))
+        }
+        "deflate" => Box::new(DeflateDecoder::Pending {
+            header: Vec::new(),
+            inner,
+        }),
+        "br" => {
+            Box::new(brotli_decompressor::DecompressorWriter::new(inner, 4096))
+        }
+        "zstd" => Box::new(zstd::stream::write::Decoder::new(inner)?),
+        _ => return Err(anyhow!("Unsupported content encoding")),
+    })
+}
+
+impl Decode for flate2::write::MultiGzDecoder<Box<dyn Decode>> {
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        (*self).finish()?.finish()
+    }
+}
+
+impl Decode for brotli_decompressor::DecompressorWriter<Box<dyn Decode>> {
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        match self.into_inner() {
+            Ok(inner) => inner.finish(),
+            Err(_) => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "incomplete brotli stream",
+            )),
+        }
+    }
+}
+
+impl Decode for zstd::stream::write::Decoder<'static, Box<dyn Decode>> {
+    fn finish(mut self: Box<Self>) -> io::Result<()> {
+        self.flush()?;
+        self.into_inner().finish()
+    }
+}
+
+/// A buffer that can be written to by the end of a decoder chain while the
+/// [StreamDecoder] holds onto it
+#[derive(Clone, Debug, Default)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl SharedBuffer {
+    /// Take everything written so far
+    fn take(&self) -> Vec<u8> {
+        std::mem::take(&mut *self.0.lock().expect("Buffer lock poisoned"))
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .lock()
+            .expect("Buffer lock poisoned")
+            .extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Decode for SharedBuffer {
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Streaming decoder for `deflate` content. Like [decompress_one], this
+/// accepts raw deflate data as well as the correct zlib-wrapped format. We
+/// can't retry once the content has been written out, so the format is
+/// detected from the zlib header instead.
+enum DeflateDecoder {
+    /// Waiting for enough content to check for a zlib header
+    Pending {
+        header: Vec<u8>,
+        inner: Box<dyn Decode>,
+    },
+    Zlib(flate2::write::ZlibDecoder<Box<dyn Decode>>),
+    Raw(flate2::write::DeflateDecoder<Box<dyn Decode>>),
+    /// Placeholder while switching states
+    Empty,
+}
+
+impl Write for DeflateDecoder {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Self::Pending { header, .. } = self {
+            header.extend_from_slice(buf);
+            if header.len() < 2 {
+                return Ok(buf.len());
+            }
+            let Self::Pending { header, inner } =
+                std::mem::replace(self, Self::Empty)
+            else {
+                unreachable!()
+            };
+            // A zlib header is a deflate method byte, with a checksum over
+            // both bytes
+            let is_zlib = header[0] & 0x0f == 8
+                && u16::from_be_bytes([header[0], header[1]]) % 31 == 0;
+            *self = if is_zlib {
+                Self::Zlib(flate2::write::ZlibDecoder::new(inner))
+            } else {
+                Self::Raw(flate2::write::DeflateDecoder::new(inner))
+            };
+            self.write_all(&header)?;
+            return Ok(buf.len());
+        }
+        match self {
+            Self::Zlib(decoder) => decoder.write(buf),
+            Self::Raw(decoder) => decoder.write(buf),
+            Self::Pending { .. } | Self::Empty => unreachable!(),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Pending { inner, .. } => inner.flush(),
+            Self::Zlib(decoder) => decoder.flush(),
+            Self::Raw(decoder) => decoder.flush(),
+            Self::Empty => Ok(()),
+        }
+    }
+}
+
+impl Decode for DeflateDecoder {
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        match *self {
+            Self::Pending { header, inner } if header.is_empty() => {
+                inner.finish()
+            }
+            Self::Pending { .. } | Self::Empty => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "incomplete deflate stream",
+            )),
+            Self::Zlib(decoder) => decoder.finish()?.finish(),
+            Self::Raw(decoder) => decoder.finish()?.finish(),
+        }
+    }
+}
+
+/// Undo a single encoding
+fn decompress_one(encoding: &str, body: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let mut decoded = Vec::new();
+    match encoding {
+        "gzip" | "x-gzip" => {
+            flate2::read::MultiGzDecoder::new(body)
+                .read_to_end(&mut decoded)?;
+        }
+        "deflate" => {
+            // The spec says this should be zlib-wrapped, but some servers send
+            // raw deflate data. Try the correct format first, then fall back
+            if flate2::read::ZlibDecoder::new(body)
+                .read_to_end(&mut decoded)
+                .is_err()
+            {
+                decoded.clear();
+                flate2::read::DeflateDecoder::new(body)
+                    .read_to_end(&mut decoded)?;
+            }
+        }
+        "br" => {
+            brotli_decompressor::Decompressor::new(body, 4096)
+                .read_to_end(&mut decoded)?;
+        }
+        "zstd" => {
+            decoded = zstd::stream::decode_all(body)?;
+        }
+        _ => return Err(anyhow!("Unsupported content encoding")),
+    }
+    Ok(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::assert_err;
+    use flate2::{write::GzEncoder, Compression};
+    use rstest::rstest;
+    use std::io::Write;
+
+    const CONTENT: &[u8] = b"{\"hello\": \"goodbye\"}";
+
+    fn gzip(body: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(body).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn deflate(body: &[u8]) -> Vec<u8> {
+        let mut encoder =
+            flate2::write::ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(body).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn raw_deflate(body: &[u8]) -> Vec<u8> {
+        let mut encoder = flate2::write::DeflateEncoder::new(
+            Vec::new(),
+            Compression::default(),
+        );
+        encoder.write_all(body).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn zstd(body: &[u8]) -> Vec<u8> {
+        zstd::stream::encode_all(body, 0).unwrap()
+    }
+
+    #[rstest]
+    #[case::gzip("gzip", gzip(CONTENT))]
+    #[case::deflate("deflate", deflate(CONTENT))]
+    #[case::raw_deflate("deflate", raw_deflate(CONTENT))]
+    // We don't have a brotli encoder, so hand-roll an uncompressed brotli
+    // stream: header (20 byte uncompressed meta-block), content, empty final
+    // meta-block
+    #[case::brotli("br", [b"\x30\x01\x10", CONTENT, b"\x03"].concat())]
+    #[case::zstd("zstd", zstd(CONTENT))]
+    #[case::case_insensitive("GZIP", gzip(CONTENT))]
+    #[case::multiple("deflate, gzip", gzip(&deflate(CONTENT)))]
+    #[case::identity("identity, gzip", gzip(CONTENT))]
+    fn test_decompress(#[case] content_encoding: &str, #[case] body: Vec<u8>) {
+        assert_eq!(
+            decompress(content_encoding, &body).unwrap().as_deref(),
+            Some(CONTENT)
+        );
+    }
+
+    /// Decoding a stream should give the same result as decoding the whole
+    /// body, no matter how the body is split up
+    #[rstest]
+    #[case::gzip("gzip", gzip(CONTENT))]
+    #[case::deflate("deflate", deflate(CONTENT))]
+    #[case::raw_deflate("deflate", raw_deflate(CONTENT))]
+    #[case::brotli("br", [b"\x30\x01\x10", CONTENT, b"\x03"].concat())]
+    #[case::zstd("zstd", zstd(CONTENT))]
+    #[case::multiple("deflate, gzip", gzip(&deflate(CONTENT)))]
+    fn test_stream_decoder(
+        #[case] content_encoding: &str,
+        #[case] body: Vec<u8>,
+        #[values(1, 3, 1000)] chunk_size: usize,
+    ) {
+        let mut decoder =
+            StreamDecoder::new(content_encoding).unwrap().unwrap();
+        let mut decoded = Vec::new();
+        for chunk in body.chunks(chunk_size) {
+            decoded.extend(decoder.decode(chunk).unwrap());
+        }
+        decoded.extend(decoder.finish().unwrap());
+        assert_eq!(decoded, CONTENT);
+    }
+
+    #[rstest]
+    #[case::empty("")]
+    #[case::identity("identity")]
+    fn test_decompress_noop(#[case] content_encoding: &str) {
+        assert_eq!(decompress(content_encoding, CONTENT).unwrap(), None);
+    }
+
+    #[rstest]
+    #[case::unknown("compress", "Unsupported content encoding")]
+    #[case::invalid("gzip", "Error decoding `gzip` content")]
+    fn test_decompress_error(
+        #[case] content_encoding: &str,
+        #[case] expected_error: &str,
+    ) {
+        assert_err!(decompress(content_encoding, CONTENT), expected_error);
+    }
+
+    #[test]
+    fn test_stream_decoder_error() {
+        assert!(StreamDecoder::new("identity").unwrap().is_none());
+        assert_err!(
+            StreamDecoder::new("compress"),
+            "Unsupported content encoding"
+        );
+        let mut decoder = StreamDecoder::new("gzip").unwrap().unwrap();
+        assert_err!(decoder.decode(CONTENT), "invalid gzip header");
+        // Content that ends early
+        let body = gzip(CONTENT);
+        let mut decoder = StreamDecoder::new("gzip").unwrap().unwrap();
+        decoder.decode(&body[..body.len() - 4]).unwrap();
+        assert_err!(decoder.finish(), "corrupt");
+    }
+}
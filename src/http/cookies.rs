@@ -0,0 +1,355 @@
+//! Cookie jar logic. Cookies received in `Set-Cookie` response headers are
+//! stored in the database, scoped to the collection and profile of the request
+//! that received them, then sent back on subsequent matching requests. Parsing
+//! and matching follow [RFC 6265](https://datatracker.ietf.org/doc/html/rfc6265),
+//! minus the public suffix list.
+
+use chrono::{DateTime, Duration, Utc};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::{self, Display, Formatter},
+    time::SystemTime,
+};
+use url::Url;
+
+/// A single cookie in the jar
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Cookie {
+    pub name: String,
+    pub value: String,
+    /// Domain that the cookie applies to. Lowercase, without a leading dot
+    pub domain: String,
+    /// If true, the cookie applies only to the exact host in `domain`.
+    /// Otherwise it applies to subdomains as well. This is false iff the
+    /// `Domain` attribute was given.
+    pub host_only: bool,
+    pub path: String,
+    /// Only send this cookie over HTTPS
+    pub secure: bool,
+    pub http_only: bool,
+    /// When does the cookie expire? `None` for session cookies. Since the jar
+    /// is persistent, session cookies live until they are deleted or replaced
+    pub expires: Option<DateTime<Utc>>,
+}
+
+impl Cookie {
+    /// Parse the value of a `Set-Cookie` header, received in response to a
+    /// request for the given URL. Returns `None` if the header is malformed or
+    /// the server isn't allowed to set this cookie.
+    pub fn parse(header: &str, url: &Url, now: DateTime<Utc>) -> Option<Self> {
+        let host = url.host_str()?.to_ascii_lowercase();
+        let mut parts = header.split(';');
+        let (name, value) = parts.next()?.split_once('=')?;
+        let name = name.trim();
+        if name.is_empty() {
+            return None;
+        }
+
+        let mut cookie = Self {
+            name: name.to_owned(),
+            value: value.trim().to_owned(),
+            domain: host.clone(),
+            host_only: true,
+            path: default_path(url),
+            secure: false,
+            http_only: false,
+            expires: None,
+        };
+        // Max-Age takes precedence over Expires, regardless of order
+        let mut max_age: Option<DateTime<Utc>> = None;
+
+        for attribute in parts {
+            let (key, value) = attribute
+                .split_once('=')
+                .map(|(key, value)| (key.trim(), value.trim()))
+                .unwrap_or((attribute.trim(), ""));
+            match key.to_ascii_lowercase().as_str() {
+                "domain" => {
+                    let domain =
+                        value.trim_start_matches('.').to_ascii_lowercase();
+                    if !domain.is_empty() {
+                        // Servers can only set cookies for their own domain
+                        if !domain_matches(&host, &domain) {
+                            return None;
+                        }
+                        cookie.domain = domain;
+                        cookie.host_only = false;
+                    }
+                }
+                "path" if value.starts_with('/') => {
+                    cookie.path = value.to_owned();
+                }
+                "secure" => cookie.secure = true,
+                "httponly" => cookie.http_only = true,
+                "max-age" => {
+                    if let Ok(seconds) = value.parse::<i64>() {
+                        max_age = Some(if seconds <= 0 {
+                            DateTime::<Utc>::MIN_UTC
+                        } else {
+                            Duration::try_seconds(seconds)
+                                .and_then(|age| now.checked_add_signed(age))
+                                .unwrap_or(DateTime::<Utc>::MAX_UTC)
+                        });
+                    }
+                }
+                "expires" => {
+                    if let Ok(time) = httpdate::parse_http_date(value) {
+                        cookie.expires =
+                            Some(DateTime::<Utc>::from(time as SystemTime));
+                    }
+                }
+                _ => {}
+            }
+        }
+        if max_age.is_some() {
+            cookie.expires = max_age;
+        }
+
+        Some(cookie)
+    }
+
+    /// Has this cookie expired?
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+
+    /// Should this cookie be sent with a request to the given URL?
+    pub fn matches(&self, url: &Url, now: DateTime<Utc>) -> bool {
+        let Some(host) = url.host_str() else {
+            return false;
+        };
+        let host = host.to_ascii_lowercase();
+        let domain_ok = if self.host_only {
+            host == self.domain
+        } else {
+            domain_matches(&host, &self.domain)
+        };
+        domain_ok
+            && path_matches(url.path(), &self.path)
+            && (!self.secure || matches!(url.scheme(), "https" | "wss"))
+            && !self.is_expired(now)
+    }
+
+    /// Build the value of a `Cookie` request header for a URL, from all the
+    /// cookies in the jar that match it. Returns `None` if nothing matches
+    pub fn header_value<'a>(
+        cookies: impl IntoIterator<Item = &'a Self>,
+        url: &Url,
+        now: DateTime<Utc>,
+    ) -> Option<String> {
+        let value = cookies
+            .into_iter()
+            .filter(|cookie| cookie.matches(url, now))
+            // More specific paths go first
+            .sorted_by_key(|cookie| std::cmp::Reverse(cookie.path.len()))
+            .map(|cookie| format!("{}={}", cookie.name, cookie.value))
+            .join("; ");
+        if value.is_empty() {
+            None
+        } else {
+            Some(value)
+        }
+    }
+}
+
+impl Display for Cookie {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}; Domain=", self.name, self.value)?;
+        if !self.host_only {
+            write!(f, ".")?;
+        }
+        write!(f, "{}; Path={}", self.domain, self.path)?;
+        if let Some(expires) = self.expires {
+            write!(f, "; Expires={}", expires.to_rfc2822())?;
+        }
+        if self.secure {
+            write!(f, "; Secure")?;
+        }
+        if self.http_only {
+            write!(f, "; HttpOnly")?;
+        }
+        Ok(())
+    }
+}
+
+/// Is the host the same as the domain, or a subdomain of it?
+fn domain_matches(host: &str, domain: &str) -> bool {
+    host == domain
+        || host
+            .strip_suffix(domain)
+            .is_some_and(|prefix| prefix.ends_with('.'))
+}
+
+/// Does the request path fall under the cookie path?
+fn path_matches(request_path: &str, cookie_path: &str) -> bool {
+    request_path == cookie_path
+        || request_path.strip_prefix(cookie_path).is_some_and(|rest| {
+            cookie_path.ends_with('/') || rest.starts_with('/')
+        })
+}
+
+/// The path to use for a cookie with no `Path` attribute: the "directory" of
+/// the request path
+fn default_path(url: &Url) -> String {
+    match url.path().rfind('/') {
+        Some(0) | None => "/".into(),
+        Some(index) => url.path()[..index].into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use rstest::rstest;
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap()
+    }
+
+    fn cookie(domain: &str, host_only: bool, path: &str) -> Cookie {
+        Cookie {
+            name: "session".into(),
+            value: "abc".into(),
+            domain: domain.into(),
+            host_only,
+            path: path.into(),
+            secure: false,
+            http_only: false,
+            expires: None,
+        }
+    }
+
+    #[rstest]
+    #[case::defaults(
+        "session=abc",
+        "http://example.com/api/users",
+        Some(cookie("example.com", true, "/api"))
+    )]
+    #[case::attributes(
+        "session=abc; Domain=.Example.com; Path=/; Secure; HttpOnly",
+        "https://api.example.com",
+        Some(Cookie {
+            secure: true,
+            http_only: true,
+            ..cookie("example.com", false, "/")
+        }),
+    )]
+    #[case::expires(
+        "session=abc; Expires=Wed, 21 Oct 2015 07:28:00 GMT",
+        "http://example.com/",
+        Some(Cookie {
+            expires: Some(Utc.with_ymd_and_hms(2015, 10, 21, 7, 28, 0).unwrap()),
+            ..cookie("example.com", true, "/")
+        }),
+    )]
+    #[case::max_age_wins(
+        "session=abc; Max-Age=60; Expires=Wed, 21 Oct 2015 07:28:00 GMT",
+        "http://example.com/",
+        Some(Cookie {
+            expires: Some(now() + Duration::try_seconds(60).unwrap()),
+            ..cookie("example.com", true, "/")
+        }),
+    )]
+    #[case::foreign_domain(
+        "session=abc; Domain=other.com",
+        "http://example.com/",
+        None
+    )]
+    #[case::no_name("=abc", "http://example.com/", None)]
+    #[case::no_value("session", "http://example.com/", None)]
+    fn test_parse(
+        #[case] header: &str,
+        #[case] url: &str,
+        #[case] expected: Option<Cookie>,
+    ) {
+        let url: Url = url.parse().unwrap();
+        assert_eq!(Cookie::parse(header, &url, now()), expected);
+    }
+
+    #[rstest]
+    #[case::host_only_exact(
+        cookie("example.com", true, "/"),
+        "http://example.com/a",
+        true
+    )]
+    #[case::host_only_subdomain(
+        cookie("example.com", true, "/"),
+        "http://api.example.com/",
+        false
+    )]
+    #[case::domain_subdomain(
+        cookie("example.com", false, "/"),
+        "http://api.example.com/",
+        true
+    )]
+    #[case::domain_suffix(
+        cookie("example.com", false, "/"),
+        "http://badexample.com/",
+        false
+    )]
+    #[case::path_prefix(
+        cookie("example.com", true, "/api"),
+        "http://example.com/api/users",
+        true
+    )]
+    #[case::path_partial(
+        cookie("example.com", true, "/api"),
+        "http://example.com/apis",
+        false
+    )]
+    #[case::secure_http(
+        Cookie { secure: true, ..cookie("example.com", true, "/") },
+        "http://example.com/",
+        false,
+    )]
+    #[case::secure_https(
+        Cookie { secure: true, ..cookie("example.com", true, "/") },
+        "https://example.com/",
+        true,
+    )]
+    #[case::secure_wss(
+        Cookie { secure: true, ..cookie("example.com", true, "/") },
+        "wss://example.com/",
+        true,
+    )]
+    #[case::expired(
+        Cookie { expires: Some(now()), ..cookie("example.com", true, "/") },
+        "http://example.com/",
+        false,
+    )]
+    fn test_matches(
+        #[case] cookie: Cookie,
+        #[case] url: &str,
+        #[case] expected: bool,
+    ) {
+        let url: Url = url.parse().unwrap();
+        assert_eq!(cookie.matches(&url, now()), expected);
+    }
+
+    #[test]
+    fn test_header_value() {
+        let cookies = [
+            Cookie {
+                name: "a".into(),
+                ..cookie("example.com", true, "/")
+            },
+            Cookie {
+                name: "b".into(),
+                ..cookie("example.com", true, "/api")
+            },
+            Cookie {
+                name: "c".into(),
+                ..cookie("other.com", true, "/")
+            },
+        ];
+        let url = "http://example.com/api/users".parse().unwrap();
+        assert_eq!(
+            Cookie::header_value(&cookies, &url, now()).as_deref(),
+            Some("b=abc; a=abc")
+        );
+        let url = "http://nothing.com/".parse().unwrap();
+        assert_eq!(Cookie::header_value(&cookies, &url, now()), None);
+    }
+}
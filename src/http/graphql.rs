@@ -0,0 +1,593 @@
+//! GraphQL support. Bodies are built from the `!graphql` body type, and
+//! schemas are fetched via introspection so queries can be checked against
+//! them.
+
+use crate::{
+    collection::{GraphQlBody, Method, Recipe, RecipeBody},
+    template::{Template, TemplateContext},
+};
+use anyhow::{anyhow, bail, Context};
+use bytes::Bytes;
+use derive_more::Display;
+use futures::try_join;
+use graphql_parser::query::{
+    self, Definition, OperationDefinition, Selection, SelectionSet,
+    TypeCondition,
+};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Formatter};
+
+/// Fetch everything we need to list operations and validate field names. This
+/// is a subset of the standard introspection query.
+const INTROSPECTION_QUERY: &str = "\
+query IntrospectionQuery {
+  __schema {
+    queryType { name }
+    mutationType { name }
+    subscriptionType { name }
+    types {
+      kind
+      name
+      fields(includeDeprecated: true) {
+        name
+        args { name type { ...TypeRef } }
+        type { ...TypeRef }
+      }
+    }
+  }
+}
+
+fragment TypeRef on __Type {
+  kind
+  name
+  ofType {
+    kind
+    name
+    ofType {
+      kind
+      name
+      ofType { kind name ofType { kind name } }
+    }
+  }
+}
+";
+
+/// A GraphQL schema, as returned by an introspection query. This only
+/// includes the parts of the schema that we use.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(rename_all = "camelCase")]
+pub struct GraphQlSchema {
+    query_type: Option<NamedType>,
+    mutation_type: Option<NamedType>,
+    subscription_type: Option<NamedType>,
+    types: Vec<SchemaType>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq))]
+struct NamedType {
+    name: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq))]
+struct SchemaType {
+    kind: String,
+    name: Option<String>,
+    /// `None` for types that don't have fields (scalars, unions, etc.)
+    fields: Option<Vec<SchemaField>>,
+}
+
+/// A field on an object or interface type. Fields on the root types are the
+/// operations that the schema supports.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq))]
+pub struct SchemaField {
+    pub name: String,
+    #[serde(default)]
+    pub args: Vec<SchemaArgument>,
+    #[serde(rename = "type")]
+    pub field_type: TypeRef,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq))]
+pub struct SchemaArgument {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub argument_type: TypeRef,
+}
+
+/// Reference to a type, possibly wrapped in list and non-null modifiers
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(rename_all = "camelCase")]
+pub struct TypeRef {
+    kind: String,
+    name: Option<String>,
+    of_type: Option<Box<TypeRef>>,
+}
+
+/// The kind of a root operation type
+#[derive(Copy, Clone, Debug, Display, PartialEq)]
+pub enum OperationKind {
+    Query,
+    Mutation,
+    Subscription,
+}
+
+impl GraphQlSchema {
+    /// Parse an introspection response body. If the response contains errors
+    /// but no schema, the errors are returned.
+    pub fn from_response(body: &[u8]) -> anyhow::Result<Self> {
+        #[derive(Deserialize)]
+        struct Response {
+            data: Option<Data>,
+            errors: Option<serde_json::Value>,
+        }
+
+        #[derive(Deserialize)]
+        struct Data {
+            #[serde(rename = "__schema")]
+            schema: GraphQlSchema,
+        }
+
+        let response: Response = serde_json::from_slice(body)
+            .context("Invalid introspection response")?;
+        match response {
+            Response {
+                data: Some(data), ..
+            } => Ok(data.schema),
+            Response {
+                data: None,
+                errors: Some(errors),
+            } => bail!(
+                "Introspection failed: {}",
+                response_errors(&serde_json::json!({"errors": errors}))
+                    .join("; ")
+            ),
+            Response {
+                data: None,
+                errors: None,
+            } => bail!("Introspection response has no data"),
+        }
+    }
+
+    /// Get all operations that the schema supports, i.e. the fields of each
+    /// root type
+    pub fn operations(&self) -> Vec<(OperationKind, &SchemaField)> {
+        [
+            OperationKind::Query,
+            OperationKind::Mutation,
+            OperationKind::Subscription,
+        ]
+        .into_iter()
+        .filter_map(|kind| Some((kind, self.root_type(kind)?)))
+        .filter_map(|(kind, type_name)| {
+            Some((kind, self.get_type(type_name)?.fields.as_ref()?))
+        })
+        .flat_map(|(kind, fields)| {
+            fields.iter().map(move |field| (kind, field))
+        })
+        .collect()
+    }
+
+    /// Check that every field selected in a query exists in the schema.
+    /// Returns a list of problems, which is empty if the query is valid.
+    /// Returns an error if the query can't be parsed.
+    pub fn validate(&self, query: &str) -> anyhow::Result<Vec<String>> {
+        let document = query::parse_query::<&str>(query)
+            .map_err(|error| anyhow!("{error}"))
+            .context("Invalid GraphQL query")?;
+        let mut problems = Vec::new();
+        for definition in &document.definitions {
+            let (type_name, selection_set) = match definition {
+                Definition::Operation(operation) => {
+                    let (kind, selection_set) = match operation {
+                        OperationDefinition::SelectionSet(selection_set)
+                        | OperationDefinition::Query(query::Query {
+                            selection_set,
+                            ..
+                        }) => (OperationKind::Query, selection_set),
+                        OperationDefinition::Mutation(mutation) => {
+                            (OperationKind::Mutation, &mutation.selection_set)
+                        }
+                        OperationDefinition::Subscription(subscription) => (
+                            OperationKind::Subscription,
+                            &subscription.selection_set,
+                        ),
+                    };
+                    let Some(type_name) = self.root_type(kind) else {
+                        problems.push(format!(
+                            "Schema does not support {kind} operations"
+                        ));
+                        continue;
+                    };
+                    (type_name, selection_set)
+                }
+                Definition::Fragment(fragment) => {
+                    let TypeCondition::On(type_name) = fragment.type_condition;
+                    (type_name, &fragment.selection_set)
+                }
+            };
+            self.validate_selection_set(
+                type_name,
+                selection_set,
+                &mut problems,
+            );
+        }
+        Ok(problems)
+    }
+
+    /// Check all fields in a selection set against a type, recursively
+    fn validate_selection_set<'a>(
+        &self,
+        type_name: &str,
+        selection_set: &SelectionSet<'a, &'a str>,
+        problems: &mut Vec<String>,
+    ) {
+        let Some(schema_type) = self.get_type(type_name) else {
+            problems.push(format!("Unknown type `{type_name}`"));
+            return;
+        };
+        for selection in &selection_set.items {
+            match selection {
+                // Meta fields (e.g. __typename) are valid on every type
+                Selection::Field(field) if field.name.starts_with("__") => {}
+                Selection::Field(field) => {
+                    // Types without fields (e.g. unions) can only be selected
+                    // via fragments, but leave that for the server to point out
+                    let Some(fields) = &schema_type.fields else {
+                        continue;
+                    };
+                    match fields.iter().find(|f| f.name == field.name) {
+                        Some(schema_field) => {
+                            if !field.selection_set.items.is_empty() {
+                                self.validate_selection_set(
+                                    schema_field.field_type.base_name(),
+                                    &field.selection_set,
+                                    problems,
+                                );
+                            }
+                        }
+                        None => problems.push(format!(
+                            "{}: Unknown field `{}` on type `{type_name}`",
+                            field.position, field.name
+                        )),
+                    }
+                }
+                Selection::InlineFragment(fragment) => {
+                    let type_name = match fragment.type_condition {
+                        Some(TypeCondition::On(type_name)) => type_name,
+                        None => type_name,
+                    };
+                    self.validate_selection_set(
+                        type_name,
+                        &fragment.selection_set,
+                        problems,
+                    );
+                }
+                // Named fragments are checked on their own
+                Selection::FragmentSpread(_) => {}
+            }
+        }
+    }
+
+    /// Get the name of the root type for an operation kind, if the schema
+    /// supports it
+    fn root_type(&self, kind: OperationKind) -> Option<&str> {
+        let root = match kind {
+            OperationKind::Query => &self.query_type,
+            OperationKind::Mutation => &self.mutation_type,
+            OperationKind::Subscription => &self.subscription_type,
+        };
+        root.as_ref().map(|root| root.name.as_str())
+    }
+
+    fn get_type(&self, name: &str) -> Option<&SchemaType> {
+        self.types
+            .iter()
+            .find(|schema_type| schema_type.name.as_deref() == Some(name))
+    }
+}
+
+impl TypeRef {
+    /// Get the name of the innermost type, stripping list/non-null wrappers
+    fn base_name(&self) -> &str {
+        match (&self.name, &self.of_type) {
+            (Some(name), _) => name,
+            (None, Some(of_type)) => of_type.base_name(),
+            (None, None) => "",
+        }
+    }
+}
+
+/// Format in GraphQL syntax, e.g. `[String!]!`
+impl fmt::Display for TypeRef {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match (self.kind.as_str(), &self.of_type) {
+            ("NON_NULL", Some(of_type)) => write!(f, "{of_type}!"),
+            ("LIST", Some(of_type)) => write!(f, "[{of_type}]"),
+            _ => write!(f, "{}", self.base_name()),
+        }
+    }
+}
+
+/// Format as a signature, e.g. `user(id: ID!): User`
+impl fmt::Display for SchemaField {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        if !self.args.is_empty() {
+            let args = self
+                .args
+                .iter()
+                .map(|arg| format!("{}: {}", arg.name, arg.argument_type))
+                .join(", ");
+            write!(f, "({args})")?;
+        }
+        write!(f, ": {}", self.field_type)
+    }
+}
+
+/// Get a copy of a recipe that sends an introspection query instead of its
+/// own body. URL, headers, etc. are retained so the request goes to the same
+/// server with the same credentials.
+pub fn introspection_recipe(recipe: &Recipe) -> Recipe {
+    Recipe {
+        method: Method::Post,
+        body: Some(RecipeBody::GraphQl(GraphQlBody {
+            query: Template::parse(INTROSPECTION_QUERY.into())
+                .expect("Introspection query is a valid template"),
+            variables: None,
+            operation_name: None,
+        })),
+        // The schema is needed in memory
+        download: None,
+        ..recipe.clone()
+    }
+}
+
+/// Get the messages from the `errors` array of a GraphQL response. Servers
+/// often report errors with a 200 status, so this is the only reliable way to
+/// detect them. Returns an empty list if the value isn't a GraphQL response.
+pub fn response_errors(body: &serde_json::Value) -> Vec<&str> {
+    body.get("errors")
+        .and_then(serde_json::Value::as_array)
+        .map(|errors| {
+            errors
+                .iter()
+                .filter_map(|error| error.get("message")?.as_str())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Render a GraphQL body into the standard JSON envelope
+pub(super) async fn render_body(
+    body: &GraphQlBody,
+    template_context: &TemplateContext,
+) -> anyhow::Result<Bytes> {
+    let (query, variables, operation_name) = try_join!(
+        async {
+            body.query
+                .render(template_context)
+                .await
+                .context("Error rendering query")
+        },
+        async {
+            Template::render_opt(body.variables.as_ref(), template_context)
+                .await
+                .context("Error rendering variables")
+        },
+        async {
+            Template::render_opt(body.operation_name.as_ref(), template_context)
+                .await
+                .context("Error rendering operation name")
+        },
+    )?;
+
+    let mut envelope = serde_json::Map::new();
+    envelope.insert("query".into(), query.into());
+    if let Some(variables) = variables {
+        let variables: serde_json::Value = serde_json::from_str(&variables)
+            .context("Error parsing variables as JSON")?;
+        if !variables.is_object() {
+            bail!("Variables must be a JSON object, but got `{variables}`");
+        }
+        envelope.insert("variables".into(), variables);
+    }
+    if let Some(operation_name) = operation_name {
+        envelope.insert("operationName".into(), operation_name.into());
+    }
+    Ok(serde_json::to_vec(&envelope)?.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+    use factori::create;
+    use indexmap::indexmap;
+    use rstest::{fixture, rstest};
+    use serde_json::json;
+
+    /// A small schema with a query and mutation root
+    #[fixture]
+    fn schema() -> GraphQlSchema {
+        let body = json!({
+            "data": {
+                "__schema": {
+                    "queryType": {"name": "Query"},
+                    "mutationType": {"name": "Mutation"},
+                    "subscriptionType": null,
+                    "types": [
+                        {
+                            "kind": "OBJECT",
+                            "name": "Query",
+                            "fields": [{
+                                "name": "user",
+                                "args": [{"name": "id", "type": non_null("ID")}],
+                                "type": named("User"),
+                            }],
+                        },
+                        {
+                            "kind": "OBJECT",
+                            "name": "Mutation",
+                            "fields": [{
+                                "name": "deleteUser",
+                                "args": [],
+                                "type": non_null("Boolean"),
+                            }],
+                        },
+                        {
+                            "kind": "OBJECT",
+                            "name": "User",
+                            "fields": [
+                                {"name": "name", "args": [], "type": named("String")},
+                                {
+                                    "name": "friends",
+                                    "args": [],
+                                    "type": {
+                                        "kind": "LIST",
+                                        "name": null,
+                                        "ofType": non_null("User"),
+                                    },
+                                },
+                            ],
+                        },
+                        {"kind": "SCALAR", "name": "String", "fields": null},
+                    ],
+                },
+            },
+        });
+        GraphQlSchema::from_response(body.to_string().as_bytes()).unwrap()
+    }
+
+    fn named(name: &str) -> serde_json::Value {
+        json!({"kind": "OBJECT", "name": name, "ofType": null})
+    }
+
+    fn non_null(name: &str) -> serde_json::Value {
+        json!({"kind": "NON_NULL", "name": null, "ofType": named(name)})
+    }
+
+    #[rstest]
+    fn test_operations(schema: GraphQlSchema) {
+        let operations = schema
+            .operations()
+            .into_iter()
+            .map(|(kind, field)| format!("{kind} {field}"))
+            .collect_vec();
+        assert_eq!(
+            operations,
+            vec!["Query user(id: ID!): User", "Mutation deleteUser: Boolean!"]
+        );
+    }
+
+    #[rstest]
+    #[case::valid("{ user(id: 1) { name friends { name __typename } } }", &[])]
+    #[case::fragment(
+        "query { user(id: 1) { ...F } } fragment F on User { nam }",
+        &["1:53: Unknown field `nam` on type `User`"],
+    )]
+    #[case::unknown_field(
+        "query Q { user(id: 1) { name email } }",
+        &["1:30: Unknown field `email` on type `User`"],
+    )]
+    #[case::inline_fragment(
+        "{ user(id: 1) { ... on User { age } } }",
+        &["1:31: Unknown field `age` on type `User`"],
+    )]
+    #[case::unsupported_operation(
+        "subscription { user }",
+        &["Schema does not support Subscription operations"],
+    )]
+    fn test_validate(
+        schema: GraphQlSchema,
+        #[case] query: &str,
+        #[case] expected: &[&str],
+    ) {
+        assert_eq!(schema.validate(query).unwrap(), expected);
+    }
+
+    #[rstest]
+    fn test_validate_syntax_error(schema: GraphQlSchema) {
+        assert!(schema.validate("{ user(").is_err());
+    }
+
+    #[test]
+    fn test_from_response_error() {
+        let body = json!({"errors": [{"message": "Introspection disabled"}]});
+        assert_eq!(
+            GraphQlSchema::from_response(body.to_string().as_bytes())
+                .unwrap_err()
+                .to_string(),
+            "Introspection failed: Introspection disabled"
+        );
+    }
+
+    #[rstest]
+    #[case::errors(
+        json!({"data": null, "errors": [{"message": "a"}, {"message": "b"}]}),
+        &["a", "b"],
+    )]
+    #[case::no_errors(json!({"data": {}}), &[])]
+    #[case::not_graphql(json!([1, 2]), &[])]
+    fn test_response_errors(
+        #[case] body: serde_json::Value,
+        #[case] expected: &[&str],
+    ) {
+        assert_eq!(response_errors(&body), expected);
+    }
+
+    #[rstest]
+    #[case::query_only(None, None, json!({"query": "{ user { name } }"}))]
+    #[case::all(
+        Some(r#"{"id": {{id}}}"#),
+        Some("GetUser"),
+        json!({
+            "query": "{ user { name } }",
+            "variables": {"id": 3},
+            "operationName": "GetUser",
+        })
+    )]
+    #[tokio::test]
+    async fn test_render_body(
+        #[case] variables: Option<&str>,
+        #[case] operation_name: Option<&str>,
+        #[case] expected: serde_json::Value,
+    ) {
+        let context = create!(
+            TemplateContext,
+            overrides: indexmap! {"id".into() => "3".into()},
+        );
+        let body = GraphQlBody {
+            query: "{ user { name } }".into(),
+            variables: variables.map(Template::from),
+            operation_name: operation_name.map(Template::from),
+        };
+        let rendered = render_body(&body, &context).await.unwrap();
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&rendered).unwrap(),
+            expected
+        );
+    }
+
+    #[tokio::test]
+    async fn test_render_body_invalid_variables() {
+        let body = GraphQlBody {
+            query: "{ user { name } }".into(),
+            variables: Some("[1]".into()),
+            operation_name: None,
+        };
+        let error = render_body(&body, &create!(TemplateContext))
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Variables must be a JSON object, but got `[1]`"
+        );
+    }
+}
@@ -0,0 +1,403 @@
+//! Hooks are external commands that run around a request. The pre-request
+//! hook runs when a request is sent, and can modify it first
+//! (e.g. to add a signature header). The post-response hook runs after a
+//! response is received, and can modify the response body, annotate the
+//! record, or fail the request.
+//!
+//! Each hook receives its input as JSON on stdin. To make changes, it prints
+//! a JSON object to stdout with just the fields it wants to change. Empty
+//! output means no changes. A non-zero exit code fails the request, with the
+//! hook's stderr as the error message.
+
+use crate::{
+    collection::HookCommand,
+    http::{Request, RequestRecord},
+    util::ResultExt,
+};
+use anyhow::{anyhow, bail, Context};
+use indexmap::IndexMap;
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue},
+    Method,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{io, process::Stdio};
+use tokio::{io::AsyncWriteExt, process::Command};
+use tracing::{debug, debug_span};
+use url::Url;
+
+/// Run the pre-request hook on a built request, and apply its changes
+pub async fn run_pre_request(
+    command: &HookCommand,
+    request: &mut Request,
+) -> anyhow::Result<()> {
+    let patch: Option<RequestPatch> =
+        run(command, &HookRequest::new(request)).await?;
+    if let Some(patch) = patch {
+        patch.apply(request)?;
+    }
+    Ok(())
+}
+
+/// Run the post-response hook on a completed request, and apply its changes
+pub async fn run_post_response(
+    command: &HookCommand,
+    record: &mut RequestRecord,
+) -> anyhow::Result<()> {
+    let input = HookRecord {
+        request: HookRequest::new(&record.request),
+        response: HookResponse {
+            status: record.response.status.as_u16(),
+            headers: header_map_to_json(&record.response.headers),
+            body: record
+                .response
+                .download
+                .is_none()
+                .then(|| record.response.text().map(String::from))
+                .flatten(),
+        },
+        attempts: record.attempts,
+    };
+    let patch: Option<ResponsePatch> = run(command, &input).await?;
+    if let Some(patch) = patch {
+        if let Some(body) = patch.body {
+            record.response.set_body_text(&body);
+        }
+        record.annotations.extend(patch.annotations);
+    }
+    Ok(())
+}
+
+/// Execute a hook command, passing it the input as JSON. Parse its output as
+/// JSON, unless it's empty
+async fn run<T: DeserializeOwned>(
+    command: &HookCommand,
+    input: &impl Serialize,
+) -> anyhow::Result<Option<T>> {
+    let [program, args @ ..] = command.as_slice() else {
+        bail!("Hook command is empty");
+    };
+    let input = serde_json::to_vec(input)?;
+    let command_str = command.join(" ");
+
+    debug_span!("Executing hook", ?command)
+        .in_scope(|| async {
+            let mut child = Command::new(program)
+                .args(args)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                // If the request is cancelled, don't leave the hook running in
+                // the background
+                .kill_on_drop(true)
+                .spawn()
+                .with_context(|| {
+                    format!("Error executing hook `{command_str}`")
+                })?;
+
+            // Write input and read output concurrently, so a hook that writes
+            // before it's done reading doesn't deadlock on a full pipe
+            let mut stdin = child.stdin.take().expect("stdin is piped");
+            let write = async move {
+                match stdin.write_all(&input).await {
+                    // The hook doesn't have to read its input
+                    Err(error) if error.kind() != io::ErrorKind::BrokenPipe => {
+                        Err(error)
+                    }
+                    _ => Ok(()),
+                }
+                // stdin is dropped here, to signal EOF
+            };
+            let (write_result, output) =
+                tokio::join!(write, child.wait_with_output());
+            let output = output.with_context(|| {
+                format!("Error executing hook `{command_str}`")
+            })?;
+            write_result.with_context(|| {
+                format!("Error writing input to hook `{command_str}`")
+            })?;
+
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            if !output.status.success() {
+                return Err(anyhow!("{}", stderr.trim())).with_context(|| {
+                    format!("Hook `{command_str}` failed ({})", output.status)
+                });
+            }
+            debug!(
+                stdout = %String::from_utf8_lossy(&output.stdout),
+                %stderr,
+                "Hook success"
+            );
+
+            if output.stdout.iter().all(u8::is_ascii_whitespace) {
+                Ok(None)
+            } else {
+                let value = serde_json::from_slice(&output.stdout)
+                    .with_context(|| {
+                        format!("Error parsing output of hook `{command_str}`")
+                    })?;
+                Ok(Some(value))
+            }
+        })
+        .await
+        .traced()
+}
+
+/// JSON form of a request, as passed to hooks. Repeated headers are collapsed
+/// to the last value. The body is `null` if it isn't valid UTF-8.
+#[derive(Debug, Serialize)]
+struct HookRequest<'a> {
+    id: String,
+    profile_id: Option<&'a str>,
+    recipe_id: &'a str,
+    method: &'a str,
+    url: &'a Url,
+    headers: IndexMap<String, String>,
+    body: Option<&'a str>,
+}
+
+impl<'a> HookRequest<'a> {
+    fn new(request: &'a Request) -> Self {
+        Self {
+            id: request.id.to_string(),
+            profile_id: request.profile_id.as_deref().map(String::as_str),
+            recipe_id: &request.recipe_id,
+            method: request.method.as_str(),
+            url: &request.url,
+            headers: header_map_to_json(&request.headers),
+            body: request
+                .body
+                .as_deref()
+                .and_then(|body| std::str::from_utf8(body).ok()),
+        }
+    }
+}
+
+/// Input for the post-response hook
+#[derive(Debug, Serialize)]
+struct HookRecord<'a> {
+    request: HookRequest<'a>,
+    response: HookResponse,
+    attempts: u32,
+}
+
+/// JSON form of a response, as passed to hooks. The body is `null` if it
+/// isn't text, or if it was downloaded to a file.
+#[derive(Debug, Serialize)]
+struct HookResponse {
+    status: u16,
+    headers: IndexMap<String, String>,
+    body: Option<String>,
+}
+
+/// Changes that the pre-request hook can make to a request. Any field that's
+/// omitted is left as is.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RequestPatch {
+    method: Option<String>,
+    url: Option<Url>,
+    /// Headers to set. A `null` value removes the header
+    headers: IndexMap<String, Option<String>>,
+    body: Option<String>,
+}
+
+impl RequestPatch {
+    fn apply(self, request: &mut Request) -> anyhow::Result<()> {
+        if let Some(method) = self.method {
+            request.method = Method::from_bytes(method.as_bytes())
+                .with_context(|| format!("Invalid HTTP method `{method}`"))?;
+        }
+        if let Some(url) = self.url {
+            request.url = url;
+        }
+        for (name, value) in self.headers {
+            let name = HeaderName::try_from(&name).with_context(|| {
+                format!("Error encoding header name `{name}`")
+            })?;
+            match value {
+                Some(value) => {
+                    let value =
+                        HeaderValue::try_from(value).with_context(|| {
+                            format!("Error encoding value for header `{name}`")
+                        })?;
+                    request.headers.insert(name, value);
+                }
+                None => {
+                    request.headers.remove(name);
+                }
+            }
+        }
+        if let Some(body) = self.body {
+            request.body = Some(body.into());
+        }
+        Ok(())
+    }
+}
+
+/// Changes that the post-response hook can make to a record
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ResponsePatch {
+    /// Replace the response body
+    body: Option<String>,
+    /// Notes to attach to the record
+    annotations: Vec<String>,
+}
+
+fn header_map_to_json(headers: &HeaderMap) -> IndexMap<String, String> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            (
+                name.to_string(),
+                String::from_utf8_lossy(value.as_bytes()).into_owned(),
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+    use bytes::Bytes;
+    use factori::create;
+    use reqwest::header;
+    use serde_json::json;
+
+    /// Build a hook that runs a shell script
+    fn sh(script: &str) -> HookCommand {
+        vec!["sh".into(), "-c".into(), script.into()]
+    }
+
+    /// The hook gets the request as JSON, and its output is applied
+    #[tokio::test]
+    async fn test_pre_request() {
+        let mut request = create!(
+            Request,
+            method: Method::POST,
+            url: "http://localhost/users".parse().unwrap(),
+            headers: header_map([
+                ("content-type", "application/json"),
+                ("x-remove", "gone"),
+            ]),
+            body: Some(br#"{"name":"Frodo"}"#.as_slice().into()),
+        );
+        let output = json!({
+            "url": "http://localhost/users?signed=true",
+            "headers": {"x-remove": null, "x-signature": "abc123"},
+            "body": "signed",
+        });
+        let command = sh(&format!("cat > /dev/null; echo '{output}'"));
+        run_pre_request(&command, &mut request).await.unwrap();
+
+        assert_eq!(request.method, Method::POST);
+        assert_eq!(request.url.as_str(), "http://localhost/users?signed=true");
+        assert_eq!(
+            request.headers,
+            header_map([
+                ("content-type", "application/json"),
+                ("x-signature", "abc123"),
+            ])
+        );
+        assert_eq!(request.body.as_deref(), Some(b"signed".as_slice()));
+    }
+
+    /// The hook's input contains the request
+    #[tokio::test]
+    async fn test_pre_request_input() {
+        let mut request = create!(
+            Request,
+            headers: header_map([("x-nonce", "1")]),
+            body: Some(b"hello".as_slice().into()),
+        );
+        // Copy the body from the input into a header
+        let command =
+            sh(r#"read input; printf '{"headers": {"x-body": "%s"}}' \
+            "$(echo "$input" | sed 's/.*"body":"\([^"]*\)".*/\1/')""#);
+        run_pre_request(&command, &mut request).await.unwrap();
+        assert_eq!(request.headers.get("x-body").unwrap(), "hello");
+    }
+
+    /// Empty output means no changes
+    #[tokio::test]
+    async fn test_pre_request_no_output() {
+        let mut request = create!(Request);
+        run_pre_request(&sh("true"), &mut request).await.unwrap();
+        assert_eq!(request, create!(Request, id: request.id));
+    }
+
+    /// The post-response hook can replace the body and add annotations
+    #[tokio::test]
+    async fn test_post_response() {
+        let mut record = create!(
+            RequestRecord,
+            response: create!(
+                Response,
+                headers: header_map([("content-type", "text/plain")]),
+                body: "encrypted".into(),
+                encoded_size: Some(5),
+            ),
+        );
+        let command = sh(
+            r#"echo '{"body": "decrypted", "annotations": ["Decrypted body"]}'"#,
+        );
+        run_post_response(&command, &mut record).await.unwrap();
+        assert_eq!(record.response.body.bytes().as_ref(), b"decrypted");
+        assert_eq!(record.annotations, vec!["Decrypted body".to_owned()]);
+        assert_eq!(
+            record.response.headers.get(header::CONTENT_TYPE).unwrap(),
+            "text/plain"
+        );
+        // The new body wasn't received over the wire
+        assert_eq!(record.response.encoded_size, None);
+    }
+
+    /// A replaced body is encoded in the response's declared charset, so it
+    /// decodes the same way as the original
+    #[tokio::test]
+    async fn test_post_response_charset() {
+        let mut record = create!(
+            RequestRecord,
+            response: create!(
+                Response,
+                headers: header_map([(
+                    "content-type",
+                    "text/plain; charset=iso-8859-1"
+                )]),
+                body: Bytes::from_static(b"caf\xe9").into(),
+            ),
+        );
+        let command = sh(r#"echo '{"body": "d\u00e9j\u00e0 vu"}'"#);
+        run_post_response(&command, &mut record).await.unwrap();
+        assert_eq!(record.response.body.bytes().as_ref(), b"d\xe9j\xe0 vu");
+        assert_eq!(record.response.text().as_deref(), Some("déjà vu"));
+    }
+
+    /// Hook errors are reported with the hook's stderr
+    #[rstest::rstest]
+    #[case::exit_code(sh("echo 'bad signature' >&2; exit 3"), "bad signature")]
+    #[case::invalid_output(sh("echo nope"), "Error parsing output")]
+    #[case::unknown_field(
+        sh(r#"echo '{"status": 500}'"#),
+        "Error parsing output"
+    )]
+    #[case::empty(vec![], "Hook command is empty")]
+    #[case::missing_program(
+        vec!["slumber-not-a-real-program".into()],
+        "Error executing hook"
+    )]
+    #[tokio::test]
+    async fn test_post_response_error(
+        #[case] command: HookCommand,
+        #[case] expected_error: &str,
+    ) {
+        let mut record = create!(RequestRecord);
+        let error = run_post_response(&command, &mut record).await.unwrap_err();
+        assert!(
+            format!("{error:#}").contains(expected_error),
+            "Expected `{error:#}` to contain `{expected_error}`"
+        );
+    }
+}
@@ -0,0 +1,730 @@
+//! A mock HTTP server, which answers requests with canned responses from the
+//! collection. Each incoming request is matched to a recipe by its method and
+//! URL path, then answered with the recipe's example response, or the most
+//! recent response to that recipe in history.
+
+use crate::{
+    collection::{
+        Collection, ExampleResponse, Profile, ProfileId, Recipe, RecipeId,
+    },
+    db::CollectionDatabase,
+    http::Response,
+};
+use anyhow::{anyhow, Context};
+use bytes::Bytes;
+use derive_more::Display;
+use hyper::{
+    service::{make_service_fn, service_fn},
+    Server,
+};
+use itertools::Itertools;
+use reqwest::{
+    header::{self, HeaderMap, HeaderName, HeaderValue},
+    Method, StatusCode,
+};
+use std::{
+    convert::Infallible, future::Future, net::TcpListener, sync::Arc,
+    time::Duration,
+};
+use tracing::info;
+
+/// Stand-in for template keys that can't be resolved without rendering. In a
+/// path, this matches any text within a single segment.
+const WILDCARD: char = '\0';
+
+/// Headers of a recorded response that don't describe the body we send back,
+/// e.g. because it was already decompressed
+const STRIPPED_HEADERS: [HeaderName; 4] = [
+    header::CONNECTION,
+    header::CONTENT_ENCODING,
+    header::CONTENT_LENGTH,
+    header::TRANSFER_ENCODING,
+];
+
+/// Settings for the mock server
+#[derive(Debug, Default)]
+pub struct MockOptions {
+    /// Profile used to resolve each recipe's host, and to find responses in
+    /// history
+    pub profile_id: Option<ProfileId>,
+    /// Send every response from the collection with this status, instead of
+    /// its own
+    pub status: Option<StatusCode>,
+    /// Wait this long before every response, instead of the example's latency
+    pub latency: Option<Duration>,
+}
+
+/// Serves responses for every recipe in a collection
+#[derive(Debug)]
+pub struct MockServer {
+    /// Ordered by specificity, so the first match is the best one
+    routes: Vec<MockRoute>,
+    database: CollectionDatabase,
+    options: MockOptions,
+}
+
+/// A recipe that the mock server can answer
+#[derive(Debug)]
+pub struct MockRoute {
+    pub recipe_id: RecipeId,
+    pub method: Method,
+    /// URL path with the host and query stripped
+    segments: Vec<PathSegment>,
+    /// Pre-built from the recipe's example, so it's only validated once
+    example: Option<MockResponse>,
+}
+
+#[derive(Debug, PartialEq)]
+enum PathSegment {
+    Literal(String),
+    /// Some text that couldn't be determined statically
+    Wildcard,
+}
+
+/// A request received by the mock server, and how it was answered
+#[derive(Debug)]
+pub struct MockHit {
+    pub method: Method,
+    /// Path and query
+    pub uri: String,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+    /// The recipe that matched the request, if any
+    pub recipe_id: Option<RecipeId>,
+    pub status: StatusCode,
+    pub source: MockSource,
+}
+
+/// Where the response to a mocked request came from
+#[derive(Copy, Clone, Debug, Display, PartialEq)]
+pub enum MockSource {
+    /// The recipe's `example_response`
+    #[display("example")]
+    Example,
+    /// The latest response to the recipe in history
+    #[display("history")]
+    History,
+    /// The recipe matched, but has no example or history to respond with
+    #[display("no response")]
+    NoResponse,
+    /// No recipe matched the request
+    #[display("no match")]
+    NoMatch,
+}
+
+#[derive(Clone, Debug)]
+struct MockResponse {
+    source: MockSource,
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Bytes,
+    latency: Option<Duration>,
+}
+
+impl MockServer {
+    /// Build a route for each recipe in the collection. Fails if the profile
+    /// doesn't exist, or any example response is invalid.
+    pub fn new(
+        collection: &Collection,
+        database: CollectionDatabase,
+        options: MockOptions,
+    ) -> anyhow::Result<Self> {
+        let profile = options
+            .profile_id
+            .as_ref()
+            .map(|profile_id| {
+                collection.profiles.get(profile_id).ok_or_else(|| {
+                    anyhow!(
+                        "No profile with ID `{profile_id}`; options are: {}",
+                        collection.profiles.keys().join(", ")
+                    )
+                })
+            })
+            .transpose()?;
+
+        let mut routes = collection
+            .recipes
+            .recipe_ids()
+            .filter_map(|recipe_id| collection.recipes.get_recipe(recipe_id))
+            .map(|recipe| MockRoute::new(recipe, profile))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        // When multiple routes match, e.g. `/users/me` and `/users/{{id}}`, the
+        // one with fewer wildcards is more specific. The sort is stable, so
+        // ties go to whichever is first in the collection
+        routes.sort_by_key(MockRoute::wildcards);
+
+        Ok(Self {
+            routes,
+            database,
+            options,
+        })
+    }
+
+    pub fn routes(&self) -> &[MockRoute] {
+        &self.routes
+    }
+
+    /// Serve requests on the listener until `shutdown` resolves. Each request
+    /// is passed to `on_hit` once it's been answered.
+    pub async fn serve(
+        self,
+        listener: TcpListener,
+        on_hit: impl Fn(MockHit) + Send + Sync + 'static,
+        shutdown: impl Future<Output = ()>,
+    ) -> anyhow::Result<()> {
+        let server = Arc::new(self);
+        let on_hit = Arc::new(on_hit);
+        let make_service = make_service_fn(move |_| {
+            let server = Arc::clone(&server);
+            let on_hit = Arc::clone(&on_hit);
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    let server = Arc::clone(&server);
+                    let on_hit = Arc::clone(&on_hit);
+                    async move {
+                        Ok::<_, Infallible>(
+                            server.handle(request, on_hit.as_ref()).await,
+                        )
+                    }
+                }))
+            }
+        });
+        Server::from_tcp(listener)?
+            .serve(make_service)
+            .with_graceful_shutdown(shutdown)
+            .await
+            .context("Error running mock server")
+    }
+
+    /// Answer a single request
+    async fn handle(
+        &self,
+        request: hyper::Request<hyper::Body>,
+        on_hit: &(impl Fn(MockHit) + Send + Sync),
+    ) -> hyper::Response<hyper::Body> {
+        let (parts, body) = request.into_parts();
+        // The response doesn't depend on the body, so if it can't be read
+        // just log it as empty
+        let body = hyper::body::to_bytes(body).await.unwrap_or_default();
+        let (recipe_id, response) =
+            self.respond(&parts.method, parts.uri.path());
+        if let Some(latency) = response.latency {
+            tokio::time::sleep(latency).await;
+        }
+
+        info!(
+            method = %parts.method,
+            uri = %parts.uri,
+            recipe_id = ?recipe_id,
+            status = %response.status,
+            source = %response.source,
+            "Mock request"
+        );
+        on_hit(MockHit {
+            method: parts.method,
+            uri: parts.uri.to_string(),
+            headers: parts.headers,
+            body,
+            recipe_id: recipe_id.cloned(),
+            status: response.status,
+            source: response.source,
+        });
+
+        let mut hyper_response =
+            hyper::Response::new(hyper::Body::from(response.body));
+        *hyper_response.status_mut() = response.status;
+        *hyper_response.headers_mut() = response.headers;
+        hyper_response
+    }
+
+    /// Find the route for a request, and get the response to send. Returns the
+    /// ID of the matched recipe, if any
+    fn respond(
+        &self,
+        method: &Method,
+        path: &str,
+    ) -> (Option<&RecipeId>, MockResponse) {
+        let Some(route) =
+            self.routes.iter().find(|route| route.matches(method, path))
+        else {
+            let response = MockResponse::error(
+                StatusCode::NOT_FOUND,
+                MockSource::NoMatch,
+                format!("No recipe matches {method} {path}"),
+            );
+            return (None, response.with_latency(self.options.latency));
+        };
+
+        let mut response = if let Some(example) = &route.example {
+            example.clone()
+        } else {
+            match self.database.get_last_request(
+                self.options.profile_id.as_ref(),
+                &route.recipe_id,
+            ) {
+                Ok(Some(record)) => MockResponse::from_history(record.response),
+                Ok(None) => MockResponse::error(
+                    StatusCode::NOT_IMPLEMENTED,
+                    MockSource::NoResponse,
+                    format!(
+                        "Recipe `{}` has no example response or history",
+                        route.recipe_id
+                    ),
+                ),
+                Err(error) => MockResponse::error(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    MockSource::NoResponse,
+                    format!("{error:#}"),
+                ),
+            }
+        };
+        // Our own errors keep their status, so they're recognizable
+        if let (Some(status), MockSource::Example | MockSource::History) =
+            (self.options.status, response.source)
+        {
+            response.status = status;
+        }
+        (
+            Some(&route.recipe_id),
+            response.with_latency(self.options.latency),
+        )
+    }
+}
+
+impl MockRoute {
+    fn new(recipe: &Recipe, profile: Option<&Profile>) -> anyhow::Result<Self> {
+        // Profile fields are substituted so their paths can be matched, e.g.
+        // if `host` is `http://localhost/api`. Anything else could be
+        // different for each request, so it matches anything
+        let url = recipe.url.replace_keys(|key| {
+            profile
+                .and_then(|profile| profile.data.get(key))
+                .map(|value| value.replace_keys(|_| WILDCARD.into()))
+                .unwrap_or_else(|| WILDCARD.into())
+        });
+        let example = recipe
+            .example_response
+            .as_ref()
+            .map(MockResponse::from_example)
+            .transpose()
+            .with_context(|| {
+                format!("Invalid example response for recipe `{}`", recipe.id)
+            })?;
+        Ok(Self {
+            recipe_id: recipe.id.clone(),
+            method: recipe.method.into(),
+            segments: parse_path(&url),
+            example,
+        })
+    }
+
+    /// Get the path this route matches, with `*` for each wildcard
+    pub fn path(&self) -> String {
+        let path: String = self
+            .segments
+            .iter()
+            .map(|segment| match segment {
+                PathSegment::Literal(literal) => literal.as_str(),
+                PathSegment::Wildcard => "*",
+            })
+            .collect();
+        if path.is_empty() {
+            "/".into()
+        } else {
+            path
+        }
+    }
+
+    fn wildcards(&self) -> usize {
+        self.segments
+            .iter()
+            .filter(|segment| **segment == PathSegment::Wildcard)
+            .count()
+    }
+
+    fn matches(&self, method: &Method, path: &str) -> bool {
+        self.method == method
+            && match_segments(&self.segments, path.trim_end_matches('/'))
+    }
+}
+
+impl MockResponse {
+    fn from_example(example: &ExampleResponse) -> anyhow::Result<Self> {
+        let status = StatusCode::from_u16(example.status)
+            .with_context(|| format!("Invalid status `{}`", example.status))?;
+        let headers = example
+            .headers
+            .iter()
+            .map(|(name, value)| {
+                Ok((
+                    HeaderName::try_from(name)
+                        .with_context(|| format!("Invalid header `{name}`"))?,
+                    HeaderValue::try_from(value).with_context(|| {
+                        format!("Invalid value for header `{name}`")
+                    })?,
+                ))
+            })
+            .collect::<anyhow::Result<HeaderMap>>()?;
+        Ok(Self {
+            source: MockSource::Example,
+            status,
+            headers,
+            body: example.body.clone().into(),
+            latency: example.latency,
+        })
+    }
+
+    fn from_history(response: Response) -> Self {
+        let mut headers = response.headers;
+        for header in STRIPPED_HEADERS {
+            headers.remove(header);
+        }
+        Self {
+            source: MockSource::History,
+            status: response.status,
+            headers,
+            body: response.body.bytes().clone(),
+            latency: None,
+        }
+    }
+
+    /// A plain text response for when we don't have a real one to send
+    fn error(status: StatusCode, source: MockSource, message: String) -> Self {
+        Self {
+            source,
+            status,
+            headers: [(
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("text/plain"),
+            )]
+            .into_iter()
+            .collect(),
+            body: message.into(),
+            latency: None,
+        }
+    }
+
+    /// Override the response's latency, if given
+    fn with_latency(self, latency: Option<Duration>) -> Self {
+        Self {
+            latency: latency.or(self.latency),
+            ..self
+        }
+    }
+}
+
+/// Get the path of a URL, and split it on wildcards. The scheme and host are
+/// stripped, as well as the query and fragment. If the URL doesn't have a
+/// scheme, it's assumed to start with a wildcard host, e.g. `{{host}}/users`.
+/// Trailing slashes are ignored.
+fn parse_path(url: &str) -> Vec<PathSegment> {
+    let url = url.split(['?', '#']).next().unwrap_or_default();
+    let path = match url.split_once("://") {
+        Some((_, rest)) => rest.find('/').map_or("", |index| &rest[index..]),
+        None => url.strip_prefix(WILDCARD).unwrap_or(url),
+    };
+    let mut segments = Vec::new();
+    for (i, literal) in path.trim_end_matches('/').split(WILDCARD).enumerate() {
+        if i > 0 {
+            segments.push(PathSegment::Wildcard);
+        }
+        if !literal.is_empty() {
+            segments.push(PathSegment::Literal(literal.to_owned()));
+        }
+    }
+    segments
+}
+
+/// Check if a path matches a sequence of segments. Each wildcard matches at
+/// least one character, but never crosses a `/`.
+fn match_segments(segments: &[PathSegment], path: &str) -> bool {
+    match segments.split_first() {
+        None => path.is_empty(),
+        Some((PathSegment::Literal(literal), rest)) => path
+            .strip_prefix(literal.as_str())
+            .is_some_and(|path| match_segments(rest, path)),
+        Some((PathSegment::Wildcard, rest)) => {
+            let end = path.find('/').unwrap_or(path.len());
+            (1..=end)
+                .filter(|index| path.is_char_boundary(*index))
+                .any(|index| match_segments(rest, &path[index..]))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+    use factori::create;
+    use indexmap::indexmap;
+    use rstest::rstest;
+    use std::sync::Mutex;
+
+    /// Build a server from a list of recipes
+    fn server(
+        recipes: Vec<Recipe>,
+        database: CollectionDatabase,
+        options: MockOptions,
+    ) -> MockServer {
+        let recipes: indexmap::IndexMap<RecipeId, Recipe> = recipes
+            .into_iter()
+            .map(|recipe| (recipe.id.clone(), recipe))
+            .collect();
+        let collection = create!(Collection, recipes: recipes.into());
+        MockServer::new(&collection, database, options).unwrap()
+    }
+
+    #[rstest]
+    #[case::no_scheme("{{host}}/users/{{id}}", "/users/*", "/users/3", true)]
+    #[case::trailing_slash(
+        "{{host}}/users/{{id}}",
+        "/users/*",
+        "/users/3/",
+        true
+    )]
+    #[case::empty_wildcard(
+        "{{host}}/users/{{id}}",
+        "/users/*",
+        "/users/",
+        false
+    )]
+    #[case::wildcard_segment(
+        "{{host}}/users/{{id}}",
+        "/users/*",
+        "/users/3/posts",
+        false
+    )]
+    #[case::scheme("http://localhost:3000/users", "/users", "/users", true)]
+    #[case::root("{{host}}", "/", "/", true)]
+    #[case::query("{{host}}/users?limit={{limit}}", "/users", "/users", true)]
+    #[case::partial_segment(
+        "https://{{env}}.fish.com/fish/{{id}}.json",
+        "/fish/*.json",
+        "/fish/1.json",
+        true
+    )]
+    #[case::profile("{{base}}/users", "/api/v1/users", "/api/v1/users", true)]
+    fn test_route_path(
+        #[case] url: &str,
+        #[case] expected_path: &str,
+        #[case] request_path: &str,
+        #[case] expected_match: bool,
+    ) {
+        let profile = create!(Profile, data: indexmap! {
+            "base".into() => "http://localhost:3000/api/v1".into(),
+        });
+        let recipe = create!(Recipe, url: url.into());
+        let route = MockRoute::new(&recipe, Some(&profile)).unwrap();
+        assert_eq!(route.path(), expected_path);
+        assert_eq!(route.matches(&Method::GET, request_path), expected_match);
+        assert!(!route.matches(&Method::POST, request_path));
+    }
+
+    /// Examples take priority over history, and the most specific route wins
+    #[test]
+    fn test_respond() {
+        let database = CollectionDatabase::testing();
+        let record = create!(
+            RequestRecord,
+            request: create!(Request, recipe_id: "get_user".into()).into(),
+            response: create!(
+                Response,
+                status: StatusCode::CREATED,
+                headers: header_map([
+                    ("content-type", "application/json"),
+                    ("content-encoding", "gzip"),
+                ]),
+                body: r#"{"id": 3}"#.to_owned().into(),
+            ),
+        );
+        database.insert_request(&record).unwrap();
+        let server = server(
+            vec![
+                create!(
+                    Recipe,
+                    id: "get_user".into(),
+                    url: "{{host}}/users/{{id}}".into(),
+                ),
+                create!(
+                    Recipe,
+                    id: "current_user".into(),
+                    url: "{{host}}/users/me".into(),
+                    example_response: Some(ExampleResponse {
+                        headers: indexmap! {
+                            "content-type".into() => "text/plain".into(),
+                        },
+                        body: "me!".into(),
+                        ..Default::default()
+                    }),
+                ),
+                create!(
+                    Recipe,
+                    id: "delete_user".into(),
+                    method: "DELETE".parse().unwrap(),
+                    url: "{{host}}/users/{{id}}".into(),
+                ),
+            ],
+            database,
+            MockOptions::default(),
+        );
+
+        let (recipe_id, response) = server.respond(&Method::GET, "/users/me");
+        assert_eq!(
+            recipe_id.map(ToString::to_string).as_deref(),
+            Some("current_user")
+        );
+        assert_eq!(response.source, MockSource::Example);
+        assert_eq!(response.status, StatusCode::OK);
+        assert_eq!(
+            response.headers,
+            header_map([("content-type", "text/plain")])
+        );
+        assert_eq!(response.body, "me!");
+
+        let (recipe_id, response) = server.respond(&Method::GET, "/users/3");
+        assert_eq!(
+            recipe_id.map(ToString::to_string).as_deref(),
+            Some("get_user")
+        );
+        assert_eq!(response.source, MockSource::History);
+        assert_eq!(response.status, StatusCode::CREATED);
+        // The body was decompressed before it was stored
+        assert_eq!(
+            response.headers,
+            header_map([("content-type", "application/json")])
+        );
+        assert_eq!(response.body, r#"{"id": 3}"#);
+
+        let (recipe_id, response) = server.respond(&Method::DELETE, "/users/3");
+        assert_eq!(
+            recipe_id.map(ToString::to_string).as_deref(),
+            Some("delete_user")
+        );
+        assert_eq!(response.source, MockSource::NoResponse);
+        assert_eq!(response.status, StatusCode::NOT_IMPLEMENTED);
+
+        let (recipe_id, response) = server.respond(&Method::GET, "/fish");
+        assert_eq!(recipe_id, None);
+        assert_eq!(response.source, MockSource::NoMatch);
+        assert_eq!(response.status, StatusCode::NOT_FOUND);
+    }
+
+    /// Status and latency overrides apply to every response from the
+    /// collection
+    #[test]
+    fn test_overrides() {
+        let server = server(
+            vec![create!(
+                Recipe,
+                url: "{{host}}/fish".into(),
+                example_response: Some(ExampleResponse {
+                    latency: Some(Duration::from_millis(100)),
+                    ..Default::default()
+                }),
+            )],
+            CollectionDatabase::testing(),
+            MockOptions {
+                status: Some(StatusCode::SERVICE_UNAVAILABLE),
+                latency: Some(Duration::from_secs(1)),
+                ..Default::default()
+            },
+        );
+
+        let (_, response) = server.respond(&Method::GET, "/fish");
+        assert_eq!(response.status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.latency, Some(Duration::from_secs(1)));
+
+        let (_, response) = server.respond(&Method::GET, "/unknown");
+        assert_eq!(response.status, StatusCode::NOT_FOUND);
+        assert_eq!(response.latency, Some(Duration::from_secs(1)));
+    }
+
+    #[rstest]
+    #[case::status(
+        ExampleResponse { status: 1000, ..Default::default() },
+        "Invalid status `1000`"
+    )]
+    #[case::header(
+        ExampleResponse {
+            headers: indexmap! {"bad header".into() => "value".into()},
+            ..Default::default()
+        },
+        "Invalid header `bad header`"
+    )]
+    fn test_invalid_example(
+        #[case] example: ExampleResponse,
+        #[case] expected_error: &str,
+    ) {
+        let collection = create!(
+            Collection,
+            recipes: indexmap! {
+                "recipe1".into() => create!(
+                    Recipe, example_response: Some(example),
+                ),
+            }
+            .into(),
+        );
+        let error = MockServer::new(
+            &collection,
+            CollectionDatabase::testing(),
+            MockOptions::default(),
+        )
+        .unwrap_err();
+        let error = format!("{error:#}");
+        assert!(
+            error.contains(expected_error),
+            "Expected `{error}` to contain `{expected_error}`"
+        );
+    }
+
+    /// Send a real request to the server, and make sure the hit is reported
+    #[tokio::test]
+    async fn test_serve() {
+        let server = server(
+            vec![create!(
+                Recipe,
+                method: "POST".parse().unwrap(),
+                url: "{{host}}/fish".into(),
+                example_response: Some(ExampleResponse {
+                    status: 201,
+                    body: "created".into(),
+                    ..Default::default()
+                }),
+            )],
+            CollectionDatabase::testing(),
+            MockOptions::default(),
+        );
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let hits = Arc::new(Mutex::new(Vec::new()));
+        let hits_clone = Arc::clone(&hits);
+        tokio::spawn(server.serve(
+            listener,
+            move |hit| hits_clone.lock().unwrap().push(hit),
+            futures::future::pending(),
+        ));
+
+        let response = reqwest::Client::new()
+            .post(format!("http://{address}/fish?size=large"))
+            .body("nemo")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(response.text().await.unwrap(), "created");
+
+        let hits = hits.lock().unwrap();
+        let [hit] = hits.as_slice() else {
+            panic!("Expected one hit, got {hits:?}");
+        };
+        assert_eq!(hit.method, Method::POST);
+        assert_eq!(hit.uri, "/fish?size=large");
+        assert_eq!(hit.body, "nemo");
+        assert_eq!(
+            hit.recipe_id.as_ref().map(ToString::to_string).as_deref(),
+            Some("recipe1")
+        );
+        assert_eq!(hit.source, MockSource::Example);
+    }
+}
@@ -5,12 +5,24 @@
 //! not a value, use [ContentType]. If you want to parse dynamically based on
 //! the response's metadata, use [ContentType::parse_response].
 
-use crate::http::Response;
+use crate::{
+    http::{EventStreamParser, Response, ServerEvent},
+    util::ResultExt,
+};
 use anyhow::{anyhow, Context};
 use derive_more::{Deref, Display, From};
 use regex::Regex;
 use serde::{de::IntoDeserializer, Deserialize, Serialize};
-use std::{borrow::Cow, ffi::OsStr, fmt::Debug, path::Path, sync::OnceLock};
+use std::{
+    borrow::Cow,
+    ffi::OsStr,
+    fmt::{self, Debug},
+    iter,
+    path::Path,
+    slice,
+    sync::OnceLock,
+};
+use url::form_urlencoded;
 
 /// All supported content types. Each variant should have a corresponding
 /// implementation of [ResponseContent].
@@ -27,11 +39,21 @@ pub enum ContentType {
     // most obvious/user-friendly value. MIME types are implemented
     // separately.
     Json,
+    #[serde(alias = "yml")]
+    Yaml,
+    /// `application/x-www-form-urlencoded`
+    Form,
+    Csv,
+    Tsv,
+    /// `text/event-stream` (Server-Sent Events)
+    EventStream,
 }
 
 /// A response content type that we know how to parse. This is defined as a
 /// trait rather than an enum because it breaks apart the logic more clearly.
-pub trait ResponseContent: Debug + Display {
+///
+/// Content is `Send + Sync` so it can be parsed/queried off the main thread.
+pub trait ResponseContent: Debug + Display + Send + Sync {
     /// Get the type of this content
     fn content_type(&self) -> ContentType;
 
@@ -49,6 +71,13 @@ pub trait ResponseContent: Debug + Display {
     /// querying intenally, so everything needs to be convertible to/from JSON.
     fn to_json(&self) -> Cow<'_, serde_json::Value>;
 
+    /// Get the content as a table, if it's tabular. Returns the header row and
+    /// the data rows. This is used to render a table in the TUI instead of
+    /// text.
+    fn as_table(&self) -> Option<(&[String], &[Vec<String>])> {
+        None
+    }
+
     /// Facilitate downcasting generic parsed bodies to concrete types for tests
     #[cfg(test)]
     fn as_any(&self) -> &dyn std::any::Any;
@@ -81,6 +110,401 @@ impl ResponseContent for Json {
     }
 }
 
+#[derive(Debug, PartialEq)]
+pub struct Yaml(serde_yaml::Value);
+
+impl ResponseContent for Yaml {
+    fn content_type(&self) -> ContentType {
+        ContentType::Yaml
+    }
+
+    fn parse(body: &[u8]) -> anyhow::Result<Self> {
+        Ok(Self(serde_yaml::from_slice(body)?))
+    }
+
+    fn prettify(&self) -> String {
+        // YAML is already pretty
+        self.to_string()
+    }
+
+    fn to_json(&self) -> Cow<'_, serde_json::Value> {
+        // YAML is a superset of JSON, so this can only fail for values that
+        // JSON can't represent, such as non-scalar map keys. In that case
+        // there's not much we can do, so just give them nothing
+        Cow::Owned(
+            serde_json::to_value(&self.0)
+                .context("Error converting YAML to JSON")
+                .traced()
+                .unwrap_or_default(),
+        )
+    }
+
+    #[cfg(test)]
+    fn as_any(&self) -> &dyn std::any::Any {
+        self as &dyn std::any::Any
+    }
+}
+
+impl Display for Yaml {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // serde_yaml can't fail serializing its own Value type
+        write!(f, "{}", serde_yaml::to_string(&self.0).unwrap())
+    }
+}
+
+/// URL-encoded form data, as a list of key/value pairs. The same key may
+/// appear multiple times.
+#[derive(Debug, PartialEq)]
+pub struct Form(Vec<(String, String)>);
+
+impl ResponseContent for Form {
+    fn content_type(&self) -> ContentType {
+        ContentType::Form
+    }
+
+    fn parse(body: &[u8]) -> anyhow::Result<Self> {
+        // The decoder is lossy, so make sure it's valid UTF-8 up front to
+        // prevent silently mangling data
+        let body = std::str::from_utf8(body).context("Form is not UTF-8")?;
+        Ok(Self(
+            form_urlencoded::parse(body.as_bytes())
+                .into_owned()
+                .collect(),
+        ))
+    }
+
+    fn prettify(&self) -> String {
+        self.0
+            .iter()
+            .map(|(key, value)| format!("{key}={value}"))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn to_json(&self) -> Cow<'_, serde_json::Value> {
+        // Each key maps to its value. If a key appears multiple times, its
+        // values are grouped into an array
+        let mut map = serde_json::Map::new();
+        for (key, value) in &self.0 {
+            let value = serde_json::Value::String(value.clone());
+            match map.get_mut(key) {
+                None => {
+                    map.insert(key.clone(), value);
+                }
+                Some(serde_json::Value::Array(values)) => values.push(value),
+                Some(existing) => {
+                    *existing =
+                        serde_json::Value::Array(vec![existing.take(), value])
+                }
+            }
+        }
+        Cow::Owned(map.into())
+    }
+
+    #[cfg(test)]
+    fn as_any(&self) -> &dyn std::any::Any {
+        self as &dyn std::any::Any
+    }
+}
+
+impl Form {
+    /// Flatten a JSON value into form pairs. Forms don't support nesting, so
+    /// this is a bit lossy:
+    /// - Object fields become pairs. Array fields become one pair per element
+    /// - A root array is treated like an object keyed by index
+    /// - Nested objects/arrays are stringified as JSON
+    fn from_json(value: &serde_json::Value) -> Self {
+        let pairs = match value {
+            serde_json::Value::Object(map) => map
+                .iter()
+                .flat_map(|(key, value)| match value {
+                    serde_json::Value::Array(values) => values
+                        .iter()
+                        .map(|value| (key.clone(), stringify_json(value)))
+                        .collect(),
+                    value => vec![(key.clone(), stringify_json(value))],
+                })
+                .collect(),
+            serde_json::Value::Array(values) => values
+                .iter()
+                .enumerate()
+                .map(|(i, value)| (i.to_string(), stringify_json(value)))
+                .collect(),
+            value => vec![(String::new(), stringify_json(value))],
+        };
+        Self(pairs)
+    }
+}
+
+impl Display for Form {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let encoded = form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(&self.0)
+            .finish();
+        write!(f, "{encoded}")
+    }
+}
+
+/// Delimiter-separated values, with a header row. The delimiter is a generic
+/// so that each variant can have its own [ResponseContent] implementation.
+/// Use the [Csv] and [Tsv] aliases.
+#[derive(Debug, PartialEq)]
+pub struct Delimited<const DELIMITER: u8> {
+    header: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+/// Comma-separated values
+pub type Csv = Delimited<b','>;
+/// Tab-separated values
+pub type Tsv = Delimited<b'\t'>;
+
+impl<const DELIMITER: u8> ResponseContent for Delimited<DELIMITER> {
+    fn content_type(&self) -> ContentType {
+        if DELIMITER == b'\t' {
+            ContentType::Tsv
+        } else {
+            ContentType::Csv
+        }
+    }
+
+    fn parse(body: &[u8]) -> anyhow::Result<Self> {
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(DELIMITER)
+            // Allow ragged rows, we'll just pad/truncate them in the TUI
+            .flexible(true)
+            .from_reader(body);
+        let header = reader.headers()?.iter().map(String::from).collect();
+        let rows = reader
+            .records()
+            .map(|record| Ok(record?.iter().map(String::from).collect()))
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self { header, rows })
+    }
+
+    fn prettify(&self) -> String {
+        // Pad each column to the width of its widest cell
+        let mut widths: Vec<usize> = self
+            .header
+            .iter()
+            .map(|cell| cell.chars().count())
+            .collect();
+        for row in &self.rows {
+            for (i, cell) in row.iter().enumerate() {
+                let width = cell.chars().count();
+                match widths.get_mut(i) {
+                    Some(max) => *max = (*max).max(width),
+                    None => widths.push(width),
+                }
+            }
+        }
+
+        iter::once(&self.header)
+            .chain(&self.rows)
+            .map(|row| {
+                row.iter()
+                    .zip(&widths)
+                    .map(|(cell, width)| format!("{cell:width$}"))
+                    .collect::<Vec<_>>()
+                    .join("  ")
+                    .trim_end()
+                    .to_owned()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn to_json(&self) -> Cow<'_, serde_json::Value> {
+        // Each row becomes an object, keyed by the header. Cells without a
+        // header are dropped
+        let rows = self
+            .rows
+            .iter()
+            .map(|row| {
+                self.header
+                    .iter()
+                    .zip(row.iter().map(String::as_str).chain(iter::repeat("")))
+                    .map(|(key, value)| (key.clone(), value.into()))
+                    .collect::<serde_json::Map<_, _>>()
+                    .into()
+            })
+            .collect();
+        Cow::Owned(serde_json::Value::Array(rows))
+    }
+
+    fn as_table(&self) -> Option<(&[String], &[Vec<String>])> {
+        Some((&self.header, &self.rows))
+    }
+
+    #[cfg(test)]
+    fn as_any(&self) -> &dyn std::any::Any {
+        self as &dyn std::any::Any
+    }
+}
+
+impl<const DELIMITER: u8> Delimited<DELIMITER> {
+    /// Convert JSON into a table. Every object in the input array becomes a
+    /// row, and the header is the union of all the objects' keys. Non-object
+    /// elements go in a single column named `value`. A root object is treated
+    /// as a single row.
+    fn from_json(value: &serde_json::Value) -> Self {
+        let elements = match value {
+            serde_json::Value::Array(elements) => elements.as_slice(),
+            value => slice::from_ref(value),
+        };
+
+        let mut header: Vec<String> = Vec::new();
+        let mut column = |key: &str| match header.iter().position(|k| k == key)
+        {
+            Some(i) => i,
+            None => {
+                header.push(key.to_owned());
+                header.len() - 1
+            }
+        };
+        let cells: Vec<Vec<(usize, String)>> = elements
+            .iter()
+            .map(|element| match element {
+                serde_json::Value::Object(map) => map
+                    .iter()
+                    .map(|(key, value)| (column(key), stringify_json(value)))
+                    .collect(),
+                value => vec![(column("value"), stringify_json(value))],
+            })
+            .collect();
+
+        let rows = cells
+            .into_iter()
+            .map(|cells| {
+                let mut row = vec![String::new(); header.len()];
+                for (i, value) in cells {
+                    row[i] = value;
+                }
+                row
+            })
+            .collect();
+        Self { header, rows }
+    }
+}
+
+impl<const DELIMITER: u8> Display for Delimited<DELIMITER> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut writer = csv::WriterBuilder::new()
+            .delimiter(DELIMITER)
+            .flexible(true)
+            .from_writer(Vec::new());
+        for row in iter::once(&self.header).chain(&self.rows) {
+            writer.write_record(row).map_err(|_| fmt::Error)?;
+        }
+        let bytes = writer.into_inner().map_err(|_| fmt::Error)?;
+        // Writer input was all strings, so output is valid UTF-8
+        write!(f, "{}", String::from_utf8_lossy(&bytes))
+    }
+}
+
+/// A Server-Sent Events stream, parsed into its events
+#[derive(Debug, PartialEq)]
+pub struct EventStream {
+    events: Vec<ServerEvent>,
+    /// Table representation of the events, so it can be shown in the TUI
+    table: (Vec<String>, Vec<Vec<String>>),
+}
+
+impl ResponseContent for EventStream {
+    fn content_type(&self) -> ContentType {
+        ContentType::EventStream
+    }
+
+    fn parse(body: &[u8]) -> anyhow::Result<Self> {
+        Ok(EventStreamParser::parse(body).into())
+    }
+
+    fn prettify(&self) -> String {
+        self.to_string()
+    }
+
+    fn to_json(&self) -> Cow<'_, serde_json::Value> {
+        // ServerEvent only contains strings, so this can't fail
+        Cow::Owned(serde_json::to_value(&self.events).unwrap())
+    }
+
+    fn as_table(&self) -> Option<(&[String], &[Vec<String>])> {
+        Some((&self.table.0, &self.table.1))
+    }
+
+    #[cfg(test)]
+    fn as_any(&self) -> &dyn std::any::Any {
+        self as &dyn std::any::Any
+    }
+}
+
+impl EventStream {
+    /// Column headers for the table view of an event stream
+    pub const TABLE_HEADER: [&'static str; 3] = ["Event", "ID", "Data"];
+
+    /// Get a single event as a table row
+    pub fn table_row(event: &ServerEvent) -> Vec<String> {
+        vec![
+            event.event.clone(),
+            event.id.clone().unwrap_or_default(),
+            event.data.clone(),
+        ]
+    }
+
+    /// Convert JSON into events. Objects are mapped to events by their `event`,
+    /// `id` and `data` fields. Any other value becomes the data of a single
+    /// event.
+    fn from_json(value: &serde_json::Value) -> Self {
+        let elements = match value {
+            serde_json::Value::Array(elements) => elements.as_slice(),
+            value => slice::from_ref(value),
+        };
+        elements
+            .iter()
+            .map(|element| {
+                let field = |name| match element {
+                    serde_json::Value::Object(map) => {
+                        map.get(name).map(stringify_json)
+                    }
+                    _ => None,
+                };
+                ServerEvent {
+                    event: field("event")
+                        .unwrap_or_else(|| ServerEvent::DEFAULT_EVENT.into()),
+                    id: field("id"),
+                    data: match element {
+                        serde_json::Value::Object(_) => {
+                            field("data").unwrap_or_default()
+                        }
+                        value => stringify_json(value),
+                    },
+                }
+            })
+            .collect::<Vec<_>>()
+            .into()
+    }
+}
+
+impl From<Vec<ServerEvent>> for EventStream {
+    fn from(events: Vec<ServerEvent>) -> Self {
+        let header = Self::TABLE_HEADER.iter().map(|s| s.to_string()).collect();
+        let rows = events.iter().map(Self::table_row).collect();
+        Self {
+            events,
+            table: (header, rows),
+        }
+    }
+}
+
+impl Display for EventStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for event in &self.events {
+            write!(f, "{event}")?;
+        }
+        Ok(())
+    }
+}
+
 impl ContentType {
     /// Parse some content of this type. Return a dynamically dispatched content
     /// object.
@@ -90,6 +514,11 @@ impl ContentType {
     ) -> anyhow::Result<Box<dyn ResponseContent>> {
         match self {
             Self::Json => Ok(Box::new(Json::parse(content)?)),
+            Self::Yaml => Ok(Box::new(Yaml::parse(content)?)),
+            Self::Form => Ok(Box::new(Form::parse(content)?)),
+            Self::Csv => Ok(Box::new(Csv::parse(content)?)),
+            Self::Tsv => Ok(Box::new(Tsv::parse(content)?)),
+            Self::EventStream => Ok(Box::new(EventStream::parse(content)?)),
         }
     }
 
@@ -103,6 +532,14 @@ impl ContentType {
     ) -> Box<dyn ResponseContent> {
         match self {
             Self::Json => Box::new(Json(content.into_owned())),
+            // JSON is valid YAML, so this conversion can't fail
+            Self::Yaml => {
+                Box::new(Yaml(serde_yaml::to_value(content.as_ref()).unwrap()))
+            }
+            Self::Form => Box::new(Form::from_json(&content)),
+            Self::Csv => Box::new(Csv::from_json(&content)),
+            Self::Tsv => Box::new(Tsv::from_json(&content)),
+            Self::EventStream => Box::new(EventStream::from_json(&content)),
         }
     }
 
@@ -112,7 +549,13 @@ impl ContentType {
         response: &Response,
     ) -> anyhow::Result<Box<dyn ResponseContent>> {
         let content_type = Self::from_response(response)?;
-        content_type.parse_content(response.body.bytes())
+        // Parsers all expect UTF-8, so convert from the body's charset first.
+        // If it can't be decoded, give the parser the raw bytes so it can
+        // generate a meaningful error
+        match response.text() {
+            Some(text) => content_type.parse_content(text.as_bytes()),
+            None => content_type.parse_content(response.body.bytes()),
+        }
     }
 
     /// Parse the content type from a file's extension
@@ -139,9 +582,10 @@ impl ContentType {
 
     /// Parse the value of the content-type header and map it to a known content
     /// type
-    fn from_header(header_value: &str) -> anyhow::Result<Self> {
+    pub(super) fn from_header(header_value: &str) -> anyhow::Result<Self> {
         // unstable: use LazyLock https://github.com/rust-lang/rust/pull/121377
         static JSON_REGEX: OnceLock<Regex> = OnceLock::new();
+        static YAML_REGEX: OnceLock<Regex> = OnceLock::new();
 
         // Remove extra metadata from the header. It feels like there should be
         // a helper for this in hyper or reqwest but I couldn't find it.
@@ -151,22 +595,50 @@ impl ContentType {
             .map(|t| t.0)
             .unwrap_or(header_value);
 
-        let regex = JSON_REGEX.get_or_init(|| {
+        let json_regex = JSON_REGEX.get_or_init(|| {
             Regex::new("^application/(\\w+\\+)?json$").unwrap()
         });
+        // There's no single standard MIME type for YAML, so accept all the
+        // common ones
+        let yaml_regex = YAML_REGEX.get_or_init(|| {
+            Regex::new("^(application|text)/((\\w+\\+)|x-)?yaml$").unwrap()
+        });
 
-        if regex.is_match(content_type) {
+        if json_regex.is_match(content_type) {
             Ok(Self::Json)
+        } else if yaml_regex.is_match(content_type) {
+            Ok(Self::Yaml)
+        } else if content_type == "application/x-www-form-urlencoded" {
+            Ok(Self::Form)
+        } else if content_type == "text/csv" {
+            Ok(Self::Csv)
+        } else if content_type == "text/tab-separated-values" {
+            Ok(Self::Tsv)
+        } else if content_type == "text/event-stream" {
+            Ok(Self::EventStream)
         } else {
             Err(anyhow!("Unknown content type {header_value:?}"))
         }
     }
 }
 
+/// Convert a JSON value to a string for a format that only supports strings.
+/// Unlike `to_string`, this doesn't quote strings.
+fn stringify_json(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::Null => String::new(),
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{test_util::_Factori_Builder_Response, util::assert_err};
+    use crate::{
+        http::Body, test_util::_Factori_Builder_Response, util::assert_err,
+    };
+    use bytes::Bytes;
     use factori::create;
     use reqwest::header::{
         HeaderMap, HeaderValue, InvalidHeaderValue, CONTENT_TYPE,
@@ -185,6 +657,20 @@ mod tests {
     )]
     // Test extended MIME type
     #[case::json_extended("application/geo+json", ContentType::Json)]
+    #[case::yaml("application/yaml", ContentType::Yaml)]
+    #[case::yaml_x("application/x-yaml", ContentType::Yaml)]
+    #[case::yaml_text("text/yaml", ContentType::Yaml)]
+    #[case::yaml_text_x("text/x-yaml", ContentType::Yaml)]
+    #[case::yaml_extended("application/openapi+yaml", ContentType::Yaml)]
+    #[case::form("application/x-www-form-urlencoded", ContentType::Form)]
+    #[case::form_with_metadata(
+        "application/x-www-form-urlencoded; charset=utf-8",
+        ContentType::Form
+    )]
+    #[case::csv("text/csv", ContentType::Csv)]
+    #[case::csv_with_metadata("text/csv; header=present", ContentType::Csv)]
+    #[case::tsv("text/tab-separated-values", ContentType::Tsv)]
+    #[case::event_stream("text/event-stream", ContentType::EventStream)]
     fn test_try_from_mime(
         #[case] mime_type: &str,
         #[case] expected: ContentType,
@@ -198,6 +684,7 @@ mod tests {
     #[case::json_empty_extension("application/+json")]
     #[case::whitespace("application/ +json")] // Spaces are bad!
     #[case::unknown("text/html")]
+    #[case::yaml_bad_prefix("image/yaml")]
     fn test_try_from_mime_error(#[case] mime_type: &str) {
         assert_err!(
             ContentType::from_header(mime_type),
@@ -205,13 +692,21 @@ mod tests {
         );
     }
 
-    #[test]
-    fn test_from_extension() {
+    #[rstest]
+    #[case::json("turbo.json", ContentType::Json)]
+    #[case::yaml("turbo.yaml", ContentType::Yaml)]
+    #[case::yml("turbo.yml", ContentType::Yaml)]
+    #[case::csv("turbo.csv", ContentType::Csv)]
+    #[case::tsv("turbo.tsv", ContentType::Tsv)]
+    fn test_from_extension(#[case] path: &str, #[case] expected: ContentType) {
         assert_eq!(
-            ContentType::from_extension(Path::new("turbo.json")).unwrap(),
-            ContentType::Json
+            ContentType::from_extension(Path::new(path)).unwrap(),
+            expected
         );
+    }
 
+    #[test]
+    fn test_from_extension_error() {
         // Errors
         assert_err!(
             ContentType::from_extension(Path::new("no_extension")),
@@ -230,9 +725,64 @@ mod tests {
         "{\"hello\": \"goodbye\"}",
         Json(json!({"hello": "goodbye"}))
     )]
-    fn test_parse_body<T: ResponseContent + PartialEq + 'static>(
+    #[case::yaml(
+        "application/yaml",
+        "hello: goodbye",
+        Yaml(serde_yaml::to_value(json!({"hello": "goodbye"})).unwrap())
+    )]
+    #[case::form(
+        "application/x-www-form-urlencoded",
+        "access_token=abc%20123&scope=read&scope=write",
+        Form(vec![
+            ("access_token".into(), "abc 123".into()),
+            ("scope".into(), "read".into()),
+            ("scope".into(), "write".into()),
+        ])
+    )]
+    #[case::csv(
+        "text/csv",
+        "name,count\nwidget,3\n\"gadget, large\",10\n",
+        Csv {
+            header: vec!["name".into(), "count".into()],
+            rows: vec![
+                vec!["widget".into(), "3".into()],
+                vec!["gadget, large".into(), "10".into()],
+            ],
+        }
+    )]
+    #[case::tsv(
+        "text/tab-separated-values",
+        "name\tcount\nwidget\t3\n",
+        Tsv {
+            header: vec!["name".into(), "count".into()],
+            rows: vec![vec!["widget".into(), "3".into()]],
+        }
+    )]
+    #[case::event_stream(
+        "text/event-stream",
+        "event: update\nid: 1\ndata: hello\n\n",
+        EventStream::from(vec![ServerEvent {
+            event: "update".into(),
+            id: Some("1".into()),
+            data: "hello".into(),
+        }])
+    )]
+    #[case::charset(
+        "application/json; charset=iso-8859-1",
+        Bytes::from_static(b"{\"caf\xe9\": \"cr\xe8me\"}"),
+        Json(json!({"café": "crème"}))
+    )]
+    #[case::bom(
+        "application/json",
+        Bytes::from_static(b"\xef\xbb\xbf{\"hello\": \"goodbye\"}"),
+        Json(json!({"hello": "goodbye"}))
+    )]
+    fn test_parse_body<
+        B: Into<Body>,
+        T: ResponseContent + PartialEq + 'static,
+    >(
         #[case] content_type: &str,
-        #[case] body: String,
+        #[case] body: B,
         #[case] expected: T,
     ) {
         let response = create!(
@@ -264,11 +814,22 @@ mod tests {
         "not json!",
         "expected ident"
     )]
+    #[case::invalid_yaml(
+        Some("application/yaml"),
+        "{not: yaml",
+        "did not find expected"
+    )]
+    #[case::invalid_form(
+        Some("application/x-www-form-urlencoded"),
+        Bytes::from_static(b"\xc3\x28"),
+        "Form is not UTF-8"
+    )]
     fn test_parse_body_error<
         T: TryInto<HeaderValue, Error = InvalidHeaderValue>,
+        B: Into<Body>,
     >(
         #[case] content_type: Option<T>,
-        #[case] body: String,
+        #[case] body: B,
         #[case] expected_error: &str,
     ) {
         let headers = match content_type {
@@ -279,6 +840,47 @@ mod tests {
         assert_err!(ContentType::parse_response(&response), expected_error);
     }
 
+    /// Test conversions between CSV and JSON
+    #[test]
+    fn test_csv_json() {
+        let csv = Csv::parse(b"name,count\nwidget,3\ngadget\n").unwrap();
+        assert_eq!(
+            csv.to_json().into_owned(),
+            json!([
+                {"name": "widget", "count": "3"},
+                {"name": "gadget", "count": ""},
+            ])
+        );
+
+        // Heterogeneous elements get merged into one header
+        let csv = Csv::from_json(&json!([{"a": 1, "b": "x"}, {"c": null}, 2]));
+        assert_eq!(csv.to_string(), "a,b,c,value\n1,x,,\n,,,\n,,,2\n");
+    }
+
+    #[test]
+    fn test_csv_prettify() {
+        let csv = Csv::parse(b"name,count\nwidget,3\ngadget,10\n").unwrap();
+        assert_eq!(csv.prettify(), "name    count\nwidget  3\ngadget  10");
+    }
+
+    #[test]
+    fn test_event_stream_json() {
+        let stream =
+            EventStream::parse(b"event: update\nid: 1\ndata: a\n\ndata: b\n\n")
+                .unwrap();
+        assert_eq!(
+            stream.to_json().into_owned(),
+            json!([
+                {"event": "update", "id": "1", "data": "a"},
+                {"event": "message", "id": "1", "data": "b"},
+            ])
+        );
+
+        let stream =
+            EventStream::from_json(&json!([{"event": "ping", "data": 3}, 2]));
+        assert_eq!(stream.to_string(), "event: ping\ndata: 3\n\ndata: 2\n\n");
+    }
+
     /// Create header map with the given value for the content-type header
     fn headers(
         content_type: impl TryInto<HeaderValue, Error = InvalidHeaderValue>,
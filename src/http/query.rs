@@ -41,6 +41,14 @@ impl Query {
         content_type.parse_json(Cow::Owned(queried))
     }
 
+    /// Apply a query to a JSON value, returning a reference to each result
+    pub fn query_values<'a>(
+        &self,
+        value: &'a serde_json::Value,
+    ) -> Vec<&'a serde_json::Value> {
+        self.0.query(value).all()
+    }
+
     /// Apply a query to some content, returning a string. The query should
     /// return a single result. If it's a scalar, that will be stringified. If
     /// it's an array/object, it'll be converted back into its input format,
@@ -75,7 +83,10 @@ impl Query {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{http::Json, util::assert_err};
+    use crate::{
+        http::{ContentType, Json},
+        util::assert_err,
+    };
     use rstest::rstest;
     use serde_json::json;
 
@@ -84,6 +95,21 @@ mod tests {
     #[case::string("$.test", json(json!({"test": "hi!"})), "hi!")]
     #[case::int("$.test", json(json!({"test": 3})), "3")]
     #[case::bool("$.test", json(json!({"test": true})), "true")]
+    #[case::yaml_object(
+        "$.test",
+        ContentType::Yaml.parse_json(Cow::Owned(json!({"test": {"a": 1}}))),
+        "a: 1\n"
+    )]
+    #[case::form_field(
+        "$.scope",
+        ContentType::Form.parse_content(b"scope=read&token=abc").unwrap(),
+        "read"
+    )]
+    #[case::form_array(
+        "$.scope",
+        ContentType::Form.parse_content(b"scope=read&scope=write").unwrap(),
+        "0=read&1=write"
+    )]
     fn test_query_to_string(
         #[case] query: &str,
         #[case] content: Box<dyn ResponseContent>,
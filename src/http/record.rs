@@ -1,8 +1,11 @@
 //! HTTP-related data types
 
 use crate::{
-    collection::{ProfileId, RecipeId},
-    http::{ContentType, ResponseContent},
+    collection::{HookCommand, ProfileId, RecipeId},
+    http::{
+        charset, ContentType, ProxyConfig, RequestTimings, ResponseContent,
+        RetryPolicy, UnixTarget,
+    },
     util::ResultExt,
 };
 use anyhow::Context;
@@ -10,6 +13,7 @@ use bytes::Bytes;
 use bytesize::ByteSize;
 use chrono::{DateTime, Duration, Utc};
 use derive_more::{Display, From};
+use encoding_rs::{Encoding, UTF_8};
 use indexmap::IndexMap;
 use reqwest::{
     header::{self, HeaderMap, HeaderValue},
@@ -17,7 +21,9 @@ use reqwest::{
 };
 use serde::{Deserialize, Serialize};
 use std::{
+    borrow::Cow,
     fmt::{Debug, Write},
+    path::PathBuf,
     sync::Arc,
 };
 use thiserror::Error;
@@ -41,8 +47,10 @@ pub struct RequestBuildError {
 #[derive(Debug, Error)]
 #[error("Error executing request {}", .request.id)]
 pub struct RequestError {
+    /// This is usually a reqwest error, but can also be an error loading
+    /// client configuration such as TLS certificates
     #[source]
-    pub error: reqwest::Error,
+    pub error: anyhow::Error,
     /// The request that caused all this ruckus
     pub request: Arc<Request>,
     /// When was the request launched?
@@ -72,7 +80,7 @@ impl Default for RequestId {
 /// A complete request+response pairing. This is generated by
 /// [HttpEngine::send](super::HttpEngine::send) when a response is received
 /// successfully for a sent request.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct RequestRecord {
     /// ID to uniquely refer to this record. Useful for historical records.
     pub id: RequestId,
@@ -85,6 +93,37 @@ pub struct RequestRecord {
     pub start_time: DateTime<Utc>,
     /// When did we finish receiving the *entire* response?
     pub end_time: DateTime<Utc>,
+    /// How many times was the request sent to get this response? This is
+    /// more than 1 if it was retried
+    pub attempts: u32,
+    /// How long each phase of the (final) request took. `None` for records
+    /// from before timings were captured
+    pub timings: Option<RequestTimings>,
+    /// Redirects that were followed to get the response, in order. The
+    /// response is from the URL that the last hop pointed to.
+    pub redirects: Vec<RedirectHop>,
+    /// Notes attached to the record by the post-response hook
+    pub annotations: Vec<String>,
+}
+
+/// One hop in a redirect chain: a response that sent us somewhere else. The
+/// body of the response is discarded.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RedirectHop {
+    /// URL that was requested
+    pub url: Url,
+    #[serde(with = "serde_status_code")]
+    pub status: StatusCode,
+    /// Response headers, including `Location` and any `Set-Cookie`
+    #[serde(with = "serde_header_map")]
+    pub headers: HeaderMap,
+}
+
+impl RedirectHop {
+    /// Get the `Location` header, i.e. where this hop redirected to
+    pub fn location(&self) -> Option<&str> {
+        self.headers.get(header::LOCATION)?.to_str().ok()
+    }
 }
 
 impl RequestRecord {
@@ -119,9 +158,74 @@ pub struct Request {
     pub headers: HeaderMap,
     /// Body content as bytes. This should be decoded as needed
     pub body: Option<Bytes>,
+    /// Should the response body be decompressed? Taken from the recipe. If
+    /// `None`, the HTTP engine will use the global config
+    #[serde(default)]
+    pub decompress: Option<bool>,
+    /// Proxy override, taken from the profile. If `None`, the HTTP engine
+    /// will use the global config
+    #[serde(default)]
+    pub proxy: Option<ProxyConfig>,
+    /// URL of the proxy the request was actually sent through, with the
+    /// password removed. Set by the HTTP engine at send time, so history
+    /// isn't affected by later config changes
+    #[serde(default)]
+    pub sent_proxy: Option<Url>,
+    /// How to retry the request if it fails. Resolved from the recipe, its
+    /// folders, and the collection
+    #[serde(default)]
+    pub retry: Option<RetryPolicy>,
+    /// Stream the response body to this file, instead of loading it into
+    /// memory. Taken from the recipe, or the CLI's `--output` flag
+    #[serde(default)]
+    pub download: Option<PathBuf>,
+    /// Command to run just before the request is sent, which can modify it.
+    /// Resolved from the recipe, its folders, and the collection. This runs
+    /// at send time rather than build time, so previews of the request (e.g.
+    /// a dry run) don't trigger it
+    #[serde(default)]
+    pub pre_request_hook: Option<HookCommand>,
+    /// Command to run once a response is received. Resolved the same way as
+    /// the pre-request hook
+    #[serde(default)]
+    pub post_response_hook: Option<HookCommand>,
 }
 
 impl Request {
+    /// Copy this request under a new ID, so it can be sent again as a
+    /// separate request
+    pub fn duplicate(&self) -> Self {
+        Self {
+            id: RequestId::new(),
+            profile_id: self.profile_id.clone(),
+            recipe_id: self.recipe_id.clone(),
+            method: self.method.clone(),
+            url: self.url.clone(),
+            headers: self.headers.clone(),
+            body: self.body.clone(),
+            decompress: self.decompress,
+            proxy: self.proxy.clone(),
+            sent_proxy: self.sent_proxy.clone(),
+            retry: self.retry.clone(),
+            download: self.download.clone(),
+            pre_request_hook: self.pre_request_hook.clone(),
+            post_response_hook: self.post_response_hook.clone(),
+        }
+    }
+
+    /// Get the proxy that this request will be sent through, given
+    /// the global proxy config. The profile's proxy takes priority, and
+    /// neither applies if the host is excluded by the `no_proxy` list.
+    pub fn effective_proxy<'a>(
+        &'a self,
+        global: Option<&'a ProxyConfig>,
+    ) -> Option<&'a ProxyConfig> {
+        self.proxy
+            .as_ref()
+            .or(global)
+            .filter(|proxy| proxy.applies_to(&self.url))
+    }
+
     /// Generate a cURL command equivalent to this request
     ///
     /// This only fails if one of the headers or body is binary and can't be
@@ -132,8 +236,17 @@ impl Request {
         // These writes are all infallible because we're writing to a string,
         // but use ? because it's shorter than unwrap().
         let method = &self.method;
-        let url = &self.url;
-        write!(&mut buf, "curl -X{method} --url '{url}'")?;
+        if let Some(target) = UnixTarget::from_url(&self.url) {
+            write!(
+                &mut buf,
+                "curl -X{method} --unix-socket '{}' --url 'http://localhost{}'",
+                target.socket.display(),
+                target.path_and_query
+            )?;
+        } else {
+            let url = &self.url;
+            write!(&mut buf, "curl -X{method} --url '{url}'")?;
+        }
 
         for (header, value) in &self.headers {
             let value =
@@ -165,13 +278,60 @@ impl Request {
 /// to the user. A simpler alternative to [reqwest::Response], because there's
 /// no way to access all resolved data on that type at once. Resolving the
 /// response body requires moving the response.
-#[derive(Debug, Serialize, Deserialize)]
+///
+/// Cloning is cheap-ish, because the body is reference counted.
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Response {
     #[serde(with = "serde_status_code")]
     pub status: StatusCode,
     #[serde(with = "serde_header_map")]
     pub headers: HeaderMap,
+    /// Body content, *after* decompression
     pub body: Body,
+    /// If the body was decompressed, this is its size in bytes *before*
+    /// decompression, i.e. the number of bytes received over the wire. `None`
+    /// if the body wasn't compressed (or decompression was disabled).
+    #[serde(default)]
+    pub encoded_size: Option<u64>,
+    /// If the body was streamed to a file, where it went. The body itself is
+    /// empty in this case, so it isn't loaded into memory or stored in history
+    #[serde(default)]
+    pub download: Option<Download>,
+}
+
+/// A response body that was written to a file instead of being kept in memory
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Download {
+    /// Absolute path of the file
+    pub path: PathBuf,
+    /// Number of bytes written
+    pub size: u64,
+}
+
+/// How much of a download has been written so far
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DownloadProgress {
+    /// Bytes received so far. This is counted *before* decompression, so it
+    /// can be compared to the total
+    pub written: u64,
+    /// Total size of the body, from `Content-Length`. `None` if the server
+    /// didn't say
+    pub total: Option<u64>,
+}
+
+impl std::fmt::Display for DownloadProgress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let written = ByteSize(self.written).to_string_as(false);
+        match self.total {
+            Some(total) if total > 0 => write!(
+                f,
+                "{written} / {} ({}%)",
+                ByteSize(total).to_string_as(false),
+                self.written * 100 / total
+            ),
+            _ => write!(f, "{written}"),
+        }
+    }
 }
 
 impl Response {
@@ -189,6 +349,52 @@ impl Response {
             .map(HeaderValue::as_bytes)
     }
 
+    /// Get the encoding declared by the `charset` parameter of the
+    /// `content-type` header, if any
+    pub fn charset(&self) -> Option<&'static Encoding> {
+        self.content_type().and_then(charset::from_content_type)
+    }
+
+    /// Get the encoding that will be used to decode the body as text. A byte
+    /// order mark takes priority, then the declared charset, then UTF-8.
+    pub fn encoding(&self) -> &'static Encoding {
+        charset::detect(self.body.bytes(), self.charset()).0
+    }
+
+    /// Decode the body as text, according to its encoding. Returns `None` if
+    /// the body isn't valid text in that encoding, e.g. if it's binary.
+    pub fn text(&self) -> Option<Cow<'_, str>> {
+        charset::decode_text(self.body.bytes(), self.charset())
+    }
+
+    /// Replace the body with new text. The text is encoded in the declared
+    /// charset, so the body still decodes according to the headers. The new
+    /// body wasn't received over the wire, so the encoded size no longer
+    /// applies.
+    pub fn set_body_text(&mut self, text: &str) {
+        let encoding = self.charset().unwrap_or(UTF_8);
+        let (bytes, _, _) = encoding.encode(text);
+        self.body = Body::new(Bytes::copy_from_slice(&bytes));
+        self.encoded_size = None;
+    }
+
+    /// Get the size of the body as it was received over the wire. If the body
+    /// was compressed, this will be smaller than the body itself.
+    pub fn wire_size(&self) -> ByteSize {
+        self.encoded_size
+            .map(ByteSize)
+            .unwrap_or_else(|| self.size())
+    }
+
+    /// Size of the body. For downloads, this is the size of the file rather
+    /// than the (empty) in-memory body
+    pub fn size(&self) -> ByteSize {
+        match &self.download {
+            Some(download) => ByteSize(download.size),
+            None => self.body.size(),
+        }
+    }
+
     /// Make the response body pretty, if possible. This fails if the response
     /// has an unknown content-type, or if the body doesn't parse according to
     /// the content-type.
@@ -199,7 +405,7 @@ impl Response {
 
 /// HTTP response body. Content is stored as bytes to support non-text content.
 /// Should be converted to text only as needed
-#[derive(Default, From, Serialize, Deserialize)]
+#[derive(Clone, Default, From, Serialize, Deserialize)]
 pub struct Body(Bytes);
 
 impl Body {
@@ -208,7 +414,7 @@ impl Body {
     }
 
     /// Raw content bytes
-    pub fn bytes(&self) -> &[u8] {
+    pub fn bytes(&self) -> &Bytes {
         &self.0
     }
 
@@ -217,7 +423,8 @@ impl Body {
         self.0.into()
     }
 
-    /// Get bytes as text, if valid UTF-8
+    /// Get bytes as text, if valid UTF-8. For response bodies, prefer
+    /// [Response::text], which respects the declared charset.
     pub fn text(&self) -> Option<&str> {
         std::str::from_utf8(&self.0).ok()
     }
@@ -370,4 +577,19 @@ mod tests {
             --data '{\"data\":\"value\"}'"
         );
     }
+
+    #[test]
+    fn test_to_curl_unix() {
+        let request = create!(
+            Request,
+            url: "unix:///var/run/docker.sock:/containers/json?all=true"
+                .parse()
+                .unwrap(),
+        );
+        assert_eq!(
+            request.to_curl().unwrap(),
+            "curl -XGET --unix-socket '/var/run/docker.sock' \
+            --url 'http://localhost/containers/json?all=true'"
+        );
+    }
 }
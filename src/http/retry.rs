@@ -0,0 +1,198 @@
+//! Automatic retries for failed requests. A retry policy can be defined on the
+//! collection, a folder, or a recipe. The nearest one to the recipe applies.
+
+use crate::collection::cereal;
+use chrono::{DateTime, Utc};
+use reqwest::{
+    header::{self, HeaderMap},
+    StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Settings for retrying a request that failed, or got a response with a
+/// status that indicates it might work if we try again
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetryPolicy {
+    /// Total number of attempts, *including* the first one
+    pub max_attempts: u32,
+    /// Retry responses with these status codes
+    pub statuses: Vec<u16>,
+    /// Retry these kinds of errors, when no response was received
+    pub errors: Vec<RetryErrorKind>,
+    /// Delay before the first retry. This doubles for each subsequent retry
+    #[serde(with = "cereal::serde_duration")]
+    pub backoff: Duration,
+    /// Upper bound on the delay between attempts. This also caps the delay
+    /// requested by a `Retry-After` header
+    #[serde(with = "cereal::serde_duration")]
+    pub max_backoff: Duration,
+    /// Randomize each delay to between half and all of its value, so many
+    /// clients don't retry in lockstep
+    pub jitter: bool,
+    /// Save every attempt that received a response to history, instead of
+    /// just the final one
+    pub store_attempts: bool,
+}
+
+/// A kind of error that can be retried
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RetryErrorKind {
+    /// Failed to connect to the server
+    Connect,
+    /// The request timed out
+    Timeout,
+}
+
+impl RetryPolicy {
+    /// Should we try again after getting a response with this status?
+    pub fn should_retry_status(&self, status: StatusCode) -> bool {
+        self.statuses.contains(&status.as_u16())
+    }
+
+    /// Should we try again after a request failed with this error?
+    pub fn should_retry_error(&self, error: &anyhow::Error) -> bool {
+        // Requests are sent by hyper, or reqwest if they go through a proxy
+        let (is_connect, is_timeout) =
+            if let Some(error) = error.downcast_ref::<hyper::Error>() {
+                (error.is_connect(), error.is_timeout())
+            } else if let Some(error) = error.downcast_ref::<reqwest::Error>() {
+                (error.is_connect(), error.is_timeout())
+            } else {
+                return false;
+            };
+        self.errors.iter().any(|kind| match kind {
+            RetryErrorKind::Connect => is_connect,
+            RetryErrorKind::Timeout => is_timeout,
+        })
+    }
+
+    /// How long to wait before the given retry? `retry` is 1 for the first
+    /// retry (i.e. the second attempt). If the failed response had a
+    /// `Retry-After` header, that takes priority over the backoff.
+    pub fn delay(&self, retry: u32, headers: Option<&HeaderMap>) -> Duration {
+        let delay = headers
+            .and_then(|headers| retry_after(headers, Utc::now()))
+            .unwrap_or_else(|| {
+                let backoff = self.backoff.saturating_mul(
+                    2u32.saturating_pow(retry.saturating_sub(1)),
+                );
+                if self.jitter {
+                    backoff.mul_f64(0.5 + fastrand::f64() / 2.0)
+                } else {
+                    backoff
+                }
+            });
+        delay.min(self.max_backoff)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            statuses: vec![429, 502, 503, 504],
+            errors: vec![RetryErrorKind::Connect, RetryErrorKind::Timeout],
+            backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+            jitter: true,
+            store_attempts: false,
+        }
+    }
+}
+
+/// Get the delay requested by a `Retry-After` header. The value can be a
+/// number of seconds, or an HTTP date
+fn retry_after(headers: &HeaderMap, now: DateTime<Utc>) -> Option<Duration> {
+    let value = headers.get(header::RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let time: DateTime<Utc> = httpdate::parse_http_date(value).ok()?.into();
+    // A date in the past means we can go right away
+    Some((time - now).to_std().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+    use rstest::rstest;
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(5),
+            jitter: false,
+            ..Default::default()
+        }
+    }
+
+    fn headers(retry_after: &'static str) -> HeaderMap {
+        [(header::RETRY_AFTER, HeaderValue::from_static(retry_after))]
+            .into_iter()
+            .collect()
+    }
+
+    #[rstest]
+    #[case::first(1, 1)]
+    #[case::second(2, 2)]
+    #[case::third(3, 4)]
+    #[case::capped(4, 5)]
+    #[case::overflow(100, 5)]
+    fn test_delay(#[case] retry: u32, #[case] expected_seconds: u64) {
+        assert_eq!(
+            policy().delay(retry, None),
+            Duration::from_secs(expected_seconds)
+        );
+    }
+
+    #[test]
+    fn test_delay_jitter() {
+        let policy = RetryPolicy {
+            jitter: true,
+            ..policy()
+        };
+        for _ in 0..100 {
+            let delay = policy.delay(3, None);
+            assert!(
+                delay >= Duration::from_secs(2)
+                    && delay <= Duration::from_secs(4),
+                "Delay {delay:?} out of range"
+            );
+        }
+    }
+
+    #[rstest]
+    #[case::seconds("3", Some(3))]
+    #[case::whitespace(" 2 ", Some(2))]
+    #[case::date("Wed, 21 Oct 2015 07:28:10 GMT", Some(10))]
+    #[case::date_past("Wed, 21 Oct 2015 07:27:00 GMT", Some(0))]
+    #[case::invalid("soon", None)]
+    fn test_retry_after(
+        #[case] value: &'static str,
+        #[case] expected_seconds: Option<u64>,
+    ) {
+        let now = "2015-10-21T07:28:00Z".parse().unwrap();
+        assert_eq!(
+            retry_after(&headers(value), now),
+            expected_seconds.map(Duration::from_secs)
+        );
+    }
+
+    /// `Retry-After` overrides the backoff, but is still capped
+    #[rstest]
+    #[case::override_backoff("3", 3)]
+    #[case::capped("60", 5)]
+    fn test_delay_retry_after(
+        #[case] value: &'static str,
+        #[case] expected_seconds: u64,
+    ) {
+        assert_eq!(
+            policy().delay(1, Some(&headers(value))),
+            Duration::from_secs(expected_seconds)
+        );
+    }
+}
@@ -0,0 +1,205 @@
+//! Server-Sent Events parsing. Follows the
+//! [event stream interpretation](https://html.spec.whatwg.org/multipage/server-sent-events.html#event-stream-interpretation)
+//! from the HTML spec, except the `retry` field is ignored because we never
+//! reconnect.
+
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display, Formatter};
+
+/// A single event received from an event stream
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ServerEvent {
+    /// Event type. `message` if the server didn't specify one
+    pub event: String,
+    /// The last event ID set by the stream, which may have been set by a
+    /// previous event
+    pub id: Option<String>,
+    /// Data lines, joined with newlines
+    pub data: String,
+}
+
+impl ServerEvent {
+    /// Event type used when the server doesn't give one
+    pub const DEFAULT_EVENT: &'static str = "message";
+}
+
+/// Serialize back to the wire format
+impl Display for ServerEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.event != Self::DEFAULT_EVENT {
+            writeln!(f, "event: {}", self.event)?;
+        }
+        if let Some(id) = &self.id {
+            writeln!(f, "id: {id}")?;
+        }
+        for line in self.data.split('\n') {
+            writeln!(f, "data: {line}")?;
+        }
+        writeln!(f)
+    }
+}
+
+/// Incremental event stream parser. Feed it bytes as they arrive, and it will
+/// spit out events as they're completed. Chunks can be split anywhere, even
+/// in the middle of a line or character.
+#[derive(Debug, Default)]
+pub struct EventStreamParser {
+    /// Bytes of the current line that haven't been terminated yet
+    line: Vec<u8>,
+    /// Did the previous chunk end with `\r`? If so, a leading `\n` in the
+    /// next chunk belongs to the same line break
+    trailing_cr: bool,
+    /// Has the start of the stream been processed? A leading BOM is skipped
+    started: bool,
+    /// Type of the pending event
+    event: Option<String>,
+    /// Data lines of the pending event
+    data: Vec<String>,
+    /// Last event ID received. This carries over to subsequent events
+    last_id: Option<String>,
+}
+
+impl EventStreamParser {
+    /// Parse an entire stream at once. Any trailing event that wasn't
+    /// terminated by a blank line is discarded
+    pub fn parse(bytes: &[u8]) -> Vec<ServerEvent> {
+        Self::default().feed(bytes)
+    }
+
+    /// Process the next chunk of the stream. Return all events that were
+    /// completed by this chunk
+    pub fn feed(&mut self, mut bytes: &[u8]) -> Vec<ServerEvent> {
+        if !self.started && !bytes.is_empty() {
+            self.started = true;
+            bytes = bytes.strip_prefix(b"\xef\xbb\xbf").unwrap_or(bytes);
+        }
+
+        let mut events = Vec::new();
+        for &byte in bytes {
+            let trailing_cr = self.trailing_cr;
+            self.trailing_cr = byte == b'\r';
+            match byte {
+                b'\n' if trailing_cr => {}
+                b'\r' | b'\n' => {
+                    let line = std::mem::take(&mut self.line);
+                    if let Some(event) =
+                        self.process_line(&String::from_utf8_lossy(&line))
+                    {
+                        events.push(event);
+                    }
+                }
+                _ => self.line.push(byte),
+            }
+        }
+        events
+    }
+
+    /// Process a single complete line. If the line completes an event, return
+    /// it
+    fn process_line(&mut self, line: &str) -> Option<ServerEvent> {
+        if line.is_empty() {
+            // Blank line dispatches the event. Events with no data are
+            // dropped
+            let event = self.event.take();
+            if self.data.is_empty() {
+                return None;
+            }
+            return Some(ServerEvent {
+                event: event
+                    .filter(|event| !event.is_empty())
+                    .unwrap_or_else(|| ServerEvent::DEFAULT_EVENT.into()),
+                id: self.last_id.clone(),
+                data: std::mem::take(&mut self.data).join("\n"),
+            });
+        }
+
+        let (field, value) = match line.split_once(':') {
+            // Comment
+            Some(("", _)) => return None,
+            Some((field, value)) => {
+                (field, value.strip_prefix(' ').unwrap_or(value))
+            }
+            None => (line, ""),
+        };
+        match field {
+            "event" => self.event = Some(value.to_owned()),
+            "data" => self.data.push(value.to_owned()),
+            "id" if !value.contains('\0') => {
+                self.last_id =
+                    Some(value.to_owned()).filter(|id| !id.is_empty())
+            }
+            _ => {}
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn event(event: &str, id: Option<&str>, data: &str) -> ServerEvent {
+        ServerEvent {
+            event: event.into(),
+            id: id.map(String::from),
+            data: data.into(),
+        }
+    }
+
+    #[rstest]
+    #[case::empty("", vec![])]
+    #[case::simple("data: hello\n\n", vec![event("message", None, "hello")])]
+    #[case::fields(
+        "event: update\nid: 1\ndata: a\ndata: b\n\n",
+        vec![event("update", Some("1"), "a\nb")]
+    )]
+    #[case::id_carries_over(
+        "id: 1\ndata: a\n\ndata: b\n\nid\ndata: c\n\n",
+        vec![
+            event("message", Some("1"), "a"),
+            event("message", Some("1"), "b"),
+            event("message", None, "c"),
+        ]
+    )]
+    #[case::comment(": keepalive\n\ndata: a\n\n", vec![event("message", None, "a")])]
+    #[case::no_space("data:a\n\n", vec![event("message", None, "a")])]
+    #[case::extra_space("data:  a\n\n", vec![event("message", None, " a")])]
+    #[case::no_colon("data\n\n", vec![event("message", None, "")])]
+    #[case::no_data("event: ping\n\n", vec![])]
+    #[case::unterminated("data: a\n\ndata: b\n", vec![event("message", None, "a")])]
+    #[case::crlf("data: a\r\n\r\ndata: b\r\r", vec![
+        event("message", None, "a"),
+        event("message", None, "b"),
+    ])]
+    #[case::bom("\u{feff}data: a\n\n", vec![event("message", None, "a")])]
+    fn test_parse(#[case] input: &str, #[case] expected: Vec<ServerEvent>) {
+        assert_eq!(EventStreamParser::parse(input.as_bytes()), expected);
+    }
+
+    /// Chunks can be split at any byte, including between `\r` and `\n` and
+    /// in the middle of a multi-byte character
+    #[test]
+    fn test_feed_split() {
+        let input = "event: café\r\ndata: a\r\n\r\ndata: b\n\n".as_bytes();
+        let expected =
+            vec![event("café", None, "a"), event("message", None, "b")];
+        for i in 0..input.len() {
+            let mut parser = EventStreamParser::default();
+            let mut events = parser.feed(&input[..i]);
+            events.extend(parser.feed(&input[i..]));
+            assert_eq!(events, expected, "split at {i}");
+        }
+    }
+
+    #[test]
+    fn test_display() {
+        let input = "event: update\nid: 1\ndata: a\ndata: b\n\n";
+        let events = EventStreamParser::parse(input.as_bytes());
+        assert_eq!(events[0].to_string(), input);
+        assert_eq!(
+            event("message", None, "hello").to_string(),
+            "data: hello\n\n"
+        );
+    }
+}
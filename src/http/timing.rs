@@ -0,0 +1,464 @@
+//! Phase timings for requests. Connections are made by a custom connector,
+//! which times the DNS lookup, TCP connect, and TLS handshake. The remaining
+//! phases are timed around the request/response life cycle.
+
+use hyper::{
+    client::{
+        connect::{dns::Name, Connected, Connection},
+        HttpConnector,
+    },
+    service::Service,
+    Uri,
+};
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use rustls::{ClientConfig, ServerName};
+use serde::{Deserialize, Serialize};
+use std::{
+    cell::Cell,
+    error::Error,
+    future::Future,
+    io,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    net::TcpStream,
+};
+use tokio_rustls::{client::TlsStream, TlsConnector};
+
+tokio::task_local! {
+    /// Connection timings for the request being sent in the current task. The
+    /// resolver and connector are shared by all requests on a client, so this
+    /// is how we attribute a connection to its request. A connection made in
+    /// a background task (e.g. a pooled connection won the race) isn't
+    /// attributed to anything.
+    static CONNECTION: Cell<ConnectionTimings>;
+}
+
+/// How long each phase of a request took, in the order they occur
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct RequestTimings {
+    /// DNS lookup for the server (or proxy). `None` if there was no lookup,
+    /// e.g. because a pooled connection was reused or the host is an IP
+    pub dns: Option<Duration>,
+    /// TCP connect to the server. `None` if a pooled connection was reused,
+    /// or the request went through a proxy
+    pub connect: Option<Duration>,
+    /// TLS handshake with the server. `None` for plain HTTP, if a pooled
+    /// connection was reused, or if the request went through a proxy
+    pub tls: Option<Duration>,
+    /// From sending the request until the response headers arrived. For a
+    /// new connection, this includes the DNS lookup, TCP connect, and TLS
+    /// handshake.
+    pub waiting: Duration,
+    /// From receiving the headers until the end of the body
+    pub download: Duration,
+}
+
+/// Time spent opening a connection. Each phase is `None` if it didn't happen
+#[derive(Copy, Clone, Debug, Default)]
+pub(super) struct ConnectionTimings {
+    pub dns: Option<Duration>,
+    pub connect: Option<Duration>,
+    pub tls: Option<Duration>,
+}
+
+/// One bar in a timing waterfall
+#[derive(Debug, PartialEq)]
+pub struct TimingPhase {
+    pub name: &'static str,
+    /// Time from the start of the request until this phase began
+    pub offset: Duration,
+    pub duration: Duration,
+}
+
+impl RequestTimings {
+    /// Combine the connection timings with the rest of the phases
+    pub(super) fn new(
+        connection: ConnectionTimings,
+        waiting: Duration,
+        download: Duration,
+    ) -> Self {
+        Self {
+            dns: connection.dns,
+            connect: connection.connect,
+            tls: connection.tls,
+            waiting,
+            download,
+        }
+    }
+
+    /// Total time across all phases
+    pub fn total(&self) -> Duration {
+        self.waiting + self.download
+    }
+
+    /// Get each phase with its offset from the start of the request, for
+    /// drawing a waterfall. Phases that didn't occur are omitted. Opening the
+    /// connection is part of the wait, so those phases are split out of the
+    /// waiting phase.
+    pub fn phases(&self) -> Vec<TimingPhase> {
+        let mut phases = Vec::with_capacity(5);
+        let mut offset = Duration::ZERO;
+        let mut waiting = self.waiting;
+        for (name, duration) in [
+            ("DNS", self.dns),
+            ("Connect", self.connect),
+            ("TLS", self.tls),
+        ] {
+            if let Some(duration) = duration {
+                phases.push(TimingPhase {
+                    name,
+                    offset,
+                    duration,
+                });
+                offset += duration;
+                waiting = waiting.saturating_sub(duration);
+            }
+        }
+        phases.push(TimingPhase {
+            name: "Waiting",
+            offset,
+            duration: waiting,
+        });
+        offset += waiting;
+        phases.push(TimingPhase {
+            name: "Download",
+            offset,
+            duration: self.download,
+        });
+        phases
+    }
+}
+
+impl ConnectionTimings {
+    /// Add the timings of another connection, e.g. one opened to follow a
+    /// redirect
+    pub fn add(&mut self, other: Self) {
+        let add = |total: &mut Option<Duration>, duration: Option<Duration>| {
+            if let Some(duration) = duration {
+                *total.get_or_insert(Duration::ZERO) += duration;
+            }
+        };
+        add(&mut self.dns, other.dns);
+        add(&mut self.connect, other.connect);
+        add(&mut self.tls, other.tls);
+    }
+}
+
+impl TimingPhase {
+    /// Render this phase as a text bar, scaled so `total` fills `width`
+    /// characters. Every phase gets at least one character so short phases
+    /// are still visible.
+    pub fn bar(&self, total: Duration, width: usize) -> String {
+        if width == 0 {
+            return String::new();
+        }
+        let scale = |duration: Duration| {
+            if total.is_zero() {
+                0
+            } else {
+                (duration.as_secs_f64() / total.as_secs_f64() * width as f64)
+                    .round() as usize
+            }
+        };
+        let start = scale(self.offset).min(width.saturating_sub(1));
+        let length = scale(self.duration).clamp(1, width - start);
+        format!("{}{}", " ".repeat(start), "█".repeat(length))
+    }
+}
+
+/// Run a request future, and measure how long it took to open a connection
+/// (if it needed one)
+pub(super) async fn measure_connection<F: Future>(
+    future: F,
+) -> (F::Output, ConnectionTimings) {
+    CONNECTION
+        .scope(Cell::new(ConnectionTimings::default()), async {
+            let output = future.await;
+            (output, CONNECTION.with(Cell::get))
+        })
+        .await
+}
+
+/// Update the connection timings for the current task, if they're being
+/// measured
+fn record(update: impl FnOnce(&mut ConnectionTimings)) {
+    let _ = CONNECTION.try_with(|cell| {
+        let mut timings = cell.get();
+        update(&mut timings);
+        cell.set(timings);
+    });
+}
+
+/// A DNS resolver that uses the system resolver (same as reqwest's default),
+/// and reports each lookup's duration to the task that triggered it. This
+/// works for both reqwest and hyper clients.
+#[derive(Copy, Clone, Debug)]
+pub(super) struct TimingResolver;
+
+impl TimingResolver {
+    async fn lookup(name: Name) -> io::Result<Addrs> {
+        let start = Instant::now();
+        // Port is replaced by the connector
+        let addrs =
+            tokio::net::lookup_host(format!("{}:0", name.as_str())).await?;
+        let dns = start.elapsed();
+        record(|timings| timings.dns = Some(dns));
+        Ok(Box::new(addrs))
+    }
+}
+
+impl Resolve for TimingResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move { Ok(Self::lookup(name).await?) })
+    }
+}
+
+impl Service<Name> for TimingResolver {
+    type Response = Addrs;
+    type Error = io::Error;
+    type Future =
+        Pin<Box<dyn Future<Output = io::Result<Addrs>> + Send + 'static>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, name: Name) -> Self::Future {
+        Box::pin(Self::lookup(name))
+    }
+}
+
+/// A connector for hyper that wraps its [HttpConnector] with TLS, and
+/// reports how long the TCP connect and TLS handshake took to the task that
+/// triggered them
+#[derive(Clone)]
+pub(super) struct TimingConnector {
+    http: HttpConnector<TimingResolver>,
+    tls: TlsConnector,
+}
+
+impl TimingConnector {
+    pub fn new(tls: ClientConfig) -> Self {
+        let mut http = HttpConnector::new_with_resolver(TimingResolver);
+        // We handle https ourselves
+        http.enforce_http(false);
+        http.set_nodelay(true);
+        Self {
+            http,
+            tls: TlsConnector::from(Arc::new(tls)),
+        }
+    }
+}
+
+impl Service<Uri> for TimingConnector {
+    type Response = TimedStream;
+    type Error = Box<dyn Error + Send + Sync>;
+    type Future =
+        Pin<Box<dyn Future<Output = Result<TimedStream, Self::Error>> + Send>>;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.http.poll_ready(cx).map_err(Self::Error::from)
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        let mut http = self.http.clone();
+        let tls = self.tls.clone();
+        Box::pin(async move {
+            let https = uri.scheme() == Some(&hyper::http::uri::Scheme::HTTPS);
+            // Strip the brackets from IPv6 addresses
+            let host = uri
+                .host()
+                .unwrap_or_default()
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .to_owned();
+
+            // The connector does the DNS lookup too, which is timed
+            // separately by the resolver
+            let start = Instant::now();
+            let stream = http.call(uri).await?;
+            let elapsed = start.elapsed();
+            record(|timings| {
+                timings.connect = Some(
+                    elapsed.saturating_sub(timings.dns.unwrap_or_default()),
+                )
+            });
+            if !https {
+                return Ok(TimedStream::Tcp(stream));
+            }
+
+            let start = Instant::now();
+            let server_name = ServerName::try_from(host.as_str())?;
+            let stream = tls.connect(server_name, stream).await?;
+            let elapsed = start.elapsed();
+            record(|timings| timings.tls = Some(elapsed));
+            Ok(TimedStream::Tls(Box::new(stream)))
+        })
+    }
+}
+
+/// A connection opened by [TimingConnector]
+#[derive(Debug)]
+pub(super) enum TimedStream {
+    Tcp(TcpStream),
+    Tls(Box<TlsStream<TcpStream>>),
+}
+
+impl Connection for TimedStream {
+    fn connected(&self) -> Connected {
+        match self {
+            Self::Tcp(stream) => stream.connected(),
+            Self::Tls(stream) => stream.get_ref().0.connected(),
+        }
+    }
+}
+
+impl AsyncRead for TimedStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Tcp(stream) => Pin::new(stream).poll_read(cx, buf),
+            Self::Tls(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for TimedStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Self::Tcp(stream) => Pin::new(stream).poll_write(cx, buf),
+            Self::Tls(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Tcp(stream) => Pin::new(stream).poll_flush(cx),
+            Self::Tls(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Tcp(stream) => Pin::new(stream).poll_shutdown(cx),
+            Self::Tls(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn test_phases() {
+        let timings = RequestTimings {
+            dns: Some(ms(10)),
+            connect: Some(ms(5)),
+            tls: Some(ms(15)),
+            waiting: ms(50),
+            download: ms(20),
+        };
+        assert_eq!(timings.total(), ms(70));
+        assert_eq!(
+            timings.phases(),
+            [
+                TimingPhase {
+                    name: "DNS",
+                    offset: ms(0),
+                    duration: ms(10),
+                },
+                TimingPhase {
+                    name: "Connect",
+                    offset: ms(10),
+                    duration: ms(5),
+                },
+                TimingPhase {
+                    name: "TLS",
+                    offset: ms(15),
+                    duration: ms(15),
+                },
+                TimingPhase {
+                    name: "Waiting",
+                    offset: ms(30),
+                    duration: ms(20),
+                },
+                TimingPhase {
+                    name: "Download",
+                    offset: ms(50),
+                    duration: ms(20),
+                },
+            ]
+        );
+    }
+
+    /// Connection phases that didn't happen are omitted
+    #[rstest]
+    #[case::reused(None, None, None, &["Waiting", "Download"])]
+    #[case::http(Some(10), Some(5), None, &["DNS", "Connect", "Waiting", "Download"])]
+    #[case::ip(None, Some(5), Some(15), &["Connect", "TLS", "Waiting", "Download"])]
+    fn test_phases_omitted(
+        #[case] dns: Option<u64>,
+        #[case] connect: Option<u64>,
+        #[case] tls: Option<u64>,
+        #[case] expected: &[&str],
+    ) {
+        let timings = RequestTimings {
+            dns: dns.map(ms),
+            connect: connect.map(ms),
+            tls: tls.map(ms),
+            waiting: ms(50),
+            download: ms(20),
+        };
+        let names = timings
+            .phases()
+            .into_iter()
+            .map(|phase| phase.name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, expected);
+    }
+
+    #[rstest]
+    #[case::start(0, 40, "██")]
+    #[case::middle(40, 40, "  ██")]
+    #[case::end(80, 20, "    █")]
+    #[case::tiny(50, 0, "   █")]
+    fn test_bar(
+        #[case] offset: u64,
+        #[case] duration: u64,
+        #[case] expected: &str,
+    ) {
+        let phase = TimingPhase {
+            name: "",
+            offset: ms(offset),
+            duration: ms(duration),
+        };
+        assert_eq!(phase.bar(ms(100), 5), expected);
+    }
+}
@@ -0,0 +1,295 @@
+//! Client certificate (mTLS) and custom CA configuration. TLS settings are
+//! defined per host in the config.
+
+mod pkcs12;
+
+use crate::{
+    http::tls::pkcs12::pkcs12_to_pem,
+    template::{Template, TemplateContext},
+};
+use anyhow::{anyhow, Context};
+use rustls::{
+    client::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+    Certificate, ClientConfig, DigitallySignedStruct, OwnedTrustAnchor,
+    PrivateKey, RootCertStore, ServerName,
+};
+use rustls_pemfile::Item;
+use serde::{Deserialize, Serialize};
+use std::{fs, path::PathBuf, sync::Arc, time::SystemTime};
+
+/// TLS settings for requests to a single host
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TlsConfig {
+    /// Client certificate to present to the server. This can be a PEM file
+    /// (containing the certificate chain, and optionally the private key) or
+    /// a PKCS#12 archive (`.p12`/`.pfx`)
+    #[serde(default)]
+    pub certificate: Option<PathBuf>,
+    /// PEM private key for the client certificate. Only needed if the key
+    /// isn't already in the certificate file
+    #[serde(default)]
+    pub key: Option<PathBuf>,
+    /// Password for a PKCS#12 archive
+    #[serde(default)]
+    pub password: Option<Template>,
+    /// Additional root CA certificates (PEM) to trust for this host, on top
+    /// of the bundled Mozilla (webpki) roots
+    #[serde(default)]
+    pub ca_certificates: Vec<PathBuf>,
+}
+
+/// A client certificate chain and its private key
+struct Identity {
+    certificates: Vec<Certificate>,
+    key: PrivateKey,
+}
+
+impl TlsConfig {
+    /// Build a client TLS config with these settings. This loads all
+    /// certificate files from disk, and renders the PKCS#12 password if
+    /// needed. If `danger` is enabled, the server's certificate isn't verified
+    pub async fn client_config(
+        &self,
+        danger: bool,
+        template_context: &TemplateContext,
+    ) -> anyhow::Result<ClientConfig> {
+        let mut roots = default_roots();
+        for path in &self.ca_certificates {
+            fs::read(path)
+                .map_err(anyhow::Error::from)
+                .and_then(|pem| {
+                    for certificate in rustls_pemfile::certs(&mut &*pem)? {
+                        roots.add(&Certificate(certificate))?;
+                    }
+                    Ok(())
+                })
+                .with_context(|| {
+                    format!("Error loading CA certificate {path:?}")
+                })?;
+        }
+        let identity = self.identity(template_context).await?;
+        client_config(roots, identity, danger)
+    }
+
+    /// Load the client identity (certificate + private key), if any
+    async fn identity(
+        &self,
+        template_context: &TemplateContext,
+    ) -> anyhow::Result<Option<Identity>> {
+        let Some(path) = &self.certificate else {
+            return Ok(None);
+        };
+        let context = || format!("Error loading client certificate {path:?}");
+
+        let bytes = fs::read(path).with_context(context)?;
+        let mut pem = if is_pkcs12(path, &bytes) {
+            let password = match &self.password {
+                Some(password) => password
+                    .render(template_context)
+                    .await
+                    .context("Error rendering certificate password")?,
+                None => String::new(),
+            };
+            pkcs12_to_pem(&bytes, &password).with_context(context)?
+        } else {
+            bytes
+        };
+        if let Some(key_path) = &self.key {
+            let key = fs::read(key_path).with_context(|| {
+                format!("Error loading client key {key_path:?}")
+            })?;
+            pem.push(b'\n');
+            pem.extend(key);
+        }
+
+        Ok(Some(parse_identity(&pem).with_context(context)?))
+    }
+}
+
+/// Build a client TLS config with no host-specific settings. If `danger` is
+/// enabled, the server's certificate isn't verified
+pub fn default_client_config(danger: bool) -> ClientConfig {
+    // This can only fail with a client identity
+    client_config(default_roots(), None, danger)
+        .expect("Error building default TLS config")
+}
+
+/// Assemble a client TLS config. This matches what reqwest would build
+fn client_config(
+    roots: RootCertStore,
+    identity: Option<Identity>,
+    danger: bool,
+) -> anyhow::Result<ClientConfig> {
+    let builder = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots);
+    let mut config = match identity {
+        Some(identity) => builder
+            .with_client_auth_cert(identity.certificates, identity.key)?,
+        None => builder.with_no_client_auth(),
+    };
+    if danger {
+        config
+            .dangerous()
+            .set_certificate_verifier(Arc::new(NoVerification));
+    }
+    // We only speak HTTP/1.1
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    Ok(config)
+}
+
+/// Root CA certificates that are trusted for every host. These are the
+/// Mozilla roots bundled via `webpki-roots`, *not* the OS trust store, which
+/// matches what reqwest uses.
+fn default_roots() -> RootCertStore {
+    let mut roots = RootCertStore::empty();
+    roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(
+        |anchor| {
+            OwnedTrustAnchor::from_subject_spki_name_constraints(
+                anchor.subject,
+                anchor.spki,
+                anchor.name_constraints,
+            )
+        },
+    ));
+    roots
+}
+
+/// Parse a PEM bundle containing a certificate chain and a private key
+fn parse_identity(pem: &[u8]) -> anyhow::Result<Identity> {
+    let mut certificates = Vec::new();
+    let mut key = None;
+    for item in rustls_pemfile::read_all(&mut &*pem)? {
+        match item {
+            Item::X509Certificate(der) => certificates.push(Certificate(der)),
+            Item::RSAKey(der) | Item::PKCS8Key(der) | Item::ECKey(der) => {
+                key = key.or(Some(PrivateKey(der)));
+            }
+            _ => {}
+        }
+    }
+    let key = key.ok_or_else(|| anyhow!("No private key found"))?;
+    if certificates.is_empty() {
+        return Err(anyhow!("No certificate found"));
+    }
+    Ok(Identity { certificates, key })
+}
+
+/// A certificate verifier that accepts anything. Only used for hosts in
+/// `ignore_certificate_hosts`!
+struct NoVerification;
+
+impl ServerCertVerifier for NoVerification {
+    fn verify_server_cert(
+        &self,
+        _: &Certificate,
+        _: &[Certificate],
+        _: &ServerName,
+        _: &mut dyn Iterator<Item = &[u8]>,
+        _: &[u8],
+        _: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        _: &[u8],
+        _: &Certificate,
+        _: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        Ok(HandshakeSignatureValid::assertion())
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        _: &[u8],
+        _: &Certificate,
+        _: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        Ok(HandshakeSignatureValid::assertion())
+    }
+}
+
+/// Is this certificate file a PKCS#12 archive? Go by extension if we can,
+/// otherwise assume anything that isn't PEM is PKCS#12
+fn is_pkcs12(path: &std::path::Path, bytes: &[u8]) -> bool {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some(extension) => {
+            extension.eq_ignore_ascii_case("p12")
+                || extension.eq_ignore_ascii_case("pfx")
+        }
+        None => {
+            let start = bytes
+                .iter()
+                .position(|byte| !byte.is_ascii_whitespace())
+                .unwrap_or(bytes.len());
+            !bytes[start..].starts_with(b"-----BEGIN")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_util::*, util::assert_err};
+    use factori::create;
+    use rstest::rstest;
+
+    const DIR: &str = "./test_data/tls";
+
+    fn path(file: &str) -> PathBuf {
+        PathBuf::from(DIR).join(file)
+    }
+
+    fn tls(certificate: &str, key: Option<&str>, password: &str) -> TlsConfig {
+        TlsConfig {
+            certificate: Some(path(certificate)),
+            key: key.map(path),
+            password: Some(password.into()),
+            ca_certificates: vec![path("ca.crt")],
+        }
+    }
+
+    #[rstest]
+    #[case::pem_combined(tls("client.pem", None, ""))]
+    #[case::pem_separate_key(tls("client.crt", Some("client.key"), ""))]
+    #[case::pkcs12(tls("client.p12", None, "hunter2"))]
+    #[case::pkcs12_aes(tls("client_aes.p12", None, "hunter2"))]
+    #[tokio::test]
+    async fn test_client_config(#[case] tls: TlsConfig) {
+        let context = create!(TemplateContext);
+        let config = tls.client_config(false, &context).await.unwrap();
+        assert!(config.client_auth_cert_resolver.has_certs());
+    }
+
+    #[rstest]
+    #[case::missing_key(
+        tls("client.crt", None, ""),
+        "Error loading client certificate"
+    )]
+    #[case::wrong_password(
+        tls("client.p12", None, "wrong"),
+        "Error loading client certificate"
+    )]
+    #[case::missing_file(
+        tls("missing.pem", None, ""),
+        "Error loading client certificate"
+    )]
+    #[case::bad_ca(
+        TlsConfig {
+            ca_certificates: vec![path("missing.crt")],
+            ..tls("client.pem", None, "")
+        },
+        "Error loading CA certificate"
+    )]
+    #[tokio::test]
+    async fn test_client_config_error(
+        #[case] tls: TlsConfig,
+        #[case] expected_error: &str,
+    ) {
+        let context = create!(TemplateContext);
+        assert_err!(tls.client_config(false, &context).await, expected_error);
+    }
+}
@@ -0,0 +1,114 @@
+//! The client that actually sends requests. Requests are sent over
+//! connections that we open ourselves, so every phase of the connection can
+//! be timed. reqwest doesn't allow a custom connector, so it's only used for
+//! requests that go through a proxy.
+
+use crate::http::{
+    timing::{TimingConnector, TimingResolver},
+    ProxyConfig, USER_AGENT,
+};
+use bytes::Bytes;
+use reqwest::{
+    header::{self, HeaderValue},
+    redirect, ResponseBuilderExt,
+};
+use rustls::ClientConfig;
+use std::{env, sync::Arc};
+
+/// Environment variables that reqwest reads proxies from
+const PROXY_VARIABLES: &[&str] = &[
+    "ALL_PROXY",
+    "all_proxy",
+    "HTTP_PROXY",
+    "http_proxy",
+    "HTTPS_PROXY",
+    "https_proxy",
+];
+
+/// A client to send requests with. Clients are cheap to clone
+#[derive(Clone, Debug)]
+pub(super) enum Transport {
+    /// Connect to the server directly, timing each phase of the connection
+    Direct(hyper::Client<TimingConnector>),
+    /// Let reqwest connect, so it can go through a proxy. Only the DNS
+    /// lookup is timed
+    Proxy(reqwest::Client),
+}
+
+impl Transport {
+    /// Build a client. If no proxy is given, requests are sent directly
+    /// unless a proxy is defined in the environment. `bypass_proxy` ignores
+    /// the environment too.
+    pub fn new(
+        proxy: Option<&ProxyConfig>,
+        bypass_proxy: bool,
+        tls: ClientConfig,
+    ) -> reqwest::Result<Self> {
+        if proxy.is_none() && (bypass_proxy || !has_env_proxy()) {
+            let client = hyper::Client::builder()
+                .build::<_, hyper::Body>(TimingConnector::new(tls));
+            return Ok(Self::Direct(client));
+        }
+
+        let mut builder = reqwest::Client::builder()
+            .user_agent(USER_AGENT)
+            .dns_resolver(Arc::new(TimingResolver))
+            .redirect(redirect::Policy::none())
+            .use_preconfigured_tls(tls);
+        if let Some(proxy) = proxy {
+            builder = builder.proxy(proxy.to_reqwest()?);
+        }
+        Ok(Self::Proxy(builder.build()?))
+    }
+
+    /// Send a request, without following redirects
+    pub async fn execute(
+        &self,
+        request: reqwest::Request,
+    ) -> anyhow::Result<reqwest::Response> {
+        let client = match self {
+            Self::Direct(client) => client,
+            Self::Proxy(client) => return Ok(client.execute(request).await?),
+        };
+
+        // The fragment is never sent
+        let url = request.url().clone();
+        let mut uri = url.clone();
+        uri.set_fragment(None);
+        let mut builder = hyper::Request::builder()
+            .method(request.method().clone())
+            .uri(uri.as_str());
+        if let Some(headers) = builder.headers_mut() {
+            *headers = request.headers().clone();
+            headers
+                .entry(header::USER_AGENT)
+                .or_insert(HeaderValue::from_static(USER_AGENT));
+        }
+        // Our request bodies are always in memory
+        let body = request
+            .body()
+            .and_then(reqwest::Body::as_bytes)
+            .map(Bytes::copy_from_slice)
+            .unwrap_or_default();
+        let response = client.request(builder.body(body.into())?).await?;
+
+        // Convert back to reqwest's type. It needs the URL for redirects
+        let (parts, body) = response.into_parts();
+        let mut builder = hyper::Response::builder()
+            .status(parts.status)
+            .version(parts.version)
+            .url(url);
+        if let Some(headers) = builder.headers_mut() {
+            *headers = parts.headers;
+        }
+        Ok(builder.body(body)?.into())
+    }
+}
+
+/// Is a proxy set in the environment? If so, reqwest has to send requests
+/// that don't have their own proxy config
+fn has_env_proxy() -> bool {
+    PROXY_VARIABLES
+        .iter()
+        .any(|name| env::var_os(name).is_some_and(|value| !value.is_empty()))
+}
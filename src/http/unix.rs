@@ -0,0 +1,147 @@
+//! HTTP over Unix domain sockets, for local daemons such as the Docker engine.
+//! reqwest only speaks TCP, so these requests are sent over a hyper connection
+//! instead. The response is converted back to reqwest's type, so everything
+//! downstream of the transport is shared with TCP requests.
+
+use anyhow::Context;
+use bytes::Bytes;
+use reqwest::header::{self, HeaderValue};
+use std::path::PathBuf;
+use tokio::net::UnixStream;
+use tracing::{debug, info};
+use url::Url;
+
+/// URL scheme for socket requests, e.g.
+/// `unix:///var/run/docker.sock:/v1.43/containers/json`
+pub const UNIX_SCHEME: &str = "unix";
+
+/// Where to send a request over a Unix socket
+#[derive(Debug, PartialEq)]
+pub struct UnixTarget {
+    /// Path to the socket file
+    pub socket: PathBuf,
+    /// Path and query of the HTTP request sent through the socket
+    pub path_and_query: String,
+}
+
+impl UnixTarget {
+    /// Get the socket target for a `unix://` URL. The URL path holds the
+    /// socket path, then the HTTP path, separated by a colon. If there's no
+    /// HTTP path, it defaults to `/`. Returns `None` for any other scheme.
+    pub fn from_url(url: &Url) -> Option<Self> {
+        if url.scheme() != UNIX_SCHEME {
+            return None;
+        }
+        let (socket, path) =
+            url.path().split_once(':').unwrap_or((url.path(), ""));
+        let path = path.trim_start_matches('/');
+        let path_and_query = match url.query() {
+            Some(query) => format!("/{path}?{query}"),
+            None => format!("/{path}"),
+        };
+        Some(Self {
+            socket: socket.into(),
+            path_and_query,
+        })
+    }
+
+    /// Get the URL for the HTTP request sent through the socket. The host is
+    /// a placeholder, because the socket doesn't have one
+    pub fn http_url(&self) -> Url {
+        format!("http://localhost{}", self.path_and_query)
+            .parse()
+            .expect("Path is from a valid URL")
+    }
+
+    /// Send a request through the socket. The request should already have
+    /// all the headers that reqwest would add, other than `Host`.
+    pub async fn send(
+        &self,
+        request: reqwest::Request,
+    ) -> anyhow::Result<reqwest::Response> {
+        info!(socket = %self.socket.display(), "Connecting to Unix socket");
+        let stream =
+            UnixStream::connect(&self.socket).await.with_context(|| {
+                format!(
+                    "Error connecting to socket `{}`",
+                    self.socket.display()
+                )
+            })?;
+        let (mut sender, connection) =
+            hyper::client::conn::handshake(stream).await?;
+        // The connection has to be driven separately. It finishes once the
+        // response body has been read (or dropped)
+        tokio::spawn(async move {
+            if let Err(error) = connection.await {
+                debug!(%error, "Unix socket connection closed");
+            }
+        });
+
+        let mut builder = hyper::Request::builder()
+            .method(request.method().clone())
+            .uri(&self.path_and_query);
+        if let Some(headers) = builder.headers_mut() {
+            *headers = request.headers().clone();
+            // HTTP/1.1 requires a host, but the socket doesn't have one
+            headers
+                .entry(header::HOST)
+                .or_insert(HeaderValue::from_static("localhost"));
+        }
+        // Our request bodies are always in memory
+        let body = request
+            .body()
+            .and_then(reqwest::Body::as_bytes)
+            .map(Bytes::copy_from_slice)
+            .unwrap_or_default();
+        let response = sender
+            .send_request(builder.body(hyper::Body::from(body))?)
+            .await?;
+        Ok(response.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::http_path(
+        "unix:///var/run/docker.sock:/v1.43/containers/json",
+        "/var/run/docker.sock",
+        "/v1.43/containers/json"
+    )]
+    #[case::query(
+        "unix:///tmp/app.sock:/items?all=true",
+        "/tmp/app.sock",
+        "/items?all=true"
+    )]
+    #[case::no_http_path("unix:///tmp/app.sock", "/tmp/app.sock", "/")]
+    #[case::empty_http_path("unix:///tmp/app.sock:", "/tmp/app.sock", "/")]
+    #[case::relative_http_path(
+        "unix:///tmp/app.sock:items",
+        "/tmp/app.sock",
+        "/items"
+    )]
+    fn test_from_url(
+        #[case] url: &str,
+        #[case] expected_socket: &str,
+        #[case] expected_path: &str,
+    ) {
+        assert_eq!(
+            UnixTarget::from_url(&url.parse().unwrap()),
+            Some(UnixTarget {
+                socket: expected_socket.into(),
+                path_and_query: expected_path.into(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_from_url_tcp() {
+        assert_eq!(
+            UnixTarget::from_url(&"http://localhost/items".parse().unwrap()),
+            None
+        );
+    }
+}
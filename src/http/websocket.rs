@@ -0,0 +1,558 @@
+//! WebSocket sessions. A session is opened with an HTTP handshake built from a
+//! WebSocket recipe, then messages flow in both directions until either side
+//! closes the connection.
+
+use crate::{
+    http::{tls, ProxyConfig},
+    util::MaybeStr,
+};
+use anyhow::{anyhow, bail, Context};
+use base64::{prelude::BASE64_STANDARD, Engine};
+use bytes::Bytes;
+use futures::{SinkExt, StreamExt};
+use reqwest::header::HeaderMap;
+use rustls::{ClientConfig, ServerName};
+use std::{
+    fmt::{self, Display, Formatter},
+    sync::Arc,
+};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::TcpStream,
+    sync::mpsc,
+};
+use tokio_rustls::TlsConnector;
+use tokio_socks::{tcp::Socks5Stream, TargetAddr};
+use tokio_tungstenite::{
+    tungstenite::{
+        self, client::IntoClientRequest, protocol::frame::coding::CloseCode,
+    },
+    Connector,
+};
+use tracing::info;
+use url::{Host, Url};
+
+/// Max size of a proxy's response to a `CONNECT` request. This only has to
+/// fit the status line and a few headers
+const MAX_CONNECT_RESPONSE_SIZE: usize = 8192;
+
+/// A single message sent or received over a WebSocket. Control frames (ping,
+/// pong, close) are handled internally and never exposed.
+#[derive(Clone, Debug, PartialEq)]
+pub enum WebSocketMessage {
+    Text(String),
+    Binary(Bytes),
+}
+
+/// Something that happened during a WebSocket session
+#[derive(Clone, Debug, PartialEq)]
+pub enum WebSocketEvent {
+    /// The handshake succeeded and the connection is open
+    Connected,
+    /// A message was sent to the server
+    Sent(WebSocketMessage),
+    /// A message was received from the server
+    Received(WebSocketMessage),
+    /// The connection was closed, by either side. This is always the last
+    /// event of a successful session
+    Closed {
+        /// Reason given in the close frame, if any
+        reason: Option<String>,
+    },
+}
+
+/// Text is shown as-is, binary as a hex string unless it's valid UTF-8
+impl Display for WebSocketMessage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Text(text) => write!(f, "{text}"),
+            Self::Binary(bytes) => write!(f, "{}", MaybeStr(bytes)),
+        }
+    }
+}
+
+/// How to reach the server for a WebSocket session. These are the same
+/// settings that would be used for an HTTP request to the same host
+pub(super) struct WebSocketTransport {
+    /// Proxy to tunnel the connection through
+    pub proxy: Option<ProxyConfig>,
+    /// TLS config for `wss` connections
+    pub tls: ClientConfig,
+}
+
+/// A connection that a WebSocket session can run over. This is a plain TCP
+/// stream, unless we tunneled through an HTTPS proxy.
+trait Connection: AsyncRead + AsyncWrite + Send + Unpin {}
+
+impl<T: AsyncRead + AsyncWrite + Send + Unpin> Connection for T {}
+
+/// Get the URL for a WebSocket handshake. HTTP URLs are mapped to their
+/// WebSocket equivalents, so recipes can share a `host` with HTTP recipes
+pub fn websocket_url(url: &Url) -> anyhow::Result<Url> {
+    let scheme = match url.scheme() {
+        "http" | "ws" => "ws",
+        "https" | "wss" => "wss",
+        scheme => {
+            return Err(anyhow!(
+                "Unsupported scheme `{scheme}` for WebSocket URL `{url}`"
+            ))
+        }
+    };
+    let mut url = url.clone();
+    // This can only fail when switching between special and non-special
+    // schemes, and these are all special
+    let _ = url.set_scheme(scheme);
+    Ok(url)
+}
+
+/// Open a WebSocket connection and run the session until it closes. Each
+/// string received from `outgoing` is sent as a text message. When
+/// `outgoing` is closed (i.e. all senders are dropped), we ask the server to
+/// close the connection. Everything that happens is reported to `on_event`.
+///
+/// Returns an error if the handshake fails, or the connection is broken
+/// without being closed properly.
+pub(super) async fn run_session(
+    url: &Url,
+    headers: HeaderMap,
+    transport: WebSocketTransport,
+    mut outgoing: mpsc::UnboundedReceiver<String>,
+    mut on_event: impl FnMut(WebSocketEvent),
+) -> anyhow::Result<()> {
+    let url = websocket_url(url)?;
+    // This generates the handshake headers (key, version, etc.)
+    let mut request = url.as_str().into_client_request()?;
+    request.headers_mut().extend(headers);
+
+    let (mut stream, _) = async {
+        let connection = connect(&url, transport.proxy.as_ref()).await?;
+        let connector = Connector::Rustls(Arc::new(transport.tls));
+        tokio_tungstenite::client_async_tls_with_config(
+            request,
+            connection,
+            None,
+            Some(connector),
+        )
+        .await
+        .map_err(anyhow::Error::from)
+    }
+    .await
+    .with_context(|| format!("Error connecting to {url}"))?;
+    info!(%url, "WebSocket connected");
+    on_event(WebSocketEvent::Connected);
+
+    let mut reason = None;
+    let mut closing = false;
+    loop {
+        tokio::select! {
+            message = outgoing.recv(), if !closing => match message {
+                Some(text) => {
+                    stream
+                        .send(tungstenite::Message::Text(text.clone()))
+                        .await
+                        .context("Error sending message")?;
+                    on_event(WebSocketEvent::Sent(WebSocketMessage::Text(text)));
+                }
+                // Don't stop yet; wait for the server to acknowledge the close
+                None => {
+                    closing = true;
+                    stream
+                        .close(None)
+                        .await
+                        .context("Error closing connection")?;
+                }
+            },
+            message = stream.next() => match message {
+                Some(Ok(tungstenite::Message::Text(text))) => on_event(
+                    WebSocketEvent::Received(WebSocketMessage::Text(text)),
+                ),
+                Some(Ok(tungstenite::Message::Binary(bytes))) => on_event(
+                    WebSocketEvent::Received(WebSocketMessage::Binary(
+                        bytes.into(),
+                    )),
+                ),
+                // The close reply is queued automatically; keep reading until
+                // the stream ends so it gets flushed
+                Some(Ok(tungstenite::Message::Close(frame))) => {
+                    closing = true;
+                    reason = frame
+                        .filter(|frame| {
+                            frame.code != CloseCode::Normal
+                                || !frame.reason.is_empty()
+                        })
+                        .map(|frame| {
+                            if frame.reason.is_empty() {
+                                frame.code.to_string()
+                            } else {
+                                format!("{} {}", frame.code, frame.reason)
+                            }
+                        });
+                }
+                // Ping/pong are handled by tungstenite
+                Some(Ok(_)) => {}
+                None | Some(Err(tungstenite::Error::ConnectionClosed)) => {
+                    break
+                }
+                Some(Err(error)) => {
+                    return Err(error).context("Error reading from connection")
+                }
+            },
+        }
+    }
+
+    info!(%url, ?reason, "WebSocket closed");
+    on_event(WebSocketEvent::Closed { reason });
+    Ok(())
+}
+
+/// Open a connection to the server, tunneling through a proxy if given. The
+/// WebSocket handshake (and TLS handshake for `wss`) is done over the
+/// returned connection.
+async fn connect(
+    url: &Url,
+    proxy: Option<&ProxyConfig>,
+) -> anyhow::Result<Box<dyn Connection>> {
+    let (host, port) = host_port(url)?;
+    let Some(proxy) = proxy else {
+        return Ok(Box::new(TcpStream::connect((host.as_str(), port)).await?));
+    };
+
+    info!(%proxy, "Connecting through proxy");
+    let (proxy_host, proxy_port) = host_port(&proxy.url)?;
+    let credentials = proxy.credentials();
+    match proxy.url.scheme() {
+        "http" => {
+            let stream =
+                TcpStream::connect((proxy_host.as_str(), proxy_port)).await?;
+            let stream = http_connect(stream, url, credentials).await?;
+            Ok(Box::new(stream))
+        }
+        "https" => {
+            let stream =
+                TcpStream::connect((proxy_host.as_str(), proxy_port)).await?;
+            let connector =
+                TlsConnector::from(Arc::new(tls::default_client_config(false)));
+            let server_name = ServerName::try_from(proxy_host.as_str())?;
+            let stream = connector.connect(server_name, stream).await?;
+            let stream = http_connect(stream, url, credentials).await?;
+            Ok(Box::new(stream))
+        }
+        scheme @ ("socks5" | "socks5h") => {
+            let proxy_address = (proxy_host.as_str(), proxy_port);
+            // socks5h lets the proxy resolve the host
+            let target = if scheme == "socks5h" {
+                TargetAddr::Domain(host.as_str().into(), port)
+            } else {
+                let address = tokio::net::lookup_host((host.as_str(), port))
+                    .await?
+                    .next()
+                    .ok_or_else(|| anyhow!("No addresses found for {host}"))?;
+                TargetAddr::Ip(address)
+            };
+            let stream = match credentials {
+                Some((username, password)) => {
+                    Socks5Stream::connect_with_password(
+                        proxy_address,
+                        target,
+                        &username,
+                        &password,
+                    )
+                    .await?
+                }
+                None => Socks5Stream::connect(proxy_address, target).await?,
+            };
+            Ok(Box::new(stream.into_inner()))
+        }
+        scheme => bail!("Unsupported proxy scheme `{scheme}`"),
+    }
+}
+
+/// Ask an HTTP proxy to open a tunnel to the server, with a `CONNECT`
+/// request. Returns the stream once the tunnel is open.
+async fn http_connect<S: AsyncRead + AsyncWrite + Unpin>(
+    mut stream: S,
+    url: &Url,
+    credentials: Option<(String, String)>,
+) -> anyhow::Result<S> {
+    let host = url.host_str().unwrap_or_default();
+    let port = url.port_or_known_default().unwrap_or_default();
+    let mut request =
+        format!("CONNECT {host}:{port} HTTP/1.1\r\nHost: {host}:{port}\r\n");
+    if let Some((username, password)) = credentials {
+        let encoded = BASE64_STANDARD.encode(format!("{username}:{password}"));
+        request.push_str(&format!("Proxy-Authorization: Basic {encoded}\r\n"));
+    }
+    request.push_str("\r\n");
+    stream.write_all(request.as_bytes()).await?;
+
+    // Read one byte at a time, so we don't consume anything past the end of
+    // the response. That belongs to the tunnel.
+    let mut response = Vec::new();
+    while !response.ends_with(b"\r\n\r\n") {
+        if response.len() >= MAX_CONNECT_RESPONSE_SIZE {
+            bail!("Invalid response from proxy");
+        }
+        response.push(stream.read_u8().await?);
+    }
+    let status_line = String::from_utf8_lossy(&response);
+    let status_line = status_line.lines().next().unwrap_or_default();
+    let is_success = status_line
+        .split_whitespace()
+        .nth(1)
+        .is_some_and(|status| status.starts_with('2'));
+    if !is_success {
+        bail!("Proxy refused to connect: {status_line}");
+    }
+    Ok(stream)
+}
+
+/// Get the host and port to connect to for a URL. IPv6 addresses are
+/// returned without brackets
+fn host_port(url: &Url) -> anyhow::Result<(String, u16)> {
+    let host = match url.host() {
+        Some(Host::Domain(domain)) => domain.to_owned(),
+        Some(Host::Ipv4(address)) => address.to_string(),
+        Some(Host::Ipv6(address)) => address.to_string(),
+        None => bail!("URL `{url}` has no host"),
+    };
+    let port = url
+        .port_or_known_default()
+        .or_else(|| url.scheme().starts_with("socks5").then_some(1080))
+        .ok_or_else(|| anyhow!("URL `{url}` has no port"))?;
+    Ok((host, port))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use tokio::{net::TcpListener, task::JoinHandle};
+
+    /// Start a local server that echoes every message back, then closes
+    /// the connection with a reason after `close_after` messages
+    async fn echo_server(close_after: Option<usize>) -> Url {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (tcp_stream, _) = listener.accept().await.unwrap();
+            let mut stream =
+                tokio_tungstenite::accept_async(tcp_stream).await.unwrap();
+            let mut count = 0;
+            while let Some(Ok(message)) = stream.next().await {
+                if message.is_close() {
+                    continue;
+                }
+                stream.send(message).await.unwrap();
+                count += 1;
+                if Some(count) == close_after {
+                    let frame = tungstenite::protocol::CloseFrame {
+                        code: CloseCode::Away,
+                        reason: "bye".into(),
+                    };
+                    stream.close(Some(frame)).await.unwrap();
+                }
+            }
+        });
+        format!("http://{address}/socket").parse().unwrap()
+    }
+
+    /// Start a local HTTP proxy that answers a single `CONNECT` request with
+    /// the given status. If the tunnel is opened, traffic is forwarded until
+    /// either side closes. The task returns the request head it received.
+    async fn http_proxy(status: &'static str) -> (Url, JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url =
+            format!("http://user:hunter2@{}", listener.local_addr().unwrap())
+                .parse()
+                .unwrap();
+        let handle = tokio::spawn(async move {
+            let (mut client, _) = listener.accept().await.unwrap();
+            let mut head = Vec::new();
+            while !head.ends_with(b"\r\n\r\n") {
+                head.push(client.read_u8().await.unwrap());
+            }
+            let head = String::from_utf8(head).unwrap();
+            client
+                .write_all(format!("HTTP/1.1 {status}\r\n\r\n").as_bytes())
+                .await
+                .unwrap();
+            if status.starts_with('2') {
+                let target = head.split_whitespace().nth(1).unwrap();
+                let mut server = TcpStream::connect(target).await.unwrap();
+                let _ = tokio::io::copy_bidirectional(&mut client, &mut server)
+                    .await;
+            }
+            head
+        });
+        (url, handle)
+    }
+
+    /// Transport settings for a test session
+    fn transport(proxy: Option<&Url>) -> WebSocketTransport {
+        WebSocketTransport {
+            proxy: proxy.map(|url| ProxyConfig {
+                url: url.clone(),
+                username: None,
+                password: None,
+                no_proxy: vec![],
+            }),
+            tls: tls::default_client_config(false),
+        }
+    }
+
+    #[rstest]
+    #[case::http("http://localhost/a?b=c", "ws://localhost/a?b=c")]
+    #[case::https("https://localhost:3000", "wss://localhost:3000/")]
+    #[case::ws("ws://localhost", "ws://localhost/")]
+    #[case::wss("wss://localhost", "wss://localhost/")]
+    fn test_websocket_url(#[case] url: &str, #[case] expected: &str) {
+        let url = url.parse().unwrap();
+        assert_eq!(websocket_url(&url).unwrap().as_str(), expected);
+    }
+
+    #[test]
+    fn test_websocket_url_error() {
+        let url = "ftp://localhost".parse().unwrap();
+        assert_eq!(
+            websocket_url(&url).unwrap_err().to_string(),
+            "Unsupported scheme `ftp` for WebSocket URL `ftp://localhost/`"
+        );
+    }
+
+    /// Session is closed by the client (by closing the outgoing channel) or
+    /// by the server
+    #[rstest]
+    #[case::client_close(None, None)]
+    #[case::server_close(Some(1), Some("1001 bye"))]
+    #[tokio::test]
+    async fn test_session(
+        #[case] close_after: Option<usize>,
+        #[case] expected_reason: Option<&str>,
+    ) {
+        let url = echo_server(close_after).await;
+        let (sender, outgoing) = mpsc::unbounded_channel();
+        sender.send("hello".to_owned()).unwrap();
+
+        let hello = || WebSocketMessage::Text("hello".into());
+        let mut sender = Some(sender);
+        let mut events = Vec::new();
+        run_session(
+            &url,
+            HeaderMap::new(),
+            transport(None),
+            outgoing,
+            |event| {
+                // Closing the channel tells the session to close the connection
+                if close_after.is_none()
+                    && event == WebSocketEvent::Received(hello())
+                {
+                    sender = None;
+                }
+                events.push(event);
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            events,
+            vec![
+                WebSocketEvent::Connected,
+                WebSocketEvent::Sent(hello()),
+                WebSocketEvent::Received(hello()),
+                WebSocketEvent::Closed {
+                    reason: expected_reason.map(String::from)
+                },
+            ]
+        );
+    }
+
+    /// Handshake errors are returned
+    #[tokio::test]
+    async fn test_session_connect_error() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        // Close the port, so the connection is refused
+        drop(listener);
+
+        let (_sender, outgoing) = mpsc::unbounded_channel();
+        let error = run_session(
+            &url.parse().unwrap(),
+            HeaderMap::new(),
+            transport(None),
+            outgoing,
+            |_| {},
+        )
+        .await
+        .unwrap_err();
+        assert!(
+            error.to_string().starts_with("Error connecting to"),
+            "Unexpected error {error:?}"
+        );
+    }
+
+    /// The connection is tunneled through an HTTP proxy, with the proxy's
+    /// credentials
+    #[tokio::test]
+    async fn test_session_proxy() {
+        let url = echo_server(Some(1)).await;
+        let (proxy_url, proxy) = http_proxy("200 Connection established").await;
+        let (sender, outgoing) = mpsc::unbounded_channel();
+        sender.send("hello".to_owned()).unwrap();
+
+        let mut events = Vec::new();
+        run_session(
+            &url,
+            HeaderMap::new(),
+            transport(Some(&proxy_url)),
+            outgoing,
+            |event| events.push(event),
+        )
+        .await
+        .unwrap();
+
+        assert!(
+            events.contains(&WebSocketEvent::Received(WebSocketMessage::Text(
+                "hello".into()
+            ))),
+            "Unexpected events {events:?}"
+        );
+        let authority =
+            format!("{}:{}", url.host_str().unwrap(), url.port().unwrap());
+        let head = proxy.await.unwrap();
+        assert_eq!(
+            head,
+            format!(
+                "CONNECT {authority} HTTP/1.1\r\nHost: {authority}\r\n\
+                Proxy-Authorization: Basic dXNlcjpodW50ZXIy\r\n\r\n"
+            )
+        );
+    }
+
+    /// A proxy refusing to open the tunnel is a connection error
+    #[tokio::test]
+    async fn test_session_proxy_refused() {
+        let url = echo_server(None).await;
+        let (proxy_url, _proxy) =
+            http_proxy("407 Proxy Authentication Required").await;
+
+        let (_sender, outgoing) = mpsc::unbounded_channel();
+        let error = run_session(
+            &url,
+            HeaderMap::new(),
+            transport(Some(&proxy_url)),
+            outgoing,
+            |_| {},
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(
+            format!("{error:#}"),
+            format!(
+                "Error connecting to {}: Proxy refused to connect: \
+                HTTP/1.1 407 Proxy Authentication Required",
+                websocket_url(&url).unwrap()
+            )
+        );
+    }
+}
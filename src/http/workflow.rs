@@ -0,0 +1,408 @@
+//! Workflows send a sequence of recipes in order. Each step can override
+//! template fields, capture values from its response for later steps to use,
+//! and check assertions. A step can also be skipped based on a condition.
+//! The workflow stops at the first step that fails.
+
+use crate::{
+    collection::{Workflow, WorkflowStep},
+    http::{
+        AssertionResult, HttpEngine, RecipeOptions, RequestBuilder,
+        RequestRecord,
+    },
+    template::TemplateContext,
+};
+use anyhow::{anyhow, Context};
+use indexmap::IndexMap;
+use tracing::info;
+
+/// Progress of a workflow, reported as each step runs
+#[derive(Debug)]
+pub enum StepUpdate {
+    /// The step is being built and sent
+    Started,
+    /// The step's condition was falsy, so it wasn't sent
+    Skipped,
+    /// The step is done, successfully or not
+    Finished(StepOutcome),
+}
+
+/// The result of running a single workflow step
+#[derive(Debug, Default)]
+pub struct StepOutcome {
+    /// `None` if the request couldn't be built or sent
+    pub record: Option<RequestRecord>,
+    pub assertions: Vec<AssertionResult>,
+    /// Values captured from the response, in the order they're defined
+    pub captures: IndexMap<String, String>,
+    /// Any error other than a failed assertion
+    pub error: Option<anyhow::Error>,
+}
+
+impl StepOutcome {
+    /// Did the step succeed, and all its assertions pass?
+    pub fn passed(&self) -> bool {
+        self.error.is_none()
+            && self.assertions.iter().all(AssertionResult::passed)
+    }
+}
+
+/// Run each step of a workflow in order, passing progress to `on_update`.
+/// Captured values are added to the template context's overrides, so they're
+/// available to later steps. Stop at the first step that fails. Return
+/// whether every step passed (or was skipped).
+pub async fn run_workflow(
+    workflow: &Workflow,
+    http_engine: &HttpEngine,
+    template_context: &mut TemplateContext,
+    mut on_update: impl FnMut(usize, StepUpdate),
+) -> bool {
+    info!(workflow_id = %workflow.id, "Running workflow");
+    for (index, step) in workflow.steps.iter().enumerate() {
+        match should_run(step, template_context).await {
+            Ok(true) => {}
+            Ok(false) => {
+                on_update(index, StepUpdate::Skipped);
+                continue;
+            }
+            Err(error) => {
+                on_update(
+                    index,
+                    StepUpdate::Finished(StepOutcome {
+                        error: Some(error),
+                        ..Default::default()
+                    }),
+                );
+                return false;
+            }
+        }
+
+        on_update(index, StepUpdate::Started);
+        let mut outcome = StepOutcome::default();
+        if let Err(error) =
+            run_step(step, http_engine, template_context, &mut outcome).await
+        {
+            outcome.error = Some(error);
+        }
+        let passed = outcome.passed();
+        on_update(index, StepUpdate::Finished(outcome));
+        if !passed {
+            return false;
+        }
+    }
+    true
+}
+
+/// Render a step's condition to see if it should run. Steps without a
+/// condition always run
+async fn should_run(
+    step: &WorkflowStep,
+    template_context: &TemplateContext,
+) -> anyhow::Result<bool> {
+    let Some(condition) = &step.condition else {
+        return Ok(true);
+    };
+    let value = condition
+        .render(template_context)
+        .await
+        .context("Error rendering condition")?;
+    Ok(!matches!(value.trim(), "" | "false" | "0"))
+}
+
+/// Build and send a step's request, then check its assertions and capture
+/// values from its response. Results are written to the outcome as they're
+/// available, so a failure partway through still has the record.
+async fn run_step(
+    step: &WorkflowStep,
+    http_engine: &HttpEngine,
+    template_context: &mut TemplateContext,
+    outcome: &mut StepOutcome,
+) -> anyhow::Result<()> {
+    let recipe = template_context
+        .collection
+        .recipes
+        .get_recipe(&step.recipe)
+        .cloned()
+        .ok_or_else(|| anyhow!("No recipe with ID `{}`", step.recipe))?;
+
+    // Step overrides only apply to this step, so put the old ones back after
+    let mut overrides = template_context.overrides.clone();
+    for (field, template) in &step.overrides {
+        let value = template
+            .render(template_context)
+            .await
+            .with_context(|| format!("Error rendering override `{field}`"))?;
+        overrides.insert(field.clone(), value);
+    }
+    let previous =
+        std::mem::replace(&mut template_context.overrides, overrides);
+    let result = RequestBuilder::new(recipe, RecipeOptions::default())
+        .build(template_context)
+        .await;
+    template_context.overrides = previous;
+
+    let record = http_engine.clone().send(result?.into()).await?;
+    // Don't bother parsing the body unless we need it
+    let body = if step.capture.is_empty() && step.assertions.body.is_empty() {
+        None
+    } else {
+        record.response.parse_body().ok()
+    };
+    outcome.assertions = step.assertions.check(&record, body.as_deref());
+    outcome.record = Some(record);
+
+    for (field, query) in &step.capture {
+        let body = body.as_deref().ok_or_else(|| {
+            anyhow!(
+                "Error capturing `{field}`: response body could not be parsed"
+            )
+        })?;
+        let value = query
+            .query_to_string(body)
+            .with_context(|| format!("Error capturing `{field}`"))?;
+        template_context
+            .overrides
+            .insert(field.clone(), value.clone());
+        outcome.captures.insert(field.clone(), value);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        collection::{Recipe, RecipeId},
+        config::Config,
+        db::CollectionDatabase,
+        http::StatusAssertion,
+        test_util::*,
+    };
+    use factori::create;
+    use indexmap::indexmap;
+    use serde_json::json;
+
+    /// Build a context for a collection with the given recipes, where the
+    /// `host` field points to the mock server
+    fn context(recipes: Vec<Recipe>, host: String) -> TemplateContext {
+        let recipes: IndexMap<RecipeId, Recipe> = recipes
+            .into_iter()
+            .map(|recipe| (recipe.id.clone(), recipe))
+            .collect();
+        create!(
+            TemplateContext,
+            collection: create!(Collection, recipes: recipes.into()),
+            overrides: indexmap! {"host".into() => host},
+        )
+    }
+
+    fn step(recipe: &str) -> WorkflowStep {
+        WorkflowStep {
+            recipe: recipe.into(),
+            overrides: IndexMap::new(),
+            capture: IndexMap::new(),
+            assertions: Default::default(),
+            condition: None,
+        }
+    }
+
+    /// Run a workflow, collecting every update
+    async fn run(
+        steps: Vec<WorkflowStep>,
+        template_context: &mut TemplateContext,
+    ) -> (bool, Vec<(usize, StepUpdate)>) {
+        let workflow = Workflow {
+            id: "workflow".to_owned().into(),
+            name: None,
+            steps,
+        };
+        let http_engine =
+            HttpEngine::new(&Config::default(), CollectionDatabase::testing());
+        let mut updates = Vec::new();
+        let passed = run_workflow(
+            &workflow,
+            &http_engine,
+            template_context,
+            |index, update| updates.push((index, update)),
+        )
+        .await;
+        (passed, updates)
+    }
+
+    /// Values captured from one step are available to later steps, and step
+    /// overrides can use them
+    #[tokio::test]
+    async fn test_capture() {
+        let mut server = mockito::Server::new_async().await;
+        let login = server
+            .mock("POST", "/login")
+            .with_header("content-type", "application/json")
+            .with_body(json!({"token": "abc123", "org": 7}).to_string())
+            .create_async()
+            .await;
+        let invite = server
+            .mock("POST", "/orgs/7/invites")
+            .match_header("authorization", "Bearer abc123")
+            .create_async()
+            .await;
+        let mut template_context = context(
+            vec![
+                create!(
+                    Recipe,
+                    id: "login".into(),
+                    method: "POST".parse().unwrap(),
+                    url: "{{host}}/login".into(),
+                ),
+                create!(
+                    Recipe,
+                    id: "invite".into(),
+                    method: "POST".parse().unwrap(),
+                    url: "{{host}}/orgs/{{org_id}}/invites".into(),
+                    headers: indexmap! {
+                        "authorization".into() => "Bearer {{token}}".into(),
+                    },
+                ),
+            ],
+            server.url(),
+        );
+
+        let (passed, updates) = run(
+            vec![
+                WorkflowStep {
+                    capture: indexmap! {
+                        "token".into() => "$.token".parse().unwrap(),
+                        "org".into() => "$.org".parse().unwrap(),
+                    },
+                    ..step("login")
+                },
+                WorkflowStep {
+                    overrides: indexmap! {"org_id".into() => "{{org}}".into()},
+                    assertions: crate::http::Assertions {
+                        status: Some(StatusAssertion::Code(200)),
+                        ..Default::default()
+                    },
+                    ..step("invite")
+                },
+            ],
+            &mut template_context,
+        )
+        .await;
+
+        login.assert();
+        invite.assert();
+        assert!(passed);
+        let StepUpdate::Finished(outcome) = &updates[1].1 else {
+            panic!("Expected finished update, got {:?}", updates[1]);
+        };
+        assert_eq!(
+            outcome.captures,
+            indexmap! {
+                "token".to_owned() => "abc123".to_owned(),
+                "org".to_owned() => "7".to_owned(),
+            }
+        );
+        // Captures outlive the workflow, but step overrides don't
+        assert_eq!(
+            template_context.overrides.get("token").map(String::as_str),
+            Some("abc123")
+        );
+        assert_eq!(template_context.overrides.get("org_id"), None);
+    }
+
+    /// Steps with a falsy condition are skipped, and the workflow stops at the
+    /// first failed step
+    #[tokio::test]
+    async fn test_condition_and_failure() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/")
+            .with_status(500)
+            .expect(1)
+            .create_async()
+            .await;
+        let mut template_context = context(
+            vec![create!(Recipe, id: "get".into(), url: "{{host}}/".into())],
+            server.url(),
+        );
+        let failing = WorkflowStep {
+            assertions: crate::http::Assertions {
+                status: Some(StatusAssertion::Code(200)),
+                ..Default::default()
+            },
+            ..step("get")
+        };
+
+        let (passed, updates) = run(
+            vec![
+                WorkflowStep {
+                    condition: Some("false".into()),
+                    ..step("get")
+                },
+                failing.clone(),
+                failing,
+            ],
+            &mut template_context,
+        )
+        .await;
+
+        mock.assert();
+        assert!(!passed);
+        let updates = updates
+            .iter()
+            .map(|(index, update)| {
+                let update = match update {
+                    StepUpdate::Started => "started",
+                    StepUpdate::Skipped => "skipped",
+                    StepUpdate::Finished(outcome) if outcome.passed() => {
+                        "passed"
+                    }
+                    StepUpdate::Finished(_) => "failed",
+                };
+                (*index, update)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            updates,
+            vec![(0, "skipped"), (1, "started"), (1, "failed")]
+        );
+    }
+
+    /// Errors are reported in the step's outcome
+    #[rstest::rstest]
+    #[case::unknown_recipe(step("unknown"), "No recipe with ID `unknown`")]
+    #[case::capture(
+        WorkflowStep {
+            capture: indexmap! {"missing".into() => "$.missing".parse().unwrap()},
+            ..step("get")
+        },
+        "Error capturing `missing`"
+    )]
+    #[tokio::test]
+    async fn test_step_error(
+        #[case] step: WorkflowStep,
+        #[case] expected_error: &str,
+    ) {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/")
+            .with_header("content-type", "application/json")
+            .with_body("{}")
+            .create_async()
+            .await;
+        let mut template_context = context(
+            vec![create!(Recipe, id: "get".into(), url: "{{host}}/".into())],
+            server.url(),
+        );
+
+        let (passed, updates) = run(vec![step], &mut template_context).await;
+
+        assert!(!passed);
+        let Some((_, StepUpdate::Finished(outcome))) = updates.last() else {
+            panic!("Expected finished update, got {updates:?}");
+        };
+        let error = format!("{:#}", outcome.error.as_ref().unwrap());
+        assert!(
+            error.contains(expected_error),
+            "Expected `{error}` to contain `{expected_error}`"
+        );
+    }
+}
@@ -19,7 +19,7 @@ use crate::{
 use derive_more::Display;
 use indexmap::IndexMap;
 use serde::Serialize;
-use std::{fmt::Debug, sync::atomic::AtomicU8};
+use std::{borrow::Cow, fmt::Debug, sync::atomic::AtomicU8};
 
 /// Maximum number of layers of nested templates
 const RECURSION_LIMIT: u8 = 10;
@@ -83,6 +83,54 @@ impl Template {
         &self.template[span.start()..span.end()]
     }
 
+    /// Get the ID of each chain used in this template. Chains used by those
+    /// chains are *not* included
+    pub fn chain_ids(&self) -> impl Iterator<Item = &str> {
+        self.chunks.iter().filter_map(|chunk| match chunk {
+            TemplateInputChunk::Key(TemplateKey::Chain(span)) => {
+                Some(self.substring(*span))
+            }
+            _ => None,
+        })
+    }
+
+    /// Get the template text with each key replaced by the output of the
+    /// given function, which receives the key's source text (e.g. `host` or
+    /// `chains.token`). Unlike rendering, this doesn't resolve any values.
+    pub fn replace_keys(
+        &self,
+        mut replace: impl FnMut(&str) -> String,
+    ) -> String {
+        self.chunks
+            .iter()
+            .map(|chunk| match chunk {
+                TemplateInputChunk::Raw(span) => {
+                    Cow::Borrowed(self.substring(*span))
+                }
+                TemplateInputChunk::Key(key) => Cow::Owned(replace(
+                    &key.map(|span| self.substring(span)).to_string(),
+                )),
+            })
+            .collect()
+    }
+
+    /// Join templates end to end, without parsing again. Keys can't span
+    /// templates, so unlike parsing the joined text, this can't fail.
+    pub fn concat<'a>(templates: impl IntoIterator<Item = &'a Self>) -> Self {
+        let mut template = String::new();
+        let mut chunks = Vec::new();
+        for piece in templates {
+            let offset = template.len();
+            template.push_str(&piece.template);
+            chunks.extend(piece.chunks.iter().map(|chunk| {
+                chunk.map(|span| {
+                    Span::new(offset + span.start(), span.end() - span.start())
+                })
+            }));
+        }
+        Self { template, chunks }
+    }
+
     /// Create a new template **without parsing**. The created template should
     /// *never* be rendered. This is only useful when creating templates purely
     /// for the purpose of being serialized, e.g. when importing an external
@@ -862,6 +910,32 @@ mod tests {
         );
     }
 
+    /// Keys are passed to the replacement function as their source text
+    #[test]
+    fn test_replace_keys() {
+        let template =
+            Template::from("{{host}}/users/{{chains.user}}?env={{env.ENV}}");
+        assert_eq!(
+            template.replace_keys(|key| format!("<{key}>")),
+            "<host>/users/<chains.user>?env=<env.ENV>"
+        );
+    }
+
+    /// Joined templates keep the keys of each piece
+    #[test]
+    fn test_concat() {
+        let template = Template::concat(&[
+            Template::from("{{host}}/users/"),
+            Template::dangerous("{{".into()),
+            Template::from("{{user_id}}"),
+        ]);
+        assert_eq!(template.as_str(), "{{host}}/users/{{{{user_id}}");
+        assert_eq!(
+            template.replace_keys(|key| format!("<{key}>")),
+            "<host>/users/{{<user_id>"
+        );
+    }
+
     /// Helper for rendering a string
     macro_rules! render {
         ($template:expr, $context:expr) => {
@@ -49,7 +49,7 @@ pub enum TemplateInputChunk<T> {
 impl<T> TemplateInputChunk<T> {
     /// Map the internal data using the given function. Useful for mapping
     /// string slices to spans and vice versa.
-    fn map<U>(self, f: impl Fn(T) -> U) -> TemplateInputChunk<U> {
+    pub(super) fn map<U>(self, f: impl Fn(T) -> U) -> TemplateInputChunk<U> {
         match self {
             Self::Raw(value) => TemplateInputChunk::Raw(f(value)),
             Self::Key(key) => TemplateInputChunk::Key(key.map(f)),
@@ -51,4 +51,10 @@ impl PromptChannel {
             .map_err(|_| anyhow!("Prompt listener dropped"))
             .traced();
     }
+
+    /// Has the listener stopped waiting for a response? This happens when the
+    /// render that asked for it is cancelled or fails for another reason
+    pub fn is_closed(&self) -> bool {
+        self.0.is_closed()
+    }
 }
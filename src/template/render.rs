@@ -270,7 +270,14 @@ impl<'a> TemplateSource<'a> for ChainTemplateSource<'a> {
                     // Guess content type based on HTTP header
                     let content_type =
                         ContentType::from_response(&response).ok();
-                    (response.body.into_bytes(), content_type)
+                    // Convert text to UTF-8 according to its charset. If it
+                    // isn't text, use the raw bytes and let it fail later
+                    let value = response
+                        .text()
+                        .map(|text| text.into_owned().into_bytes());
+                    let value =
+                        value.unwrap_or_else(|| response.body.into_bytes());
+                    (value, content_type)
                 }
                 ChainSource::File { path } => {
                     self.render_file(context, path).await?
@@ -469,6 +476,9 @@ impl<'a> ChainTemplateSource<'a> {
             .in_scope(|| async {
                 let output = Command::new(program)
                     .args(args)
+                    // If the render is cancelled, don't leave the command
+                    // running in the background
+                    .kill_on_drop(true)
                     .output()
                     .await
                     .map_err(|error| ChainError::Command {
@@ -1,7 +1,7 @@
 use crate::{
     collection::{
         Chain, ChainSource, Collection, Folder, Profile, ProfileId, Recipe,
-        RecipeId, RecipeNode, RecipeTree,
+        RecipeBody, RecipeId, RecipeNode, RecipeTree,
     },
     db::CollectionDatabase,
     http::{Body, Request, RequestId, RequestRecord, Response},
@@ -20,6 +20,9 @@ factori!(Collection, {
         profiles = Default::default(),
         chains = Default::default(),
         recipes = Default::default(),
+        retry = None,
+        hooks = Default::default(),
+        workflows = Default::default(),
         _ignore = Default::default(),
     }
 });
@@ -29,6 +32,7 @@ factori!(Profile, {
         id = "profile1".into(),
         name = None,
         data = Default::default(),
+        proxy = None,
     }
 });
 
@@ -37,6 +41,8 @@ factori!(Folder, {
         id = "folder1".into(),
         name = None,
         children = Default::default(),
+        retry = None,
+        hooks = Default::default(),
     }
 });
 
@@ -50,6 +56,12 @@ factori!(Recipe, {
         authentication = None,
         query = Default::default(),
         headers = Default::default(),
+        decompress = None,
+        retry = None,
+        download = None,
+        hooks = Default::default(),
+        assertions = Default::default(),
+        example_response = None,
     }
 });
 
@@ -62,6 +74,13 @@ factori!(Request, {
         url = "http://localhost/url".parse().unwrap(),
         headers = HeaderMap::new(),
         body = None,
+        decompress = None,
+        retry = None,
+        proxy = None,
+        sent_proxy = None,
+        download = None,
+        pre_request_hook = None,
+        post_response_hook = None,
     }
 });
 
@@ -70,6 +89,8 @@ factori!(Response, {
         status = StatusCode::OK,
         headers = HeaderMap::new(),
         body = Body::default(),
+        encoded_size = None,
+        download = None,
     }
 });
 
@@ -89,6 +110,10 @@ factori!(RequestRecord, {
         response = response(),
         start_time = Utc::now(),
         end_time = Utc::now(),
+        attempts = 1,
+        timings = None,
+        redirects = Vec::new(),
+        annotations = Vec::new(),
     }
 });
 
@@ -172,6 +197,12 @@ impl From<&str> for Template {
 }
 // Can't implement this for From<String> because it conflicts with TryFrom
 
+impl From<&str> for RecipeBody {
+    fn from(value: &str) -> Self {
+        Self::Raw(value.into())
+    }
+}
+
 /// Helper for creating a header map
 pub fn header_map<'a>(
     headers: impl IntoIterator<Item = (&'a str, &'a str)>,
@@ -4,16 +4,24 @@ mod message;
 mod view;
 
 use crate::{
-    collection::{Collection, CollectionFile, ProfileId, RecipeId},
+    collection::{
+        Collection, CollectionFile, ProfileId, RecipeId, RecipeNode, WorkflowId,
+    },
     config::Config,
     db::{CollectionDatabase, Database},
-    http::{HttpEngine, Request, RequestBuilder},
+    http::{
+        introspection_recipe, run_workflow, HttpEngine, Request,
+        RequestBuilder, StepUpdate, StreamHandler,
+    },
     template::{Prompter, Template, TemplateChunk, TemplateContext},
     tui::{
         context::TuiContext,
         input::Action,
         message::{Message, MessageSender, RequestConfig},
-        view::{ModalPriority, PreviewPrompter, RequestState, View},
+        view::{
+            ModalPriority, PreviewPrompter, RequestState, View,
+            WebSocketSession,
+        },
     },
     util::Replaceable,
 };
@@ -36,8 +44,12 @@ use std::{
     sync::{Arc, OnceLock},
     time::{Duration, Instant},
 };
-use tokio::sync::mpsc::{self, UnboundedReceiver};
+use tokio::sync::{
+    mpsc::{self, UnboundedReceiver},
+    oneshot,
+};
 use tracing::{debug, error, info, trace};
+use uuid::Uuid;
 
 /// Main controller struct for the TUI. The app uses a React-like architecture
 /// for the view, with a wrapping controller (this struct). The main loop goes
@@ -225,6 +237,17 @@ impl Tui {
                 self.view.open_modal(error, ModalPriority::High)
             }
 
+            Message::GraphQlIntrospect(request_config) => {
+                self.introspect(request_config)?
+            }
+            Message::GraphQlIntrospected {
+                recipe_id,
+                operation_count,
+            } => self.view.notify(format!(
+                "Loaded GraphQL schema for `{recipe_id}` \
+                ({operation_count} operations)"
+            )),
+
             // Manage HTTP life cycle
             Message::HttpBeginRequest(request_config) => {
                 self.send_request(request_config)?
@@ -251,6 +274,47 @@ impl Tui {
                     RequestState::loading(request),
                 );
             }
+            Message::HttpStreamStart {
+                request,
+                status,
+                headers,
+                cancel,
+            } => {
+                self.view.set_request_state(
+                    request.profile_id.clone(),
+                    request.recipe_id.clone(),
+                    RequestState::streaming(
+                        Arc::clone(&request),
+                        status,
+                        headers,
+                        cancel,
+                    ),
+                );
+            }
+            Message::HttpStreamChunk { request, chunk } => {
+                self.view.push_stream_chunk(
+                    request.profile_id.clone(),
+                    request.recipe_id.clone(),
+                    request.id,
+                    chunk,
+                );
+            }
+            Message::HttpRetry { request, attempt } => {
+                self.view.set_request_attempt(
+                    request.profile_id.clone(),
+                    request.recipe_id.clone(),
+                    request.id,
+                    attempt,
+                );
+            }
+            Message::HttpDownloadProgress { request, progress } => {
+                self.view.set_download_progress(
+                    request.profile_id.clone(),
+                    request.recipe_id.clone(),
+                    request.id,
+                    progress,
+                );
+            }
             Message::HttpComplete(result) => {
                 let (profile_id, recipe_id, state) = match result {
                     Ok(record) => (
@@ -291,6 +355,48 @@ impl Tui {
                     destination,
                 )?;
             }
+
+            // Manage WebSocket life cycle
+            Message::WebSocketConnect(request_config) => {
+                self.connect_websocket(request_config)?
+            }
+            Message::WebSocketSend {
+                profile_id,
+                template,
+                sender,
+            } => self.send_websocket_message(profile_id, template, sender)?,
+            Message::WebSocketUpdate {
+                profile_id,
+                recipe_id,
+                session_id,
+                update,
+            } => self.view.update_websocket_session(
+                profile_id, recipe_id, session_id, update,
+            ),
+
+            Message::WorkflowRun {
+                profile_id,
+                workflow_id,
+            } => self.run_workflow(profile_id, &workflow_id)?,
+            Message::WorkflowUpdate {
+                run_id,
+                index,
+                update,
+            } => {
+                // Show the step's response in the primary view too
+                if let StepUpdate::Finished(outcome) = &update {
+                    if let Some(record) = &outcome.record {
+                        self.load_request(
+                            record.request.profile_id.as_ref(),
+                            &record.request.recipe_id,
+                        )?;
+                    }
+                }
+                self.view.update_workflow(run_id, index, update);
+            }
+            Message::WorkflowComplete { run_id } => {
+                self.view.complete_workflow(run_id)
+            }
         }
         Ok(())
     }
@@ -405,6 +511,38 @@ impl Tui {
         Ok(())
     }
 
+    /// Fetch a GraphQL schema in a separate task. The request is built from
+    /// the recipe, but with the body replaced by an introspection query
+    fn introspect(&self, request_config: RequestConfig) -> anyhow::Result<()> {
+        let recipe = self
+            .collection_file
+            .collection
+            .recipes
+            .get_recipe(&request_config.recipe_id)
+            .ok_or_else(|| {
+                anyhow!("No recipe with ID `{}`", request_config.recipe_id)
+            })?;
+        let builder = RequestBuilder::new(
+            introspection_recipe(recipe),
+            request_config.options,
+        );
+        let http_engine = self.http_engine.clone();
+        let messages_tx = self.messages_tx.clone();
+        let template_context =
+            self.template_context(request_config.profile_id, true)?;
+        self.spawn(async move {
+            let request = builder.build(&template_context).await?;
+            let recipe_id = request.recipe_id.clone();
+            let schema = http_engine.introspect(request.into()).await?;
+            messages_tx.send(Message::GraphQlIntrospected {
+                recipe_id,
+                operation_count: schema.operations().len(),
+            });
+            Ok(())
+        });
+        Ok(())
+    }
+
     /// Launch an HTTP request in a separate task
     fn send_request(
         &mut self,
@@ -425,17 +563,12 @@ impl Tui {
             ..
         } = request_config;
 
-        // Mark request state as building
         let request_id = builder.id();
-        self.view.set_request_state(
-            profile_id.clone(),
-            recipe_id.clone(),
-            RequestState::building(request_id),
-        );
+        let state_key = (profile_id.clone(), recipe_id.clone());
 
         // We can't use self.spawn here because HTTP errors are handled
         // differently from all other error types
-        tokio::spawn(async move {
+        let task = tokio::spawn(async move {
             // Build the request
             let request: Arc<Request> = builder
                 .build(&template_context)
@@ -457,8 +590,57 @@ impl Tui {
                 request: Arc::clone(&request),
             });
 
+            // Event streams are displayed as they arrive, so forward each
+            // chunk to the main thread
+            let (cancel_tx, cancel_rx) = oneshot::channel();
+            let stream = StreamHandler {
+                on_retry: Box::new({
+                    let messages_tx = messages_tx.clone();
+                    let request = Arc::clone(&request);
+                    move |attempt| {
+                        messages_tx.send(Message::HttpRetry {
+                            request: Arc::clone(&request),
+                            attempt,
+                        })
+                    }
+                }),
+                on_start: Box::new({
+                    let messages_tx = messages_tx.clone();
+                    let request = Arc::clone(&request);
+                    move |status, headers| {
+                        messages_tx.send(Message::HttpStreamStart {
+                            request,
+                            status,
+                            headers,
+                            cancel: cancel_tx,
+                        })
+                    }
+                }),
+                on_chunk: Box::new({
+                    let messages_tx = messages_tx.clone();
+                    let request = Arc::clone(&request);
+                    move |chunk| {
+                        messages_tx.send(Message::HttpStreamChunk {
+                            request: Arc::clone(&request),
+                            chunk,
+                        })
+                    }
+                }),
+                on_download: Box::new({
+                    let messages_tx = messages_tx.clone();
+                    let request = Arc::clone(&request);
+                    move |progress| {
+                        messages_tx.send(Message::HttpDownloadProgress {
+                            request: Arc::clone(&request),
+                            progress,
+                        })
+                    }
+                }),
+                cancel: cancel_rx,
+            };
+
             // Send the request and report the result to the main thread
-            let result = http_engine.send(request).await;
+            let result = http_engine.send_streaming(request, stream).await;
             messages_tx.send(Message::HttpComplete(result));
 
             // By returning an empty result, we can use `?` to break out early.
@@ -466,6 +648,135 @@ impl Tui {
             Ok::<(), ()>(())
         });
 
+        // Mark request state as building. The view holds onto the task
+        // handle so the user can cancel the request. Any messages the task
+        // sends are handled in a later loop iteration, so this state will
+        // always be in place first
+        let (profile_id, recipe_id) = state_key;
+        self.view.set_request_state(
+            profile_id,
+            recipe_id,
+            RequestState::building(request_id, task.abort_handle()),
+        );
+
+        Ok(())
+    }
+
+    /// Open a WebSocket connection in a separate task. The session runs in
+    /// that task until the connection is closed
+    fn connect_websocket(
+        &mut self,
+        request_config: RequestConfig,
+    ) -> anyhow::Result<()> {
+        let http_engine = self.http_engine.clone();
+        let builder = self.get_request_builder(request_config.clone())?;
+        let messages_tx = self.messages_tx.clone();
+
+        let template_context =
+            self.template_context(request_config.profile_id.clone(), true)?;
+        let RequestConfig {
+            profile_id,
+            recipe_id,
+            ..
+        } = request_config;
+
+        // The view holds onto the sender, so it can send messages and close
+        // the connection
+        let session_id = builder.id();
+        let (sender, outgoing) = mpsc::unbounded_channel();
+        self.view.set_websocket_session(
+            profile_id.clone(),
+            recipe_id.clone(),
+            WebSocketSession::new(session_id, sender),
+        );
+
+        tokio::spawn(async move {
+            let report = |update| {
+                messages_tx.send(Message::WebSocketUpdate {
+                    profile_id: profile_id.clone(),
+                    recipe_id: recipe_id.clone(),
+                    session_id,
+                    update,
+                })
+            };
+            let result = async {
+                let request = builder.build(&template_context).await?;
+                http_engine
+                    .connect_websocket(request.into(), outgoing, |event| {
+                        report(Ok(event))
+                    })
+                    .await
+            }
+            .await;
+            if let Err(error) = result {
+                report(Err(error));
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Render a message template, then send it over an open WebSocket
+    fn send_websocket_message(
+        &self,
+        profile_id: Option<ProfileId>,
+        template: String,
+        sender: mpsc::UnboundedSender<String>,
+    ) -> anyhow::Result<()> {
+        let template = Template::parse(template)?;
+        let template_context = self.template_context(profile_id, true)?;
+        self.spawn(async move {
+            let message = template
+                .render(&template_context)
+                .await
+                .context("Error rendering message")?;
+            // If the connection closed in the meantime, the message is lost
+            let _ = sender.send(message);
+            Ok(())
+        });
+        Ok(())
+    }
+
+    /// Run a workflow in a separate task, reporting the progress of each step
+    /// to the view
+    fn run_workflow(
+        &mut self,
+        profile_id: Option<ProfileId>,
+        workflow_id: &WorkflowId,
+    ) -> anyhow::Result<()> {
+        let workflow = self
+            .collection_file
+            .collection
+            .workflows
+            .get(workflow_id)
+            .cloned()
+            .ok_or_else(|| anyhow!("No workflow with ID `{workflow_id}`"))?;
+        let http_engine = self.http_engine.clone();
+        let mut template_context = self.template_context(profile_id, true)?;
+        let messages_tx = self.messages_tx.clone();
+
+        let run_id = Uuid::new_v4();
+        let task = tokio::spawn({
+            let workflow = workflow.clone();
+            async move {
+                run_workflow(
+                    &workflow,
+                    &http_engine,
+                    &mut template_context,
+                    |index, update| {
+                        messages_tx.send(Message::WorkflowUpdate {
+                            run_id,
+                            index,
+                            update,
+                        })
+                    },
+                )
+                .await;
+                messages_tx.send(Message::WorkflowComplete { run_id });
+            }
+        });
+        self.view
+            .start_workflow(run_id, workflow, task.abort_handle());
         Ok(())
     }
 
@@ -496,13 +807,15 @@ impl Tui {
             recipe_id, options, ..
         }: RequestConfig,
     ) -> anyhow::Result<RequestBuilder> {
-        let recipe = self
-            .collection_file
-            .collection
-            .recipes
-            .get_recipe(&recipe_id)
-            .ok_or_else(|| anyhow!("No recipe with ID `{recipe_id}`"))?
-            .clone();
+        // WebSocket recipes are built as their handshake request
+        let recipe =
+            match self.collection_file.collection.recipes.get(&recipe_id) {
+                Some(RecipeNode::Recipe(recipe)) => recipe.clone(),
+                Some(RecipeNode::WebSocket(websocket)) => websocket.to_recipe(),
+                Some(RecipeNode::Folder(_)) | None => {
+                    return Err(anyhow!("No recipe with ID `{recipe_id}`"))
+                }
+            };
         Ok(RequestBuilder::new(recipe, options))
     }
 
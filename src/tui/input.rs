@@ -195,6 +195,7 @@ impl Default for InputEngine {
                 Action::Fullscreen => KeyCode::Char('f').into(),
                 Action::ReloadCollection => KeyCode::F(5).into(),
                 Action::Search => KeyCode::Char('/').into(),
+                Action::Delete => KeyCode::Delete.into(),
                 Action::PreviousPane => KeyCode::BackTab.into(),
                 Action::NextPane => KeyCode::Tab.into(),
                 Action::Up => KeyCode::Up.into(),
@@ -277,6 +278,8 @@ pub enum Action {
     /// Start a search/filter operation
     #[display("Search/Filter")]
     Search,
+    /// Delete the selected item
+    Delete,
     /// Force a collection reload (typically it's automatic)
     #[display("Reload Collection")]
     ReloadCollection,
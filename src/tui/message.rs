@@ -2,18 +2,22 @@
 //! state updates.
 
 use crate::{
-    collection::{Collection, ProfileId, RecipeId},
+    collection::{Collection, ProfileId, RecipeId, WorkflowId},
     http::{
-        RecipeOptions, Request, RequestBuildError, RequestError, RequestRecord,
+        DownloadProgress, RecipeOptions, Request, RequestBuildError,
+        RequestError, RequestId, RequestRecord, StepUpdate, WebSocketEvent,
     },
     template::{Prompt, Prompter, Template, TemplateChunk},
     util::ResultExt,
 };
 use anyhow::Context;
+use bytes::Bytes;
 use derive_more::From;
+use reqwest::{header::HeaderMap, StatusCode};
 use std::sync::{Arc, OnceLock};
-use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::{mpsc::UnboundedSender, oneshot};
 use tracing::trace;
+use uuid::Uuid;
 
 /// Wrapper around a sender for async messages. Cheap to clone and pass around
 #[derive(Clone, Debug, From)]
@@ -70,6 +74,14 @@ pub enum Message {
     /// An error occurred in some async process and should be shown to the user
     Error { error: anyhow::Error },
 
+    /// Fetch the GraphQL schema for a recipe, and cache it in the database
+    GraphQlIntrospect(RequestConfig),
+    /// Introspection finished and the schema was cached
+    GraphQlIntrospected {
+        recipe_id: RecipeId,
+        operation_count: usize,
+    },
+
     /// Launch an HTTP request from the given recipe/profile.
     HttpBeginRequest(RequestConfig),
     /// Request failed to build
@@ -84,6 +96,25 @@ pub enum Message {
         recipe_id: RecipeId,
         request: Arc<Request>,
     },
+    /// The response headers were received, and the body is going to be
+    /// streamed. Chunks of the body will follow in [Message::HttpStreamChunk]
+    HttpStreamStart {
+        request: Arc<Request>,
+        status: StatusCode,
+        headers: HeaderMap,
+        /// Send on (or drop) this to close the stream
+        cancel: oneshot::Sender<()>,
+    },
+    /// Received the next chunk of a streaming response body
+    HttpStreamChunk { request: Arc<Request>, chunk: Bytes },
+    /// The request failed, and is about to be sent again according to its
+    /// retry policy
+    HttpRetry { request: Arc<Request>, attempt: u32 },
+    /// More of a download has been written to its file
+    HttpDownloadProgress {
+        request: Arc<Request>,
+        progress: DownloadProgress,
+    },
     /// The HTTP request either succeeded or failed. We don't need to store the
     /// recipe ID here because it's in the inner container already. Combining
     /// these two cases saves a bit of boilerplate.
@@ -115,6 +146,39 @@ pub enum Message {
         profile_id: Option<ProfileId>,
         destination: Arc<OnceLock<Vec<TemplateChunk>>>,
     },
+
+    /// Open a WebSocket connection from the given WebSocket recipe/profile
+    WebSocketConnect(RequestConfig),
+    /// Render a message template, then send it over an open WebSocket
+    WebSocketSend {
+        profile_id: Option<ProfileId>,
+        /// Raw template text entered by the user
+        template: String,
+        sender: UnboundedSender<String>,
+    },
+    /// Something happened in a WebSocket session. An error means the session
+    /// is over
+    WebSocketUpdate {
+        profile_id: Option<ProfileId>,
+        recipe_id: RecipeId,
+        session_id: RequestId,
+        update: anyhow::Result<WebSocketEvent>,
+    },
+
+    /// Run a workflow with the given profile
+    WorkflowRun {
+        profile_id: Option<ProfileId>,
+        workflow_id: WorkflowId,
+    },
+    /// A step of a running workflow made progress
+    WorkflowUpdate {
+        run_id: Uuid,
+        index: usize,
+        update: StepUpdate,
+    },
+    /// A workflow finished running, either because every step ran or one
+    /// failed
+    WorkflowComplete { run_id: Uuid },
 }
 
 /// Configuration that defines how to render a request
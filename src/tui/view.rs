@@ -2,17 +2,19 @@ mod common;
 mod component;
 mod draw;
 mod event;
+mod highlight;
 mod state;
 mod theme;
 mod util;
 
 pub use common::modal::{IntoModal, ModalPriority};
-pub use state::RequestState;
+pub use state::{RequestState, WebSocketSession};
 pub use theme::Theme;
 pub use util::PreviewPrompter;
 
 use crate::{
-    collection::{Collection, ProfileId, RecipeId},
+    collection::{Collection, ProfileId, RecipeId, Workflow},
+    http::{DownloadProgress, RequestId, StepUpdate, WebSocketEvent},
     tui::{
         context::TuiContext,
         input::Action,
@@ -26,9 +28,12 @@ use crate::{
     },
 };
 use anyhow::anyhow;
+use bytes::Bytes;
 use ratatui::Frame;
 use std::fmt::Debug;
+use tokio::task::AbortHandle;
 use tracing::{error, trace, trace_span};
+use uuid::Uuid;
 
 /// Primary entrypoint for the view. This contains the main draw functions, as
 /// well as bindings for externally modifying the view state. We use a component
@@ -77,6 +82,126 @@ impl View {
         });
     }
 
+    /// Queue an event to add the next chunk of a streaming response body to
+    /// the request state. The chunk is dropped if the request is no longer
+    /// the current one for its profile+recipe.
+    pub fn push_stream_chunk(
+        &mut self,
+        profile_id: Option<ProfileId>,
+        recipe_id: RecipeId,
+        request_id: RequestId,
+        chunk: Bytes,
+    ) {
+        EventQueue::push(Event::HttpStreamChunk {
+            profile_id,
+            recipe_id,
+            request_id,
+            chunk,
+        });
+    }
+
+    /// Queue an event to mark a loading request as being on a new attempt.
+    /// The update is dropped if the request is no longer the current one for
+    /// its profile+recipe.
+    pub fn set_request_attempt(
+        &mut self,
+        profile_id: Option<ProfileId>,
+        recipe_id: RecipeId,
+        request_id: RequestId,
+        attempt: u32,
+    ) {
+        EventQueue::push(Event::HttpRetry {
+            profile_id,
+            recipe_id,
+            request_id,
+            attempt,
+        });
+    }
+
+    /// Queue an event to update the progress of a loading request's download.
+    /// The update is dropped if the request is no longer the current one for
+    /// its profile+recipe.
+    pub fn set_download_progress(
+        &mut self,
+        profile_id: Option<ProfileId>,
+        recipe_id: RecipeId,
+        request_id: RequestId,
+        progress: DownloadProgress,
+    ) {
+        EventQueue::push(Event::HttpDownloadProgress {
+            profile_id,
+            recipe_id,
+            request_id,
+            progress,
+        });
+    }
+
+    /// Queue an event to track a new WebSocket session. Any existing session
+    /// for the profile+recipe is replaced, which closes its connection.
+    pub fn set_websocket_session(
+        &mut self,
+        profile_id: Option<ProfileId>,
+        recipe_id: RecipeId,
+        session: WebSocketSession,
+    ) {
+        EventQueue::push(Event::WebSocketSetSession {
+            profile_id,
+            recipe_id,
+            session,
+        });
+    }
+
+    /// Queue an event to update a WebSocket session. The update is dropped if
+    /// the session is no longer the current one for its profile+recipe.
+    pub fn update_websocket_session(
+        &mut self,
+        profile_id: Option<ProfileId>,
+        recipe_id: RecipeId,
+        session_id: RequestId,
+        update: anyhow::Result<WebSocketEvent>,
+    ) {
+        EventQueue::push(Event::WebSocketUpdate {
+            profile_id,
+            recipe_id,
+            session_id,
+            update,
+        });
+    }
+
+    /// Queue an event to show the progress of a new workflow run
+    pub fn start_workflow(
+        &mut self,
+        run_id: Uuid,
+        workflow: Workflow,
+        abort: AbortHandle,
+    ) {
+        EventQueue::push(Event::WorkflowStart {
+            run_id,
+            workflow,
+            abort,
+        });
+    }
+
+    /// Queue an event to update a step of a workflow run. The update is
+    /// dropped if the run is no longer being shown
+    pub fn update_workflow(
+        &mut self,
+        run_id: Uuid,
+        index: usize,
+        update: StepUpdate,
+    ) {
+        EventQueue::push(Event::WorkflowUpdate {
+            run_id,
+            index,
+            update,
+        });
+    }
+
+    /// Queue an event to mark a workflow run as finished
+    pub fn complete_workflow(&mut self, run_id: Uuid) {
+        EventQueue::push(Event::WorkflowComplete { run_id });
+    }
+
     /// Queue an event to open a new modal. The input can be anything that
     /// converts to modal content
     pub fn open_modal(
@@ -3,9 +3,12 @@
 
 pub mod actions;
 pub mod header_table;
+pub mod hex_window;
+pub mod json_tree;
 pub mod list;
 pub mod modal;
 pub mod table;
+pub mod table_window;
 pub mod tabs;
 pub mod template_preview;
 pub mod text_box;
@@ -211,6 +214,7 @@ impl Generate for &RequestError {
     where
         Self: 'this,
     {
-        self.error.to_string().into()
+        // Defer to the underlying anyhow error
+        self.error.generate()
     }
 }
@@ -90,6 +90,12 @@ where
 pub enum GlobalAction {
     #[display("Edit Collection")]
     EditCollection,
+    #[display("View Cookies")]
+    ViewCookies,
+    #[display("Clear Cookies")]
+    ClearCookies,
+    #[display("Run Workflow")]
+    RunWorkflow,
 }
 
 impl ToStringGenerate for GlobalAction {}
@@ -0,0 +1,179 @@
+use crate::tui::{
+    context::TuiContext,
+    input::Action,
+    view::{
+        draw::Draw,
+        event::{Event, EventHandler, Update},
+    },
+};
+use bytes::Bytes;
+use ratatui::{
+    prelude::Rect,
+    text::{Line, Span},
+    widgets::Paragraph,
+    Frame,
+};
+use std::{cell::Cell, cmp, fmt::Write};
+
+/// A scrollable hex dump of binary content. Each line shows the byte offset,
+/// the hex value of each byte, and the printable ASCII representation. Only
+/// the visible lines are formatted, so this is cheap even for huge bodies.
+#[derive(derive_more::Debug)]
+pub struct HexWindow {
+    /// Cheap to clone, so we can hang onto the content without copying it
+    #[debug(skip)]
+    bytes: Bytes,
+    /// Index of the first visible line
+    offset_y: usize,
+    window_height: Cell<usize>,
+}
+
+impl HexWindow {
+    const BYTES_PER_LINE: usize = 16;
+
+    pub fn new(bytes: Bytes) -> Self {
+        Self {
+            bytes,
+            offset_y: 0,
+            window_height: Cell::default(),
+        }
+    }
+
+    /// Get the entire dump as text. This formats *every* line, so it may be
+    /// slow for large content.
+    pub fn text(&self) -> String {
+        self.bytes
+            .chunks(Self::BYTES_PER_LINE)
+            .enumerate()
+            .map(|(i, chunk)| {
+                format!("{} {}", offset(i * Self::BYTES_PER_LINE), dump(chunk))
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn line_count(&self) -> usize {
+        self.bytes.len().div_ceil(Self::BYTES_PER_LINE)
+    }
+
+    /// Get the final line that we can't scroll past. This will be the first
+    /// line of the last page
+    fn max_scroll_line(&self) -> usize {
+        self.line_count().saturating_sub(self.window_height.get())
+    }
+
+    fn scroll_up(&mut self, lines: usize) {
+        self.offset_y = self.offset_y.saturating_sub(lines);
+    }
+
+    fn scroll_down(&mut self, lines: usize) {
+        self.offset_y = cmp::min(self.offset_y + lines, self.max_scroll_line());
+    }
+
+    /// Scroll to a specific line number. The target line will end up as close
+    /// to the top of the page as possible
+    fn scroll_to(&mut self, line: usize) {
+        self.offset_y = cmp::min(line, self.max_scroll_line());
+    }
+}
+
+impl EventHandler for HexWindow {
+    fn update(&mut self, event: Event) -> Update {
+        match event {
+            Event::Input {
+                action: Some(action),
+                ..
+            } => match action {
+                Action::Up | Action::ScrollUp => self.scroll_up(1),
+                Action::Down | Action::ScrollDown => self.scroll_down(1),
+                Action::PageUp => self.scroll_up(self.window_height.get()),
+                Action::PageDown => self.scroll_down(self.window_height.get()),
+                Action::Home => self.scroll_to(0),
+                Action::End => self.scroll_to(usize::MAX),
+                _ => return Update::Propagate(event),
+            },
+            _ => return Update::Propagate(event),
+        }
+        Update::Consumed
+    }
+}
+
+impl Draw for HexWindow {
+    fn draw(&self, frame: &mut Frame, _: (), area: Rect) {
+        let theme = &TuiContext::get().theme;
+        let window_height = area.height as usize;
+        self.window_height.set(window_height);
+
+        // Only format the lines that are on screen
+        let start = self.offset_y * Self::BYTES_PER_LINE;
+        let end = cmp::min(
+            start + window_height * Self::BYTES_PER_LINE,
+            self.bytes.len(),
+        );
+        let lines: Vec<Line> = self.bytes[start..end]
+            .chunks(Self::BYTES_PER_LINE)
+            .enumerate()
+            .map(|(i, chunk)| {
+                let offset = offset(start + i * Self::BYTES_PER_LINE);
+                Line::from(vec![
+                    Span::styled(offset, theme.text_window.line_number),
+                    " ".into(),
+                    dump(chunk).into(),
+                ])
+            })
+            .collect();
+        frame.render_widget(Paragraph::new(lines), area);
+    }
+}
+
+/// Format the byte offset for the start of a line
+fn offset(offset: usize) -> String {
+    format!("{offset:08x}")
+}
+
+/// Format a single line of bytes as hex and ASCII columns. Short lines (i.e.
+/// the last line) will be padded so the ASCII column stays aligned.
+fn dump(chunk: &[u8]) -> String {
+    let mut hex = String::new();
+    for i in 0..HexWindow::BYTES_PER_LINE {
+        // Extra space in the middle for readability
+        if i == HexWindow::BYTES_PER_LINE / 2 {
+            hex.push(' ');
+        }
+        match chunk.get(i) {
+            // Writing to a string is infallible
+            Some(byte) => write!(&mut hex, " {byte:02x}").unwrap(),
+            None => hex.push_str("   "),
+        }
+    }
+    let ascii: String = chunk
+        .iter()
+        .map(|&byte| {
+            if byte.is_ascii_graphic() || byte == b' ' {
+                byte as char
+            } else {
+                '.'
+            }
+        })
+        .collect();
+    format!("{hex}  |{ascii}|")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text() {
+        let window = HexWindow::new(Bytes::from_static(
+            b"Hello, world!\n\x00\x01\xff\xfeslumber",
+        ));
+        assert_eq!(
+            window.text(),
+            "00000000  48 65 6c 6c 6f 2c 20 77  6f 72 6c 64 21 0a 00 01  \
+            |Hello, world!...|\n\
+            00000010  ff fe 73 6c 75 6d 62 65  72                       \
+            |..slumber|"
+        );
+    }
+}
@@ -0,0 +1,441 @@
+use crate::tui::{
+    context::TuiContext,
+    input::Action,
+    view::{
+        draw::Draw,
+        event::{Event, EventHandler, Update},
+    },
+};
+use ratatui::{
+    prelude::Rect,
+    text::{Line, Span},
+    widgets::Paragraph,
+    Frame,
+};
+use serde_json::Value;
+use std::{cell::Cell, cmp};
+
+/// A collapsible tree view of a JSON value. Objects and arrays can be
+/// expanded/collapsed, and the JSONPath of the selected node can be retrieved.
+///
+/// The tree is flattened into a list of nodes in document order on
+/// construction, so navigation and drawing never need to walk the value.
+#[derive(derive_more::Debug)]
+pub struct JsonTree {
+    #[debug(skip)]
+    nodes: Vec<Node>,
+    /// Expansion state for each node. Only meaningful for containers
+    expanded: Vec<bool>,
+    /// Indexes of visible nodes, i.e. nodes whose ancestors are all expanded.
+    /// Recomputed whenever a node is expanded/collapsed.
+    visible: Vec<usize>,
+    /// Index of the selected node, *within the visible list*
+    selected: usize,
+    /// Index of the first visible node that's on screen
+    offset_y: usize,
+    window_height: Cell<usize>,
+}
+
+/// A single node in the tree
+#[derive(Debug)]
+struct Node {
+    depth: usize,
+    parent: Option<usize>,
+    /// Key within the parent. `None` for the root
+    key: Option<PathSegment>,
+    kind: NodeKind,
+}
+
+#[derive(Debug)]
+enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+#[derive(Debug)]
+enum NodeKind {
+    /// Object or array. `end` is the index *after* the last descendant, which
+    /// allows skipping the entire subtree.
+    Container {
+        is_array: bool,
+        len: usize,
+        end: usize,
+    },
+    /// Scalar value, pre-formatted as JSON
+    Scalar(String),
+}
+
+impl JsonTree {
+    /// Build a tree from a JSON value. This is linear in the size of the
+    /// value, so for large values it should be done off the main thread. Only
+    /// the root is initially expanded.
+    pub fn new(value: &Value) -> Self {
+        let mut nodes = Vec::new();
+        flatten(value, None, None, 0, &mut nodes);
+        let expanded = (0..nodes.len()).map(|i| i == 0).collect();
+        let mut tree = Self {
+            nodes,
+            expanded,
+            visible: Vec::new(),
+            selected: 0,
+            offset_y: 0,
+            window_height: Cell::default(),
+        };
+        tree.update_visible();
+        tree
+    }
+
+    /// Get the JSONPath to the selected node
+    pub fn selected_path(&self) -> String {
+        let mut segments = Vec::new();
+        let mut index = Some(self.visible[self.selected]);
+        while let Some(i) = index {
+            let node = &self.nodes[i];
+            segments.extend(node.key.as_ref());
+            index = node.parent;
+        }
+        let mut path = "$".to_owned();
+        for segment in segments.into_iter().rev() {
+            match segment {
+                PathSegment::Key(key) if is_identifier(key) => {
+                    path.push('.');
+                    path.push_str(key);
+                }
+                PathSegment::Key(key) => {
+                    let key = key.replace('\\', "\\\\").replace('\'', "\\'");
+                    path.push_str(&format!("['{key}']"));
+                }
+                PathSegment::Index(index) => {
+                    path.push_str(&format!("[{index}]"))
+                }
+            }
+        }
+        path
+    }
+
+    /// Recompute the list of visible nodes
+    fn update_visible(&mut self) {
+        let selected_node = self.visible.get(self.selected).copied();
+        self.visible.clear();
+        let mut i = 0;
+        while i < self.nodes.len() {
+            self.visible.push(i);
+            i = match self.nodes[i].kind {
+                // Skip the children of collapsed nodes
+                NodeKind::Container { end, .. } if !self.expanded[i] => end,
+                _ => i + 1,
+            };
+        }
+        // Keep the same node selected. It must still be visible, because we
+        // only ever collapse the selected node or its ancestors, and we select
+        // the collapsed node in that case
+        if let Some(node) = selected_node {
+            self.selected = self.visible.binary_search(&node).unwrap_or(0);
+        }
+    }
+
+    fn selected_node(&self) -> usize {
+        self.visible[self.selected]
+    }
+
+    fn is_container(&self, node: usize) -> bool {
+        matches!(self.nodes[node].kind, NodeKind::Container { len, .. } if len > 0)
+    }
+
+    fn select(&mut self, index: usize) {
+        self.selected = cmp::min(index, self.visible.len().saturating_sub(1));
+        // Scroll to keep the selection on screen
+        let window_height = cmp::max(self.window_height.get(), 1);
+        if self.selected < self.offset_y {
+            self.offset_y = self.selected;
+        } else if self.selected >= self.offset_y + window_height {
+            self.offset_y = self.selected + 1 - window_height;
+        }
+    }
+
+    /// Collapse the selected node. If it's already collapsed (or not a
+    /// container), select its parent instead. Returns `false` if there was
+    /// nothing to do.
+    fn collapse(&mut self) -> bool {
+        let node = self.selected_node();
+        if self.is_container(node) && self.expanded[node] {
+            self.expanded[node] = false;
+            self.update_visible();
+            true
+        } else if let Some(parent) = self.nodes[node].parent {
+            let index = self.visible.binary_search(&parent).unwrap_or(0);
+            self.select(index);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Expand the selected node. If it's already expanded, select its first
+    /// child instead. Returns `false` if there was nothing to do.
+    fn expand(&mut self) -> bool {
+        let node = self.selected_node();
+        if !self.is_container(node) {
+            false
+        } else if !self.expanded[node] {
+            self.expanded[node] = true;
+            self.update_visible();
+            true
+        } else {
+            self.select(self.selected + 1);
+            true
+        }
+    }
+
+    /// Generate the display line for a node
+    fn line(&self, index: usize) -> Line<'_> {
+        let theme = &TuiContext::get().theme;
+        let node = &self.nodes[index];
+        let mut spans: Vec<Span> = vec!["  ".repeat(node.depth).into()];
+
+        match node.kind {
+            NodeKind::Container { len, .. } if len > 0 => spans
+                .push(if self.expanded[index] { "▾ " } else { "▸ " }.into()),
+            _ => spans.push("  ".into()),
+        }
+
+        match &node.key {
+            Some(PathSegment::Key(key)) => {
+                spans.push(Span::styled(
+                    serde_json::to_string(key).unwrap(),
+                    theme.syntax.key,
+                ));
+                spans.push(Span::styled(": ", theme.syntax.punctuation));
+            }
+            Some(PathSegment::Index(index)) => {
+                spans.push(Span::styled(
+                    format!("{index}: "),
+                    theme.text_window.line_number,
+                ));
+            }
+            None => {}
+        }
+
+        match &node.kind {
+            NodeKind::Container { is_array, len, .. } => {
+                let (open, close, unit) = if *is_array {
+                    ("[", "]", if *len == 1 { "item" } else { "items" })
+                } else {
+                    ("{", "}", if *len == 1 { "key" } else { "keys" })
+                };
+                spans.push(Span::styled(open, theme.syntax.punctuation));
+                spans.push(format!("{len} {unit}").into());
+                spans.push(Span::styled(close, theme.syntax.punctuation));
+            }
+            NodeKind::Scalar(value) => {
+                let style = match value.as_bytes().first() {
+                    Some(b'"') => theme.syntax.string,
+                    Some(b't' | b'f') => theme.syntax.boolean,
+                    Some(b'n') => theme.syntax.null,
+                    _ => theme.syntax.number,
+                };
+                spans.push(Span::styled(value.as_str(), style));
+            }
+        }
+        Line::from(spans)
+    }
+}
+
+impl EventHandler for JsonTree {
+    fn update(&mut self, event: Event) -> Update {
+        match event {
+            Event::Input {
+                action: Some(action),
+                ..
+            } => match action {
+                Action::Up | Action::ScrollUp => {
+                    self.select(self.selected.saturating_sub(1))
+                }
+                Action::Down | Action::ScrollDown => {
+                    self.select(self.selected + 1)
+                }
+                Action::PageUp => self.select(
+                    self.selected.saturating_sub(self.window_height.get()),
+                ),
+                Action::PageDown => {
+                    self.select(self.selected + self.window_height.get())
+                }
+                Action::Home => self.select(0),
+                Action::End => self.select(usize::MAX),
+                // If there's nothing to expand/collapse, let the parent use
+                // the action (e.g. to switch tabs)
+                Action::Left if self.collapse() => {}
+                Action::Right if self.expand() => {}
+                _ => return Update::Propagate(event),
+            },
+            _ => return Update::Propagate(event),
+        }
+        Update::Consumed
+    }
+}
+
+impl Draw for JsonTree {
+    fn draw(&self, frame: &mut Frame, _: (), area: Rect) {
+        let theme = &TuiContext::get().theme;
+        self.window_height.set(area.height as usize);
+
+        // Only generate the lines that are on screen
+        let lines: Vec<Line> = self
+            .visible
+            .iter()
+            .enumerate()
+            .skip(self.offset_y)
+            .take(area.height as usize)
+            .map(|(i, node)| {
+                let line = self.line(*node);
+                if i == self.selected {
+                    line.patch_style(theme.list.highlight)
+                } else {
+                    line
+                }
+            })
+            .collect();
+        frame.render_widget(Paragraph::new(lines), area);
+    }
+}
+
+/// Recursively flatten a JSON value into a list of nodes, in document order
+fn flatten(
+    value: &Value,
+    parent: Option<usize>,
+    key: Option<PathSegment>,
+    depth: usize,
+    nodes: &mut Vec<Node>,
+) {
+    let index = nodes.len();
+    let node = |kind| Node {
+        depth,
+        parent,
+        key,
+        kind,
+    };
+    match value {
+        Value::Array(array) => {
+            nodes.push(node(NodeKind::Container {
+                is_array: true,
+                len: array.len(),
+                end: 0, // Filled in below
+            }));
+            for (i, child) in array.iter().enumerate() {
+                let key = Some(PathSegment::Index(i));
+                flatten(child, Some(index), key, depth + 1, nodes);
+            }
+        }
+        Value::Object(object) => {
+            nodes.push(node(NodeKind::Container {
+                is_array: false,
+                len: object.len(),
+                end: 0, // Filled in below
+            }));
+            for (k, child) in object {
+                let key = Some(PathSegment::Key(k.clone()));
+                flatten(child, Some(index), key, depth + 1, nodes);
+            }
+        }
+        _ => {
+            // serde_json can't fail serializing its own Value type
+            nodes.push(node(NodeKind::Scalar(value.to_string())));
+            return;
+        }
+    }
+    let subtree_end = nodes.len();
+    if let NodeKind::Container { end, .. } = &mut nodes[index].kind {
+        *end = subtree_end;
+    }
+}
+
+/// Can this object key be used in dot notation in a JSONPath?
+fn is_identifier(key: &str) -> bool {
+    let mut chars = key.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Get the path of each visible node
+    fn visible_paths(tree: &mut JsonTree) -> Vec<String> {
+        let selected = tree.selected;
+        let paths = (0..tree.visible.len())
+            .map(|i| {
+                tree.selected = i;
+                tree.selected_path()
+            })
+            .collect();
+        tree.selected = selected;
+        paths
+    }
+
+    #[test]
+    fn test_navigation() {
+        let mut tree = JsonTree::new(&json!({
+            "users": [{"name": "Ted"}, {"name": "Barney"}],
+            "weird key": 1,
+        }));
+        tree.window_height.set(10);
+
+        // Only the root is expanded initially
+        assert_eq!(
+            visible_paths(&mut tree),
+            vec!["$", "$.users", "$['weird key']"]
+        );
+
+        // Down into users, expand it, then into the first item
+        tree.select(1);
+        assert!(tree.expand());
+        assert!(tree.expand()); // Already expanded - select first child
+        assert_eq!(tree.selected_path(), "$.users[0]");
+        assert!(tree.expand());
+        assert_eq!(
+            visible_paths(&mut tree),
+            vec![
+                "$",
+                "$.users",
+                "$.users[0]",
+                "$.users[0].name",
+                "$.users[1]",
+                "$['weird key']",
+            ]
+        );
+
+        // Scalars can't be expanded
+        tree.select(3);
+        assert!(!tree.expand());
+
+        // Left goes to the parent, then collapses it
+        assert!(tree.collapse());
+        assert_eq!(tree.selected_path(), "$.users[0]");
+        assert!(tree.collapse());
+        assert!(tree.collapse());
+        assert_eq!(tree.selected_path(), "$.users");
+        assert!(tree.collapse());
+        assert_eq!(
+            visible_paths(&mut tree),
+            vec!["$", "$.users", "$['weird key']"]
+        );
+        assert_eq!(tree.selected_path(), "$.users");
+
+        // Root collapses, then there's nowhere left to go
+        assert!(tree.collapse());
+        assert!(tree.collapse());
+        assert!(!tree.collapse());
+        assert_eq!(visible_paths(&mut tree), vec!["$"]);
+    }
+
+    #[test]
+    fn test_selected_path_escaping() {
+        let mut tree = JsonTree::new(&json!({"it's": 1}));
+        tree.select(1);
+        assert_eq!(tree.selected_path(), "$['it\\'s']");
+    }
+}
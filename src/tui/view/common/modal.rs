@@ -33,6 +33,13 @@ pub trait Modal: Draw<()> + EventHandler {
     /// Optional callback when the modal is closed. Useful for finishing
     /// operations that require ownership of the modal data.
     fn on_close(self: Box<Self>) {}
+
+    /// Is this modal no longer relevant? E.g. a prompt for a request that
+    /// was cancelled. Stale modals are dropped from the queue without being
+    /// shown.
+    fn is_stale(&self) -> bool {
+        false
+    }
 }
 
 /// Define how a type can be converted into a modal. Often times, implementors
@@ -67,7 +74,12 @@ pub enum ModalPriority {
 impl ModalQueue {
     /// Is there a modal open right now?
     pub fn is_open(&self) -> bool {
-        !self.queue.is_empty()
+        self.active().is_some()
+    }
+
+    /// Get the modal being shown, skipping any that have gone stale
+    fn active(&self) -> Option<&Component<Box<dyn Modal>>> {
+        self.queue.iter().find(|modal| !modal.is_stale())
     }
 
     /// Add a new modal, to either the beginning or end of the queue, depending
@@ -87,8 +99,14 @@ impl ModalQueue {
     /// Close the current modal, and return the closed modal if any
     pub fn close(&mut self) -> Option<Box<dyn Modal>> {
         trace!("Closing modal");
+        self.prune();
         self.queue.pop_front().map(Component::into_inner)
     }
+
+    /// Drop all stale modals from the queue
+    fn prune(&mut self) {
+        self.queue.retain(|modal| !modal.is_stale());
+    }
 }
 
 impl EventHandler for ModalQueue {
@@ -124,6 +142,7 @@ impl EventHandler for ModalQueue {
     }
 
     fn children(&mut self) -> Vec<Component<&mut dyn EventHandler>> {
+        self.prune();
         match self.queue.front_mut() {
             Some(first) => vec![first.as_child()],
             None => vec![],
@@ -133,7 +152,7 @@ impl EventHandler for ModalQueue {
 
 impl Draw for ModalQueue {
     fn draw(&self, frame: &mut Frame, _: (), area: Rect) {
-        if let Some(modal) = self.queue.front() {
+        if let Some(modal) = self.active() {
             let (width, height) = modal.dimensions();
 
             // The child gave us the content dimensions, we need to add one cell
@@ -169,3 +188,73 @@ impl EventHandler for Box<dyn Modal> {
         self.deref_mut().children()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{cell::Cell, rc::Rc};
+
+    /// A modal that can be marked stale from outside the queue
+    #[derive(Debug)]
+    struct TestModal {
+        title: &'static str,
+        stale: Rc<Cell<bool>>,
+    }
+
+    /// Create a modal, and a handle to mark it stale
+    fn modal(title: &'static str) -> (Box<dyn Modal>, Rc<Cell<bool>>) {
+        let stale = Rc::new(Cell::new(false));
+        let modal = TestModal {
+            title,
+            stale: Rc::clone(&stale),
+        };
+        (Box::new(modal), stale)
+    }
+
+    impl Modal for TestModal {
+        fn title(&self) -> &str {
+            self.title
+        }
+
+        fn dimensions(&self) -> (Constraint, Constraint) {
+            (Constraint::Length(1), Constraint::Length(1))
+        }
+
+        fn is_stale(&self) -> bool {
+            self.stale.get()
+        }
+    }
+
+    impl EventHandler for TestModal {}
+
+    impl Draw for TestModal {
+        fn draw(&self, _: &mut Frame, _: (), _: Rect) {}
+    }
+
+    /// Stale modals are skipped while open, and dropped when the active modal
+    /// is closed
+    #[test]
+    fn test_prune_stale() {
+        let mut queue = ModalQueue::default();
+        let (first, first_stale) = modal("first");
+        let (second, _) = modal("second");
+        let (third, third_stale) = modal("third");
+        queue.open(first, ModalPriority::Low);
+        queue.open(second, ModalPriority::Low);
+        queue.open(third, ModalPriority::Low);
+        assert_eq!(queue.active().unwrap().title(), "first");
+
+        first_stale.set(true);
+        assert_eq!(queue.active().unwrap().title(), "second");
+
+        // Closing prunes the stale modal that was ahead of the active one
+        assert_eq!(queue.close().unwrap().title(), "second");
+        assert_eq!(queue.queue.len(), 1);
+
+        // Once everything left is stale, nothing is open
+        third_stale.set(true);
+        assert!(!queue.is_open());
+        assert!(queue.close().is_none());
+        assert!(queue.queue.is_empty());
+    }
+}
@@ -71,6 +71,40 @@ impl<'a, const COLS: usize> Generate for Table<'a, COLS, Row<'a>> {
     where
         Self: 'this;
 
+    fn generate<'this>(self) -> Self::Output<'this>
+    where
+        Self: 'this,
+    {
+        DynamicTable {
+            title: self.title,
+            rows: self.rows,
+            header: self.header.map(Row::new),
+            alternate_row_style: self.alternate_row_style,
+            column_widths: self.column_widths.to_vec(),
+        }
+        .generate()
+    }
+}
+
+/// Tabular data display where the number of columns isn't known until runtime,
+/// e.g. because it's loaded from a response body. Prefer [Table] when the
+/// columns are static, because it enforces the column count.
+#[derive(Debug, Default)]
+pub struct DynamicTable<'a> {
+    pub title: Option<&'a str>,
+    pub rows: Vec<Row<'a>>,
+    /// Optional header row. Length should match column length
+    pub header: Option<Row<'a>>,
+    /// Use a different styling for alternating rows
+    pub alternate_row_style: bool,
+    pub column_widths: Vec<Constraint>,
+}
+
+impl<'a> Generate for DynamicTable<'a> {
+    type Output<'this> = ratatui::widgets::Table<'this>
+    where
+        Self: 'this;
+
     fn generate<'this>(self) -> Self::Output<'this>
     where
         Self: 'this,
@@ -98,7 +132,7 @@ impl<'a, const COLS: usize> Generate for Table<'a, COLS, Row<'a>> {
 
         // Add optional header if given
         if let Some(header) = self.header {
-            table = table.header(Row::new(header).style(theme.table.header));
+            table = table.header(header.style(theme.table.header));
         }
 
         table
@@ -0,0 +1,212 @@
+use crate::tui::{
+    input::Action,
+    view::{
+        common::table::DynamicTable,
+        draw::{Draw, Generate},
+        event::{Event, EventHandler, Update},
+    },
+};
+use ratatui::{
+    prelude::{Alignment, Constraint, Rect},
+    text::Text,
+    widgets::Row,
+    Frame,
+};
+use std::{cell::Cell, cmp};
+
+/// A scrollable (but not editable) table of text cells, where the number of
+/// columns isn't known until runtime. Vertical scrolling moves by row, and
+/// horizontal scrolling moves by column. Like [TextWindow], the data is not
+/// externally mutable. If you need to update it, reconstruct the component.
+///
+/// [TextWindow]: super::text_window::TextWindow
+#[derive(derive_more::Debug)]
+pub struct TableWindow {
+    #[debug(skip)]
+    header: Vec<String>,
+    #[debug(skip)]
+    rows: Vec<Vec<String>>,
+    /// Width of each column, which is the width of its widest cell (capped)
+    column_widths: Vec<u16>,
+    /// Columns where every value is numeric are right-aligned, everything else
+    /// is left-aligned
+    column_alignments: Vec<Alignment>,
+    /// Index of the first visible row
+    offset_row: usize,
+    /// Index of the leftmost visible column
+    offset_column: usize,
+    window_height: Cell<usize>,
+}
+
+impl TableWindow {
+    /// Cells wider than this will be truncated
+    const MAX_COLUMN_WIDTH: u16 = 40;
+
+    pub fn new(header: Vec<String>, rows: Vec<Vec<String>>) -> Self {
+        // Rows can be ragged, so the widest row defines the column count
+        let num_columns = rows
+            .iter()
+            .map(Vec::len)
+            .chain([header.len()])
+            .max()
+            .unwrap_or_default();
+
+        let mut column_widths = vec![0; num_columns];
+        let mut numeric = vec![true; num_columns];
+        for (i, cell) in header.iter().enumerate() {
+            column_widths[i] = cell.chars().count();
+        }
+        for row in &rows {
+            for (i, cell) in row.iter().enumerate() {
+                column_widths[i] =
+                    cmp::max(column_widths[i], cell.chars().count());
+                numeric[i] &= cell.is_empty() || cell.parse::<f64>().is_ok();
+            }
+        }
+
+        Self {
+            header,
+            rows,
+            column_widths: column_widths
+                .into_iter()
+                .map(|width| {
+                    cmp::min(width, Self::MAX_COLUMN_WIDTH as usize) as u16
+                })
+                .collect(),
+            column_alignments: numeric
+                .into_iter()
+                .map(|numeric| {
+                    if numeric {
+                        Alignment::Right
+                    } else {
+                        Alignment::Left
+                    }
+                })
+                .collect(),
+            offset_row: 0,
+            offset_column: 0,
+            window_height: Cell::default(),
+        }
+    }
+
+    /// Get the final row that we can't scroll past. This will be the first row
+    /// of the last page
+    fn max_scroll_row(&self) -> usize {
+        self.rows.len().saturating_sub(self.window_height.get())
+    }
+
+    fn scroll_up(&mut self, rows: usize) {
+        self.offset_row = self.offset_row.saturating_sub(rows);
+    }
+
+    fn scroll_down(&mut self, rows: usize) {
+        self.offset_row =
+            cmp::min(self.offset_row + rows, self.max_scroll_row());
+    }
+
+    /// Scroll to a specific row. The target row will end up as close to the top
+    /// of the page as possible
+    fn scroll_to(&mut self, row: usize) {
+        self.offset_row = cmp::min(row, self.max_scroll_row());
+    }
+
+    fn scroll_left(&mut self) {
+        self.offset_column = self.offset_column.saturating_sub(1);
+    }
+
+    fn scroll_right(&mut self) {
+        self.offset_column = cmp::min(
+            self.offset_column + 1,
+            self.column_widths.len().saturating_sub(1),
+        );
+    }
+
+    /// Build a row of cells, only including visible columns
+    fn row<'a>(&self, cells: &'a [String], columns: usize) -> Row<'a> {
+        Row::new((self.offset_column..self.offset_column + columns).map(|i| {
+            let cell = cells.get(i).map(String::as_str).unwrap_or_default();
+            Text::from(cell).alignment(self.column_alignments[i])
+        }))
+    }
+}
+
+impl EventHandler for TableWindow {
+    fn update(&mut self, event: Event) -> Update {
+        match event {
+            Event::Input {
+                action: Some(action),
+                ..
+            } => match action {
+                Action::Up | Action::ScrollUp => self.scroll_up(1),
+                Action::Down | Action::ScrollDown => self.scroll_down(1),
+                Action::ScrollLeft => self.scroll_left(),
+                Action::ScrollRight => self.scroll_right(),
+                Action::PageUp => self.scroll_up(self.window_height.get()),
+                Action::PageDown => self.scroll_down(self.window_height.get()),
+                Action::Home => self.scroll_to(0),
+                Action::End => self.scroll_to(usize::MAX),
+                _ => return Update::Propagate(event),
+            },
+            _ => return Update::Propagate(event),
+        }
+        Update::Consumed
+    }
+}
+
+impl Draw for TableWindow {
+    fn draw(&self, frame: &mut Frame, _: (), area: Rect) {
+        // Header takes up one row
+        let window_height = area.height.saturating_sub(1) as usize;
+        self.window_height.set(window_height);
+
+        // Include as many columns as will fit, starting from the scroll offset.
+        // The last one may get cut off, which is fine.
+        let mut total_width = 0;
+        let column_widths: Vec<Constraint> = self.column_widths
+            [self.offset_column..]
+            .iter()
+            .take_while(|width| {
+                let fits = total_width < area.width;
+                // Add 1 for the column spacing
+                total_width = total_width.saturating_add(**width + 1);
+                fits
+            })
+            .map(|width| Constraint::Length(*width))
+            .collect();
+        let columns = column_widths.len();
+
+        let rows = self.rows[self.offset_row..]
+            .iter()
+            .take(window_height)
+            .map(|row| self.row(row, columns))
+            .collect();
+        let table = DynamicTable {
+            header: Some(self.row(&self.header, columns)),
+            rows,
+            column_widths,
+            ..Default::default()
+        };
+        frame.render_widget(table.generate(), area);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_column_layout() {
+        let table = TableWindow::new(
+            vec!["name".into(), "count".into()],
+            vec![
+                vec!["widget".into(), "3".into()],
+                vec!["gadget".into(), "".into(), "extra".into()],
+            ],
+        );
+        assert_eq!(table.column_widths, vec![6, 5, 5]);
+        assert_eq!(
+            table.column_alignments,
+            vec![Alignment::Left, Alignment::Right, Alignment::Left]
+        );
+    }
+}
@@ -4,39 +4,49 @@ use crate::tui::{
     view::{
         draw::{Draw, Generate},
         event::{Event, EventHandler, Update},
+        highlight::Language,
         util::layout,
     },
 };
 use ratatui::{
     prelude::{Alignment, Constraint, Direction, Rect},
-    text::{Line, Text},
+    text::{Line, Span, Text},
     widgets::Paragraph,
     Frame,
 };
-use std::{cell::Cell, cmp, fmt::Debug};
+use std::{cell::Cell, cmp, fmt::Debug, iter};
+
+/// Lines longer than this (in bytes) won't be syntax highlighted
+const MAX_HIGHLIGHT_LENGTH: usize = 10_000;
 
 /// A scrollable (but not editable) block of text. Text is not externally
 /// mutable. If you need to update the text, store this in a `StateCell` and
 /// reconstruct the entire component.
 ///
-/// The generic parameter allows for any type that can be converted to ratatui's
-/// `Text`, e.g. `String` or `TemplatePreview`.
+/// The generic parameter allows for any type that implements [WindowText],
+/// e.g. [IndexedText] or `TemplatePreview`. Only the visible lines are
+/// rendered. If a language is given, the visible lines are syntax highlighted
+/// too, which keeps large bodies cheap to draw.
 #[derive(derive_more::Debug, Default)]
 pub struct TextWindow<T> {
     #[debug(skip)]
     text: T,
+    highlight: Option<Language>,
     offset_x: u16,
-    offset_y: u16,
+    /// Vertical values are `usize` because large bodies can have more lines
+    /// than a `u16` can count
+    offset_y: usize,
     text_width: Cell<u16>,
-    text_height: Cell<u16>,
+    text_height: Cell<usize>,
     window_width: Cell<u16>,
-    window_height: Cell<u16>,
+    window_height: Cell<usize>,
 }
 
 impl<T> TextWindow<T> {
     pub fn new(text: T) -> Self {
         Self {
             text,
+            highlight: None,
             offset_x: 0,
             offset_y: 0,
             text_width: Cell::default(),
@@ -46,13 +56,19 @@ impl<T> TextWindow<T> {
         }
     }
 
+    /// Enable syntax highlighting for the given language
+    pub fn with_highlight(mut self, language: Option<Language>) -> Self {
+        self.highlight = language;
+        self
+    }
+
     pub fn text(&self) -> &T {
         &self.text
     }
 
     /// Get the final line that we can't scroll past. This will be the first
     /// line of the last page of text
-    fn max_scroll_line(&self) -> u16 {
+    fn max_scroll_line(&self) -> usize {
         self.text_height
             .get()
             .saturating_sub(self.window_height.get())
@@ -66,17 +82,20 @@ impl<T> TextWindow<T> {
             .saturating_sub(self.window_width.get())
     }
 
-    fn scroll_up(&mut self, lines: u16) {
+    fn scroll_up(&mut self, lines: usize) {
         self.offset_y = self.offset_y.saturating_sub(lines);
     }
 
-    fn scroll_down(&mut self, lines: u16) {
-        self.offset_y = cmp::min(self.offset_y + lines, self.max_scroll_line());
+    fn scroll_down(&mut self, lines: usize) {
+        self.offset_y = cmp::min(
+            self.offset_y.saturating_add(lines),
+            self.max_scroll_line(),
+        );
     }
 
     /// Scroll to a specific line number. The target line will end up as close
     /// to the top of the page as possible
-    fn scroll_to(&mut self, line: u16) {
+    fn scroll_to(&mut self, line: usize) {
         self.offset_y = cmp::min(line, self.max_scroll_line());
     }
 
@@ -104,7 +123,7 @@ impl<T: Debug> EventHandler for TextWindow<T> {
                 Action::PageUp => self.scroll_up(self.window_height.get()),
                 Action::PageDown => self.scroll_down(self.window_height.get()),
                 Action::Home => self.scroll_to(0),
-                Action::End => self.scroll_to(u16::MAX),
+                Action::End => self.scroll_to(usize::MAX),
                 _ => return Update::Propagate(event),
             },
             _ => return Update::Propagate(event),
@@ -113,16 +132,11 @@ impl<T: Debug> EventHandler for TextWindow<T> {
     }
 }
 
-impl<T> Draw for TextWindow<T>
-where
-    T: 'static,
-    for<'a> &'a T: Generate<Output<'a> = Text<'a>>,
-{
+impl<T: WindowText> Draw for TextWindow<T> {
     fn draw(&self, frame: &mut Frame, _: (), area: Rect) {
         let theme = &TuiContext::get().theme;
-        let text = Paragraph::new(self.text.generate());
         // Assume no line wrapping when calculating line count
-        let text_height = text.line_count(u16::MAX) as u16;
+        let text_height = self.text.line_count();
 
         let [gutter_area, _, text_area] = layout(
             area,
@@ -138,14 +152,16 @@ where
         );
 
         // Store text and window sizes for calculations in the update code
-        self.text_width.set(text.line_width() as u16);
+        self.text_width
+            .set(cmp::min(self.text.width(), u16::MAX as usize) as u16);
         self.text_height.set(text_height);
         self.window_width.set(text_area.width);
-        self.window_height.set(text_area.height);
+        self.window_height.set(text_area.height as usize);
 
         // Draw line numbers in the gutter
         let first_line = self.offset_y + 1;
-        let last_line = cmp::min(first_line + text_area.height, text_height);
+        let last_line =
+            cmp::min(first_line + text_area.height as usize, text_height);
         frame.render_widget(
             Paragraph::new(
                 (first_line..=last_line)
@@ -157,10 +173,179 @@ where
             gutter_area,
         );
 
-        // Darw the text content
+        // Draw the text content. Only the visible lines are laid out
+        let visible = self.text.lines(self.offset_y, text_area.height as usize);
+        let raw_lines: Vec<String>;
+        let lines: Vec<Line> = match self.highlight {
+            Some(language) => {
+                // Highlight each line's raw content from scratch
+                raw_lines = visible
+                    .into_iter()
+                    .map(|line| {
+                        line.spans
+                            .into_iter()
+                            .map(|span| span.content)
+                            .collect::<String>()
+                    })
+                    .collect();
+                raw_lines
+                    .iter()
+                    .map(|line| {
+                        // Highlighting a massive line (e.g. minified JSON)
+                        // on every frame is too slow
+                        if line.len() > MAX_HIGHLIGHT_LENGTH {
+                            line.as_str().into()
+                        } else {
+                            language.highlight(line, &theme.syntax)
+                        }
+                    })
+                    .collect()
+            }
+            None => visible,
+        };
         frame.render_widget(
-            text.scroll((self.offset_y, self.offset_x)),
+            Paragraph::new(lines).scroll((0, self.offset_x)),
             text_area,
         );
     }
 }
+
+/// Text that can be displayed in a [TextWindow]. Only the visible lines are
+/// requested on each draw, so implementors backed by large text can avoid work
+/// proportional to the size of the entire text.
+pub trait WindowText {
+    /// Total number of lines
+    fn line_count(&self) -> usize;
+
+    /// Width of the widest line, in columns
+    fn width(&self) -> usize;
+
+    /// Get up to `count` lines, starting at line `start`
+    fn lines(&self, start: usize, count: usize) -> Vec<Line<'_>>;
+}
+
+/// Text that's been split into lines ahead of time, so any line can be
+/// accessed without scanning the text. Use this for text that could be large,
+/// such as request/response bodies. Building this is linear in the size of the
+/// text, so for large text it should be done off the main thread.
+#[derive(Debug, Default)]
+pub struct IndexedText {
+    text: String,
+    /// Byte offset of the start of each line
+    line_starts: Vec<usize>,
+    width: usize,
+}
+
+impl IndexedText {
+    pub fn new(text: String) -> Self {
+        let line_starts: Vec<usize> = iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            // A trailing newline doesn't start a new line
+            .filter(|start| *start < text.len())
+            .collect();
+        let mut indexed = Self {
+            text,
+            line_starts,
+            width: 0,
+        };
+        indexed.width = (0..indexed.line_starts.len())
+            .map(|index| Span::raw(indexed.line(index)).width())
+            .max()
+            .unwrap_or(0);
+        indexed
+    }
+
+    /// Get the full text
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    /// Get a single line, without its line ending
+    fn line(&self, index: usize) -> &str {
+        let start = self.line_starts[index];
+        let end = self
+            .line_starts
+            .get(index + 1)
+            .copied()
+            .unwrap_or(self.text.len());
+        let line = &self.text[start..end];
+        let line = line.strip_suffix('\n').unwrap_or(line);
+        line.strip_suffix('\r').unwrap_or(line)
+    }
+}
+
+impl WindowText for IndexedText {
+    fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn lines(&self, start: usize, count: usize) -> Vec<Line<'_>> {
+        let end = cmp::min(start.saturating_add(count), self.line_count());
+        (start..end).map(|index| self.line(index).into()).collect()
+    }
+}
+
+/// Small text can just be generated in full on every draw
+impl<T: 'static> WindowText for T
+where
+    for<'a> &'a T: Generate<Output<'a> = Text<'a>>,
+{
+    fn line_count(&self) -> usize {
+        self.generate().lines.len()
+    }
+
+    fn width(&self) -> usize {
+        self.generate().width()
+    }
+
+    fn lines(&self, start: usize, count: usize) -> Vec<Line<'_>> {
+        self.generate()
+            .lines
+            .into_iter()
+            .skip(start)
+            .take(count)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::empty("", &[])]
+    #[case::single("one", &["one"])]
+    #[case::trailing_newline("one\ntwo\n", &["one", "two"])]
+    #[case::blank_lines("one\n\nthree", &["one", "", "three"])]
+    #[case::crlf("one\r\ntwo", &["one", "two"])]
+    fn test_indexed_text(#[case] text: &str, #[case] expected: &[&str]) {
+        let text = IndexedText::new(text.into());
+        assert_eq!(text.line_count(), expected.len());
+        assert_eq!(
+            text.width(),
+            expected.iter().map(|line| line.len()).max().unwrap_or(0)
+        );
+        assert_eq!(
+            text.lines(0, usize::MAX),
+            expected
+                .iter()
+                .map(|line| Line::from(*line))
+                .collect::<Vec<_>>()
+        );
+        // Windowed access
+        assert_eq!(
+            text.lines(1, 1),
+            expected
+                .iter()
+                .skip(1)
+                .take(1)
+                .map(|line| Line::from(*line))
+                .collect::<Vec<_>>()
+        );
+    }
+}
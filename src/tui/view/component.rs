@@ -1,5 +1,7 @@
 //! Specific single-use components
 
+mod cookies;
+mod graphql;
 mod help;
 mod misc;
 mod primary;
@@ -8,9 +10,12 @@ mod profile_list;
 mod recipe_list;
 mod recipe_pane;
 mod record_body;
+mod redirect_list;
 mod request_pane;
 mod response_pane;
 mod root;
+mod websocket_pane;
+mod workflow;
 
 pub use root::Root;
 
@@ -0,0 +1,234 @@
+//! Modal for inspecting and editing the cookie jar
+
+use crate::{
+    collection::ProfileId,
+    http::Cookie,
+    tui::{
+        context::TuiContext,
+        input::Action,
+        view::{
+            common::{modal::Modal, table::Table, text_box::TextBox},
+            draw::{Draw, Generate},
+            event::{Event, EventHandler, EventQueue, Update},
+            state::select::SelectState,
+            util::layout,
+            Component,
+        },
+    },
+};
+use itertools::Itertools;
+use ratatui::{
+    layout::{Constraint, Direction, Rect},
+    widgets::{Paragraph, TableState},
+    Frame,
+};
+
+/// Show all cookies in the jar for a profile. The selected cookie's value can
+/// be edited or the cookie deleted. Changes are written to the database
+/// immediately.
+#[derive(derive_more::Debug)]
+pub struct CookiesModal {
+    profile_id: Option<ProfileId>,
+    title: String,
+    #[debug(skip)]
+    cookies: Component<SelectState<Cookie, TableState>>,
+    /// Text box for editing the selected cookie's value. `Some` only while
+    /// editing
+    #[debug(skip)]
+    edit_text_box: Option<Component<TextBox>>,
+}
+
+/// Callback event to start editing the selected cookie
+struct EditCookie;
+
+/// Callback event from the edit text box. `None` if editing was cancelled
+struct EditSubmit(Option<String>);
+
+impl CookiesModal {
+    pub fn new(profile_id: Option<ProfileId>) -> Self {
+        let title = match &profile_id {
+            Some(profile_id) => format!("Cookies ({profile_id})"),
+            None => "Cookies".into(),
+        };
+        let mut modal = Self {
+            profile_id,
+            title,
+            cookies: Default::default(),
+            edit_text_box: None,
+        };
+        modal.reload();
+        modal
+    }
+
+    /// Load cookies from the database, retaining the current selection if
+    /// possible
+    fn reload(&mut self) {
+        let selected = self.cookies.selected_index();
+        let cookies = TuiContext::get()
+            .database
+            .get_cookies(self.profile_id.as_ref())
+            // Error is already traced, just show an empty jar
+            .unwrap_or_default();
+        let len = cookies.len();
+        let state: SelectState<_, TableState> = SelectState::new(cookies)
+            .on_submit(|_| EventQueue::push(Event::other(EditCookie)));
+        if let Some(selected) = selected {
+            state
+                .state_mut()
+                .select(Some(selected.min(len.saturating_sub(1))));
+        }
+        self.cookies = state.into();
+    }
+
+    fn start_edit(&mut self) {
+        if let Some(cookie) = self.cookies.selected() {
+            self.edit_text_box = Some(
+                TextBox::default()
+                    .with_default(cookie.value.clone())
+                    .with_on_submit(|text_box| {
+                        EventQueue::push(Event::other(EditSubmit(Some(
+                            text_box.text().to_owned(),
+                        ))))
+                    })
+                    .with_on_cancel(|_| {
+                        EventQueue::push(Event::other(EditSubmit(None)))
+                    })
+                    .into(),
+            );
+        }
+    }
+
+    fn finish_edit(&mut self, value: Option<String>) {
+        self.edit_text_box = None;
+        if let (Some(value), Some(cookie)) = (value, self.cookies.selected()) {
+            let cookie = Cookie {
+                value,
+                ..cookie.clone()
+            };
+            let _ = TuiContext::get()
+                .database
+                .set_cookie(self.profile_id.as_ref(), &cookie);
+            self.reload();
+        }
+    }
+
+    fn delete_selected(&mut self) {
+        if let Some(cookie) = self.cookies.selected() {
+            let _ = TuiContext::get()
+                .database
+                .delete_cookie(self.profile_id.as_ref(), cookie);
+            self.reload();
+        }
+    }
+}
+
+impl Modal for CookiesModal {
+    fn title(&self) -> &str {
+        &self.title
+    }
+
+    fn dimensions(&self) -> (Constraint, Constraint) {
+        (Constraint::Percentage(80), Constraint::Percentage(50))
+    }
+}
+
+impl EventHandler for CookiesModal {
+    fn update(&mut self, event: Event) -> Update {
+        match event {
+            Event::Input {
+                action: Some(Action::Delete),
+                ..
+            } => self.delete_selected(),
+            Event::Other(ref other) => {
+                if other.is::<EditCookie>() {
+                    self.start_edit();
+                } else if let Some(EditSubmit(value)) =
+                    other.downcast_ref::<EditSubmit>()
+                {
+                    self.finish_edit(value.clone());
+                } else {
+                    return Update::Propagate(event);
+                }
+            }
+            _ => return Update::Propagate(event),
+        }
+        Update::Consumed
+    }
+
+    fn children(&mut self) -> Vec<Component<&mut dyn EventHandler>> {
+        match &mut self.edit_text_box {
+            Some(text_box) => vec![text_box.as_child()],
+            None => vec![self.cookies.as_child()],
+        }
+    }
+}
+
+impl Draw for CookiesModal {
+    fn draw(&self, frame: &mut Frame, _: (), area: Rect) {
+        if self.cookies.items().is_empty() {
+            frame.render_widget(Paragraph::new("No cookies"), area);
+            return;
+        }
+
+        let input_engine = &TuiContext::get().input_engine;
+        let [table_area, edit_area, help_area] = layout(
+            area,
+            Direction::Vertical,
+            [
+                Constraint::Min(0),
+                Constraint::Length(self.edit_text_box.is_some() as u16),
+                Constraint::Length(1),
+            ],
+        );
+
+        let table = Table {
+            header: Some(["Domain", "Path", "Name", "Value", "Expires"]),
+            rows: self
+                .cookies
+                .items()
+                .iter()
+                .map(|cookie| {
+                    let domain = if cookie.host_only {
+                        cookie.domain.clone()
+                    } else {
+                        format!(".{}", cookie.domain)
+                    };
+                    let expires = cookie
+                        .expires
+                        .map(|expires| expires.to_rfc3339())
+                        .unwrap_or_else(|| "Session".into());
+                    [
+                        domain,
+                        cookie.path.clone(),
+                        cookie.name.clone(),
+                        cookie.value.clone(),
+                        expires,
+                    ]
+                })
+                .collect_vec(),
+            column_widths: &[
+                Constraint::Percentage(20),
+                Constraint::Percentage(10),
+                Constraint::Percentage(20),
+                Constraint::Percentage(30),
+                Constraint::Percentage(20),
+            ],
+            ..Default::default()
+        };
+        frame.render_stateful_widget(
+            table.generate(),
+            table_area,
+            &mut self.cookies.state_mut(),
+        );
+
+        if let Some(text_box) = &self.edit_text_box {
+            text_box.draw(frame, (), edit_area);
+        }
+
+        let help = [(Action::Submit, "Edit"), (Action::Delete, "Delete")]
+            .into_iter()
+            .map(|(action, label)| input_engine.add_hint(label, action))
+            .join(" / ");
+        frame.render_widget(Paragraph::new(help), help_area);
+    }
+}
@@ -0,0 +1,105 @@
+//! Modal for browsing a recipe's GraphQL schema
+
+use crate::{
+    collection::{ProfileId, RecipeId},
+    tui::{
+        context::TuiContext,
+        view::{
+            common::{modal::Modal, table::Table},
+            draw::{Draw, Generate},
+            event::EventHandler,
+            state::select::SelectState,
+            Component,
+        },
+    },
+};
+use itertools::Itertools;
+use ratatui::{
+    layout::{Constraint, Rect},
+    widgets::{Paragraph, TableState, Wrap},
+    Frame,
+};
+
+/// List all operations in the cached schema for a recipe. The schema is
+/// loaded once when the modal is opened. If it hasn't been introspected yet,
+/// tell the user how to do that.
+#[derive(derive_more::Debug)]
+pub struct GraphQlOperationsModal {
+    title: String,
+    /// Operation kind and signature. `None` if there's no cached schema
+    #[debug(skip)]
+    operations: Option<Component<SelectState<[String; 2], TableState>>>,
+}
+
+impl GraphQlOperationsModal {
+    pub fn new(profile_id: Option<ProfileId>, recipe_id: RecipeId) -> Self {
+        let schema = TuiContext::get()
+            .database
+            .get_graphql_schema(profile_id.as_ref(), &recipe_id)
+            // Error is already traced, treat it as not cached
+            .unwrap_or_default();
+        let operations = schema.map(|schema| {
+            let operations = schema
+                .operations()
+                .into_iter()
+                .map(|(kind, field)| [kind.to_string(), field.to_string()])
+                .collect_vec();
+            SelectState::new(operations).into()
+        });
+        Self {
+            title: format!("GraphQL Operations ({recipe_id})"),
+            operations,
+        }
+    }
+}
+
+impl Modal for GraphQlOperationsModal {
+    fn title(&self) -> &str {
+        &self.title
+    }
+
+    fn dimensions(&self) -> (Constraint, Constraint) {
+        (Constraint::Percentage(80), Constraint::Percentage(60))
+    }
+}
+
+impl EventHandler for GraphQlOperationsModal {
+    fn children(&mut self) -> Vec<Component<&mut dyn EventHandler>> {
+        self.operations
+            .iter_mut()
+            .map(Component::as_child)
+            .collect()
+    }
+}
+
+impl Draw for GraphQlOperationsModal {
+    fn draw(&self, frame: &mut Frame, _: (), area: Rect) {
+        let Some(operations) = &self.operations else {
+            frame.render_widget(
+                Paragraph::new(
+                    "No schema loaded. Use Introspect GraphQL Schema in the \
+                    recipe's actions menu to fetch it.",
+                )
+                .wrap(Wrap::default()),
+                area,
+            );
+            return;
+        };
+
+        let table = Table {
+            header: Some(["Kind", "Operation"]),
+            rows: operations
+                .items()
+                .iter()
+                .map(|[kind, signature]| [kind.as_str(), signature.as_str()])
+                .collect_vec(),
+            column_widths: &[Constraint::Length(12), Constraint::Min(0)],
+            ..Default::default()
+        };
+        frame.render_stateful_widget(
+            table.generate(),
+            area,
+            &mut operations.state_mut(),
+        );
+    }
+}
@@ -3,19 +3,24 @@
 
 use crate::{
     template::{Prompt, PromptChannel},
-    tui::view::{
-        common::{
-            modal::{IntoModal, Modal},
-            text_box::TextBox,
+    tui::{
+        context::TuiContext,
+        input::Action,
+        view::{
+            common::{
+                modal::{IntoModal, Modal},
+                text_box::TextBox,
+            },
+            component::Component,
+            draw::{Draw, Generate},
+            event::{Event, EventHandler, EventQueue, Update},
+            state::Notification,
         },
-        component::Component,
-        draw::{Draw, Generate},
-        event::{Event, EventHandler, EventQueue},
-        state::Notification,
     },
 };
 use ratatui::{
     prelude::{Constraint, Rect},
+    text::{Line, Text},
     widgets::{Paragraph, Wrap},
     Frame,
 };
@@ -109,6 +114,11 @@ impl Modal for PromptModal {
             self.channel.respond(self.text_box.into_inner().into_text());
         }
     }
+
+    fn is_stale(&self) -> bool {
+        // The request asking for this was cancelled or failed
+        self.channel.is_closed()
+    }
 }
 
 impl EventHandler for PromptModal {
@@ -131,6 +141,72 @@ impl IntoModal for Prompt {
     }
 }
 
+/// Ask the user to confirm a destructive action before doing it. Submit runs
+/// the action, and cancel closes the modal without doing anything.
+#[derive(derive_more::Debug)]
+pub struct ConfirmModal {
+    title: String,
+    message: String,
+    #[debug(skip)]
+    on_confirm: Box<dyn Fn()>,
+}
+
+impl ConfirmModal {
+    pub fn new(
+        title: impl Into<String>,
+        message: impl Into<String>,
+        on_confirm: impl 'static + Fn(),
+    ) -> Self {
+        Self {
+            title: title.into(),
+            message: message.into(),
+            on_confirm: Box::new(on_confirm),
+        }
+    }
+}
+
+impl Modal for ConfirmModal {
+    fn title(&self) -> &str {
+        &self.title
+    }
+
+    fn dimensions(&self) -> (Constraint, Constraint) {
+        (Constraint::Percentage(40), Constraint::Length(3))
+    }
+}
+
+impl EventHandler for ConfirmModal {
+    fn update(&mut self, event: Event) -> Update {
+        match event {
+            Event::Input {
+                action: Some(Action::Submit),
+                ..
+            } => {
+                (self.on_confirm)();
+                EventQueue::push(Event::CloseModal);
+                Update::Consumed
+            }
+            _ => Update::Propagate(event),
+        }
+    }
+}
+
+impl Draw for ConfirmModal {
+    fn draw(&self, frame: &mut Frame, _: (), area: Rect) {
+        let input_engine = &TuiContext::get().input_engine;
+        let text = Text::from(vec![
+            Line::from(self.message.as_str()),
+            Line::default(),
+            Line::from(format!(
+                "{} / {}",
+                input_engine.add_hint("Confirm", Action::Submit),
+                input_engine.add_hint("Cancel", Action::Cancel)
+            )),
+        ]);
+        frame.render_widget(Paragraph::new(text).wrap(Wrap::default()), area);
+    }
+}
+
 #[derive(Debug)]
 pub struct NotificationText {
     notification: Notification,
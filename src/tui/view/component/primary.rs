@@ -1,7 +1,9 @@
 //! Components for the "primary" view, which is the paned request/response view
 
 use crate::{
-    collection::{Collection, Profile, Recipe},
+    collection::{
+        Collection, Profile, ProfileId, Recipe, RecipeId, WebSocketRecipe,
+    },
     tui::{
         context::TuiContext,
         input::Action,
@@ -16,13 +18,14 @@ use crate::{
                 recipe_pane::{RecipePane, RecipePaneProps},
                 request_pane::{RequestPane, RequestPaneProps},
                 response_pane::{ResponsePane, ResponsePaneProps},
+                websocket_pane::{WebSocketPane, WebSocketPaneProps},
             },
             draw::Draw,
             event::{Event, EventHandler, EventQueue, Update},
             state::{
                 fixed_select::FixedSelectState,
                 persistence::{Persistent, PersistentKey},
-                RequestState,
+                RequestState, StateCell, WebSocketSession,
             },
             util::layout,
             Component,
@@ -44,6 +47,9 @@ pub struct PrimaryView {
     // Own state
     selected_pane: Persistent<FixedSelectState<PrimaryPane>>,
     fullscreen_mode: Persistent<Option<FullscreenMode>>,
+    /// The selected WebSocket recipe, converted to a regular recipe so it can
+    /// be shown in the recipe pane
+    websocket_recipe: StateCell<RecipeId, Recipe>,
 
     // Children
     #[debug(skip)]
@@ -58,10 +64,15 @@ pub struct PrimaryView {
     request_pane: Component<RequestPane>,
     #[debug(skip)]
     response_pane: Component<ResponsePane>,
+    /// Replaces the request and response panes for WebSocket recipes
+    #[debug(skip)]
+    websocket_pane: Component<WebSocketPane>,
 }
 
 pub struct PrimaryViewProps<'a> {
     pub active_request: Option<&'a RequestState>,
+    /// Session for the selected WebSocket recipe, if any
+    pub active_session: Option<&'a WebSocketSession>,
 }
 
 /// Selectable panes in the primary view mode
@@ -113,6 +124,7 @@ impl PrimaryView {
                 PersistentKey::FullscreenMode,
                 None,
             ),
+            websocket_recipe: Default::default(),
 
             profile_list_pane,
             recipe_list_pane,
@@ -120,6 +132,7 @@ impl PrimaryView {
             recipe_pane: Default::default(),
             request_pane: Default::default(),
             response_pane: Default::default(),
+            websocket_pane: Default::default(),
         }
     }
 
@@ -129,6 +142,26 @@ impl PrimaryView {
         self.recipe_list_pane.selected_recipe()
     }
 
+    /// Which WebSocket recipe in the recipe list is selected? `None` if a
+    /// regular recipe or folder is selected.
+    pub fn selected_websocket(&self) -> Option<&WebSocketRecipe> {
+        self.recipe_list_pane.selected_websocket()
+    }
+
+    /// Select a recipe, and the profile it was rendered with, and focus the
+    /// recipe list
+    pub fn select_recipe(
+        &mut self,
+        profile_id: Option<&ProfileId>,
+        recipe_id: &RecipeId,
+    ) {
+        if let Some(profile_id) = profile_id {
+            self.profile_list_pane.select_profile(profile_id);
+        }
+        self.recipe_list_pane.select_recipe(recipe_id);
+        self.selected_pane.select(&PrimaryPane::RecipeList);
+    }
+
     /// Which profile in the list is selected? `None` iff the list is empty.
     /// Exposing inner state is hacky but it's an easy shortcut
     pub fn selected_profile(&self) -> Option<&Profile> {
@@ -181,19 +214,28 @@ impl PrimaryView {
                 recipe_area,
             )
         } else {
-            self.recipe_pane.draw(
+            self.draw_recipe_pane(
                 frame,
-                RecipePaneProps {
-                    is_selected: self.is_selected(PrimaryPane::Recipe),
-                    selected_recipe: self.selected_recipe(),
-                    selected_profile_id: self
-                        .selected_profile()
-                        .map(|profile| &profile.id),
-                },
+                self.is_selected(PrimaryPane::Recipe),
                 recipe_area,
             );
         }
 
+        // WebSocket sessions don't fit the request/response model, so they
+        // get one pane in place of both
+        if self.selected_websocket().is_some() {
+            self.websocket_pane.draw(
+                frame,
+                WebSocketPaneProps {
+                    is_selected: self.is_selected(PrimaryPane::Request)
+                        || self.is_selected(PrimaryPane::Response),
+                    session: props.active_session,
+                },
+                request_area.union(response_area),
+            );
+            return;
+        }
+
         self.request_pane.draw(
             frame,
             RequestPaneProps {
@@ -207,11 +249,50 @@ impl PrimaryView {
             ResponsePaneProps {
                 is_selected: self.is_selected(PrimaryPane::Response),
                 active_request: props.active_request,
+                assertions: self
+                    .selected_recipe()
+                    .map(|recipe| &recipe.assertions),
             },
             response_area,
         );
     }
 
+    /// Draw the recipe pane for the selected recipe. WebSocket recipes are
+    /// shown as their handshake request
+    fn draw_recipe_pane(
+        &self,
+        frame: &mut Frame,
+        is_selected: bool,
+        area: Rect,
+    ) {
+        let selected_profile_id =
+            self.selected_profile().map(|profile| &profile.id);
+        if let Some(websocket) = self.selected_websocket() {
+            let recipe = self
+                .websocket_recipe
+                .get_or_update(websocket.id.clone(), || websocket.to_recipe());
+            self.recipe_pane.draw(
+                frame,
+                RecipePaneProps {
+                    is_selected,
+                    selected_recipe: Some(&recipe),
+                    selected_profile_id,
+                },
+                area,
+            );
+        } else {
+            self.recipe_pane.draw(
+                frame,
+                RecipePaneProps {
+                    is_selected,
+                    selected_recipe: self.selected_recipe(),
+                    selected_profile_id,
+                },
+                area,
+            );
+        }
+    }
+
     fn toggle_fullscreen(&mut self, mode: FullscreenMode) {
         // If we're already in the given mode, exit
         *self.fullscreen_mode = if Some(mode) == *self.fullscreen_mode {
@@ -280,9 +361,19 @@ impl EventHandler for PrimaryView {
                     });
                 }
             }
-            // Send HTTP request
+            // Send HTTP request, or open a WebSocket connection
             Event::HttpSendRequest => {
-                if let Some(recipe) = self.selected_recipe() {
+                if let Some(websocket) = self.selected_websocket() {
+                    TuiContext::send_message(Message::WebSocketConnect(
+                        RequestConfig {
+                            recipe_id: websocket.id.clone(),
+                            profile_id: self
+                                .selected_profile()
+                                .map(|profile| profile.id.clone()),
+                            options: self.recipe_pane.recipe_options(),
+                        },
+                    ));
+                } else if let Some(recipe) = self.selected_recipe() {
                     TuiContext::send_message(Message::HttpBeginRequest(
                         RequestConfig {
                             // Reach into the children to grab state (ugly!)
@@ -313,7 +404,10 @@ impl EventHandler for PrimaryView {
                         self.selected_pane.select(&PrimaryPane::RecipeList);
                     } else if self.recipe_pane.intersects(mouse) {
                         self.selected_pane.select(&PrimaryPane::Recipe);
-                    } else if self.request_pane.intersects(mouse) {
+                    } else if self.request_pane.intersects(mouse)
+                        || (self.selected_websocket().is_some()
+                            && self.websocket_pane.intersects(mouse))
+                    {
                         self.selected_pane.select(&PrimaryPane::Request);
                     } else if self.response_pane.intersects(mouse) {
                         self.selected_pane.select(&PrimaryPane::Response);
@@ -381,8 +475,14 @@ impl EventHandler for PrimaryView {
     }
 
     fn children(&mut self) -> Vec<Component<&mut dyn EventHandler>> {
+        let is_websocket = self.selected_websocket().is_some();
         let child = if let Some(fullscreen_mode) = *self.fullscreen_mode {
             match fullscreen_mode {
+                FullscreenMode::Request | FullscreenMode::Response
+                    if is_websocket =>
+                {
+                    self.websocket_pane.as_child()
+                }
                 FullscreenMode::Recipe => self.recipe_list_pane.as_child(),
                 FullscreenMode::Request => self.request_pane.as_child(),
                 FullscreenMode::Response => self.response_pane.as_child(),
@@ -392,6 +492,11 @@ impl EventHandler for PrimaryView {
                 PrimaryPane::ProfileList => self.profile_list_pane.as_child(),
                 PrimaryPane::RecipeList => self.recipe_list_pane.as_child(),
                 PrimaryPane::Recipe => self.recipe_pane.as_child(),
+                PrimaryPane::Request | PrimaryPane::Response
+                    if is_websocket =>
+                {
+                    self.websocket_pane.as_child()
+                }
                 PrimaryPane::Request => self.request_pane.as_child(),
                 PrimaryPane::Response => self.response_pane.as_child(),
             }
@@ -404,17 +509,21 @@ impl<'a> Draw<PrimaryViewProps<'a>> for PrimaryView {
     fn draw(&self, frame: &mut Frame, props: PrimaryViewProps<'a>, area: Rect) {
         match *self.fullscreen_mode {
             None => self.draw_all_panes(frame, props, area),
-            Some(FullscreenMode::Recipe) => self.recipe_pane.draw(
-                frame,
-                RecipePaneProps {
-                    is_selected: true,
-                    selected_recipe: self.selected_recipe(),
-                    selected_profile_id: self
-                        .selected_profile()
-                        .map(|profile| &profile.id),
-                },
-                area,
-            ),
+            Some(FullscreenMode::Recipe) => {
+                self.draw_recipe_pane(frame, true, area)
+            }
+            Some(FullscreenMode::Request | FullscreenMode::Response)
+                if self.selected_websocket().is_some() =>
+            {
+                self.websocket_pane.draw(
+                    frame,
+                    WebSocketPaneProps {
+                        is_selected: true,
+                        session: props.active_session,
+                    },
+                    area,
+                )
+            }
             Some(FullscreenMode::Request) => self.request_pane.draw(
                 frame,
                 RequestPaneProps {
@@ -428,6 +537,9 @@ impl<'a> Draw<PrimaryViewProps<'a>> for PrimaryView {
                 ResponsePaneProps {
                     is_selected: true,
                     active_request: props.active_request,
+                    assertions: self
+                        .selected_recipe()
+                        .map(|recipe| &recipe.assertions),
                 },
                 area,
             ),
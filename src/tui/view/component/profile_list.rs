@@ -50,6 +50,11 @@ impl ProfileListPane {
     pub fn profiles(&self) -> &SelectState<Profile> {
         &self.profiles
     }
+
+    /// Select a profile by ID. If it doesn't exist, do nothing
+    pub fn select_profile(&mut self, profile_id: &ProfileId) {
+        self.profiles.select(profile_id);
+    }
 }
 
 impl EventHandler for ProfileListPane {
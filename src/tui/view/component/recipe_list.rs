@@ -1,5 +1,8 @@
 use crate::{
-    collection::{Recipe, RecipeId, RecipeLookupKey, RecipeNode, RecipeTree},
+    collection::{
+        Recipe, RecipeId, RecipeLookupKey, RecipeNode, RecipeTree,
+        WebSocketRecipe,
+    },
     tui::{
         context::TuiContext,
         input::Action,
@@ -86,6 +89,32 @@ impl RecipeListPane {
         self.select_state.selected().and_then(RecipeNode::recipe)
     }
 
+    /// Which WebSocket recipe in the recipe list is selected? `None` if
+    /// anything else is selected.
+    pub fn selected_websocket(&self) -> Option<&WebSocketRecipe> {
+        self.select_state.selected().and_then(RecipeNode::websocket)
+    }
+
+    /// Select a recipe by ID, expanding its ancestor folders so it's visible.
+    /// If it doesn't exist, do nothing
+    pub fn select_recipe(&mut self, recipe_id: &RecipeId) {
+        let Some(lookup_key) = self.recipes.get_lookup_key(recipe_id) else {
+            return;
+        };
+        let [ancestors @ .., _] = lookup_key.as_slice() else {
+            return;
+        };
+        let mut changed = false;
+        for folder_id in ancestors {
+            changed |= self.collapsed.remove(folder_id);
+        }
+        if changed {
+            **self.select_state =
+                build_select_state(&self.recipes, &self.collapsed);
+        }
+        self.select_state.select(recipe_id);
+    }
+
     /// Set the currently selected folder as expanded/collapsed (or toggle it).
     /// If a folder is not selected, do nothing. Returns whether a change was
     /// made.
@@ -188,6 +217,7 @@ impl Draw<RecipeListPaneProps> for RecipeListPane {
                         (icon, folder.name())
                     }
                     RecipeNode::Recipe(recipe) => ("", recipe.name()),
+                    RecipeNode::WebSocket(websocket) => ("⇄", websocket.name()),
                 };
                 let depth = self
                     .recipes
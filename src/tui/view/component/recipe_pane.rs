@@ -1,5 +1,5 @@
 use crate::{
-    collection::{Authentication, ProfileId, Recipe, RecipeId},
+    collection::{Authentication, ProfileId, Recipe, RecipeBody, RecipeId},
     http::RecipeOptions,
     template::Template,
     tui::{
@@ -9,12 +9,14 @@ use crate::{
         view::{
             common::{
                 actions::ActionsModal,
+                modal::ModalPriority,
                 table::{Table, ToggleRow},
                 tabs::Tabs,
                 template_preview::TemplatePreview,
                 text_window::TextWindow,
                 Pane,
             },
+            component::graphql::GraphQlOperationsModal,
             draw::{Draw, Generate, ToStringGenerate},
             event::{Event, EventHandler, EventQueue, Update},
             state::{
@@ -115,6 +117,11 @@ enum MenuAction {
     CopyBody,
     #[display("Copy as cURL")]
     CopyCurl,
+    /// Fetch the GraphQL schema from the recipe's URL
+    #[display("Introspect GraphQL Schema")]
+    IntrospectSchema,
+    #[display("View GraphQL Operations")]
+    ViewOperations,
 }
 
 impl ToStringGenerate for MenuAction {}
@@ -161,6 +168,19 @@ impl RecipePane {
             MenuAction::CopyUrl => Message::CopyRequestUrl(request_config),
             MenuAction::CopyBody => Message::CopyRequestBody(request_config),
             MenuAction::CopyCurl => Message::CopyRequestCurl(request_config),
+            MenuAction::IntrospectSchema => {
+                Message::GraphQlIntrospect(request_config)
+            }
+            MenuAction::ViewOperations => {
+                EventQueue::open_modal(
+                    GraphQlOperationsModal::new(
+                        request_config.profile_id,
+                        request_config.recipe_id,
+                    ),
+                    ModalPriority::Low,
+                );
+                return;
+            }
         };
         TuiContext::send_message(message);
     }
@@ -344,7 +364,7 @@ impl RecipeState {
             .into(),
             body: recipe.body.as_ref().map(|body| {
                 TextWindow::new(TemplatePreview::new(
-                    body.clone(),
+                    body_preview(body),
                     selected_profile_id.cloned(),
                 ))
                 .into()
@@ -493,3 +513,28 @@ impl PartialEq<RowState> for String {
         self == &other.key
     }
 }
+
+/// Get a template to preview a body. GraphQL bodies are shown as the query,
+/// with the operation name and variables in comments, rather than the JSON
+/// that will actually be sent. This is much easier to read.
+fn body_preview(body: &RecipeBody) -> Template {
+    match body {
+        RecipeBody::Raw(template) => template.clone(),
+        RecipeBody::GraphQl(body) => {
+            // Join the pieces as templates, so keys are still highlighted
+            // in the preview
+            let mut pieces = Vec::new();
+            if let Some(operation_name) = &body.operation_name {
+                pieces.push(Template::dangerous("# Operation: ".into()));
+                pieces.push(operation_name.clone());
+                pieces.push(Template::dangerous("\n".into()));
+            }
+            pieces.push(body.query.clone());
+            if let Some(variables) = &body.variables {
+                pieces.push(Template::dangerous("\n\n# Variables\n".into()));
+                pieces.push(variables.clone());
+            }
+            Template::concat(&pieces)
+        }
+    }
+}
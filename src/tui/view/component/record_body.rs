@@ -1,13 +1,21 @@
 //! Request/response body display component
 
 use crate::{
-    http::{Query, ResponseContent},
+    http::{self, Query, ResponseContent},
     tui::{
+        context::TuiContext,
         input::Action,
         view::{
-            common::{text_box::TextBox, text_window::TextWindow},
+            common::{
+                hex_window::HexWindow,
+                json_tree::JsonTree,
+                table_window::TableWindow,
+                text_box::TextBox,
+                text_window::{IndexedText, TextWindow},
+            },
             draw::Draw,
             event::{Event, EventHandler, EventQueue, Update},
+            highlight::Language,
             state::StateCell,
             util::layout,
             Component,
@@ -16,22 +24,38 @@ use crate::{
     util::{MaybeStr, ResultExt},
 };
 use anyhow::Context;
+use bytes::Bytes;
 use derive_more::Debug;
+use encoding_rs::Encoding;
 use ratatui::{
     layout::{Constraint, Direction},
     prelude::Rect,
+    widgets::Paragraph,
     Frame,
 };
 use serde_json_path::JsonPath;
-use std::cell::Cell;
+use std::{
+    cell::{Cell, RefCell},
+    sync::{Arc, Mutex},
+};
+use tokio::task::JoinHandle;
+use tracing::error;
 
 /// Display text body of a request/response
 #[derive(Debug)]
 pub struct RecordBody {
-    /// Body text content. State cell allows us to reset this whenever the
-    /// request changes
+    /// Body content. Decoding, querying and prettifying a large body is slow,
+    /// so the view is built in a background task. State cell allows us to
+    /// rebuild it whenever any of the view settings change.
     #[debug(skip)]
-    text_window: StateCell<Option<Query>, Component<TextWindow<String>>>,
+    view: StateCell<ViewKey, RefCell<ViewState>>,
+    /// User's choice of hex mode. If `None`, hex mode is used iff the body is
+    /// binary
+    hex_override: Option<bool>,
+    /// Show parsed content as a collapsible tree? Hex mode takes priority
+    tree_mode: bool,
+    /// Was hex mode used in the last draw? Needed to toggle the mode
+    is_hex: Cell<bool>,
     /// Store whether the body can be queried. True only if it's a recognized
     /// and parsed format
     query_available: Cell<bool>,
@@ -43,26 +67,111 @@ pub struct RecordBody {
 }
 
 pub struct RecordBodyProps<'a> {
-    pub raw_body: &'a [u8],
-    pub parsed_body: Option<&'a dyn ResponseContent>,
+    pub raw_body: &'a Bytes,
+    /// Declared encoding of the body. If `None`, assume UTF-8
+    pub charset: Option<&'static Encoding>,
+    /// Language to use for syntax highlighting, if known
+    pub language: Option<Language>,
+    pub parsed_body: Option<Arc<dyn ResponseContent>>,
 }
 
 /// Callback event from the query text box when user hits Enter
 struct QuerySubmit(String);
 
+/// Everything that determines how the body is displayed. If any of these
+/// change, the view has to be rebuilt.
+#[derive(Clone, PartialEq)]
+struct ViewKey {
+    query: Option<Query>,
+    hex_override: Option<bool>,
+    tree_mode: bool,
+    is_parsed: bool,
+}
+
+/// Body view, which may still be under construction
+enum ViewState {
+    /// The view is being built in a background task, which will store it in
+    /// `destination` when done
+    Loading {
+        destination: Arc<Mutex<Option<BodyView>>>,
+        task: JoinHandle<()>,
+    },
+    Ready(BodyView),
+    /// The background task panicked, so the view will never be ready
+    Failed,
+}
+
+/// The different ways a body can be displayed. This is selected based on the
+/// body's content type.
+enum BodyView {
+    /// Plain (possibly prettified) text
+    Text(Component<TextWindow<IndexedText>>),
+    /// Hex dump of raw bytes
+    Hex(Component<HexWindow>),
+    /// Collapsible tree of parsed content
+    Tree {
+        tree: Component<JsonTree>,
+        /// Parsed content, so we can generate text for copying on demand
+        content: Arc<dyn ResponseContent>,
+    },
+    /// Tabular data, e.g. CSV
+    Table {
+        table: Component<TableWindow>,
+        /// Text representation of the table, for copying
+        text: String,
+    },
+}
+
 impl RecordBody {
-    /// Get visible body text
+    /// Get visible body text. Returns `None` if the body is still loading
     pub fn text(&self) -> Option<String> {
-        self.text_window
-            .get()
-            .map(|text_window| text_window.inner().text().to_owned())
+        let state = self.view.get()?;
+        let state = state.borrow();
+        let ViewState::Ready(view) = &*state else {
+            return None;
+        };
+        Some(match view {
+            BodyView::Text(text_window) => {
+                text_window.inner().text().as_str().to_owned()
+            }
+            BodyView::Hex(hex_window) => hex_window.inner().text(),
+            BodyView::Tree { content, .. } => content.prettify(),
+            BodyView::Table { text, .. } => text.clone(),
+        })
+    }
+
+    /// Get the JSONPath of the selected node in tree mode. Returns `None` if
+    /// not in tree mode
+    pub fn selected_path(&self) -> Option<String> {
+        let state = self.view.get()?;
+        let state = state.borrow();
+        match &*state {
+            ViewState::Ready(BodyView::Tree { tree, .. }) => {
+                Some(tree.inner().selected_path())
+            }
+            _ => None,
+        }
+    }
+
+    /// Switch between the hex dump and the regular view
+    pub fn toggle_hex(&mut self) {
+        self.hex_override = Some(!self.is_hex.get());
+    }
+
+    /// Switch between the tree view and the regular view. Only has an effect
+    /// if the body has been parsed
+    pub fn toggle_tree(&mut self) {
+        self.tree_mode = !self.tree_mode;
     }
 }
 
 impl Default for RecordBody {
     fn default() -> Self {
         Self {
-            text_window: Default::default(),
+            view: Default::default(),
+            hex_override: None,
+            tree_mode: false,
+            is_hex: Cell::new(false),
             query_available: Cell::new(false),
             query: Default::default(),
             query_text_box: TextBox::default()
@@ -110,8 +219,15 @@ impl EventHandler for RecordBody {
     fn children(&mut self) -> Vec<Component<&mut dyn EventHandler>> {
         if self.query_text_box.is_focused() {
             vec![self.query_text_box.as_child()]
-        } else if let Some(text_window) = self.text_window.get_mut() {
-            vec![text_window.as_child()]
+        } else if let Some(ViewState::Ready(view)) =
+            self.view.get_mut().map(RefCell::get_mut)
+        {
+            match view {
+                BodyView::Text(text_window) => vec![text_window.as_child()],
+                BodyView::Hex(hex_window) => vec![hex_window.as_child()],
+                BodyView::Tree { tree, .. } => vec![tree.as_child()],
+                BodyView::Table { table, .. } => vec![table.as_child()],
+            }
         } else {
             vec![]
         }
@@ -120,8 +236,71 @@ impl EventHandler for RecordBody {
 
 impl<'a> Draw<RecordBodyProps<'a>> for RecordBody {
     fn draw(&self, frame: &mut Frame, props: RecordBodyProps, area: Rect) {
-        // Body can only be queried if it's been parsed
-        let query_available = props.parsed_body.is_some();
+        let key = ViewKey {
+            query: self.query.clone(),
+            hex_override: self.hex_override,
+            tree_mode: self.tree_mode,
+            is_parsed: props.parsed_body.is_some(),
+        };
+        let state = self.view.get_or_update(key.clone(), || {
+            // Build the view in the background, and it'll be stored back
+            // here when ready
+            let destination = Arc::new(Mutex::new(None));
+            let task_destination = Arc::clone(&destination);
+            let body = props.raw_body.clone();
+            let charset = props.charset;
+            let language = props.language;
+            let parsed_body = props.parsed_body.clone();
+            let task = tokio::task::spawn_blocking(move || {
+                let view = init_view(body, charset, language, parsed_body, key);
+                *task_destination.lock().unwrap() = Some(view);
+            });
+            RefCell::new(ViewState::Loading { destination, task })
+        });
+
+        // Check if the background task has finished. Check the task before
+        // the destination, so a view stored in between isn't missed
+        let mut state = state.borrow_mut();
+        let loaded = match &*state {
+            ViewState::Loading { destination, task } => {
+                let is_finished = task.is_finished();
+                match destination.lock().unwrap().take() {
+                    Some(view) => Some(ViewState::Ready(view)),
+                    None if is_finished => {
+                        error!("Body view task panicked");
+                        Some(ViewState::Failed)
+                    }
+                    None => None,
+                }
+            }
+            ViewState::Ready(_) | ViewState::Failed => None,
+        };
+        if let Some(loaded) = loaded {
+            *state = loaded;
+        }
+
+        let view = match &*state {
+            ViewState::Loading { .. } => {
+                frame.render_widget(Paragraph::new("Loading..."), area);
+                return;
+            }
+            ViewState::Ready(view) => view,
+            ViewState::Failed => {
+                frame.render_widget(
+                    Paragraph::new("Error displaying body")
+                        .style(TuiContext::get().theme.text.error),
+                    area,
+                );
+                return;
+            }
+        };
+        let is_hex = matches!(view, BodyView::Hex(_));
+        self.is_hex.set(is_hex);
+
+        // Body can only be queried if it's been parsed. The tree always shows
+        // the whole body, so it can't be queried either
+        let query_available = props.parsed_body.is_some()
+            && matches!(view, BodyView::Text(_) | BodyView::Table { .. });
         self.query_available.set(query_available);
 
         let [body_area, query_area] = layout(
@@ -134,14 +313,14 @@ impl<'a> Draw<RecordBodyProps<'a>> for RecordBody {
         );
 
         // Draw the body
-        let text = self.text_window.get_or_update(self.query.clone(), || {
-            init_text_window(
-                props.raw_body,
-                props.parsed_body,
-                self.query.as_ref(),
-            )
-        });
-        text.draw(frame, (), body_area);
+        match view {
+            BodyView::Text(text_window) => {
+                text_window.draw(frame, (), body_area)
+            }
+            BodyView::Hex(hex_window) => hex_window.draw(frame, (), body_area),
+            BodyView::Tree { tree, .. } => tree.draw(frame, (), body_area),
+            BodyView::Table { table, .. } => table.draw(frame, (), body_area),
+        }
 
         if query_available {
             self.query_text_box.draw(frame, (), query_area);
@@ -149,25 +328,59 @@ impl<'a> Draw<RecordBodyProps<'a>> for RecordBody {
     }
 }
 
-fn init_text_window(
-    raw_body: &[u8],
-    parsed_body: Option<&dyn ResponseContent>,
-    query: Option<&Query>,
-) -> Component<TextWindow<String>> {
-    // Query and prettify text if possible. This involves a lot of cloning
-    // because it makes stuff easier. If it becomes a bottleneck on large
-    // responses it's fixable.
-    let body = parsed_body
-        .map(|parsed_body| {
-            // Body is a known content type so we parsed it - apply a query if
-            // necessary and prettify the output
-            query
-                .map(|query| query.query(parsed_body).prettify())
-                .unwrap_or_else(|| parsed_body.prettify())
-        })
+/// Build the view for a body. This can be slow for large bodies, so it should
+/// be called off the main thread.
+fn init_view(
+    body: Bytes,
+    charset: Option<&'static Encoding>,
+    language: Option<Language>,
+    parsed_body: Option<Arc<dyn ResponseContent>>,
+    key: ViewKey,
+) -> BodyView {
+    let text = http::decode_text(&body, charset);
+
+    // Default to hex for anything that can't be shown as text
+    if key.hex_override.unwrap_or(text.is_none()) {
+        return BodyView::Hex(HexWindow::new(body.clone()).into());
+    }
+
+    // The tree shows the whole body, regardless of the query
+    if let (true, Some(parsed_body)) = (key.tree_mode, &parsed_body) {
+        return BodyView::Tree {
+            tree: JsonTree::new(&parsed_body.to_json()).into(),
+            content: Arc::clone(parsed_body),
+        };
+    }
+
+    let Some(parsed_body) = parsed_body else {
         // Content couldn't be parsed, fall back to the raw text
-        // If the text isn't UTF-8, we'll show a placeholder instead
-        .unwrap_or_else(|| format!("{:#}", MaybeStr(raw_body)));
+        // If the text isn't valid, we'll show the raw bytes instead
+        let text = match text {
+            Some(text) => text.into_owned(),
+            None => format!("{:#}", MaybeStr(&body)),
+        };
+        return BodyView::Text(
+            TextWindow::new(IndexedText::new(text))
+                .with_highlight(language)
+                .into(),
+        );
+    };
+
+    // Body is a known content type so we parsed it - apply a query if
+    // necessary
+    let queried = key.query.map(|query| query.query(&*parsed_body));
+    let content = queried.as_deref().unwrap_or(&*parsed_body);
 
-    TextWindow::new(body).into()
+    // Tabular content gets a table, everything else is prettified text
+    match content.as_table() {
+        Some((header, rows)) => BodyView::Table {
+            table: TableWindow::new(header.to_vec(), rows.to_vec()).into(),
+            text: content.to_string(),
+        },
+        None => BodyView::Text(
+            TextWindow::new(IndexedText::new(content.prettify()))
+                .with_highlight(language)
+                .into(),
+        ),
+    }
 }
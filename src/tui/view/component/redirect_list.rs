@@ -0,0 +1,122 @@
+//! Collapsible list of the redirects that were followed to get a response
+
+use crate::{
+    http::RedirectHop,
+    tui::{
+        context::TuiContext,
+        input::Action,
+        view::{
+            draw::Draw,
+            event::{Event, EventHandler, Update},
+            state::select::SelectState,
+            Component,
+        },
+    },
+    util::MaybeStr,
+};
+use itertools::Itertools;
+use ratatui::{
+    prelude::Rect,
+    text::{Line, Span, Text},
+    widgets::{List, ListItem, Paragraph},
+    Frame,
+};
+
+/// Show each hop of a redirect chain on one line. The selected hop can be
+/// expanded to show its response headers.
+#[derive(Debug)]
+pub struct RedirectList {
+    hops: Component<SelectState<RedirectItem>>,
+}
+
+#[derive(Debug)]
+struct RedirectItem {
+    hop: RedirectHop,
+    expanded: bool,
+}
+
+impl RedirectList {
+    pub fn new(hops: Vec<RedirectHop>) -> Self {
+        let items = hops
+            .into_iter()
+            .map(|hop| RedirectItem {
+                hop,
+                expanded: false,
+            })
+            .collect();
+        Self {
+            hops: SelectState::new(items).into(),
+        }
+    }
+
+    /// Expand or collapse the selected hop. Returns `false` if it was already
+    /// in that state
+    fn set_expanded(&mut self, expanded: bool) -> bool {
+        match self.hops.selected_mut() {
+            Some(item) if item.expanded != expanded => {
+                item.expanded = expanded;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl EventHandler for RedirectList {
+    fn update(&mut self, event: Event) -> Update {
+        match event {
+            // If there's nothing to expand/collapse, let the parent use the
+            // action (e.g. to switch tabs)
+            Event::Input {
+                action: Some(Action::Left),
+                ..
+            } if self.set_expanded(false) => Update::Consumed,
+            Event::Input {
+                action: Some(Action::Right),
+                ..
+            } if self.set_expanded(true) => Update::Consumed,
+            _ => Update::Propagate(event),
+        }
+    }
+
+    fn children(&mut self) -> Vec<Component<&mut dyn EventHandler>> {
+        vec![self.hops.as_child()]
+    }
+}
+
+impl Draw for RedirectList {
+    fn draw(&self, frame: &mut Frame, _: (), area: Rect) {
+        if self.hops.items().is_empty() {
+            frame.render_widget(Paragraph::new("No redirects"), area);
+            return;
+        }
+
+        let theme = &TuiContext::get().theme;
+        let items = self
+            .hops
+            .items()
+            .iter()
+            .map(|item| {
+                let hop = &item.hop;
+                let mut lines = vec![Line::from(vec![
+                    if item.expanded { "▾ " } else { "▸ " }.into(),
+                    Span::styled(hop.status.to_string(), theme.text.highlight),
+                    format!(" {}", hop.url).into(),
+                    format!(" → {}", hop.location().unwrap_or("?")).into(),
+                ])];
+                if item.expanded {
+                    lines.extend(hop.headers.iter().map(|(name, value)| {
+                        Line::from(format!(
+                            "    {name}: {}",
+                            MaybeStr(value.as_bytes())
+                        ))
+                    }));
+                }
+                ListItem::new(Text::from(lines))
+            })
+            .collect_vec();
+        let list = List::new(items).highlight_style(theme.list.highlight);
+        self.hops.set_area(area); // Needed for tracking cursor events
+        frame.render_stateful_widget(list, area, &mut self.hops.state_mut());
+    }
+}
@@ -1,5 +1,5 @@
 use crate::{
-    http::{Request, RequestId, ResponseContent},
+    http::{Request, RequestId},
     tui::{
         context::TuiContext,
         input::Action,
@@ -12,6 +12,7 @@ use crate::{
             component::record_body::{RecordBody, RecordBodyProps},
             draw::{Draw, Generate, ToStringGenerate},
             event::{Event, EventHandler, EventQueue, Update},
+            highlight::Language,
             state::{persistence::PersistentKey, RequestState, StateCell},
             util::layout,
             Component,
@@ -21,9 +22,11 @@ use crate::{
 use derive_more::{Debug, Display};
 use ratatui::{
     prelude::{Alignment, Constraint, Direction, Rect},
+    text::Line,
     widgets::{Paragraph, Wrap},
     Frame,
 };
+use reqwest::header;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use strum::{EnumCount, EnumIter};
@@ -47,6 +50,8 @@ enum MenuAction {
     CopyUrl,
     #[display("Copy Body")]
     CopyBody,
+    #[display("Toggle Hex View")]
+    ToggleHex,
 }
 
 impl ToStringGenerate for MenuAction {}
@@ -109,9 +114,21 @@ impl<'a> Draw<RequestPaneProps<'a>> for RequestPane {
                     );
                     None
                 }
-                RequestState::Loading { request, .. } => Some(request),
+                RequestState::Loading { request, .. }
+                | RequestState::Streaming { request, .. } => Some(request),
                 RequestState::Response { record, .. } => Some(&record.request),
                 RequestState::RequestError { error } => Some(&error.request),
+                RequestState::Cancelled {
+                    request: Some(request),
+                    ..
+                } => Some(request),
+                RequestState::Cancelled { request: None, .. } => {
+                    frame.render_widget(
+                        Paragraph::new("Request cancelled before sending"),
+                        area,
+                    );
+                    None
+                }
             };
 
             if let Some(request) = request {
@@ -119,9 +136,6 @@ impl<'a> Draw<RequestPaneProps<'a>> for RequestPane {
                     frame,
                     RenderedRequestProps {
                         request: Arc::clone(request),
-                        // For simplicity, don't format body or make it
-                        // queryable
-                        parsed_body: None,
                     },
                     area,
                 )
@@ -157,9 +171,8 @@ impl Default for RenderedRequest {
     }
 }
 
-struct RenderedRequestProps<'a> {
+struct RenderedRequestProps {
     request: Arc<Request>,
-    parsed_body: Option<&'a dyn ResponseContent>,
 }
 
 #[derive(
@@ -206,6 +219,11 @@ impl EventHandler for RenderedRequest {
                             TuiContext::send_message(Message::CopyText(body));
                         }
                     }
+                    Some(MenuAction::ToggleHex) => {
+                        if let Some(state) = self.state.get_mut() {
+                            state.body.toggle_hex();
+                        }
+                    }
                     None => return Update::Propagate(event),
                 }
             }
@@ -231,13 +249,8 @@ impl EventHandler for RenderedRequest {
     }
 }
 
-impl<'a> Draw<RenderedRequestProps<'a>> for RenderedRequest {
-    fn draw(
-        &self,
-        frame: &mut Frame,
-        props: RenderedRequestProps<'a>,
-        area: Rect,
-    ) {
+impl Draw<RenderedRequestProps> for RenderedRequest {
+    fn draw(&self, frame: &mut Frame, props: RenderedRequestProps, area: Rect) {
         let state = self.state.get_or_update(props.request.id, || State {
             request: Arc::clone(&props.request),
             body: Default::default(),
@@ -256,9 +269,14 @@ impl<'a> Draw<RenderedRequestProps<'a>> for RenderedRequest {
         // Main content for the response
         match self.tabs.selected() {
             Tab::Url => {
+                let mut lines = vec![Line::from(state.request.url.to_string())];
+                // Show which proxy the request went through, if any
+                if let Some(proxy) = &state.request.sent_proxy {
+                    lines.push(Line::default());
+                    lines.push(format!("Proxy: {proxy}").into());
+                }
                 frame.render_widget(
-                    Paragraph::new(state.request.url.to_string())
-                        .wrap(Wrap::default()),
+                    Paragraph::new(lines).wrap(Wrap::default()),
                     content_area,
                 );
             }
@@ -268,7 +286,19 @@ impl<'a> Draw<RenderedRequestProps<'a>> for RenderedRequest {
                         frame,
                         RecordBodyProps {
                             raw_body: body,
-                            parsed_body: props.parsed_body,
+                            charset: None,
+                            language: state
+                                .request
+                                .headers
+                                .get(header::CONTENT_TYPE)
+                                .and_then(|value| {
+                                    Language::from_content_type(
+                                        value.as_bytes(),
+                                    )
+                                }),
+                            // For simplicity, don't format body or make it
+                            // queryable
+                            parsed_body: None,
                         },
                         content_area,
                     );
@@ -1,32 +1,47 @@
 use crate::{
-    http::{RequestId, RequestRecord, ResponseContent},
+    http::{
+        response_errors, summarize_assertions, AssertionResult, Assertions,
+        ContentType, EventStream, RequestId, RequestRecord, RequestTimings,
+        ServerEvent,
+    },
     tui::{
         context::TuiContext,
         input::Action,
         message::Message,
         view::{
             common::{
-                actions::ActionsModal, header_table::HeaderTable, tabs::Tabs,
-                Pane,
+                actions::ActionsModal, header_table::HeaderTable, table::Table,
+                tabs::Tabs, Pane,
+            },
+            component::{
+                record_body::{RecordBody, RecordBodyProps},
+                redirect_list::RedirectList,
             },
-            component::record_body::{RecordBody, RecordBodyProps},
             draw::{Draw, Generate, ToStringGenerate},
             event::{Event, EventHandler, EventQueue, Update},
-            state::{persistence::PersistentKey, RequestState, StateCell},
+            highlight::Language,
+            state::{
+                persistence::PersistentKey, ParsedBody, RequestState, StateCell,
+            },
             util::layout,
             Component,
         },
     },
 };
-use chrono::Utc;
+use bytesize::ByteSize;
+use chrono::{DateTime, Utc};
 use derive_more::{Debug, Display};
+use encoding_rs::UTF_8;
+use itertools::Itertools;
 use ratatui::{
     prelude::{Alignment, Constraint, Direction, Rect},
-    text::Line,
+    text::{Line, Text},
     widgets::{Paragraph, Wrap},
     Frame,
 };
+use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
+use std::cell::Cell;
 use strum::{EnumCount, EnumIter};
 
 /// Display HTTP response state, which could be in progress, complete, or
@@ -39,6 +54,8 @@ pub struct ResponsePane {
 pub struct ResponsePaneProps<'a> {
     pub is_selected: bool,
     pub active_request: Option<&'a RequestState>,
+    /// Assertions from the selected recipe, to check against the response
+    pub assertions: Option<&'a Assertions>,
 }
 
 /// Items in the actions popup menu
@@ -46,6 +63,16 @@ pub struct ResponsePaneProps<'a> {
 enum MenuAction {
     #[display("Copy Body")]
     CopyBody,
+    #[display("Toggle Hex View")]
+    ToggleHex,
+    #[display("Toggle Tree View")]
+    ToggleTree,
+    /// Copy the JSONPath of the selected node in the tree view
+    #[display("Copy JSONPath")]
+    CopyPath,
+    /// Parse a body that was too large to be parsed automatically
+    #[display("Parse Body")]
+    ParseBody,
 }
 
 impl ToStringGenerate for MenuAction {}
@@ -78,10 +105,28 @@ impl<'a> Draw<ResponsePaneProps<'a>> for ResponsePane {
         match props.active_request {
             None | Some(RequestState::BuildError { .. }) => {}
             Some(RequestState::Building { .. }) => {
-                frame.render_widget(Paragraph::new("Loading..."), area)
+                frame.render_widget(Paragraph::new(loading_text(None)), area)
             }
-            Some(RequestState::Loading { start_time, .. }) => {
-                frame.render_widget(Paragraph::new("Loading..."), area);
+            Some(RequestState::Loading {
+                request,
+                start_time,
+                attempt,
+                download,
+                ..
+            }) => {
+                let text = if let Some(progress) = download {
+                    TuiContext::get().input_engine.add_hint(
+                        format!("Downloading... {progress}"),
+                        Action::Cancel,
+                    )
+                } else {
+                    // Show progress through the retry policy, if there is one
+                    let attempt = request.retry.as_ref().map(|retry| {
+                        format!("attempt {attempt}/{}", retry.max_attempts)
+                    });
+                    loading_text(attempt.as_deref())
+                };
+                frame.render_widget(Paragraph::new(text), area);
                 let duration = Utc::now() - start_time;
                 frame.render_widget(
                     Paragraph::new(duration.generate())
@@ -89,6 +134,38 @@ impl<'a> Draw<ResponsePaneProps<'a>> for ResponsePane {
                     area,
                 );
             }
+            Some(RequestState::Cancelled {
+                start_time,
+                end_time,
+                ..
+            }) => {
+                frame.render_widget(Paragraph::new("Cancelled"), area);
+                let duration = *end_time - start_time;
+                frame.render_widget(
+                    Paragraph::new(duration.generate())
+                        .alignment(Alignment::Right),
+                    area,
+                );
+            }
+
+            Some(RequestState::Streaming {
+                start_time,
+                status,
+                events,
+                size,
+                cancel,
+                ..
+            }) => draw_stream(
+                frame,
+                StreamProps {
+                    start_time: *start_time,
+                    status: *status,
+                    events,
+                    size: *size,
+                    is_open: cancel.is_some(),
+                },
+                area,
+            ),
 
             Some(RequestState::Response {
                 record,
@@ -97,7 +174,8 @@ impl<'a> Draw<ResponsePaneProps<'a>> for ResponsePane {
                 frame,
                 CompleteResponseContentProps {
                     record,
-                    parsed_body: parsed_body.as_deref(),
+                    parsed_body,
+                    assertions: props.assertions,
                 },
                 area,
             ),
@@ -111,6 +189,88 @@ impl<'a> Draw<ResponsePaneProps<'a>> for ResponsePane {
     }
 }
 
+/// Placeholder for a pending response, with a hint on how to cancel it
+fn loading_text(attempt: Option<&str>) -> String {
+    let label = match attempt {
+        Some(attempt) => format!("Loading... {attempt}"),
+        None => "Loading...".into(),
+    };
+    TuiContext::get()
+        .input_engine
+        .add_hint(label, Action::Cancel)
+}
+
+struct StreamProps<'a> {
+    start_time: DateTime<Utc>,
+    status: StatusCode,
+    events: &'a [ServerEvent],
+    size: usize,
+    /// Has the user *not* closed the stream yet?
+    is_open: bool,
+}
+
+/// Display a live event stream. The newest events are always shown at the
+/// bottom. Once the stream ends, the full response is shown as normal and can
+/// be scrolled.
+fn draw_stream(frame: &mut Frame, props: StreamProps, area: Rect) {
+    let [header_area, events_area] = layout(
+        area,
+        Direction::Vertical,
+        [Constraint::Length(1), Constraint::Min(0)],
+    );
+
+    let state = if props.is_open {
+        TuiContext::get()
+            .input_engine
+            .add_hint("Streaming", Action::Cancel)
+    } else {
+        "Closing...".into()
+    };
+    frame.render_widget(
+        Paragraph::new(format!("{} - {state}", props.status)),
+        header_area,
+    );
+    frame.render_widget(
+        Paragraph::new(Line::from(vec![
+            format!(
+                "{} events / {} / ",
+                props.events.len(),
+                ByteSize(props.size as u64).to_string_as(false)
+            )
+            .into(),
+            (Utc::now() - props.start_time).generate(),
+        ]))
+        .alignment(Alignment::Right),
+        header_area,
+    );
+
+    // Only show as many events as fit, minus the header row
+    let visible = (events_area.height as usize).saturating_sub(1);
+    let skip = props.events.len().saturating_sub(visible);
+    let table = Table {
+        header: Some(EventStream::TABLE_HEADER),
+        rows: props.events[skip..]
+            .iter()
+            .map(|event| {
+                let [event, id, data]: [String; 3] =
+                    EventStream::table_row(event)
+                        .try_into()
+                        // Row length is tied to the header length
+                        .unwrap();
+                // Keep each event on one line
+                [event, id, data.replace('\n', " ")]
+            })
+            .collect_vec(),
+        column_widths: &[
+            Constraint::Percentage(15),
+            Constraint::Percentage(15),
+            Constraint::Percentage(70),
+        ],
+        ..Default::default()
+    };
+    frame.render_widget(table.generate(), events_area);
+}
+
 /// Display response success state (tab container)
 #[derive(Debug)]
 struct CompleteResponseContent {
@@ -120,6 +280,16 @@ struct CompleteResponseContent {
     /// loaded request changes
     #[debug(skip)]
     body: StateCell<RequestId, Component<RecordBody>>,
+    /// Redirect list is persisted for the same reason as the body
+    #[debug(skip)]
+    redirects: StateCell<RequestId, Component<RedirectList>>,
+    /// Checking assertions requires the body as JSON, so cache the results.
+    /// They're checked again once the body has been parsed
+    #[debug(skip)]
+    assertion_results: StateCell<(RequestId, bool), Vec<AssertionResult>>,
+    /// User asked to parse the body. The parsed body is owned by the parent,
+    /// so we have to wait for the next draw to kick off the parse
+    parse_requested: Cell<bool>,
 }
 
 impl Default for CompleteResponseContent {
@@ -127,13 +297,17 @@ impl Default for CompleteResponseContent {
         Self {
             tabs: Tabs::new(PersistentKey::ResponseTab).into(),
             body: Default::default(),
+            redirects: Default::default(),
+            assertion_results: Default::default(),
+            parse_requested: Cell::new(false),
         }
     }
 }
 
 struct CompleteResponseContentProps<'a> {
     record: &'a RequestRecord,
-    parsed_body: Option<&'a dyn ResponseContent>,
+    parsed_body: &'a ParsedBody,
+    assertions: Option<&'a Assertions>,
 }
 
 #[derive(
@@ -150,6 +324,9 @@ struct CompleteResponseContentProps<'a> {
 enum Tab {
     Body,
     Headers,
+    Redirects,
+    Timing,
+    Assertions,
 }
 
 impl EventHandler for CompleteResponseContent {
@@ -171,6 +348,28 @@ impl EventHandler for CompleteResponseContent {
                             TuiContext::send_message(Message::CopyText(body));
                         }
                     }
+                    Some(MenuAction::ToggleHex) => {
+                        if let Some(body) = self.body.get_mut() {
+                            body.toggle_hex();
+                        }
+                    }
+                    Some(MenuAction::ToggleTree) => {
+                        if let Some(body) = self.body.get_mut() {
+                            body.toggle_tree();
+                        }
+                    }
+                    Some(MenuAction::CopyPath) => {
+                        if let Some(path) = self
+                            .body
+                            .get()
+                            .and_then(|body| body.selected_path())
+                        {
+                            TuiContext::send_message(Message::CopyText(path));
+                        }
+                    }
+                    Some(MenuAction::ParseBody) => {
+                        self.parse_requested.set(true)
+                    }
                     None => return Update::Propagate(event),
                 }
             }
@@ -188,7 +387,12 @@ impl EventHandler for CompleteResponseContent {
                     children.push(body.as_child());
                 }
             }
-            Tab::Headers => {}
+            Tab::Redirects => {
+                if let Some(redirects) = self.redirects.get_mut() {
+                    children.push(redirects.as_child());
+                }
+            }
+            Tab::Headers | Tab::Timing | Tab::Assertions => {}
         }
         // Tabs goes last, because pane content gets priority
         children.push(self.tabs.as_child());
@@ -204,26 +408,84 @@ impl<'a> Draw<CompleteResponseContentProps<'a>> for CompleteResponseContent {
         area: Rect,
     ) {
         let response = &props.record.response;
+        if self.parse_requested.replace(false) {
+            props.parsed_body.parse(response);
+        }
+
+        // GraphQL servers typically report errors with a 200, so call them
+        // out separately from the status
+        let graphql_errors = props
+            .parsed_body
+            .get()
+            // Converting other types to JSON on every frame is wasteful, and
+            // GraphQL is always JSON anyway
+            .filter(|body| matches!(body.content_type(), ContentType::Json))
+            .map(|body| {
+                response_errors(&body.to_json())
+                    .into_iter()
+                    .map(String::from)
+                    .collect_vec()
+            })
+            .unwrap_or_default();
 
         // Split the main area again to allow tabs
-        let [header_area, tabs_area, content_area] = layout(
-            area,
-            Direction::Vertical,
-            [
-                Constraint::Length(1),
-                Constraint::Length(1),
-                Constraint::Min(0),
-            ],
-        );
+        let annotations = &props.record.annotations;
+        let [header_area, errors_area, annotations_area, tabs_area, content_area] =
+            layout(
+                area,
+                Direction::Vertical,
+                [
+                    Constraint::Length(1),
+                    Constraint::Length(!graphql_errors.is_empty() as u16),
+                    Constraint::Length(annotations.len() as u16),
+                    Constraint::Length(1),
+                    Constraint::Min(0),
+                ],
+            );
 
-        // Metadata
-        frame.render_widget(
-            Paragraph::new(response.status.to_string()),
-            header_area,
-        );
+        // Metadata. If the body had to be converted to UTF-8 for display,
+        // mention it so the user isn't confused by what they copy
+        let mut status = response.status.to_string();
+        let encoding = response.encoding();
+        if encoding != UTF_8 && !response.body.bytes().is_empty() {
+            status.push_str(&format!(" (decoded from {})", encoding.name()));
+        }
+        if !props.parsed_body.is_started() {
+            status.push_str(" (too large to parse automatically)");
+        }
+        match props.record.redirects.len() {
+            0 => {}
+            1 => status.push_str(" (1 redirect)"),
+            n => status.push_str(&format!(" ({n} redirects)")),
+        }
+        if props.record.attempts > 1 {
+            status.push_str(&format!(" ({} attempts)", props.record.attempts));
+        }
+        let assertion_results = props
+            .assertions
+            .filter(|assertions| !assertions.is_empty())
+            .map(|assertions| {
+                let parsed_body = props.parsed_body.get();
+                self.assertion_results.get_or_update(
+                    (props.record.id, parsed_body.is_some()),
+                    || assertions.check(props.record, parsed_body.as_deref()),
+                )
+            });
+        if let Some(results) = &assertion_results {
+            status.push_str(&format!(" ({})", summarize_assertions(results)));
+        }
+        frame.render_widget(Paragraph::new(status), header_area);
+        let mut size = response.size().to_string_as(false);
+        if response.encoded_size.is_some() {
+            // Body was compressed on the wire, show both sizes
+            size.push_str(&format!(
+                " ({} compressed)",
+                response.wire_size().to_string_as(false)
+            ));
+        }
         frame.render_widget(
             Paragraph::new(Line::from(vec![
-                props.record.response.body.size().to_string_as(false).into(),
+                size.into(),
                 " / ".into(),
                 props.record.duration().generate(),
             ]))
@@ -231,11 +493,49 @@ impl<'a> Draw<CompleteResponseContentProps<'a>> for CompleteResponseContent {
             header_area,
         );
 
+        if !graphql_errors.is_empty() {
+            frame.render_widget(
+                Paragraph::new(format!(
+                    "GraphQL errors: {}",
+                    graphql_errors.join("; ")
+                ))
+                .style(TuiContext::get().theme.text.error),
+                errors_area,
+            );
+        }
+
+        // Notes from the post-response hook
+        frame.render_widget(
+            Paragraph::new(
+                annotations
+                    .iter()
+                    .map(|annotation| Line::from(annotation.as_str()))
+                    .collect_vec(),
+            )
+            .style(TuiContext::get().theme.text.highlight),
+            annotations_area,
+        );
+
         // Navigation tabs
         self.tabs.draw(frame, (), tabs_area);
 
         // Main content for the response
         match self.tabs.selected() {
+            Tab::Body if response.download.is_some() => {
+                if let Some(download) = &response.download {
+                    frame.render_widget(
+                        Paragraph::new(format!(
+                            "Body saved to {}",
+                            download.path.display()
+                        ))
+                        .wrap(Wrap::default()),
+                        content_area,
+                    )
+                }
+            }
+            Tab::Body if props.parsed_body.is_pending() => {
+                frame.render_widget(Paragraph::new("Parsing..."), content_area)
+            }
             Tab::Body => {
                 let body =
                     self.body.get_or_update(props.record.id, Default::default);
@@ -243,7 +543,11 @@ impl<'a> Draw<CompleteResponseContentProps<'a>> for CompleteResponseContent {
                     frame,
                     RecordBodyProps {
                         raw_body: response.body.bytes(),
-                        parsed_body: props.parsed_body,
+                        charset: response.charset(),
+                        language: response
+                            .content_type()
+                            .and_then(Language::from_content_type),
+                        parsed_body: props.parsed_body.get(),
                     },
                     content_area,
                 );
@@ -256,6 +560,90 @@ impl<'a> Draw<CompleteResponseContentProps<'a>> for CompleteResponseContent {
                 .generate(),
                 content_area,
             ),
+            Tab::Redirects => {
+                let redirects =
+                    self.redirects.get_or_update(props.record.id, || {
+                        RedirectList::new(props.record.redirects.clone()).into()
+                    });
+                redirects.draw(frame, (), content_area);
+            }
+            Tab::Timing => {
+                draw_timings(frame, props.record.timings.as_ref(), content_area)
+            }
+            Tab::Assertions => draw_assertion_results(
+                frame,
+                assertion_results.as_deref().map(Vec::as_slice),
+                content_area,
+            ),
         }
     }
 }
+
+/// Draw the outcome of each assertion on the response
+fn draw_assertion_results(
+    frame: &mut Frame,
+    results: Option<&[AssertionResult]>,
+    area: Rect,
+) {
+    let Some(results) = results else {
+        frame.render_widget(Paragraph::new("No assertions"), area);
+        return;
+    };
+    let theme = &TuiContext::get().theme;
+    let lines = results
+        .iter()
+        .map(|result| {
+            if result.passed() {
+                Line::styled(format!("✓ {result}"), theme.text.success)
+            } else {
+                Line::styled(format!("✗ {result}"), theme.text.error)
+            }
+        })
+        .collect_vec();
+    frame.render_widget(Paragraph::new(lines).wrap(Wrap::default()), area);
+}
+
+/// Draw a waterfall of how long each phase of the request took
+fn draw_timings(
+    frame: &mut Frame,
+    timings: Option<&RequestTimings>,
+    area: Rect,
+) {
+    let Some(timings) = timings else {
+        frame.render_widget(Paragraph::new("No timing data"), area);
+        return;
+    };
+    let generate_duration = |duration: std::time::Duration| {
+        chrono::Duration::from_std(duration)
+            .unwrap_or_else(|_| chrono::Duration::zero())
+            .generate()
+    };
+
+    const LABEL_WIDTH: u16 = 10;
+    // Bars get whatever's left after the labels and column spacing
+    let bar_width = area.width.saturating_sub(LABEL_WIDTH * 2 + 2) as usize;
+    let total = timings.total();
+    let mut rows = timings
+        .phases()
+        .into_iter()
+        .map(|phase| {
+            [
+                phase.name.into(),
+                generate_duration(phase.duration).into(),
+                Text::from(phase.bar(total, bar_width)),
+            ]
+        })
+        .collect_vec();
+    rows.push(["Total".into(), generate_duration(total).into(), "".into()]);
+    let table = Table {
+        header: Some(["Phase", "Duration", ""]),
+        rows,
+        column_widths: &[
+            Constraint::Length(LABEL_WIDTH),
+            Constraint::Length(LABEL_WIDTH),
+            Constraint::Min(0),
+        ],
+        ..Default::default()
+    };
+    frame.render_widget(table.generate(), area);
+}
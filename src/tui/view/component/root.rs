@@ -1,19 +1,24 @@
 use crate::{
-    collection::{Collection, ProfileId, RecipeId},
+    collection::{Collection, ProfileId, RecipeId, Workflow},
     tui::{
         context::TuiContext,
         input::Action,
         message::Message,
         view::{
-            common::{actions::GlobalAction, modal::ModalQueue},
+            common::{
+                actions::GlobalAction,
+                modal::{ModalPriority, ModalQueue},
+            },
             component::{
+                cookies::CookiesModal,
                 help::HelpFooter,
-                misc::NotificationText,
+                misc::{ConfirmModal, NotificationText},
                 primary::{PrimaryView, PrimaryViewProps},
+                workflow::{WorkflowListModal, WorkflowPane},
             },
             draw::Draw,
-            event::{Event, EventHandler, Update},
-            state::RequestState,
+            event::{Event, EventHandler, EventQueue, Update},
+            state::{Notification, RequestState, WebSocketSession},
             util::layout,
             Component,
         },
@@ -38,6 +43,14 @@ pub struct Root {
     /// list.
     #[debug(skip)]
     active_requests: HashMap<(Option<ProfileId>, RecipeId), RequestState>,
+    /// WebSocket sessions, keyed the same way as requests. Sessions are only
+    /// stored in memory, so this is populated when the user connects
+    #[debug(skip)]
+    websocket_sessions:
+        HashMap<(Option<ProfileId>, RecipeId), WebSocketSession>,
+    /// Workflows in the collection, to be listed when the user runs one
+    #[debug(skip)]
+    workflows: Vec<Workflow>,
 
     // ==== Children =====
     /// We hold onto the primary view even when it's not visible, because we
@@ -48,6 +61,10 @@ pub struct Root {
     modal_queue: Component<ModalQueue>,
     #[debug(skip)]
     notification_text: Option<Component<NotificationText>>,
+    /// Progress of the latest workflow run. While this is open, it replaces
+    /// the primary view
+    #[debug(skip)]
+    workflow_pane: Option<Component<WorkflowPane>>,
 }
 
 impl Root {
@@ -55,25 +72,53 @@ impl Root {
         Self {
             // State
             active_requests: HashMap::new(),
+            websocket_sessions: HashMap::new(),
+            workflows: collection.workflows.values().cloned().collect(),
 
             // Children
             primary_view: PrimaryView::new(collection).into(),
             modal_queue: Component::default(),
             notification_text: None,
+            workflow_pane: None,
         }
     }
 
+    /// ID of the selected profile, if any
+    fn selected_profile_id(&self) -> Option<ProfileId> {
+        self.primary_view
+            .selected_profile()
+            .map(|profile| profile.id.clone())
+    }
+
     /// Get the request state to be displayed
     fn active_request(&self) -> Option<&RequestState> {
         // "No Profile" _is_ a profile
-        let profile_id = self
-            .primary_view
-            .selected_profile()
-            .map(|profile| profile.id.clone());
+        let profile_id = self.selected_profile_id();
         let recipe_id = self.primary_view.selected_recipe()?.id.clone();
         self.active_requests.get(&(profile_id, recipe_id))
     }
 
+    /// Get a mutable reference to the request state being displayed
+    fn active_request_mut(&mut self) -> Option<&mut RequestState> {
+        let profile_id = self.selected_profile_id();
+        let recipe_id = self.primary_view.selected_recipe()?.id.clone();
+        self.active_requests.get_mut(&(profile_id, recipe_id))
+    }
+
+    /// Get the WebSocket session to be displayed
+    fn active_session(&self) -> Option<&WebSocketSession> {
+        let profile_id = self.selected_profile_id();
+        let recipe_id = self.primary_view.selected_websocket()?.id.clone();
+        self.websocket_sessions.get(&(profile_id, recipe_id))
+    }
+
+    /// Get a mutable reference to the WebSocket session being displayed
+    fn active_session_mut(&mut self) -> Option<&mut WebSocketSession> {
+        let profile_id = self.selected_profile_id();
+        let recipe_id = self.primary_view.selected_websocket()?.id.clone();
+        self.websocket_sessions.get_mut(&(profile_id, recipe_id))
+    }
+
     /// Update the active HTTP request state
     fn update_request(
         &mut self,
@@ -84,15 +129,43 @@ impl Root {
         // Update the state if any of these conditions match:
         // - There's nothing there yet
         // - This is a new request
-        // - This is an update to the request already in place
+        // - This is an update to the request already in place, and that
+        //   request hasn't finished (e.g. been cancelled) yet
         match self.active_requests.entry((profile_id, recipe_id)) {
             Entry::Vacant(entry) => {
                 entry.insert(state);
             }
             Entry::Occupied(mut entry)
-                if state.is_initial() || entry.get().id() == state.id() =>
+                if state.is_initial()
+                    || (entry.get().id() == state.id()
+                        && !entry.get().is_complete()) =>
             {
-                entry.insert(state);
+                let previous = entry.insert(state);
+                match (previous, entry.get_mut()) {
+                    // Hang onto the task handle so the request can still be
+                    // cancelled once it's been sent
+                    (
+                        RequestState::Building { abort, .. },
+                        RequestState::Loading {
+                            abort: loading_abort,
+                            ..
+                        },
+                    ) => {
+                        *loading_abort = Some(abort);
+                    }
+                    // A stream is a continuation of the loading state, so
+                    // retain the original launch time
+                    (
+                        RequestState::Loading { start_time, .. },
+                        RequestState::Streaming {
+                            start_time: stream_start_time,
+                            ..
+                        },
+                    ) => {
+                        *stream_start_time = start_time;
+                    }
+                    _ => {}
+                }
             }
             Entry::Occupied(_) => {
                 // State is already holding a different request, throw
@@ -111,6 +184,132 @@ impl EventHandler for Root {
                 recipe_id,
                 state,
             } => self.update_request(profile_id, recipe_id, state),
+            Event::HttpStreamChunk {
+                profile_id,
+                recipe_id,
+                request_id,
+                chunk,
+            } => {
+                if let Some(state) =
+                    self.active_requests.get_mut(&(profile_id, recipe_id))
+                {
+                    if state.id() == request_id {
+                        state.push_chunk(&chunk);
+                    }
+                }
+            }
+
+            Event::HttpRetry {
+                profile_id,
+                recipe_id,
+                request_id,
+                attempt,
+            } => {
+                if let Some(state) =
+                    self.active_requests.get_mut(&(profile_id, recipe_id))
+                {
+                    if state.id() == request_id {
+                        state.set_attempt(attempt);
+                    }
+                }
+            }
+            Event::HttpDownloadProgress {
+                profile_id,
+                recipe_id,
+                request_id,
+                progress,
+            } => {
+                if let Some(state) =
+                    self.active_requests.get_mut(&(profile_id, recipe_id))
+                {
+                    if state.id() == request_id {
+                        state.set_download_progress(progress);
+                    }
+                }
+            }
+
+            Event::WebSocketSetSession {
+                profile_id,
+                recipe_id,
+                session,
+            } => {
+                self.websocket_sessions
+                    .insert((profile_id, recipe_id), session);
+            }
+            Event::WebSocketUpdate {
+                profile_id,
+                recipe_id,
+                session_id,
+                update,
+            } => {
+                if let Some(session) =
+                    self.websocket_sessions.get_mut(&(profile_id, recipe_id))
+                {
+                    if session.id() == session_id {
+                        session.update(update);
+                    }
+                }
+            }
+            Event::WebSocketSend(template) => {
+                if let Some(sender) =
+                    self.active_session().and_then(WebSocketSession::sender)
+                {
+                    TuiContext::send_message(Message::WebSocketSend {
+                        profile_id: self.selected_profile_id(),
+                        template,
+                        sender: sender.clone(),
+                    });
+                }
+            }
+
+            Event::WorkflowStart {
+                run_id,
+                workflow,
+                abort,
+            } => {
+                // Only one run is shown at a time, so stop the old one
+                if let Some(pane) = &mut self.workflow_pane {
+                    pane.cancel();
+                }
+                self.workflow_pane =
+                    Some(WorkflowPane::new(run_id, &workflow, abort).into());
+            }
+            Event::WorkflowUpdate {
+                run_id,
+                index,
+                update,
+            } => {
+                if let Some(pane) = &mut self.workflow_pane {
+                    if pane.run_id() == run_id {
+                        pane.update_step(index, update);
+                    }
+                }
+            }
+            Event::WorkflowComplete { run_id } => {
+                if let Some(pane) = &mut self.workflow_pane {
+                    if pane.run_id() == run_id {
+                        pane.finish();
+                    }
+                }
+            }
+            Event::WorkflowOpenRecord { record } => {
+                let profile_id = record.request.profile_id.clone();
+                let recipe_id = record.request.recipe_id.clone();
+                self.primary_view
+                    .select_recipe(profile_id.as_ref(), &recipe_id);
+                // Replace whatever's displayed, unless a request for the
+                // recipe is still in flight
+                match self.active_requests.entry((profile_id, recipe_id)) {
+                    Entry::Occupied(entry) if !entry.get().is_complete() => {}
+                    Entry::Occupied(mut entry) => {
+                        entry.insert(RequestState::response(record));
+                    }
+                    Entry::Vacant(entry) => {
+                        entry.insert(RequestState::response(record));
+                    }
+                }
+                self.workflow_pane = None;
+            }
 
             Event::Notify(notification) => {
                 self.notification_text =
@@ -125,6 +324,26 @@ impl EventHandler for Root {
                 ..
             } => match action {
                 Action::Quit => TuiContext::send_message(Message::Quit),
+                // Cancel the displayed request, if it's still in progress
+                Action::Cancel
+                    if self
+                        .active_request_mut()
+                        .is_some_and(RequestState::cancel) => {}
+                // Close the displayed response stream, if there is one
+                Action::Cancel
+                    if self
+                        .active_request_mut()
+                        .is_some_and(RequestState::close_stream) => {}
+                // Close the displayed WebSocket connection, if there is one
+                Action::Cancel
+                    if self
+                        .active_session_mut()
+                        .is_some_and(WebSocketSession::close) => {}
+                // Close the workflow pane. A running workflow is cancelled by
+                // the pane itself first
+                Action::Cancel if self.workflow_pane.is_some() => {
+                    self.workflow_pane = None;
+                }
                 Action::ReloadCollection => {
                     TuiContext::send_message(Message::CollectionStartReload)
                 }
@@ -140,6 +359,49 @@ impl EventHandler for Root {
                     Some(GlobalAction::EditCollection) => {
                         TuiContext::send_message(Message::CollectionEdit)
                     }
+                    Some(GlobalAction::ViewCookies) => {
+                        EventQueue::open_modal(
+                            CookiesModal::new(self.selected_profile_id()),
+                            ModalPriority::Low,
+                        );
+                    }
+                    Some(GlobalAction::ClearCookies) => {
+                        let profile_id = self.selected_profile_id();
+                        EventQueue::open_modal(
+                            ConfirmModal::new(
+                                "Clear Cookies",
+                                "Delete all cookies for the selected profile?",
+                                move || {
+                                    if let Ok(count) = TuiContext::get()
+                                        .database
+                                        .clear_cookies(profile_id.as_ref())
+                                    {
+                                        EventQueue::push(Event::Notify(
+                                            Notification::new(format!(
+                                                "Cleared {count} cookie(s)"
+                                            )),
+                                        ));
+                                    }
+                                },
+                            ),
+                            ModalPriority::Low,
+                        );
+                    }
+                    Some(GlobalAction::RunWorkflow) => {
+                        if self.workflows.is_empty() {
+                            EventQueue::push(Event::Notify(Notification::new(
+                                "No workflows in collection".into(),
+                            )));
+                        } else {
+                            EventQueue::open_modal(
+                                WorkflowListModal::new(
+                                    &self.workflows,
+                                    self.selected_profile_id(),
+                                ),
+                                ModalPriority::Low,
+                            );
+                        }
+                    }
                     None => return Update::Propagate(event),
                 }
             }
@@ -159,7 +421,10 @@ impl EventHandler for Root {
         // still accept input ourselves though, which should only be
         // high-priority stuff
         if !modal_open {
-            children.push(self.primary_view.as_child());
+            match &mut self.workflow_pane {
+                Some(workflow_pane) => children.push(workflow_pane.as_child()),
+                None => children.push(self.primary_view.as_child()),
+            }
         }
 
         children
@@ -176,13 +441,18 @@ impl Draw for Root {
         );
 
         // Main content
-        self.primary_view.draw(
-            frame,
-            PrimaryViewProps {
-                active_request: self.active_request(),
-            },
-            main_area,
-        );
+        if let Some(workflow_pane) = &self.workflow_pane {
+            workflow_pane.draw(frame, (), main_area);
+        } else {
+            self.primary_view.draw(
+                frame,
+                PrimaryViewProps {
+                    active_request: self.active_request(),
+                    active_session: self.active_session(),
+                },
+                main_area,
+            );
+        }
 
         // Footer
         let [notification_area, help_area] = layout(
@@ -199,3 +469,122 @@ impl Draw for Root {
         self.modal_queue.draw(frame, (), frame.size());
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{http::RequestId, test_util::*, tui::context::tui_context};
+    use factori::create;
+    use rstest::rstest;
+    use std::future;
+    use uuid::Uuid;
+
+    /// Updates for a request that was cancelled are thrown away, but a new
+    /// request replaces it
+    #[rstest]
+    #[tokio::test]
+    async fn test_update_request_stale(_tui_context: ()) {
+        let mut root = Root::new(&Collection::default());
+        let recipe_id = RecipeId::from("recipe1");
+        let key = (None, recipe_id.clone());
+        let task = tokio::spawn(future::pending::<()>());
+        let id = RequestId::new();
+        root.update_request(
+            None,
+            recipe_id.clone(),
+            RequestState::building(id, task.abort_handle()),
+        );
+        assert!(root.active_requests.get_mut(&key).unwrap().cancel());
+
+        // The build finished before the task was aborted
+        root.update_request(
+            None,
+            recipe_id.clone(),
+            RequestState::loading(create!(Request, id: id).into()),
+        );
+        assert!(matches!(
+            root.active_requests[&key],
+            RequestState::Cancelled { .. }
+        ));
+
+        let new_id = RequestId::new();
+        root.update_request(
+            None,
+            recipe_id.clone(),
+            RequestState::building(new_id, task.abort_handle()),
+        );
+        assert_eq!(root.active_requests[&key].id(), new_id);
+    }
+
+    /// The building task's abort handle is carried over once the request is
+    /// loading, so it can still be cancelled
+    #[rstest]
+    #[tokio::test]
+    async fn test_update_request_keep_abort(_tui_context: ()) {
+        let mut root = Root::new(&Collection::default());
+        let recipe_id = RecipeId::from("recipe1");
+        let task = tokio::spawn(future::pending::<()>());
+        let id = RequestId::new();
+        root.update_request(
+            None,
+            recipe_id.clone(),
+            RequestState::building(id, task.abort_handle()),
+        );
+        root.update_request(
+            None,
+            recipe_id.clone(),
+            RequestState::loading(create!(Request, id: id).into()),
+        );
+
+        let state = root.active_requests.get_mut(&(None, recipe_id)).unwrap();
+        assert!(state.cancel());
+        assert!(task.await.unwrap_err().is_cancelled());
+    }
+
+    /// Dropping the view, e.g. when the collection is reloaded, stops the
+    /// running workflow
+    #[rstest]
+    #[tokio::test]
+    async fn test_workflow_drop(_tui_context: ()) {
+        let mut root = Root::new(&Collection::default());
+        let task = tokio::spawn(future::pending::<()>());
+        root.update(Event::WorkflowStart {
+            run_id: Uuid::new_v4(),
+            workflow: workflow(),
+            abort: task.abort_handle(),
+        });
+        assert!(root.workflow_pane.is_some());
+
+        drop(root);
+        assert!(task.await.unwrap_err().is_cancelled());
+    }
+
+    /// Opening a step's record closes the workflow, and shows the record in
+    /// the primary view
+    #[rstest]
+    #[tokio::test]
+    async fn test_workflow_open_record(_tui_context: ()) {
+        let mut root = Root::new(&Collection::default());
+        let task = tokio::spawn(future::pending::<()>());
+        root.update(Event::WorkflowStart {
+            run_id: Uuid::new_v4(),
+            workflow: workflow(),
+            abort: task.abort_handle(),
+        });
+        let record = create!(RequestRecord);
+        let id = record.id;
+        let key = (None, record.request.recipe_id.clone());
+
+        root.update(Event::WorkflowOpenRecord { record });
+        assert!(root.workflow_pane.is_none());
+        assert_eq!(root.active_requests[&key].id(), id);
+    }
+
+    fn workflow() -> Workflow {
+        Workflow {
+            id: "workflow1".to_owned().into(),
+            name: None,
+            steps: Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,209 @@
+use crate::tui::{
+    context::TuiContext,
+    input::Action,
+    view::{
+        common::{table::Table, text_box::TextBox, Pane},
+        draw::{Draw, Generate},
+        event::{Event, EventHandler, EventQueue, Update},
+        state::{WebSocketSession, WebSocketStatus},
+        util::layout,
+        Component,
+    },
+};
+use itertools::Itertools;
+use ratatui::{
+    prelude::{Alignment, Constraint, Direction, Rect},
+    widgets::{Cell, Paragraph, Wrap},
+    Frame,
+};
+use std::cell::Cell as StdCell;
+
+/// Display a WebSocket session: connection status, a log of all messages, and
+/// a text box to compose new messages. This takes the place of the request
+/// and response panes when a WebSocket recipe is selected.
+#[derive(derive_more::Debug)]
+pub struct WebSocketPane {
+    /// Where the user writes messages. Messages are templates, which are
+    /// rendered when sent
+    #[debug(skip)]
+    text_box: Component<TextBox>,
+    /// Was the displayed session connected in the last draw? Messages can
+    /// only be written while connected
+    is_connected: StdCell<bool>,
+}
+
+pub struct WebSocketPaneProps<'a> {
+    pub is_selected: bool,
+    pub session: Option<&'a WebSocketSession>,
+}
+
+impl WebSocketPane {
+    /// Create an empty text box
+    fn text_box(focused: bool) -> Component<TextBox> {
+        TextBox::default()
+            .with_focus(focused)
+            .with_placeholder("Message (templates are rendered before sending)")
+            // Root owns the session, so let it handle sending
+            .with_on_submit(|text_box| {
+                EventQueue::push(Event::WebSocketSend(
+                    text_box.text().to_owned(),
+                ))
+            })
+            .into()
+    }
+}
+
+impl Default for WebSocketPane {
+    fn default() -> Self {
+        Self {
+            text_box: Self::text_box(false),
+            is_connected: StdCell::new(false),
+        }
+    }
+}
+
+impl EventHandler for WebSocketPane {
+    fn update(&mut self, event: Event) -> Update {
+        match event {
+            // Start writing a message. If the text box is already focused it
+            // will have handled this. If not connected, let the parent
+            // (re)connect
+            Event::Input {
+                action: Some(Action::Submit),
+                ..
+            } if self.is_connected.get() => self.text_box.focus(),
+            // Clear the box so the next message can be written right away,
+            // then let the parent actually send it
+            Event::WebSocketSend(_) => {
+                self.text_box = Self::text_box(true);
+                return Update::Propagate(event);
+            }
+            _ => return Update::Propagate(event),
+        }
+        Update::Consumed
+    }
+
+    fn children(&mut self) -> Vec<Component<&mut dyn EventHandler>> {
+        if self.text_box.is_focused() {
+            vec![self.text_box.as_child()]
+        } else {
+            vec![]
+        }
+    }
+}
+
+impl<'a> Draw<WebSocketPaneProps<'a>> for WebSocketPane {
+    fn draw(
+        &self,
+        frame: &mut Frame,
+        props: WebSocketPaneProps<'a>,
+        area: Rect,
+    ) {
+        let context = TuiContext::get();
+
+        // Render outermost block
+        let title = context
+            .input_engine
+            .add_hint("Session", Action::SelectRequest);
+        let block = Pane {
+            title: &title,
+            is_focused: props.is_selected,
+        };
+        let block = block.generate();
+        frame.render_widget(&block, area);
+        let area = block.inner(area);
+
+        let Some(session) = props.session else {
+            frame.render_widget(
+                Paragraph::new(
+                    context.input_engine.add_hint("Connect", Action::Submit),
+                ),
+                area,
+            );
+            return;
+        };
+
+        let [status_area, messages_area, text_box_area] = layout(
+            area,
+            Direction::Vertical,
+            [
+                Constraint::Length(1),
+                Constraint::Min(0),
+                Constraint::Length(1),
+            ],
+        );
+
+        // Connection status
+        let status = match session.status() {
+            WebSocketStatus::Connecting => "Connecting...".into(),
+            WebSocketStatus::Connected => {
+                context.input_engine.add_hint("Connected", Action::Cancel)
+            }
+            WebSocketStatus::Closing => "Closing...".into(),
+            WebSocketStatus::Closed { reason: None } => "Closed".into(),
+            WebSocketStatus::Closed {
+                reason: Some(reason),
+            } => format!("Closed: {reason}"),
+            WebSocketStatus::Error(error) => format!("Error: {error}"),
+        };
+        let status = if matches!(
+            session.status(),
+            WebSocketStatus::Closed { .. } | WebSocketStatus::Error(_)
+        ) {
+            let reconnect =
+                context.input_engine.add_hint("Reconnect", Action::Submit);
+            format!("{status} - {reconnect}")
+        } else {
+            status
+        };
+        frame.render_widget(Paragraph::new(status), status_area);
+        frame.render_widget(
+            Paragraph::new(format!("{} messages", session.messages().len()))
+                .alignment(Alignment::Right),
+            status_area,
+        );
+
+        // If the connection never opened, show the full error
+        if let (WebSocketStatus::Error(error), true) =
+            (session.status(), session.messages().is_empty())
+        {
+            frame.render_widget(
+                Paragraph::new(error.generate()).wrap(Wrap::default()),
+                messages_area,
+            );
+        }
+
+        // Only show as many messages as fit, newest at the bottom
+        let messages = session.messages();
+        let skip = messages.len().saturating_sub(messages_area.height as usize);
+        let table = Table {
+            rows: messages[skip..]
+                .iter()
+                .map(|entry| {
+                    let direction = if entry.sent { "→" } else { "←" };
+                    [
+                        Cell::from(entry.time.generate()),
+                        direction.into(),
+                        // Keep each message on one line
+                        entry.message.to_string().replace('\n', " ").into(),
+                    ]
+                })
+                .collect_vec(),
+            column_widths: &[
+                Constraint::Length(15),
+                Constraint::Length(1),
+                Constraint::Min(0),
+            ],
+            ..Default::default()
+        };
+        if !messages.is_empty() {
+            frame.render_widget(table.generate(), messages_area);
+        }
+
+        let is_connected = session.sender().is_some();
+        self.is_connected.set(is_connected);
+        if is_connected {
+            self.text_box.draw(frame, (), text_box_area);
+        }
+    }
+}
@@ -0,0 +1,379 @@
+//! Components for running workflows: a modal to pick one, and a pane that
+//! shows the progress of each step
+
+use crate::{
+    collection::{ProfileId, RecipeId, Workflow, WorkflowId},
+    http::{RequestRecord, StepOutcome, StepUpdate},
+    tui::{
+        context::TuiContext,
+        input::Action,
+        message::Message,
+        view::{
+            common::{modal::Modal, Pane},
+            draw::{Draw, Generate},
+            event::{Event, EventHandler, EventQueue, Update},
+            state::select::SelectState,
+            util::layout,
+            Component,
+        },
+    },
+};
+use itertools::Itertools;
+use ratatui::{
+    layout::{Constraint, Direction, Rect},
+    text::{Line, Span, Text},
+    widgets::{List, ListItem, Paragraph, Wrap},
+    Frame,
+};
+use tokio::task::AbortHandle;
+use uuid::Uuid;
+
+/// List the collection's workflows. Submitting one runs it with the selected
+/// profile
+#[derive(Debug)]
+pub struct WorkflowListModal {
+    workflows: Component<SelectState<(WorkflowId, String)>>,
+}
+
+impl WorkflowListModal {
+    pub fn new<'a>(
+        workflows: impl IntoIterator<Item = &'a Workflow>,
+        profile_id: Option<ProfileId>,
+    ) -> Self {
+        let workflows = workflows
+            .into_iter()
+            .map(|workflow| (workflow.id.clone(), workflow.name().to_owned()))
+            .collect();
+        let workflows =
+            SelectState::new(workflows).on_submit(move |(workflow_id, _)| {
+                EventQueue::push(Event::CloseModal);
+                TuiContext::send_message(Message::WorkflowRun {
+                    profile_id: profile_id.clone(),
+                    workflow_id: workflow_id.clone(),
+                });
+            });
+        Self {
+            workflows: workflows.into(),
+        }
+    }
+}
+
+impl Modal for WorkflowListModal {
+    fn title(&self) -> &str {
+        "Run Workflow"
+    }
+
+    fn dimensions(&self) -> (Constraint, Constraint) {
+        (
+            Constraint::Length(40),
+            Constraint::Length(self.workflows.items().len() as u16),
+        )
+    }
+}
+
+impl EventHandler for WorkflowListModal {
+    fn children(&mut self) -> Vec<Component<&mut dyn EventHandler>> {
+        vec![self.workflows.as_child()]
+    }
+}
+
+impl Draw for WorkflowListModal {
+    fn draw(&self, frame: &mut Frame, _: (), area: Rect) {
+        let items = self
+            .workflows
+            .items()
+            .iter()
+            .map(|(_, name)| ListItem::new(name.as_str()))
+            .collect_vec();
+        let list = List::new(items)
+            .highlight_style(TuiContext::get().theme.list.highlight);
+        frame.render_stateful_widget(
+            list,
+            area,
+            &mut self.workflows.state_mut(),
+        );
+    }
+}
+
+/// Show each step of a running (or finished) workflow, with a summary of the
+/// selected step. This replaces the primary view until it's closed. Once the
+/// workflow is done, a step's full request and response can be opened in the
+/// primary view. The workflow is cancelled if the pane is dropped.
+#[derive(derive_more::Debug)]
+pub struct WorkflowPane {
+    run_id: Uuid,
+    title: String,
+    /// Handle to the task running the workflow, so it can be cancelled
+    abort: AbortHandle,
+    finished: bool,
+    #[debug(skip)]
+    steps: Component<SelectState<StepItem>>,
+}
+
+#[derive(Debug)]
+struct StepItem {
+    recipe_id: RecipeId,
+    state: StepState,
+}
+
+#[derive(Debug)]
+enum StepState {
+    Pending,
+    Running,
+    Skipped,
+    /// The workflow was cancelled while this step was running
+    Cancelled,
+    Finished(StepOutcome),
+}
+
+impl StepItem {
+    /// The request+response from this step, if it got that far
+    fn record(&self) -> Option<&RequestRecord> {
+        match &self.state {
+            StepState::Finished(outcome) => outcome.record.as_ref(),
+            _ => None,
+        }
+    }
+}
+
+impl WorkflowPane {
+    pub fn new(run_id: Uuid, workflow: &Workflow, abort: AbortHandle) -> Self {
+        let steps = workflow
+            .steps
+            .iter()
+            .map(|step| StepItem {
+                recipe_id: step.recipe.clone(),
+                state: StepState::Pending,
+            })
+            .collect();
+        Self {
+            run_id,
+            title: format!("Workflow: {}", workflow.name()),
+            abort,
+            finished: false,
+            steps: SelectState::new(steps).into(),
+        }
+    }
+
+    pub fn run_id(&self) -> Uuid {
+        self.run_id
+    }
+
+    /// Update the state of a step. The selection follows the workflow, so the
+    /// latest step is always shown
+    pub fn update_step(&mut self, index: usize, update: StepUpdate) {
+        if let Some(step) = self.steps.items_mut().get_mut(index) {
+            step.state = match update {
+                StepUpdate::Started => StepState::Running,
+                StepUpdate::Skipped => StepState::Skipped,
+                StepUpdate::Finished(outcome) => StepState::Finished(outcome),
+            };
+            self.steps.select_index(index);
+        }
+    }
+
+    /// Mark the workflow as done. Any remaining steps will never run
+    pub fn finish(&mut self) {
+        self.finished = true;
+    }
+
+    /// Cancel the workflow, if it's still running. Return `false` if it
+    /// already finished
+    pub fn cancel(&mut self) -> bool {
+        if self.finished {
+            return false;
+        }
+        self.abort.abort();
+        self.finished = true;
+        for step in self.steps.items_mut() {
+            if let StepState::Running = step.state {
+                step.state = StepState::Cancelled;
+            }
+        }
+        true
+    }
+
+    /// Draw the details of a step. Most of this comes from its outcome
+    fn draw_step(&self, frame: &mut Frame, step: &StepItem, area: Rect) {
+        let theme = &TuiContext::get().theme;
+        let outcome = match &step.state {
+            StepState::Finished(outcome) => outcome,
+            StepState::Pending if self.finished => {
+                frame.render_widget(Paragraph::new("Not run"), area);
+                return;
+            }
+            state => {
+                frame.render_widget(Paragraph::new(state.label()), area);
+                return;
+            }
+        };
+
+        let mut lines: Vec<Line> = Vec::new();
+        if let Some(record) = &outcome.record {
+            let request = &record.request;
+            let response = &record.response;
+            lines.push(format!("{} {}", request.method, request.url).into());
+            lines.push(Line::from(vec![
+                Span::styled(response.status.to_string(), theme.text.highlight),
+                format!(" ({}ms)", record.duration().num_milliseconds()).into(),
+            ]));
+        }
+        if let Some(error) = &outcome.error {
+            lines.push(
+                Span::styled(format!("{error:#}"), theme.text.error).into(),
+            );
+        }
+        if !outcome.captures.is_empty() {
+            lines.push("".into());
+            lines.push(Span::styled("Captured", theme.text.highlight).into());
+            lines.extend(
+                outcome.captures.iter().map(|(field, value)| {
+                    format!("  {field} = {value}").into()
+                }),
+            );
+        }
+        if !outcome.assertions.is_empty() {
+            lines.push("".into());
+            lines.push(Span::styled("Assertions", theme.text.highlight).into());
+            lines.extend(outcome.assertions.iter().map(|assertion| {
+                if assertion.passed() {
+                    Line::from(vec![
+                        Span::styled("  ✓ ", theme.text.success),
+                        assertion.to_string().into(),
+                    ])
+                } else {
+                    Line::from(vec![
+                        Span::styled("  ✗ ", theme.text.error),
+                        assertion.to_string().into(),
+                    ])
+                }
+            }));
+        }
+        frame.render_widget(
+            Paragraph::new(Text::from(lines)).wrap(Wrap::default()),
+            area,
+        );
+    }
+}
+
+impl StepState {
+    fn label(&self) -> &'static str {
+        match self {
+            Self::Pending => "Pending",
+            Self::Running => "Running...",
+            Self::Skipped => "Skipped",
+            Self::Cancelled => "Cancelled",
+            Self::Finished(outcome) if outcome.passed() => "Passed",
+            Self::Finished(_) => "Failed",
+        }
+    }
+}
+
+impl Drop for WorkflowPane {
+    fn drop(&mut self) {
+        // Closing the pane, or rebuilding the view on collection reload,
+        // shouldn't leave the workflow running in the background
+        self.abort.abort();
+    }
+}
+
+impl EventHandler for WorkflowPane {
+    fn update(&mut self, event: Event) -> Update {
+        match event {
+            Event::Input {
+                action: Some(Action::Cancel),
+                ..
+            } if self.cancel() => Update::Consumed,
+            // Opening a record closes the pane, so wait until the workflow is
+            // done
+            Event::Input {
+                action: Some(Action::Submit),
+                ..
+            } if self.finished => {
+                if let Some(record) =
+                    self.steps.selected().and_then(StepItem::record)
+                {
+                    EventQueue::push(Event::WorkflowOpenRecord {
+                        record: record.clone(),
+                    });
+                }
+                Update::Consumed
+            }
+            _ => Update::Propagate(event),
+        }
+    }
+
+    fn children(&mut self) -> Vec<Component<&mut dyn EventHandler>> {
+        vec![self.steps.as_child()]
+    }
+}
+
+impl Draw for WorkflowPane {
+    fn draw(&self, frame: &mut Frame, _: (), area: Rect) {
+        let context = TuiContext::get();
+        let theme = &context.theme;
+        let block = Pane {
+            title: &self.title,
+            is_focused: true,
+        }
+        .generate();
+        frame.render_widget(&block, area);
+        let area = block.inner(area);
+
+        let [main_area, help_area] = layout(
+            area,
+            Direction::Vertical,
+            [Constraint::Min(0), Constraint::Length(1)],
+        );
+        let [list_area, detail_area] = layout(
+            main_area,
+            Direction::Horizontal,
+            [Constraint::Max(40), Constraint::Min(0)],
+        );
+
+        let items = self
+            .steps
+            .items()
+            .iter()
+            .enumerate()
+            .map(|(index, step)| {
+                let (mark, style) = match &step.state {
+                    StepState::Pending => ("·", Default::default()),
+                    StepState::Running => ("…", theme.text.highlight),
+                    StepState::Skipped => ("-", Default::default()),
+                    StepState::Finished(outcome) if outcome.passed() => {
+                        ("✓", theme.text.success)
+                    }
+                    StepState::Cancelled | StepState::Finished(_) => {
+                        ("✗", theme.text.error)
+                    }
+                };
+                ListItem::new(Line::from(vec![
+                    Span::styled(mark, style),
+                    format!(" {}. {}", index + 1, step.recipe_id).into(),
+                ]))
+            })
+            .collect_vec();
+        let list = List::new(items).highlight_style(theme.list.highlight);
+        frame.render_stateful_widget(
+            list,
+            list_area,
+            &mut self.steps.state_mut(),
+        );
+
+        if let Some(step) = self.steps.selected() {
+            self.draw_step(frame, step, detail_area);
+        }
+
+        let help = if self.finished {
+            format!(
+                "{} / {}",
+                context.input_engine.add_hint("Open", Action::Submit),
+                context.input_engine.add_hint("Close", Action::Cancel)
+            )
+        } else {
+            context.input_engine.add_hint("Cancel", Action::Cancel)
+        };
+        frame.render_widget(Paragraph::new(help), help_area);
+    }
+}
@@ -2,19 +2,25 @@
 //! events (e.g. HTTP responses)
 
 use crate::{
-    collection::{ProfileId, RecipeId},
+    collection::{ProfileId, RecipeId, Workflow},
+    http::{
+        DownloadProgress, RequestId, RequestRecord, StepUpdate, WebSocketEvent,
+    },
     tui::{
         input::Action,
         view::{
             common::modal::{Modal, ModalPriority},
-            state::{Notification, RequestState},
+            state::{Notification, RequestState, WebSocketSession},
             Component,
         },
     },
 };
+use bytes::Bytes;
 use crossterm::event::{MouseEvent, MouseEventKind};
 use std::{any::Any, cell::RefCell, collections::VecDeque, fmt::Debug};
+use tokio::task::AbortHandle;
 use tracing::trace;
+use uuid::Uuid;
 
 /// A UI element that can handle user/async input. This trait facilitates an
 /// on-demand tree structure, where each element can furnish its list of
@@ -117,6 +123,70 @@ pub enum Event {
         #[debug(skip)]
         state: RequestState,
     },
+    /// Received the next chunk of a streaming response body
+    HttpStreamChunk {
+        profile_id: Option<ProfileId>,
+        recipe_id: RecipeId,
+        request_id: RequestId,
+        #[debug(skip)]
+        chunk: Bytes,
+    },
+    /// A loading request is being retried
+    HttpRetry {
+        profile_id: Option<ProfileId>,
+        recipe_id: RecipeId,
+        request_id: RequestId,
+        attempt: u32,
+    },
+    /// More of a loading request's download has been written
+    HttpDownloadProgress {
+        profile_id: Option<ProfileId>,
+        recipe_id: RecipeId,
+        request_id: RequestId,
+        progress: DownloadProgress,
+    },
+
+    // WebSocket
+    /// Start tracking a new WebSocket session, replacing (and closing) any
+    /// existing one for the profile+recipe
+    WebSocketSetSession {
+        profile_id: Option<ProfileId>,
+        recipe_id: RecipeId,
+        #[debug(skip)]
+        session: WebSocketSession,
+    },
+    /// Update a WebSocket session based on events from the connection
+    WebSocketUpdate {
+        profile_id: Option<ProfileId>,
+        recipe_id: RecipeId,
+        session_id: RequestId,
+        update: anyhow::Result<WebSocketEvent>,
+    },
+    /// User wants to send a message (template) over the displayed WebSocket
+    WebSocketSend(String),
+
+    // Workflows
+    /// A workflow was launched. Show its progress, replacing any previous run
+    WorkflowStart {
+        run_id: Uuid,
+        workflow: Workflow,
+        abort: AbortHandle,
+    },
+    /// A step of a running workflow made progress
+    WorkflowUpdate {
+        run_id: Uuid,
+        index: usize,
+        #[debug(skip)]
+        update: StepUpdate,
+    },
+    /// A workflow finished running
+    WorkflowComplete { run_id: Uuid },
+    /// Close the workflow pane, and show the record from one of its steps in
+    /// the primary view
+    WorkflowOpenRecord {
+        #[debug(skip)]
+        record: RequestRecord,
+    },
 
     /// Show a modal to the user
     OpenModal {
@@ -0,0 +1,326 @@
+//! Syntax highlighting for text bodies. Highlighting is done one line at a
+//! time, so that only the visible portion of a large body ever needs to be
+//! styled. This is exact for prettified JSON, where no token spans multiple
+//! lines. For XML/HTML it's an approximation: a tag or comment that wraps onto
+//! multiple lines will only be highlighted on its first line.
+
+use crate::tui::view::theme::ThemeSyntax;
+use ratatui::text::{Line, Span};
+
+/// A language that we know how to highlight
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Language {
+    Json,
+    /// Also used for HTML, which is close enough for highlighting purposes
+    Xml,
+}
+
+impl Language {
+    /// Guess the language of a body from its `Content-Type` header
+    pub fn from_content_type(header_value: &[u8]) -> Option<Self> {
+        let header_value = std::str::from_utf8(header_value).ok()?;
+        let mime = header_value
+            .split_once(';')
+            .map(|t| t.0)
+            .unwrap_or(header_value)
+            .trim()
+            .to_ascii_lowercase();
+        if mime.ends_with("/json") || mime.ends_with("+json") {
+            Some(Self::Json)
+        } else if mime.ends_with("/xml")
+            || mime.ends_with("+xml")
+            || mime == "text/html"
+        {
+            Some(Self::Xml)
+        } else {
+            None
+        }
+    }
+
+    /// Style a single line of text
+    pub fn highlight<'a>(self, line: &'a str, theme: &ThemeSyntax) -> Line<'a> {
+        let mut spans = Vec::new();
+        match self {
+            Self::Json => highlight_json(line, theme, &mut spans),
+            Self::Xml => highlight_xml(line, theme, &mut spans),
+        }
+        Line::from(spans)
+    }
+}
+
+fn highlight_json<'a>(
+    line: &'a str,
+    theme: &ThemeSyntax,
+    spans: &mut Vec<Span<'a>>,
+) {
+    let bytes = line.as_bytes();
+    let mut plain_start = 0;
+    let mut i = 0;
+    // Flush any unstyled text before a token, then add the token
+    let mut push = |spans: &mut Vec<Span<'a>>, start, end, style| {
+        if plain_start < start {
+            spans.push(Span::raw(&line[plain_start..start]));
+        }
+        spans.push(Span::styled(&line[start..end], style));
+        plain_start = end;
+    };
+
+    while i < bytes.len() {
+        let start = i;
+        match bytes[i] {
+            b'"' => {
+                i = string_end(bytes, i);
+                // A string followed by a colon is an object key
+                let is_key = line[i..].trim_start().starts_with(':');
+                let style = if is_key { theme.key } else { theme.string };
+                push(spans, start, i, style);
+            }
+            b'-' | b'0'..=b'9' => {
+                i += 1;
+                while i < bytes.len()
+                    && matches!(
+                        bytes[i],
+                        b'0'..=b'9' | b'.' | b'e' | b'E' | b'+' | b'-'
+                    )
+                {
+                    i += 1;
+                }
+                push(spans, start, i, theme.number);
+            }
+            b'{' | b'}' | b'[' | b']' | b',' | b':' => {
+                i += 1;
+                push(spans, start, i, theme.punctuation);
+            }
+            _ => {
+                let rest = &line[i..];
+                if let Some(word) = ["true", "false", "null"]
+                    .into_iter()
+                    .find(|word| rest.starts_with(word))
+                {
+                    i += word.len();
+                    let style = if word == "null" {
+                        theme.null
+                    } else {
+                        theme.boolean
+                    };
+                    push(spans, start, i, style);
+                } else {
+                    // Skip to the next char boundary
+                    i += rest.chars().next().map_or(1, char::len_utf8);
+                }
+            }
+        }
+    }
+    if plain_start < line.len() {
+        spans.push(Span::raw(&line[plain_start..]));
+    }
+}
+
+fn highlight_xml<'a>(
+    line: &'a str,
+    theme: &ThemeSyntax,
+    spans: &mut Vec<Span<'a>>,
+) {
+    let mut rest = line;
+    while !rest.is_empty() {
+        // Text content, up to the next tag
+        let Some(tag_start) = rest.find('<') else {
+            spans.push(Span::raw(rest));
+            break;
+        };
+        if tag_start > 0 {
+            spans.push(Span::raw(&rest[..tag_start]));
+        }
+        rest = &rest[tag_start..];
+
+        if rest.starts_with("<!--") {
+            let end = rest.find("-->").map_or(rest.len(), |i| i + 3);
+            spans.push(Span::styled(&rest[..end], theme.comment));
+            rest = &rest[end..];
+            continue;
+        }
+
+        // Opening punctuation, e.g. `<`, `</`, `<?`, `<!`
+        let open_length = if rest[1..].starts_with(['/', '?', '!']) {
+            2
+        } else {
+            1
+        };
+        spans.push(Span::styled(&rest[..open_length], theme.punctuation));
+        rest = &rest[open_length..];
+
+        // Tag name
+        let name_end = rest
+            .find(|c: char| c.is_whitespace() || c == '>' || c == '/')
+            .unwrap_or(rest.len());
+        if name_end > 0 {
+            spans.push(Span::styled(&rest[..name_end], theme.tag));
+        }
+        rest = &rest[name_end..];
+
+        // Attributes, until the end of the tag
+        while let Some(c) = rest.chars().next() {
+            if c == '>' || rest.starts_with("/>") || rest.starts_with("?>") {
+                let end = if c == '>' { 1 } else { 2 };
+                spans.push(Span::styled(&rest[..end], theme.punctuation));
+                rest = &rest[end..];
+                break;
+            } else if c == '"' || c == '\'' {
+                let end = rest[1..].find(c).map_or(rest.len(), |i| i + 2);
+                spans.push(Span::styled(&rest[..end], theme.string));
+                rest = &rest[end..];
+            } else if c == '=' {
+                spans.push(Span::styled(&rest[..1], theme.punctuation));
+                rest = &rest[1..];
+            } else if c.is_whitespace() {
+                let end = rest
+                    .find(|c: char| !c.is_whitespace())
+                    .unwrap_or(rest.len());
+                spans.push(Span::raw(&rest[..end]));
+                rest = &rest[end..];
+            } else {
+                let end = rest
+                    .find(|c: char| {
+                        c.is_whitespace() || matches!(c, '=' | '>' | '/' | '?')
+                    })
+                    .unwrap_or(rest.len())
+                    // Always make progress, e.g. on a stray `/`
+                    .max(c.len_utf8());
+                spans.push(Span::styled(&rest[..end], theme.key));
+                rest = &rest[end..];
+            }
+        }
+    }
+}
+
+/// Find the end of a JSON string that starts at the given index. Returns the
+/// index *after* the closing quote, or the end of the input if it's
+/// unterminated.
+fn string_end(bytes: &[u8], start: usize) -> usize {
+    let mut i = start + 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'"' => return i + 1,
+            _ => i += 1,
+        }
+    }
+    bytes.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tui::view::Theme;
+    use ratatui::style::Style;
+    use rstest::rstest;
+
+    /// Highlight a line, then map each span to the name of its style, so the
+    /// assertions don't depend on the actual theme colors
+    fn highlight(language: Language, line: &str) -> Vec<(&str, &str)> {
+        let theme = Theme::default().syntax;
+        let style_name = |style: Style| {
+            [
+                (theme.key, "key"),
+                (theme.string, "string"),
+                (theme.number, "number"),
+                (theme.boolean, "boolean"),
+                (theme.null, "null"),
+                (theme.punctuation, "punctuation"),
+                (theme.tag, "tag"),
+                (theme.comment, "comment"),
+            ]
+            .into_iter()
+            .find(|(s, _)| *s == style)
+            .map_or("plain", |(_, name)| name)
+        };
+        language
+            .highlight(line, &theme)
+            .spans
+            .into_iter()
+            .map(|span| {
+                let name = style_name(span.style);
+                // Spans all borrow from the input, so this can't fail
+                let std::borrow::Cow::Borrowed(content) = span.content else {
+                    panic!("Span should be borrowed")
+                };
+                (content, name)
+            })
+            .collect()
+    }
+
+    #[rstest]
+    #[case::json("application/json", Some(Language::Json))]
+    #[case::json_suffix(
+        "application/vnd.api+json; charset=utf-8",
+        Some(Language::Json)
+    )]
+    #[case::xml("text/xml", Some(Language::Xml))]
+    #[case::html("text/html; charset=utf-8", Some(Language::Xml))]
+    #[case::unknown("text/plain", None)]
+    fn test_from_content_type(
+        #[case] header_value: &str,
+        #[case] expected: Option<Language>,
+    ) {
+        assert_eq!(
+            Language::from_content_type(header_value.as_bytes()),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_highlight_json() {
+        assert_eq!(
+            highlight(
+                Language::Json,
+                r#"  "k\"ey": ["v", -1.5e3, true, null],"#
+            ),
+            vec![
+                ("  ", "plain"),
+                (r#""k\"ey""#, "key"),
+                (":", "punctuation"),
+                (" ", "plain"),
+                ("[", "punctuation"),
+                (r#""v""#, "string"),
+                (",", "punctuation"),
+                (" ", "plain"),
+                ("-1.5e3", "number"),
+                (",", "punctuation"),
+                (" ", "plain"),
+                ("true", "boolean"),
+                (",", "punctuation"),
+                (" ", "plain"),
+                ("null", "null"),
+                ("]", "punctuation"),
+                (",", "punctuation"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_highlight_xml() {
+        assert_eq!(
+            highlight(
+                Language::Xml,
+                r#"<a href="/x">text</a><br/><!-- hi -->"#
+            ),
+            vec![
+                ("<", "punctuation"),
+                ("a", "tag"),
+                (" ", "plain"),
+                ("href", "key"),
+                ("=", "punctuation"),
+                ("\"/x\"", "string"),
+                (">", "punctuation"),
+                ("text", "plain"),
+                ("</", "punctuation"),
+                ("a", "tag"),
+                (">", "punctuation"),
+                ("<", "punctuation"),
+                ("br", "tag"),
+                ("/>", "punctuation"),
+                ("<!-- hi -->", "comment"),
+            ]
+        );
+    }
+}
@@ -4,15 +4,24 @@ pub mod fixed_select;
 pub mod persistence;
 pub mod select;
 
-use crate::http::{
-    Request, RequestBuildError, RequestError, RequestId, RequestRecord,
-    ResponseContent,
+use crate::{
+    http::{
+        DownloadProgress, EventStreamParser, Request, RequestBuildError,
+        RequestError, RequestId, RequestRecord, Response, ResponseContent,
+        ServerEvent, WebSocketEvent, WebSocketMessage,
+    },
+    tui::context::TuiContext,
 };
 use chrono::{DateTime, Duration, Utc};
 use derive_more::Deref;
+use reqwest::{header::HeaderMap, StatusCode};
 use std::{
-    cell::{Ref, RefCell},
-    sync::Arc,
+    cell::{Cell, Ref, RefCell},
+    sync::{Arc, OnceLock},
+};
+use tokio::{
+    sync::{mpsc::UnboundedSender, oneshot},
+    task::AbortHandle,
 };
 
 /// An internally mutable cell for UI state. Certain state needs to be updated
@@ -98,7 +107,13 @@ impl<K, V> Default for StateCell<K, V> {
 pub enum RequestState {
     /// The request is being built. Typically this is very fast, but can be
     /// slow if a chain source takes a while.
-    Building { id: RequestId },
+    Building {
+        id: RequestId,
+        start_time: DateTime<Utc>,
+        /// Handle to the task that builds and sends the request, so it can be
+        /// cancelled
+        abort: AbortHandle,
+    },
 
     /// Something went wrong during the build :(
     BuildError { error: RequestBuildError },
@@ -111,6 +126,33 @@ pub enum RequestState {
         /// pointer to the request as well
         request: Arc<Request>,
         start_time: DateTime<Utc>,
+        /// Handle to the sending task. This is carried over from the
+        /// `Building` state, so it's only `None` until the state is stored
+        abort: Option<AbortHandle>,
+        /// Which attempt is in flight? Only goes above 1 if the request has
+        /// a retry policy
+        attempt: u32,
+        /// How much of the body has been downloaded, if the request is
+        /// downloading to a file and the response has started
+        download: Option<DownloadProgress>,
+    },
+
+    /// The response headers have been received, and an event stream body is
+    /// being received. Once the stream ends (or is closed by the user), this
+    /// transitions to `Response`.
+    Streaming {
+        request: Arc<Request>,
+        start_time: DateTime<Utc>,
+        status: StatusCode,
+        headers: HeaderMap,
+        /// Events received so far
+        events: Vec<ServerEvent>,
+        parser: EventStreamParser,
+        /// Number of body bytes received so far
+        size: usize,
+        /// Send on this to close the stream. `None` once the user has closed
+        /// it, while we wait for the final response
+        cancel: Option<oneshot::Sender<()>>,
     },
 
     /// A resolved HTTP response, with all content loaded and ready to be
@@ -118,13 +160,22 @@ pub enum RequestState {
     /// received response is considered a "success".
     Response {
         record: RequestRecord,
-        /// For responses of a known content type, we'll pre-parsed it so we
-        /// can do fancy things like prettification and filtering later
-        parsed_body: Option<Box<dyn ResponseContent>>,
+        /// For responses of a known content type, we'll parse it so we can
+        /// do fancy things like prettification and filtering later
+        parsed_body: ParsedBody,
     },
 
     /// Error occurred sending the request or receiving the response.
     RequestError { error: RequestError },
+
+    /// The user cancelled the request before it completed. `request` is
+    /// `None` if it was cancelled while still being built.
+    Cancelled {
+        id: RequestId,
+        request: Option<Arc<Request>>,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    },
 }
 
 #[derive(Debug)]
@@ -141,9 +192,11 @@ impl RequestState {
     /// cycle
     pub fn id(&self) -> RequestId {
         match self {
-            Self::Building { id } => *id,
+            Self::Building { id, .. } | Self::Cancelled { id, .. } => *id,
             Self::BuildError { error } => error.id,
-            Self::Loading { request, .. } => request.id,
+            Self::Loading { request, .. } | Self::Streaming { request, .. } => {
+                request.id
+            }
             Self::RequestError { error } => error.request.id,
             Self::Response { record, .. } => record.id,
         }
@@ -159,7 +212,8 @@ impl RequestState {
     pub fn metadata(&self) -> Option<RequestMetadata> {
         match self {
             Self::Building { .. } | Self::BuildError { .. } => None,
-            Self::Loading { start_time, .. } => Some(RequestMetadata {
+            Self::Loading { start_time, .. }
+            | Self::Streaming { start_time, .. } => Some(RequestMetadata {
                 start_time: *start_time,
                 duration: Utc::now() - start_time,
             }),
@@ -171,12 +225,37 @@ impl RequestState {
                 start_time: error.start_time,
                 duration: error.end_time - error.start_time,
             }),
+            Self::Cancelled {
+                start_time,
+                end_time,
+                ..
+            } => Some(RequestMetadata {
+                start_time: *start_time,
+                duration: *end_time - start_time,
+            }),
         }
     }
 
-    /// Initialize a new request in the `Building` state
-    pub fn building(id: RequestId) -> Self {
-        Self::Building { id }
+    /// Is the request done? Once a request is complete, no more updates
+    /// should be accepted for it
+    pub fn is_complete(&self) -> bool {
+        matches!(
+            self,
+            Self::BuildError { .. }
+                | Self::Response { .. }
+                | Self::RequestError { .. }
+                | Self::Cancelled { .. }
+        )
+    }
+
+    /// Initialize a new request in the `Building` state. The abort handle
+    /// should point to the task that's building and sending the request
+    pub fn building(id: RequestId, abort: AbortHandle) -> Self {
+        Self::Building {
+            id,
+            start_time: Utc::now(),
+            abort,
+        }
     }
 
     /// Create a loading state with the current timestamp. This will generally
@@ -187,15 +266,122 @@ impl RequestState {
         Self::Loading {
             request,
             start_time: Utc::now(),
+            abort: None,
+            attempt: 1,
+            download: None,
         }
     }
 
-    /// Create a request state from a completed response. This is **expensive**,
-    /// don't call it unless you need the value.
+    /// Update the attempt number of a loading request. Does nothing if the
+    /// request isn't loading
+    pub fn set_attempt(&mut self, new_attempt: u32) {
+        if let Self::Loading { attempt, .. } = self {
+            *attempt = new_attempt;
+        }
+    }
+
+    /// Update the download progress of a loading request. Does nothing if
+    /// the request isn't loading
+    pub fn set_download_progress(&mut self, progress: DownloadProgress) {
+        if let Self::Loading { download, .. } = self {
+            *download = Some(progress);
+        }
+    }
+
+    /// Create a streaming state, for when the headers of an event stream
+    /// response are received
+    pub fn streaming(
+        request: Arc<Request>,
+        status: StatusCode,
+        headers: HeaderMap,
+        cancel: oneshot::Sender<()>,
+    ) -> Self {
+        Self::Streaming {
+            request,
+            start_time: Utc::now(),
+            status,
+            headers,
+            events: Vec::new(),
+            parser: EventStreamParser::default(),
+            size: 0,
+            cancel: Some(cancel),
+        }
+    }
+
+    /// Add the next chunk of the body to a streaming response. Does nothing
+    /// if the request isn't streaming
+    pub fn push_chunk(&mut self, chunk: &[u8]) {
+        if let Self::Streaming {
+            events,
+            parser,
+            size,
+            ..
+        } = self
+        {
+            *size += chunk.len();
+            events.extend(parser.feed(chunk));
+        }
+    }
+
+    /// Close a streaming response. The request will complete with everything
+    /// received so far. Return `false` if there's no open stream to close
+    pub fn close_stream(&mut self) -> bool {
+        match self {
+            Self::Streaming { cancel, .. } => cancel
+                .take()
+                // Receiver may already be gone if the stream just ended
+                .map(|cancel| {
+                    let _ = cancel.send(());
+                })
+                .is_some(),
+            _ => false,
+        }
+    }
+
+    /// Cancel a request that's being built or is in flight. This aborts the
+    /// task, which drops any pending prompts, chained requests, and the HTTP
+    /// request itself. Return `false` if there's nothing to cancel
+    pub fn cancel(&mut self) -> bool {
+        let (id, request, start_time, abort) = match self {
+            Self::Building {
+                id,
+                start_time,
+                abort,
+            } => (*id, None, *start_time, Some(&*abort)),
+            Self::Loading {
+                request,
+                start_time,
+                abort,
+                ..
+            } => (
+                request.id,
+                Some(Arc::clone(request)),
+                *start_time,
+                abort.as_ref(),
+            ),
+            _ => return false,
+        };
+        if let Some(abort) = abort {
+            abort.abort();
+        }
+        *self = Self::Cancelled {
+            id,
+            request,
+            start_time,
+            end_time: Utc::now(),
+        };
+        true
+    }
+
+    /// Create a request state from a completed response. This will kick off a
+    /// background task to parse the body, unless the body is too large. Large
+    /// bodies can be parsed on demand with [ParsedBody::parse].
     pub fn response(record: RequestRecord) -> Self {
-        // Parsing might get slow on large responses, maybe we want to punt
-        // this into a separate task?
-        let parsed_body = record.response.parse_body().ok();
+        let parsed_body = ParsedBody::default();
+        let large_body_size = TuiContext::get().config.large_body_size;
+        if record.response.body.size().as_u64() <= large_body_size {
+            parsed_body.parse(&record.response);
+        }
         Self::Response {
             record,
             parsed_body,
@@ -203,6 +389,162 @@ impl RequestState {
     }
 }
 
+/// A response body, parsed according to its content type. Parsing can be slow
+/// for large bodies, so it's done in a background task. Until the task is done,
+/// the parsed value is unavailable.
+#[derive(Debug, Default)]
+pub struct ParsedBody {
+    /// Has the parse task been started?
+    started: Cell<bool>,
+    /// Populated by the parse task when it's done. The inner value is `None`
+    /// if the body couldn't be parsed, e.g. it has an unknown content type
+    parsed: Arc<OnceLock<Option<Arc<dyn ResponseContent>>>>,
+}
+
+impl ParsedBody {
+    /// Start parsing the body in a background task. If parsing has already
+    /// been started, do nothing.
+    pub fn parse(&self, response: &Response) {
+        if self.started.replace(true) {
+            return;
+        }
+        let response = response.clone();
+        let parsed = Arc::clone(&self.parsed);
+        tokio::task::spawn_blocking(move || {
+            let _ = parsed.set(response.parse_body().ok().map(Arc::from));
+        });
+    }
+
+    /// Get the parsed body. Returns `None` if parsing hasn't been started or
+    /// finished, or if the body couldn't be parsed.
+    pub fn get(&self) -> Option<Arc<dyn ResponseContent>> {
+        self.parsed.get().cloned().flatten()
+    }
+
+    /// Has parsing been started?
+    pub fn is_started(&self) -> bool {
+        self.started.get()
+    }
+
+    /// Is the parse task running?
+    pub fn is_pending(&self) -> bool {
+        self.started.get() && self.parsed.get().is_none()
+    }
+}
+
+/// State of a WebSocket connection opened from a WebSocket recipe. Like
+/// [RequestState], only one session is stored per profile+recipe at a time.
+/// Replacing a session closes its connection.
+#[derive(Debug)]
+pub struct WebSocketSession {
+    /// ID of the handshake request, used to match updates to the session
+    id: RequestId,
+    status: WebSocketStatus,
+    /// Every message sent or received, oldest first
+    messages: Vec<WebSocketLogEntry>,
+    /// Queue messages to be sent. Dropping this closes the connection, so
+    /// it's `None` once the user has closed it
+    sender: Option<UnboundedSender<String>>,
+}
+
+/// Stage of a WebSocket connection's life cycle
+#[derive(Debug)]
+pub enum WebSocketStatus {
+    /// The handshake request is being built or sent
+    Connecting,
+    Connected,
+    /// The user closed the connection, and we're waiting for the server to
+    /// acknowledge it
+    Closing,
+    Closed {
+        /// Reason given by the closing side, if any
+        reason: Option<String>,
+    },
+    /// The connection couldn't be opened, or was broken
+    Error(anyhow::Error),
+}
+
+/// A message sent or received during a WebSocket session
+#[derive(Debug)]
+pub struct WebSocketLogEntry {
+    pub time: DateTime<Utc>,
+    /// `true` if we sent this, `false` if the server did
+    pub sent: bool,
+    pub message: WebSocketMessage,
+}
+
+impl WebSocketSession {
+    /// Start tracking a new session, which is connecting
+    pub fn new(id: RequestId, sender: UnboundedSender<String>) -> Self {
+        Self {
+            id,
+            status: WebSocketStatus::Connecting,
+            messages: Vec::new(),
+            sender: Some(sender),
+        }
+    }
+
+    pub fn id(&self) -> RequestId {
+        self.id
+    }
+
+    pub fn status(&self) -> &WebSocketStatus {
+        &self.status
+    }
+
+    pub fn messages(&self) -> &[WebSocketLogEntry] {
+        &self.messages
+    }
+
+    /// Get a handle for sending messages. `None` if the connection isn't
+    /// open, or is being closed
+    pub fn sender(&self) -> Option<&UnboundedSender<String>> {
+        self.sender
+            .as_ref()
+            .filter(|_| matches!(self.status, WebSocketStatus::Connected))
+    }
+
+    /// Apply an update from the session task. An error ends the session
+    pub fn update(&mut self, update: anyhow::Result<WebSocketEvent>) {
+        match update {
+            Ok(WebSocketEvent::Connected) => {
+                // User may have closed the connection before it opened
+                if self.sender.is_some() {
+                    self.status = WebSocketStatus::Connected;
+                }
+            }
+            Ok(WebSocketEvent::Sent(message)) => self.push(true, message),
+            Ok(WebSocketEvent::Received(message)) => self.push(false, message),
+            Ok(WebSocketEvent::Closed { reason }) => {
+                self.status = WebSocketStatus::Closed { reason };
+                self.sender = None;
+            }
+            Err(error) => {
+                self.status = WebSocketStatus::Error(error);
+                self.sender = None;
+            }
+        }
+    }
+
+    /// Close the connection. Return `false` if it's not open (or opening)
+    pub fn close(&mut self) -> bool {
+        if self.sender.take().is_some() {
+            self.status = WebSocketStatus::Closing;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn push(&mut self, sent: bool, message: WebSocketMessage) {
+        self.messages.push(WebSocketLogEntry {
+            time: Utc::now(),
+            sent,
+            message,
+        });
+    }
+}
+
 /// A notification is an ephemeral informational message generated by some async
 /// action. It doesn't grab focus, but will be useful to the user nonetheless.
 /// It should be shown for a short period of time, then disappear on its own.
@@ -220,3 +562,69 @@ impl Notification {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+    use factori::create;
+    use std::future;
+
+    /// Cancelling a request aborts its task, and moves it to the cancelled
+    /// state
+    #[tokio::test]
+    async fn test_cancel() {
+        let task = tokio::spawn(future::pending::<()>());
+        let id = RequestId::new();
+        let mut state = RequestState::building(id, task.abort_handle());
+
+        assert!(state.cancel());
+        assert!(task.await.unwrap_err().is_cancelled());
+        assert!(matches!(
+            state,
+            RequestState::Cancelled { id: cancelled_id, request: None, .. }
+                if cancelled_id == id
+        ));
+        assert!(state.is_complete());
+
+        // Cancelled requests can't be cancelled again
+        assert!(!state.cancel());
+    }
+
+    /// Requests in flight keep the request when cancelled
+    #[tokio::test]
+    async fn test_cancel_loading() {
+        let task = tokio::spawn(future::pending::<()>());
+        let request = Arc::new(create!(Request));
+        let mut state = RequestState::Loading {
+            request: Arc::clone(&request),
+            start_time: Utc::now(),
+            abort: Some(task.abort_handle()),
+            attempt: 1,
+            download: None,
+        };
+
+        assert!(state.cancel());
+        assert!(task.await.unwrap_err().is_cancelled());
+        assert!(matches!(
+            state,
+            RequestState::Cancelled { request: Some(cancelled), .. }
+                if cancelled.id == request.id
+        ));
+    }
+
+    /// Completed requests can't be cancelled
+    #[test]
+    fn test_cancel_complete() {
+        let mut state = RequestState::RequestError {
+            error: RequestError {
+                error: anyhow::anyhow!("oh no"),
+                request: create!(Request).into(),
+                start_time: Utc::now(),
+                end_time: Utc::now(),
+            },
+        };
+        assert!(!state.cancel());
+        assert!(matches!(state, RequestState::RequestError { .. }));
+    }
+}
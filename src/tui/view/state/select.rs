@@ -73,6 +73,11 @@ impl<Item, State: SelectStateData> SelectState<Item, State> {
         &self.items
     }
 
+    /// Get a mutable reference to all items in the list
+    pub fn items_mut(&mut self) -> &mut [Item] {
+        &mut self.items
+    }
+
     /// Get the index of the currently selected item (if any)
     pub fn selected_index(&self) -> Option<usize> {
         self.state.borrow().selected()
@@ -83,6 +88,12 @@ impl<Item, State: SelectStateData> SelectState<Item, State> {
         self.items.get(self.state.borrow().selected()?)
     }
 
+    /// Get a mutable reference to the currently selected item (if any)
+    pub fn selected_mut(&mut self) -> Option<&mut Item> {
+        let index = self.state.get_mut().selected()?;
+        self.items.get_mut(index)
+    }
+
     /// Get a mutable reference to state. This uses `RefCell` underneath so it
     /// will panic if aliased. Only call this during the draw phase!
     pub fn state_mut(&self) -> impl DerefMut<Target = State> + '_ {
@@ -114,7 +125,7 @@ impl<Item, State: SelectStateData> SelectState<Item, State> {
     }
 
     /// Select an item by index
-    fn select_index(&mut self, index: usize) {
+    pub fn select_index(&mut self, index: usize) {
         let state = self.state.get_mut();
         let current = state.selected();
         state.select(index);
@@ -6,6 +6,7 @@ use ratatui::style::{Color, Modifier, Style};
 pub struct Theme {
     pub pane: ThemePane,
     pub list: ThemeList,
+    pub syntax: ThemeSyntax,
     pub tab: ThemeTab,
     pub table: ThemeTable,
     pub template_preview: ThemeTemplatePreview,
@@ -37,6 +38,22 @@ pub struct ThemePane {
     pub border_selected: Style,
 }
 
+/// Styles for syntax highlighting of text bodies
+#[derive(Debug)]
+pub struct ThemeSyntax {
+    /// Object keys (JSON) or attribute names (XML/HTML)
+    pub key: Style,
+    pub string: Style,
+    pub number: Style,
+    pub boolean: Style,
+    pub null: Style,
+    /// Brackets, commas, colons, etc.
+    pub punctuation: Style,
+    /// XML/HTML tag names
+    pub tag: Style,
+    pub comment: Style,
+}
+
 /// Styles for Tab component
 #[derive(Debug)]
 pub struct ThemeTab {
@@ -68,6 +85,10 @@ pub struct ThemeTemplatePreview {
 pub struct ThemeText {
     /// Text that needs some visual emphasis/separation
     pub highlight: Style,
+    /// Text describing an error
+    pub error: Style,
+    /// Text describing a success, e.g. a passed assertion
+    pub success: Style,
 }
 
 /// Styles for TextBox component
@@ -101,6 +122,20 @@ impl Default for Theme {
                     .fg(Color::Black)
                     .add_modifier(Modifier::BOLD),
             },
+            syntax: ThemeSyntax {
+                key: Style::default().fg(Color::LightBlue),
+                string: Style::default().fg(Color::Green),
+                number: Style::default().fg(Color::LightMagenta),
+                boolean: Style::default().fg(Color::Yellow),
+                null: Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::ITALIC),
+                punctuation: Style::default().fg(Color::Gray),
+                tag: Style::default().fg(Color::Cyan),
+                comment: Style::default()
+                    .fg(Color::DarkGray)
+                    .add_modifier(Modifier::ITALIC),
+            },
             tab: ThemeTab {
                 highlight: Style::default()
                     .fg(Self::PRIMARY_COLOR)
@@ -129,6 +164,10 @@ impl Default for Theme {
                 highlight: Style::default()
                     .fg(Color::Black)
                     .bg(Self::PRIMARY_COLOR),
+                error: Style::default()
+                    .fg(Self::ERROR_COLOR)
+                    .add_modifier(Modifier::BOLD),
+                success: Style::default().fg(Self::PRIMARY_COLOR),
             },
             text_box: ThemeTextBox {
                 text: Style::default().bg(Color::DarkGray),